@@ -7,7 +7,8 @@ import { Observable } from 'rxjs';
 })
 
 export class UdhcpdManagerService {
-  private baseUrl = '/api/udhcpd';
+  // 目前界面只管理 default 实例
+  private baseUrl = '/api/udhcpd/default';
 
   constructor(private http: HttpClient) {}
 
@@ -23,8 +24,12 @@ export class UdhcpdManagerService {
     return this.http.post(`${this.baseUrl}/restart`, {});
   }
 
-  status(): Observable<{ running: boolean }> {
-    return this.http.get<{ running: boolean }>(`${this.baseUrl}/status`);
+  status(): Observable<{ running: boolean; config_dirty: boolean | null; auto_apply: boolean }> {
+    return this.http.get<{ running: boolean; config_dirty: boolean | null; auto_apply: boolean }>(`${this.baseUrl}/status`);
+  }
+
+  setAutoApply(enabled: boolean): Observable<any> {
+    return this.http.post(`${this.baseUrl}/auto-apply`, { enabled });
   }
 
   getConfig(): Observable<any> {
@@ -43,6 +43,7 @@ interface UdhcpdConfig {
 })
 export class UdhcpdManagerComponent implements OnInit {
   status = false;
+  configDirty = false;
   config?: UdhcpdConfig;
 
   rangeForm: FormGroup;
@@ -102,7 +103,11 @@ export class UdhcpdManagerComponent implements OnInit {
 
   reloadStatus(): void {
     this.service.status().subscribe({
-      next: (res) => (this.status = !!res?.running),
+      next: (res) => {
+        this.status = !!res?.running;
+        // 配置已修改但运行中的 udhcpd 尚未重启
+        this.configDirty = res?.config_dirty === true;
+      },
       error: () => (this.status = false),
     });
   }
@@ -113,6 +118,7 @@ export class UdhcpdManagerComponent implements OnInit {
    * 并使代码逻辑更清晰、更健壮。
    */
   loadConfig(): void {
+    this.reloadStatus();
     this.service.getConfig().subscribe({
       next: (raw) => {
         // 1. 将服务器可能返回的不同字段名和数据类型，统一为标准化的内部格式。
@@ -0,0 +1,662 @@
+// src/addressing_manager.rs
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::fs_util::write_secret;
+use crate::interfaces::check_interface;
+use crate::interfaces::{self, run_ip, InterfaceAddress, MAX_NAME_LEN};
+use crate::ip_cidr::IpCidr;
+use crate::supervisor::{ProcessSupervisor, SupervisorStatus};
+
+/// 应用新配置后等待连通性恢复的最长时间，超时则回滚
+const CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// 以太网 MTU 1500 减去 8 字节的 PPPoE/PPP 头
+const DEFAULT_PPPOE_MTU: u16 = 1492;
+/// pppd 2.5 的插件名，pppd 2.4 中为 rp-pppoe.so
+const PPPOE_PLUGIN: &str = "pppoe.so";
+/// PPPoE 会话接口名为 `ppp-<接口名>`，受 15 字符的接口名长度限制
+const PPP_PREFIX: &str = "ppp-";
+
+/// 接口在网络中的角色，供防火墙、NAT 等按角色选择接口
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterfaceRole {
+    Wan,
+    #[default]
+    Lan,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StaticAddressing {
+    /// IPv4 与 IPv6 地址，例如 `192.168.1.1/24`、`2001:db8::1/64`
+    pub addresses: Vec<IpCidr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<Ipv4Addr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway6: Option<Ipv6Addr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DhcpClient {
+    /// 通过选项 12 向服务端报告的主机名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PppoeClient {
+    pub username: String,
+    /// API 响应中不返回；更新时留空表示保留原密码
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub password: String,
+    /// 只连接提供该服务名的接入集中器
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(default = "default_pppoe_mtu")]
+    pub mtu: u16,
+}
+
+fn default_pppoe_mtu() -> u16 {
+    DEFAULT_PPPOE_MTU
+}
+
+/// 接口取得地址的方式
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum Addressing {
+    Static(StaticAddressing),
+    /// 由 udhcpc 获取地址、网关与 DNS
+    Dhcp(DhcpClient),
+    /// 由 pppd 建立 PPPoE 会话，地址位于 `ppp-<接口名>` 上
+    Pppoe(PppoeClient),
+}
+
+/// 一个接口的地址配置，保存在 interfaces.toml 的 `[[interfaces]]` 中
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InterfaceAddressing {
+    pub name: String,
+    #[serde(default)]
+    pub role: InterfaceRole,
+    #[serde(flatten)]
+    pub addressing: Addressing,
+    /// 连通性检查时 ping 的地址；未设置时静态地址检查网关，DHCP/PPPoE 检查是否取得了 IPv4 地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check_host: Option<IpAddr>,
+}
+
+impl InterfaceAddressing {
+    /// 实际承载地址的接口
+    pub fn device(&self) -> String {
+        match self.addressing {
+            Addressing::Pppoe(_) => format!("{}{}", PPP_PREFIX, self.name),
+            _ => self.name.clone(),
+        }
+    }
+
+    /// 去掉密码，用于 API 响应
+    pub fn redacted(&self) -> InterfaceAddressing {
+        let mut entry = self.clone();
+        if let Addressing::Pppoe(pppoe) = &mut entry.addressing {
+            pppoe.password.clear();
+        }
+        entry
+    }
+
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        if self.name.trim().is_empty() {
+            issues.push(ValidationIssue::new("name", "Interface name cannot be empty"));
+        }
+
+        match &self.addressing {
+            Addressing::Static(s) => {
+                if s.addresses.is_empty() {
+                    issues.push(ValidationIssue::new("addresses", "Static addressing requires at least one address"));
+                }
+                for (i, address) in s.addresses.iter().enumerate() {
+                    let field = format!("addresses[{}]", i);
+                    if s.addresses[..i].iter().any(|a| a.addr == address.addr) {
+                        issues.push(ValidationIssue::new(field, format!("{} is listed more than once", address.addr)));
+                    } else if address.is_ipv4() && address.len < 31 && address.network() == *address {
+                        issues.push(ValidationIssue::new(field, format!("{} is a network address", address)));
+                    }
+                }
+                if let Some(gateway) = s.gateway {
+                    let v4: Vec<&IpCidr> = s.addresses.iter().filter(|a| a.is_ipv4()).collect();
+                    if v4.iter().any(|a| a.addr == IpAddr::V4(gateway)) {
+                        issues.push(ValidationIssue::new("gateway", "Gateway cannot be one of the interface's own addresses"));
+                    } else if !v4.iter().any(|a| a.contains(IpAddr::V4(gateway))) {
+                        issues.push(ValidationIssue::new(
+                            "gateway",
+                            format!("Gateway {} is not in any IPv4 subnet of the interface", gateway),
+                        ));
+                    }
+                }
+                // 链路本地网关不需要全局 IPv6 地址
+                if let Some(gateway6) = s.gateway6 {
+                    let link_local = (gateway6.segments()[0] & 0xffc0) == 0xfe80;
+                    if !link_local && !s.addresses.iter().any(|a| a.contains(IpAddr::V6(gateway6))) {
+                        issues.push(ValidationIssue::new(
+                            "gateway6",
+                            format!("Gateway {} is neither link-local nor in any IPv6 subnet of the interface", gateway6),
+                        ));
+                    }
+                }
+            }
+            Addressing::Dhcp(d) => {
+                if let Some(hostname) = &d.hostname {
+                    let valid = !hostname.is_empty()
+                        && hostname.len() <= 63
+                        && hostname.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                        && !hostname.starts_with('-');
+                    if !valid {
+                        issues.push(ValidationIssue::new("hostname", format!("'{}' is not a valid hostname", hostname)));
+                    }
+                }
+            }
+            Addressing::Pppoe(p) => {
+                if self.name.len() + PPP_PREFIX.len() > MAX_NAME_LEN {
+                    issues.push(ValidationIssue::new(
+                        "name",
+                        format!("PPPoE needs an interface name of at most {} characters", MAX_NAME_LEN - PPP_PREFIX.len()),
+                    ));
+                }
+                if p.username.is_empty() {
+                    issues.push(ValidationIssue::new("username", "PPPoE username cannot be empty"));
+                }
+                if p.password.is_empty() {
+                    issues.push(ValidationIssue::new("password", "PPPoE password cannot be empty"));
+                }
+                let fields = [("username", Some(&p.username)), ("password", Some(&p.password)), ("service", p.service.as_ref())];
+                for (field, value) in fields {
+                    // 这些值以双引号写入 pppd 的选项文件
+                    if value.is_some_and(|v| v.chars().any(|c| c.is_control() || c == '"' || c == '\\')) {
+                        issues.push(ValidationIssue::new(field, "Must not contain quotes, backslashes or control characters"));
+                    }
+                }
+                if !(576..=DEFAULT_PPPOE_MTU).contains(&p.mtu) {
+                    issues.push(ValidationIssue::new("mtu", format!("MTU must be between 576 and {}", DEFAULT_PPPOE_MTU)));
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// 渲染 pppd 的选项文件，其中包含明文密码
+    fn render_pppd_options(&self, pppoe: &PppoeClient) -> String {
+        let mut content = String::from("# Generated by meowtail from interfaces.toml, do not edit\n");
+        content.push_str(&format!("plugin {}\nnic-{}\nifname {}\n", PPPOE_PLUGIN, self.name, self.device()));
+        content.push_str(&format!("user \"{}\"\npassword \"{}\"\n", pppoe.username, pppoe.password));
+        if let Some(service) = &pppoe.service {
+            content.push_str(&format!("rp_pppoe_service \"{}\"\n", service));
+        }
+        content.push_str("noauth\nhide-password\nnoipdefault\ndefaultroute\nusepeerdns\n");
+        content.push_str("persist\nmaxfail 0\nholdoff 5\nlcp-echo-interval 20\nlcp-echo-failure 3\n");
+        content.push_str(&format!("mtu {}\nmru {}\n", pppoe.mtu, pppoe.mtu));
+        content
+    }
+}
+
+/// interfaces.toml 的内容
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AddressingConfig {
+    pub interfaces: Vec<InterfaceAddressing>,
+}
+
+impl AddressingConfig {
+    pub fn validate(&self) -> Result<()> {
+        let mut issues = Vec::new();
+        for (i, entry) in self.interfaces.iter().enumerate() {
+            let prefix = format!("interfaces[{}]", i);
+            if self.interfaces[..i].iter().any(|e| e.name == entry.name) {
+                issues.push(ValidationIssue::new(
+                    format!("{}.name", prefix),
+                    format!("Interface {} is configured more than once", entry.name),
+                ));
+            }
+            issues.extend(
+                entry
+                    .validation_issues()
+                    .into_iter()
+                    .map(|issue| ValidationIssue::new(format!("{}.{}", prefix, issue.field), issue.message)),
+            );
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&InterfaceAddressing> {
+        self.interfaces.iter().find(|e| e.name == name)
+    }
+}
+
+/// 最近一次应用某个接口配置的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ApplyResult {
+    pub at: DateTime<Utc>,
+    pub ok: bool,
+    /// 连通性检查失败后是否已恢复原来的地址
+    pub rolled_back: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AddressingStatus {
+    #[serde(flatten)]
+    pub config: InterfaceAddressing,
+    pub device: String,
+    pub ipv4: Vec<InterfaceAddress>,
+    pub ipv6: Vec<InterfaceAddress>,
+    /// udhcpc 或 pppd 的监管状态，静态地址时为 null
+    pub client: Option<SupervisorStatus>,
+    pub last_apply: Option<ApplyResult>,
+}
+
+/// 应用新配置前接口上的地址与系统的默认路由，用于回滚
+struct Snapshot {
+    addresses: Vec<IpCidr>,
+    default_routes: Vec<(&'static str, String)>,
+}
+
+impl Snapshot {
+    fn capture(name: &str) -> Snapshot {
+        Snapshot {
+            addresses: current_addresses(name),
+            default_routes: default_routes(),
+        }
+    }
+
+    fn restore(&self, name: &str) -> Result<()> {
+        sync_addresses(name, &self.addresses)?;
+        let current = default_routes();
+        for (family, route) in current.iter().filter(|r| !self.default_routes.contains(r)) {
+            let mut args = vec![*family, "route", "del"];
+            args.extend(route.split_whitespace());
+            let _ = run_ip(&args);
+        }
+        for (family, route) in self.default_routes.iter().filter(|r| !current.contains(r)) {
+            let mut args = vec![*family, "route", "replace"];
+            args.extend(route.split_whitespace());
+            run_ip(&args)?;
+        }
+        Ok(())
+    }
+}
+
+/// 管理接口的静态地址、DHCP 客户端（udhcpc）与 PPPoE（pppd）。
+/// 配置保存在 interfaces.toml，通过连通性检查的配置另存为 interfaces.toml.good，
+/// 启动时若检查失败则回退到其中的配置。
+pub struct AddressingManager {
+    config_path: PathBuf,
+    good_path: PathBuf,
+    config_lock: Mutex<()>,
+    clients: Mutex<HashMap<String, ProcessSupervisor>>,
+    results: Mutex<HashMap<String, ApplyResult>>,
+}
+
+impl AddressingManager {
+    pub fn new<P: Into<PathBuf>>(config_path: P) -> Self {
+        let config_path = config_path.into();
+        let mut good_path = config_path.as_os_str().to_owned();
+        good_path.push(".good");
+        AddressingManager {
+            config_path,
+            good_path: good_path.into(),
+            config_lock: Mutex::new(()),
+            clients: Mutex::new(HashMap::new()),
+            results: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 读取配置，文件不存在时返回空配置
+    pub fn read_config(&self) -> Result<AddressingConfig> {
+        read_config_file(&self.config_path)
+    }
+
+    fn write_config(&self, config: &AddressingConfig) -> Result<()> {
+        let content = toml::to_string_pretty(config).map_err(|e| AppError::Process(e.to_string()))?;
+        // PPPoE 密码也保存在这里
+        write_secret(&self.config_path, &content)
+    }
+
+    /// 记录通过连通性检查的接口配置
+    fn mark_good(&self, entry: Option<&InterfaceAddressing>, name: &str) -> Result<()> {
+        let mut good = read_config_file(&self.good_path).unwrap_or_default();
+        good.interfaces.retain(|e| e.name != name);
+        good.interfaces.extend(entry.cloned());
+        let content = toml::to_string_pretty(&good).map_err(|e| AppError::Process(e.to_string()))?;
+        write_secret(&self.good_path, &content)
+    }
+
+    pub fn list(&self) -> Result<Vec<AddressingStatus>> {
+        Ok(self.read_config()?.interfaces.iter().map(|e| self.status_of(e)).collect())
+    }
+
+    pub fn get(&self, name: &str) -> Result<AddressingStatus> {
+        let config = self.read_config()?;
+        let entry = config
+            .get(name)
+            .ok_or_else(|| AppError::NotFound(format!("No addressing configured for interface {}", name)))?;
+        Ok(self.status_of(entry))
+    }
+
+    fn status_of(&self, entry: &InterfaceAddressing) -> AddressingStatus {
+        let device = entry.device();
+        let (ipv4, ipv6) = interfaces::get(&device)
+            .map(|i| (i.ipv4, i.ipv6))
+            .unwrap_or_default();
+        AddressingStatus {
+            config: entry.redacted(),
+            device,
+            ipv4,
+            ipv6,
+            client: self.clients.lock().unwrap().get(&entry.name).map(|c| c.status()),
+            last_apply: self.results.lock().unwrap().get(&entry.name).cloned(),
+        }
+    }
+
+    /// 应用并保存一个接口的配置。连通性检查失败时恢复原来的地址并返回 Conflict，配置不会被保存。
+    pub fn set(&self, mut entry: InterfaceAddressing) -> Result<AddressingStatus> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        check_interface(&entry.name)?;
+
+        let mut config = self.read_config()?;
+        let previous = config.get(&entry.name).cloned();
+        if let (Addressing::Pppoe(new), Some(Addressing::Pppoe(old))) =
+            (&mut entry.addressing, previous.as_ref().map(|p| &p.addressing))
+        {
+            if new.password.is_empty() {
+                new.password = old.password.clone();
+            }
+        }
+        match config.interfaces.iter_mut().find(|e| e.name == entry.name) {
+            Some(existing) => *existing = entry.clone(),
+            None => config.interfaces.push(entry.clone()),
+        }
+        config.validate()?;
+
+        self.apply_checked(&entry, previous.as_ref())?;
+        self.write_config(&config)?;
+        self.mark_good(Some(&entry), &entry.name)?;
+        Ok(self.status_of(&entry))
+    }
+
+    /// 停止管理接口：停止 udhcpc/pppd 并删除配置，接口上现有的地址保持不变
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let mut config = self.read_config()?;
+        let before = config.interfaces.len();
+        config.interfaces.retain(|e| e.name != name);
+        if config.interfaces.len() == before {
+            return Err(AppError::NotFound(format!("No addressing configured for interface {}", name)));
+        }
+        self.stop_client(name)?;
+        self.write_config(&config)?;
+        self.mark_good(None, name)?;
+        self.results.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    /// 启动时应用所有接口的配置；检查失败的接口回退到最近一次通过检查的配置
+    pub fn apply_all(&self) {
+        let config = match self.read_config().and_then(|c| c.validate().map(|_| c)) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("[addressing] Not applying {:?}: {}", self.config_path, e);
+                return;
+            }
+        };
+        let good = read_config_file(&self.good_path).unwrap_or_default();
+
+        for entry in &config.interfaces {
+            if let Err(e) = interfaces::check(&entry.name) {
+                eprintln!("[addressing] Skipping {}: {}", entry.name, e);
+                continue;
+            }
+            let fallback = good.get(&entry.name).filter(|g| *g != entry);
+            match self.apply_checked(entry, fallback) {
+                Ok(()) => {
+                    if let Err(e) = self.mark_good(Some(entry), &entry.name) {
+                        eprintln!("[addressing] Failed to record good configuration for {}: {}", entry.name, e);
+                    }
+                }
+                Err(e) => eprintln!("[addressing] {}", e),
+            }
+        }
+    }
+
+    /// 应用配置并检查连通性，失败时回滚到 `previous`（没有时恢复应用前接口上的地址）
+    fn apply_checked(&self, entry: &InterfaceAddressing, previous: Option<&InterfaceAddressing>) -> Result<()> {
+        let snapshot = Snapshot::capture(&entry.name);
+        let outcome = self
+            .apply(entry)
+            .map_err(|e| e.to_string())
+            .and_then(|_| check_connectivity(entry));
+
+        let failure = match outcome {
+            Ok(()) => {
+                self.record(&entry.name, true, false, format!("Applied {} addressing", mode_name(entry)));
+                return Ok(());
+            }
+            Err(failure) => failure,
+        };
+
+        match self.roll_back(&entry.name, &snapshot, previous) {
+            Ok(()) => {
+                let message = format!("{}; previous addressing of {} restored", failure, entry.name);
+                self.record(&entry.name, false, true, message.clone());
+                Err(AppError::Conflict(message))
+            }
+            Err(e) => {
+                let message = format!("{}; rolling back {} also failed: {}", failure, entry.name, e);
+                self.record(&entry.name, false, false, message.clone());
+                Err(AppError::Process(message))
+            }
+        }
+    }
+
+    fn roll_back(&self, name: &str, snapshot: &Snapshot, previous: Option<&InterfaceAddressing>) -> Result<()> {
+        self.stop_client(name)?;
+        snapshot.restore(name)?;
+        match previous {
+            Some(previous) if !matches!(previous.addressing, Addressing::Static(_)) => self.apply(previous),
+            _ => Ok(()),
+        }
+    }
+
+    fn record(&self, name: &str, ok: bool, rolled_back: bool, message: String) {
+        if !ok {
+            eprintln!("[addressing] {}", message);
+        }
+        self.results.lock().unwrap().insert(
+            name.to_string(),
+            ApplyResult {
+                at: Utc::now(),
+                ok,
+                rolled_back,
+                message,
+            },
+        );
+    }
+
+    fn apply(&self, entry: &InterfaceAddressing) -> Result<()> {
+        self.stop_client(&entry.name)?;
+        run_ip(&["link", "set", "dev", &entry.name, "up"])?;
+
+        match &entry.addressing {
+            Addressing::Static(s) => {
+                sync_addresses(&entry.name, &s.addresses)?;
+                if let Some(gateway) = s.gateway {
+                    run_ip(&["-4", "route", "replace", "default", "via", &gateway.to_string(), "dev", &entry.name])?;
+                }
+                if let Some(gateway6) = s.gateway6 {
+                    run_ip(&["-6", "route", "replace", "default", "via", &gateway6.to_string(), "dev", &entry.name])?;
+                }
+                Ok(())
+            }
+            Addressing::Dhcp(d) => {
+                let mut args = vec!["-f".to_string(), "-R".to_string(), "-i".to_string(), entry.name.clone()];
+                if let Some(hostname) = &d.hostname {
+                    args.push("-x".to_string());
+                    args.push(format!("hostname:{}", hostname));
+                }
+                let pid_path = format!("/tmp/meowtail_udhcpc_{}.pid", entry.name);
+                let supervisor = ProcessSupervisor::new(&format!("udhcpc-{}", entry.name), "udhcpc", args, pid_path);
+                self.start_client(&entry.name, supervisor, || {
+                    // 去掉旧的静态地址，之后由 udhcpc 的脚本配置
+                    run_ip(&["-4", "addr", "flush", "dev", &entry.name, "scope", "global"])
+                })
+            }
+            Addressing::Pppoe(p) => {
+                // 绝对路径，pppd 的工作目录与 meowtail 不同时也能接管
+                let options_path =
+                    std::path::absolute(self.config_path.with_file_name(format!("pppoe-{}.options", entry.name)))?;
+                write_secret(&options_path, &entry.render_pppd_options(p))?;
+                let args = vec!["nodetach".to_string(), "file".to_string(), options_path.to_string_lossy().into_owned()];
+                let pid_path = format!("/tmp/meowtail_pppd_{}.pid", entry.name);
+                let supervisor = ProcessSupervisor::new(&format!("pppd-{}", entry.name), "pppd", args, pid_path);
+                self.start_client(&entry.name, supervisor, || Ok(()))
+            }
+        }
+    }
+
+    /// 接管已在运行的客户端（例如 meowtail 重启前启动的），否则先执行 `prepare` 再启动
+    fn start_client<F: FnOnce() -> Result<()>>(&self, name: &str, supervisor: ProcessSupervisor, prepare: F) -> Result<()> {
+        if let Some(pid) = supervisor.adopt() {
+            println!("[addressing] Supervising running client for {} with PID {}", name, pid);
+        } else {
+            prepare()?;
+            supervisor.start()?;
+        }
+        self.clients.lock().unwrap().insert(name.to_string(), supervisor);
+        Ok(())
+    }
+
+    fn stop_client(&self, name: &str) -> Result<()> {
+        let client = self.clients.lock().unwrap().remove(name);
+        if let Some(client) = client {
+            client.stop()?;
+        }
+        Ok(())
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<AddressingConfig> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(AddressingConfig::default()),
+        Err(e) => return Err(e.into()),
+    };
+    toml::from_str(&content).map_err(|e| AppError::ConfigParse(format!("{:?}: {}", path, e)))
+}
+
+fn mode_name(entry: &InterfaceAddressing) -> &'static str {
+    match entry.addressing {
+        Addressing::Static(_) => "static",
+        Addressing::Dhcp(_) => "DHCP",
+        Addressing::Pppoe(_) => "PPPoE",
+    }
+}
+
+/// 接口上的全局地址（不含 IPv6 链路本地地址）
+fn current_addresses(name: &str) -> Vec<IpCidr> {
+    let Ok(interface) = interfaces::get(name) else {
+        return vec![];
+    };
+    interface
+        .ipv4
+        .iter()
+        .chain(interface.ipv6.iter())
+        .filter(|a| !matches!(a.address, IpAddr::V6(v6) if (v6.segments()[0] & 0xffc0) == 0xfe80))
+        .filter_map(|a| IpCidr::new(a.address, a.prefix_len).ok())
+        .collect()
+}
+
+/// 让接口上的全局地址与 `wanted` 一致，只增删有差异的地址以免中断现有连接
+fn sync_addresses(name: &str, wanted: &[IpCidr]) -> Result<()> {
+    let current = current_addresses(name);
+    for address in current.iter().filter(|a| !wanted.contains(a)) {
+        run_ip(&["addr", "del", &address.to_string(), "dev", name])?;
+    }
+    for address in wanted.iter().filter(|a| !current.contains(a)) {
+        run_ip(&["addr", "add", &address.to_string(), "dev", name])?;
+    }
+    Ok(())
+}
+
+/// `ip route show default` 的输出，每行一条，按地址族区分
+fn default_routes() -> Vec<(&'static str, String)> {
+    let mut routes = Vec::new();
+    for family in ["-4", "-6"] {
+        let Ok(output) = Command::new("ip").args([family, "route", "show", "default"]).output() else {
+            continue;
+        };
+        routes.extend(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(|l| (family, l.trim().to_string())),
+        );
+    }
+    routes
+}
+
+/// 等待接口恢复连通：ping `check_host`（静态地址默认为网关），
+/// 没有可 ping 的目标时 DHCP/PPPoE 以取得 IPv4 地址为准
+fn check_connectivity(entry: &InterfaceAddressing) -> std::result::Result<(), String> {
+    let device = entry.device();
+    let target = entry.check_host.or(match &entry.addressing {
+        Addressing::Static(s) => s.gateway.map(IpAddr::V4).or(s.gateway6.map(IpAddr::V6)),
+        _ => None,
+    });
+    if target.is_none() && matches!(entry.addressing, Addressing::Static(_)) {
+        return Ok(());
+    }
+
+    let deadline = Instant::now() + CHECK_TIMEOUT;
+    loop {
+        let ok = match target {
+            Some(host) => ping(&device, host),
+            None => interfaces::get(&device).is_ok_and(|i| !i.ipv4.is_empty()),
+        };
+        if ok {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(match target {
+                Some(host) => format!("No reply from {} via {} within {}s", host, device, CHECK_TIMEOUT.as_secs()),
+                None => format!("{} did not obtain an IPv4 address within {}s", device, CHECK_TIMEOUT.as_secs()),
+            });
+        }
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+fn ping(device: &str, host: IpAddr) -> bool {
+    let family = if host.is_ipv4() { "-4" } else { "-6" };
+    Command::new("ping")
+        .args([family, "-c", "1", "-W", "2", "-I", device, &host.to_string()])
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
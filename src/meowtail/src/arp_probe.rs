@@ -0,0 +1,113 @@
+// src/arp_probe.rs
+
+use std::io;
+use std::net::Ipv4Addr;
+use std::process::Command;
+use std::str::FromStr;
+use std::thread;
+
+use serde::Serialize;
+
+use crate::mac_addr::MacAddr;
+
+/// 并行探测时同时运行的 arping 进程数
+const PARALLEL_PROBES: usize = 32;
+
+/// 一次 ARP 探测的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ArpProbeResult {
+    pub interface: String,
+    pub ip: Ipv4Addr,
+    pub in_use: bool,
+    /// 应答者的 MAC，有多个应答时取第一个
+    pub mac: Option<MacAddr>,
+    pub vendor: Option<&'static str>,
+    /// 地址属于本机的某个接口
+    pub local: bool,
+}
+
+/// 用 `arping -D`（重复地址检测，发送方地址为 0.0.0.0，不会污染邻居的 ARP 缓存）
+/// 探测 `ip` 是否已有主机在用。`wait_secs` 为等待应答的秒数。
+pub fn probe(interface: &str, ip: Ipv4Addr, wait_secs: u32) -> io::Result<ArpProbeResult> {
+    if let Some(mac) = local_mac_for(ip) {
+        return Ok(ArpProbeResult {
+            interface: interface.to_string(),
+            ip,
+            in_use: true,
+            mac,
+            vendor: mac.and_then(|m| m.vendor()),
+            local: true,
+        });
+    }
+
+    let wait = wait_secs.max(1).to_string();
+    let output = Command::new("arping")
+        .args(["-D", "-c", &wait, "-w", &wait, "-I", interface, &ip.to_string()])
+        .output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mac = parse_reply_mac(&stdout);
+    // 有应答时 arping -D 以 1 退出；退出码 2 等表示参数或接口错误
+    let in_use = mac.is_some() || output.status.code() == Some(1);
+    if !in_use && !output.status.success() {
+        return Err(io::Error::other(format!(
+            "arping failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(ArpProbeResult {
+        interface: interface.to_string(),
+        ip,
+        in_use,
+        mac,
+        vendor: mac.and_then(|m| m.vendor()),
+        local: false,
+    })
+}
+
+/// 并行探测多个地址，结果与输入顺序一致
+pub fn probe_many(interface: &str, ips: &[Ipv4Addr], wait_secs: u32) -> Vec<io::Result<ArpProbeResult>> {
+    let mut results = Vec::with_capacity(ips.len());
+    for chunk in ips.chunks(PARALLEL_PROBES) {
+        let handles: Vec<_> = chunk
+            .iter()
+            .map(|&ip| {
+                let interface = interface.to_string();
+                thread::spawn(move || probe(&interface, ip, wait_secs))
+            })
+            .collect();
+        results.extend(
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| Err(io::Error::other("ARP probe thread panicked")))),
+        );
+    }
+    results
+}
+
+/// 取出 `Unicast reply from 192.168.1.5 [AA:BB:CC:DD:EE:FF]  0.7ms` 中的 MAC（iputils 与 busybox 的 arping 格式相同）
+fn parse_reply_mac(output: &str) -> Option<MacAddr> {
+    output
+        .lines()
+        .filter(|line| line.contains("reply from"))
+        .find_map(|line| {
+            let start = line.find('[')?;
+            let end = line[start..].find(']')? + start;
+            MacAddr::from_str(&line[start + 1..end]).ok()
+        })
+}
+
+/// 地址属于本机时返回 Some，内层为该接口的 MAC（回环等接口没有）
+fn local_mac_for(ip: Ipv4Addr) -> Option<Option<MacAddr>> {
+    let addrs: Vec<_> = nix::ifaddrs::getifaddrs().ok()?.collect();
+    let interface = addrs
+        .iter()
+        .find(|ifa| ifa.address.and_then(|a| a.as_sockaddr_in().map(|a| a.ip())) == Some(ip))?;
+    Some(
+        addrs
+            .iter()
+            .filter(|ifa| ifa.interface_name == interface.interface_name)
+            .find_map(|ifa| ifa.address?.as_link_addr()?.addr())
+            .and_then(|octets| MacAddr::from_octets(octets).ok()),
+    )
+}
@@ -8,14 +8,53 @@ use std::io::{self, Write};
 use std::path::PathBuf;
 use std::sync::Mutex;
 
+use crate::dhcp_backend::BackendKind;
+use crate::management_access::ManagementAccess;
+
+// 一个 udhcpd 实例的启动选项
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UdhcpdInstanceConfig {
+    pub name: String,
+    pub autostart: bool,
+    // 配置写入成功后自动重启正在运行的 udhcpd
+    #[serde(default)]
+    pub auto_apply: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Config {
     pub admin_username: String,
     pub admin_password_hash: String, // 存储密码的哈希值而非明文
     pub jwt_secret: String,
-    pub listen_address: String,
+    // 管理界面监听的地址，都使用 listen_port
+    #[serde(default)]
+    pub listen_addresses: Vec<String>,
     pub listen_port: u16,
-    pub udhcpd_enabled: bool,
+    // 允许访问管理界面的来源
+    #[serde(default)]
+    pub management: ManagementAccess,
+    // DHCP 服务端实现：udhcpd（默认）或 dnsmasq，对所有实例生效
+    #[serde(default)]
+    pub dhcp_backend: BackendKind,
+    // 启动时是否运行 RA/DHCPv6 服务
+    #[serde(default)]
+    pub dhcpv6_autostart: bool,
+    // 启动时是否运行本地 DNS 服务
+    #[serde(default)]
+    pub dns_autostart: bool,
+    // 探测其他 DHCP 服务端的间隔（秒），0 表示不定期探测
+    #[serde(default = "default_rogue_dhcp_interval")]
+    pub rogue_dhcp_interval_secs: u64,
+    #[serde(default)]
+    pub udhcpd_instances: Vec<UdhcpdInstanceConfig>,
+    // 以下两项为单实例时代的旧配置，只在加载时迁移到 udhcpd_instances 中的 default 实例
+    #[serde(default, skip_serializing)]
+    pub udhcpd_enabled: Option<bool>,
+    #[serde(default, skip_serializing)]
+    pub udhcpd_auto_apply: bool,
+    // 只支持一个监听地址时的旧配置，加载时迁移到 listen_addresses
+    #[serde(default, skip_serializing)]
+    pub listen_address: String,
 }
 
 impl Config {
@@ -25,8 +64,12 @@ impl Config {
 
         if config_path.exists() {
             let config_str = fs::read_to_string(config_path)?;
-            toml::from_str(&config_str)
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            let mut config: Config = toml::from_str(&config_str)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if config.migrate() {
+                config.save()?;
+            }
+            Ok(config)
         } else {
             println!("Configuration file not found. Creating a default one at {:?}", config_path);
             let default_config = Self::default();
@@ -35,6 +78,28 @@ impl Config {
         }
     }
 
+    // 迁移旧版配置项，有改动时返回 true。
+    // 只有仍带着旧 udhcpd_enabled 键的文件才补 default 实例，用户删光实例后不会被加回来
+    fn migrate(&mut self) -> bool {
+        let mut migrated = false;
+        if let Some(enabled) = self.udhcpd_enabled.take() {
+            if self.udhcpd_instances.is_empty() {
+                self.udhcpd_instances.push(UdhcpdInstanceConfig {
+                    name: "default".to_string(),
+                    autostart: enabled,
+                    auto_apply: self.udhcpd_auto_apply,
+                });
+            }
+            migrated = true;
+        }
+        if self.listen_addresses.is_empty() {
+            let address = if self.listen_address.is_empty() { "0.0.0.0" } else { &self.listen_address };
+            self.listen_addresses.push(address.to_string());
+            migrated = true;
+        }
+        migrated
+    }
+
     // 保存配置到文件
     pub fn save(&self) -> io::Result<()> {
         let config_path = Self::get_config_path()?;
@@ -46,6 +111,11 @@ impl Config {
         Ok(())
     }
     
+    // 按名字查找 udhcpd 实例的启动选项
+    pub fn udhcpd_instance_mut(&mut self, name: &str) -> Option<&mut UdhcpdInstanceConfig> {
+        self.udhcpd_instances.iter_mut().find(|i| i.name == name)
+    }
+
     // 获取配置文件的路径 (与可执行文件同目录)
     fn get_config_path() -> io::Result<PathBuf> {
         let mut exe_path = env::current_exe()?;
@@ -54,6 +124,10 @@ impl Config {
     }
 }
 
+fn default_rogue_dhcp_interval() -> u64 {
+    300
+}
+
 impl Default for Config {
     fn default() -> Self {
         // 生成一个复杂的随机字符串作为 JWT Secret
@@ -73,12 +147,47 @@ impl Default for Config {
             // 生产环境中应该存储哈希值: e.g., hash("Change_ME")
             admin_password_hash: "Change_ME".to_string(),
             jwt_secret,
-            listen_address: "0.0.0.0".to_string(),
+            listen_addresses: vec!["0.0.0.0".to_string()],
             listen_port: 81,
-            udhcpd_enabled: true,
+            management: ManagementAccess::default(),
+            dhcp_backend: BackendKind::default(),
+            dhcpv6_autostart: false,
+            dns_autostart: false,
+            rogue_dhcp_interval_secs: default_rogue_dhcp_interval(),
+            udhcpd_instances: vec![UdhcpdInstanceConfig {
+                name: "default".to_string(),
+                autostart: true,
+                auto_apply: false,
+            }],
+            udhcpd_enabled: None,
+            udhcpd_auto_apply: false,
+            listen_address: String::new(),
         }
     }
 }
 
 // 将配置包装在 Mutex 中，以便在多线程环境中安全地修改
-pub type AppConfig = Mutex<Config>;
\ No newline at end of file
+pub type AppConfig = Mutex<Config>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "admin_username = \"admin\"\nadmin_password_hash = \"x\"\njwt_secret = \"s\"\nlisten_addresses = [\"0.0.0.0\"]\nlisten_port = 81\n";
+
+    #[test]
+    fn migrates_legacy_single_instance() {
+        let mut config: Config = toml::from_str(&format!("{}udhcpd_enabled = true\nudhcpd_auto_apply = true\n", BASE)).unwrap();
+        assert!(config.migrate());
+        assert_eq!(config.udhcpd_instances.len(), 1);
+        assert!(config.udhcpd_instances[0].autostart && config.udhcpd_instances[0].auto_apply);
+        assert!(!toml::to_string(&config).unwrap().contains("udhcpd_enabled"));
+    }
+
+    #[test]
+    fn keeps_an_empty_instance_list() {
+        let mut config: Config = toml::from_str(BASE).unwrap();
+        assert!(!config.migrate());
+        assert!(config.udhcpd_instances.is_empty());
+    }
+}
@@ -0,0 +1,1159 @@
+// src/ddns_manager.rs
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::fs_util::write_secret;
+use crate::interfaces;
+use crate::portmap_manager::PortMapManager;
+
+const CLOUDFLARE_API: &str = "https://api.cloudflare.com/client/v4";
+const USER_AGENT: &str = concat!("meowtail/", env!("CARGO_PKG_VERSION"));
+/// 后台线程检查是否有需要更新的主机的间隔
+const TICK_SECS: u64 = 15;
+/// 失败后第一次重试的等待时间，之后每次翻倍
+const RETRY_BASE_SECS: i64 = 60;
+const RETRY_MAX_SECS: i64 = 3600;
+const MIN_INTERVAL_SECS: u64 = 60;
+/// 每个主机保留的更新记录条数
+const HISTORY_LEN: usize = 20;
+const REQUEST_TIMEOUT_SECS: u32 = 30;
+
+fn default_interval() -> u64 {
+    300
+}
+
+fn default_dyndns2_server() -> String {
+    "members.dyndns.org".to_string()
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// 更新主机记录的服务商
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum Provider {
+    /// DynDNS2 协议（dyn.com、No-IP、很多路由器兼容的服务商）
+    Dyndns2 {
+        #[serde(default = "default_dyndns2_server")]
+        server: String,
+        username: String,
+        #[serde(default)]
+        password: String,
+    },
+    /// Cloudflare API，记录不存在时自动创建
+    Cloudflare {
+        zone_id: String,
+        /// 需要 Zone.DNS 编辑权限
+        #[serde(default)]
+        api_token: String,
+        #[serde(default)]
+        proxied: bool,
+    },
+    /// 自定义请求，`url`、`body` 与 `headers` 中的 `{hostname}`、`{ip}`、
+    /// `{username}`、`{password}` 会被替换
+    Http {
+        url: String,
+        #[serde(default = "default_method")]
+        method: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        headers: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        body: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        password: Option<String>,
+        /// 除 2xx 状态码外，响应还必须包含这段文字才算成功
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        success: Option<String>,
+    },
+}
+
+impl Provider {
+    fn secret_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Provider::Dyndns2 { password, .. } => Some(password),
+            Provider::Cloudflare { api_token, .. } => Some(api_token),
+            Provider::Http { password, .. } => password.as_mut(),
+        }
+    }
+}
+
+/// 一个需要指向 WAN 地址的主机名
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DdnsHost {
+    /// 由 meowtail 分配
+    #[serde(default)]
+    pub id: u32,
+    pub hostname: String,
+    #[serde(flatten)]
+    pub provider: Provider,
+}
+
+impl DdnsHost {
+    /// 去掉密码与 API token，用于返回给客户端
+    pub fn redacted(&self) -> DdnsHost {
+        let mut host = self.clone();
+        if let Some(secret) = host.provider.secret_mut() {
+            secret.clear();
+        }
+        host
+    }
+
+    /// 客户端提交空的密码或 token 时沿用原来的值
+    fn keep_secret(&mut self, previous: &DdnsHost) {
+        let mut previous = previous.provider.clone();
+        if std::mem::discriminant(&self.provider) != std::mem::discriminant(&previous) {
+            return;
+        }
+        if let (Some(secret), Some(old)) = (self.provider.secret_mut(), previous.secret_mut()) {
+            if secret.is_empty() {
+                *secret = std::mem::take(old);
+            }
+        }
+    }
+}
+
+/// ddns.toml 的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DdnsConfig {
+    pub enabled: bool,
+    /// 检查 WAN 地址是否变化的间隔
+    pub interval_secs: u64,
+    /// 设置后通过这个地址查询公网地址，用于 WAN 口在运营商 NAT 之后的情况；
+    /// 未设置时使用端口映射外部接口上的 IPv4 地址
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lookup_url: Option<String>,
+    pub hosts: Vec<DdnsHost>,
+}
+
+impl Default for DdnsConfig {
+    fn default() -> Self {
+        DdnsConfig {
+            enabled: false,
+            interval_secs: default_interval(),
+            lookup_url: None,
+            hosts: Vec::new(),
+        }
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    (url.starts_with("http://") || url.starts_with("https://")) && !url.chars().any(|c| c.is_control() || c.is_whitespace())
+}
+
+fn is_valid_hostname(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+impl DdnsConfig {
+    pub fn redacted(&self) -> DdnsConfig {
+        let mut config = self.clone();
+        config.hosts = config.hosts.iter().map(DdnsHost::redacted).collect();
+        config
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let mut issues = Vec::new();
+        if self.interval_secs < MIN_INTERVAL_SECS {
+            issues.push(ValidationIssue::new(
+                "interval_secs",
+                format!("Must be at least {} seconds", MIN_INTERVAL_SECS),
+            ));
+        }
+        if let Some(url) = &self.lookup_url {
+            if !is_http_url(url) {
+                issues.push(ValidationIssue::new("lookup_url", "Must be an http:// or https:// URL"));
+            }
+        }
+        for (i, host) in self.hosts.iter().enumerate() {
+            let field = |name: &str| format!("hosts[{}].{}", i, name);
+            if self.hosts[..i].iter().any(|h| h.id == host.id) {
+                issues.push(ValidationIssue::new(field("id"), format!("Host ID {} is used more than once", host.id)));
+            }
+            if !is_valid_hostname(&host.hostname) {
+                issues.push(ValidationIssue::new(field("hostname"), format!("'{}' is not a valid hostname", host.hostname)));
+            }
+            let mut text_fields: Vec<(&str, &str)> = Vec::new();
+            match &host.provider {
+                Provider::Dyndns2 { server, username, password } => {
+                    if server.is_empty() || server.contains(['/', '?', '#']) {
+                        issues.push(ValidationIssue::new(field("server"), "Must be a host name, optionally with a port"));
+                    }
+                    if username.is_empty() {
+                        issues.push(ValidationIssue::new(field("username"), "Username cannot be empty"));
+                    }
+                    if password.is_empty() {
+                        issues.push(ValidationIssue::new(field("password"), "Password cannot be empty"));
+                    }
+                    text_fields.extend([("server", server.as_str()), ("username", username), ("password", password)]);
+                }
+                Provider::Cloudflare { zone_id, api_token, .. } => {
+                    if zone_id.is_empty() || !zone_id.chars().all(|c| c.is_ascii_alphanumeric()) {
+                        issues.push(ValidationIssue::new(field("zone_id"), "Must be the zone ID shown in the Cloudflare dashboard"));
+                    }
+                    if api_token.is_empty() {
+                        issues.push(ValidationIssue::new(field("api_token"), "API token cannot be empty"));
+                    }
+                    text_fields.push(("api_token", api_token));
+                }
+                Provider::Http { url, method, headers, username, password, .. } => {
+                    if !is_http_url(url) {
+                        issues.push(ValidationIssue::new(field("url"), "Must be an http:// or https:// URL"));
+                    }
+                    if !["GET", "POST", "PUT", "PATCH"].contains(&method.as_str()) {
+                        issues.push(ValidationIssue::new(field("method"), "Must be GET, POST, PUT or PATCH"));
+                    }
+                    for (j, header) in headers.iter().enumerate() {
+                        if header.split_once(':').is_none_or(|(name, _)| name.trim().is_empty()) {
+                            issues.push(ValidationIssue::new(format!("hosts[{}].headers[{}]", i, j), "Must be in the form 'Name: value'"));
+                        }
+                        text_fields.push(("headers", header));
+                    }
+                    if password.is_some() && username.is_none() {
+                        issues.push(ValidationIssue::new(field("username"), "A password needs a username"));
+                    }
+                    text_fields.extend(username.iter().map(|u| ("username", u.as_str())));
+                    text_fields.extend(password.iter().map(|p| ("password", p.as_str())));
+                }
+            }
+            // 这些值会写入 HTTP 头部
+            for (name, value) in text_fields {
+                if value.chars().any(|c| c.is_control()) {
+                    issues.push(ValidationIssue::new(field(name), "Must not contain control characters"));
+                }
+            }
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+}
+
+/// 一次更新或检测的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateEvent {
+    pub at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<IpAddr>,
+    pub success: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+struct HostState {
+    /// 最近一次成功推送给服务商的地址
+    last_address: Option<IpAddr>,
+    last_success: Option<DateTime<Utc>>,
+    failures: u32,
+    next_attempt: Option<DateTime<Utc>>,
+    /// 服务商拒绝了请求（例如认证失败），修改配置或手动更新前不再重试
+    stopped: Option<String>,
+    history: VecDeque<UpdateEvent>,
+}
+
+#[derive(Debug, Default)]
+struct DdnsState {
+    address: Option<IpAddr>,
+    address_error: Option<String>,
+    checked_at: Option<DateTime<Utc>>,
+    next_check: Option<DateTime<Utc>>,
+    hosts: HashMap<u32, HostState>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HostStatus {
+    #[serde(flatten)]
+    pub host: DdnsHost,
+    pub last_address: Option<IpAddr>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub failures: u32,
+    pub next_attempt: Option<DateTime<Utc>>,
+    pub stopped: Option<String>,
+    /// 最近的更新记录，新的在前
+    pub history: Vec<UpdateEvent>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DdnsStatus {
+    pub enabled: bool,
+    /// 最近一次检测到的 WAN 地址
+    pub address: Option<IpAddr>,
+    pub address_error: Option<String>,
+    pub checked_at: Option<DateTime<Utc>>,
+    pub hosts: Vec<HostStatus>,
+}
+
+/// 服务商返回的失败
+enum Failure {
+    /// 临时错误，按退避时间重试
+    Retry(String),
+    /// 重试也不会成功的错误
+    Fatal(String),
+}
+
+impl From<AppError> for Failure {
+    fn from(e: AppError) -> Self {
+        Failure::Retry(e.to_string())
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    url: String,
+    headers: Vec<String>,
+    user: Option<String>,
+    body: Option<String>,
+}
+
+impl HttpRequest {
+    fn get(url: String) -> Self {
+        HttpRequest {
+            method: "GET".to_string(),
+            url,
+            headers: Vec::new(),
+            user: None,
+            body: None,
+        }
+    }
+}
+
+struct HttpReply {
+    status: u16,
+    body: String,
+}
+
+impl HttpReply {
+    fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// 认证失败或没有权限，重试没有意义
+    fn is_denied(&self) -> bool {
+        self.status == 401 || self.status == 403
+    }
+
+    fn summary(&self) -> String {
+        let body: String = self.body.trim().chars().take(200).collect();
+        format!("HTTP {}: {}", self.status, body)
+    }
+}
+
+/// curl 配置文件中的带引号字符串
+fn curl_quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// 响应中第一个看起来像 IP 地址的片段，兼容纯文本与 checkip 风格的 HTML
+fn find_address(body: &str) -> Option<IpAddr> {
+    body.split(|c: char| !(c.is_ascii_hexdigit() || c == '.' || c == ':'))
+        .filter(|token| token.contains(['.', ':']))
+        .find_map(|token| token.trim_matches(['.', ':']).parse().ok())
+}
+
+/// 运营商 NAT 或私有地址，外部无法通过它访问
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private() || ip.is_loopback() || ip.is_link_local() || (a == 100 && (64..128).contains(&b))
+}
+
+fn backoff_secs(failures: u32) -> i64 {
+    (RETRY_BASE_SECS << failures.saturating_sub(1).min(6)).min(RETRY_MAX_SECS)
+}
+
+/// 检测 WAN 地址并在变化时更新 DDNS 记录，配置保存在 ddns.toml
+pub struct DdnsManager {
+    curl_path: String,
+    /// Cloudflare API 的地址，测试时指向本地服务
+    cloudflare_api: String,
+    /// DynDNS2 请求使用的协议，测试时为 http
+    dyndns2_scheme: &'static str,
+    config_path: PathBuf,
+    config_lock: Mutex<()>,
+    portmap: Arc<PortMapManager>,
+    state: Mutex<DdnsState>,
+    /// 后台线程与手动更新不同时进行
+    run_lock: Mutex<()>,
+}
+
+impl DdnsManager {
+    pub fn new<P: Into<PathBuf>>(curl_path: &str, config_path: P, portmap: Arc<PortMapManager>) -> Self {
+        DdnsManager {
+            curl_path: curl_path.to_string(),
+            cloudflare_api: CLOUDFLARE_API.to_string(),
+            dyndns2_scheme: "https",
+            config_path: config_path.into(),
+            config_lock: Mutex::new(()),
+            portmap,
+            state: Mutex::new(DdnsState::default()),
+            run_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn read_config(&self) -> Result<DdnsConfig> {
+        let content = match fs::read_to_string(&self.config_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(DdnsConfig::default()),
+            Err(e) => return Err(e.into()),
+        };
+        toml::from_str(&content).map_err(|e| AppError::ConfigParse(format!("{:?}: {}", self.config_path, e)))
+    }
+
+    fn write_config(&self, config: &DdnsConfig) -> Result<()> {
+        let content = toml::to_string_pretty(config).map_err(|e| AppError::Process(e.to_string()))?;
+        // 文件中有密码与 API token
+        write_secret(&self.config_path, &content)
+    }
+
+    /// 在锁内修改配置：校验成功后保存，改动过的主机在下一轮重新更新
+    pub fn update_config<T, F: FnOnce(&mut DdnsConfig) -> Result<T>>(&self, update: F) -> Result<T> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let previous = self.read_config()?;
+        let mut config = previous.clone();
+        let result = update(&mut config)?;
+        config.validate()?;
+        self.write_config(&config)?;
+
+        let mut state = self.state.lock().unwrap();
+        state.hosts.retain(|id, _| config.hosts.iter().any(|h| h.id == *id));
+        for host in &config.hosts {
+            if !previous.hosts.contains(host) {
+                if let Some(host_state) = state.hosts.get_mut(&host.id) {
+                    *host_state = HostState {
+                        history: std::mem::take(&mut host_state.history),
+                        ..HostState::default()
+                    };
+                }
+            }
+        }
+        if config.lookup_url != previous.lookup_url {
+            state.next_check = None;
+        }
+        Ok(result)
+    }
+
+    pub fn replace_config(&self, mut config: DdnsConfig) -> Result<()> {
+        self.update_config(|current| {
+            for host in &mut config.hosts {
+                if let Some(previous) = current.hosts.iter().find(|h| h.id == host.id) {
+                    host.keep_secret(previous);
+                }
+            }
+            *current = config;
+            Ok(())
+        })
+    }
+
+    /// 添加主机并返回分配的 ID
+    pub fn add_host(&self, mut host: DdnsHost) -> Result<DdnsHost> {
+        self.update_config(|c| {
+            host.id = c.hosts.iter().map(|h| h.id).max().unwrap_or(0) + 1;
+            c.hosts.push(host.clone());
+            Ok(host)
+        })
+    }
+
+    pub fn update_host(&self, id: u32, mut host: DdnsHost) -> Result<DdnsHost> {
+        host.id = id;
+        self.update_config(|c| {
+            let existing = c
+                .hosts
+                .iter_mut()
+                .find(|h| h.id == id)
+                .ok_or_else(|| AppError::NotFound(format!("DDNS host {}", id)))?;
+            host.keep_secret(existing);
+            *existing = host.clone();
+            Ok(host)
+        })
+    }
+
+    pub fn remove_host(&self, id: u32) -> Result<()> {
+        self.update_config(|c| {
+            if !c.hosts.iter().any(|h| h.id == id) {
+                return Err(AppError::NotFound(format!("DDNS host {}", id)));
+            }
+            c.hosts.retain(|h| h.id != id);
+            Ok(())
+        })
+    }
+
+    pub fn status(&self) -> Result<DdnsStatus> {
+        let config = self.read_config()?;
+        let state = self.state.lock().unwrap();
+        let hosts = config
+            .hosts
+            .iter()
+            .map(|host| {
+                let host_state = state.hosts.get(&host.id);
+                HostStatus {
+                    host: host.redacted(),
+                    last_address: host_state.and_then(|s| s.last_address),
+                    last_success: host_state.and_then(|s| s.last_success),
+                    failures: host_state.map_or(0, |s| s.failures),
+                    next_attempt: host_state.and_then(|s| s.next_attempt),
+                    stopped: host_state.and_then(|s| s.stopped.clone()),
+                    history: host_state.map(|s| s.history.iter().rev().cloned().collect()).unwrap_or_default(),
+                }
+            })
+            .collect();
+        Ok(DdnsStatus {
+            enabled: config.enabled,
+            address: state.address,
+            address_error: state.address_error.clone(),
+            checked_at: state.checked_at,
+            hosts,
+        })
+    }
+
+    /// 立即检测地址并更新所有主机，包括地址没有变化或已停止重试的主机
+    pub fn update_now(&self) -> Result<DdnsStatus> {
+        let config = self.read_config()?;
+        self.run(&config, true);
+        self.status()
+    }
+
+    /// 后台线程：按间隔检测地址，地址变化或重试时间到时更新
+    pub fn spawn(manager: Arc<DdnsManager>) {
+        thread::spawn(move || loop {
+            match manager.read_config() {
+                Ok(config) if config.enabled => manager.run(&config, false),
+                Ok(_) => {}
+                Err(e) => eprintln!("[ddns] {}", e),
+            }
+            thread::sleep(Duration::from_secs(TICK_SECS));
+        });
+    }
+
+    fn run(&self, config: &DdnsConfig, force: bool) {
+        let _guard = self.run_lock.lock().unwrap();
+        let now = Utc::now();
+        {
+            let state = self.state.lock().unwrap();
+            let check_due = state.next_check.is_none_or(|t| now >= t);
+            let retry_due = config.hosts.iter().any(|h| {
+                state
+                    .hosts
+                    .get(&h.id)
+                    .is_none_or(|s| s.stopped.is_none() && (s.last_address.is_none() || s.next_attempt.is_some_and(|t| now >= t)))
+            });
+            if !force && !check_due && !retry_due {
+                return;
+            }
+        }
+
+        let detected = self.detect_address(config);
+        let address = {
+            let mut state = self.state.lock().unwrap();
+            state.checked_at = Some(now);
+            match detected {
+                Ok(address) => {
+                    if state.address.is_some_and(|a| a != address) {
+                        println!("[ddns] WAN address changed to {}", address);
+                    }
+                    state.address = Some(address);
+                    state.address_error = None;
+                    state.next_check = Some(now + chrono::Duration::seconds(config.interval_secs as i64));
+                    address
+                }
+                Err(e) => {
+                    eprintln!("[ddns] Failed to detect WAN address: {}", e);
+                    state.address_error = Some(e.to_string());
+                    state.next_check = Some(now + chrono::Duration::seconds(RETRY_BASE_SECS));
+                    return;
+                }
+            }
+        };
+
+        for host in &config.hosts {
+            let due = {
+                let state = self.state.lock().unwrap();
+                match state.hosts.get(&host.id) {
+                    _ if force => true,
+                    None => true,
+                    Some(s) if s.stopped.is_some() => false,
+                    Some(s) if s.failures > 0 => s.next_attempt.is_none_or(|t| now >= t),
+                    Some(s) => s.last_address != Some(address),
+                }
+            };
+            if !due {
+                continue;
+            }
+            let result = self.update_host_record(host, address);
+            let at = Utc::now();
+            let mut state = self.state.lock().unwrap();
+            let host_state = state.hosts.entry(host.id).or_default();
+            let (success, message) = match result {
+                Ok(message) => {
+                    host_state.last_address = Some(address);
+                    host_state.last_success = Some(at);
+                    host_state.failures = 0;
+                    host_state.next_attempt = None;
+                    host_state.stopped = None;
+                    (true, message)
+                }
+                Err(Failure::Retry(message)) => {
+                    host_state.failures += 1;
+                    host_state.next_attempt = Some(at + chrono::Duration::seconds(backoff_secs(host_state.failures)));
+                    (false, message)
+                }
+                Err(Failure::Fatal(message)) => {
+                    host_state.failures += 1;
+                    host_state.next_attempt = None;
+                    host_state.stopped = Some(message.clone());
+                    (false, message)
+                }
+            };
+            if !success {
+                eprintln!("[ddns] Failed to update {}: {}", host.hostname, message);
+            }
+            host_state.history.push_back(UpdateEvent {
+                at,
+                address: Some(address),
+                success,
+                message,
+            });
+            while host_state.history.len() > HISTORY_LEN {
+                host_state.history.pop_front();
+            }
+        }
+    }
+
+    fn detect_address(&self, config: &DdnsConfig) -> Result<IpAddr> {
+        if let Some(url) = &config.lookup_url {
+            let reply = self.request(&HttpRequest::get(url.clone()))?;
+            if !reply.is_success() {
+                return Err(AppError::Process(format!("{} returned {}", url, reply.summary())));
+            }
+            return find_address(&reply.body)
+                .ok_or_else(|| AppError::Process(format!("{} did not return an IP address", url)));
+        }
+        let interface = self.portmap.config().external_interface;
+        let info = interfaces::get(&interface)?;
+        let address = info
+            .ipv4
+            .iter()
+            .find_map(|a| match a.address {
+                IpAddr::V4(ip) => Some(ip),
+                IpAddr::V6(_) => None,
+            })
+            .ok_or_else(|| AppError::Process(format!("{} has no IPv4 address", interface)))?;
+        if is_private_v4(address) {
+            return Err(AppError::Process(format!(
+                "{} has the private address {}, set lookup_url to detect the public address",
+                interface, address
+            )));
+        }
+        Ok(IpAddr::V4(address))
+    }
+
+    /// 通过 curl 发送请求，参数经 stdin 传入以免密码出现在进程列表中
+    fn request(&self, request: &HttpRequest) -> Result<HttpReply> {
+        let mut options = vec![
+            format!("url = {}", curl_quote(&request.url)),
+            format!("request = {}", curl_quote(&request.method)),
+            format!("user-agent = {}", curl_quote(USER_AGENT)),
+            format!("max-time = {}", REQUEST_TIMEOUT_SECS),
+            "silent".to_string(),
+            "show-error".to_string(),
+            format!("write-out = {}", curl_quote("\n%{http_code}")),
+        ];
+        options.extend(request.headers.iter().map(|h| format!("header = {}", curl_quote(h))));
+        if let Some(user) = &request.user {
+            options.push(format!("user = {}", curl_quote(user)));
+        }
+        if let Some(body) = &request.body {
+            options.push(format!("data-raw = {}", curl_quote(body)));
+        }
+
+        let mut child = Command::new(&self.curl_path)
+            .args(["--config", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(options.join("\n").as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(AppError::Process(format!(
+                "Request to {} failed: {}",
+                request.url.split('?').next().unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (body, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+        let status = status
+            .trim()
+            .parse()
+            .map_err(|_| AppError::Process(format!("curl printed an invalid status code '{}'", status.trim())))?;
+        Ok(HttpReply {
+            status,
+            body: body.to_string(),
+        })
+    }
+
+    fn update_host_record(&self, host: &DdnsHost, address: IpAddr) -> std::result::Result<String, Failure> {
+        match &host.provider {
+            Provider::Dyndns2 { server, username, password } => self.update_dyndns2(host, server, username, password, address),
+            Provider::Cloudflare { zone_id, api_token, proxied } => {
+                self.update_cloudflare(host, zone_id, api_token, *proxied, address)
+            }
+            Provider::Http { .. } => self.update_http(host, address),
+        }
+    }
+
+    fn update_dyndns2(
+        &self,
+        host: &DdnsHost,
+        server: &str,
+        username: &str,
+        password: &str,
+        address: IpAddr,
+    ) -> std::result::Result<String, Failure> {
+        let mut request = HttpRequest::get(format!(
+            "{}://{}/nic/update?hostname={}&myip={}",
+            self.dyndns2_scheme, server, host.hostname, address
+        ));
+        request.user = Some(format!("{}:{}", username, password));
+        let reply = self.request(&request)?;
+        let answer = reply.body.trim();
+        let code = answer.split_whitespace().next().unwrap_or_default();
+        match code {
+            "good" | "nochg" => Ok(answer.to_string()),
+            // 协议规定这些结果需要人工处理，继续重试可能导致账号被封禁
+            "badauth" | "!donator" | "notfqdn" | "nohost" | "numhost" | "abuse" | "badagent" | "badsys" => {
+                Err(Failure::Fatal(answer.to_string()))
+            }
+            _ if reply.is_denied() => Err(Failure::Fatal(reply.summary())),
+            _ => Err(Failure::Retry(reply.summary())),
+        }
+    }
+
+    fn update_cloudflare(
+        &self,
+        host: &DdnsHost,
+        zone_id: &str,
+        api_token: &str,
+        proxied: bool,
+        address: IpAddr,
+    ) -> std::result::Result<String, Failure> {
+        let record_type = if address.is_ipv4() { "A" } else { "AAAA" };
+        let records_url = format!("{}/zones/{}/dns_records", self.cloudflare_api, zone_id);
+        let headers = vec![format!("Authorization: Bearer {}", api_token), "Content-Type: application/json".to_string()];
+        let call = |method: &str, url: String, body: Option<String>| -> std::result::Result<Value, Failure> {
+            let reply = self.request(&HttpRequest {
+                method: method.to_string(),
+                url,
+                headers: headers.clone(),
+                user: None,
+                body,
+            })?;
+            let value: Value = serde_json::from_str(&reply.body).unwrap_or(Value::Null);
+            if reply.is_success() && value["success"].as_bool() == Some(true) {
+                return Ok(value);
+            }
+            let errors: Vec<String> = value["errors"]
+                .as_array()
+                .map(|errors| errors.iter().filter_map(|e| e["message"].as_str().map(str::to_string)).collect())
+                .unwrap_or_default();
+            let message = if errors.is_empty() {
+                reply.summary()
+            } else {
+                format!("HTTP {}: {}", reply.status, errors.join("; "))
+            };
+            if reply.is_denied() {
+                Err(Failure::Fatal(message))
+            } else {
+                Err(Failure::Retry(message))
+            }
+        };
+
+        let found = call("GET", format!("{}?type={}&name={}", records_url, record_type, host.hostname), None)?;
+        let record = json!({
+            "type": record_type,
+            "name": host.hostname,
+            "content": address.to_string(),
+            "ttl": 1,
+            "proxied": proxied,
+        })
+        .to_string();
+        match found["result"].as_array().and_then(|records| records.first()) {
+            Some(existing) if existing["content"].as_str() == Some(&address.to_string()) => {
+                Ok(format!("{} record already points to {}", record_type, address))
+            }
+            Some(existing) => {
+                let id = existing["id"].as_str().unwrap_or_default();
+                call("PUT", format!("{}/{}", records_url, id), Some(record))?;
+                Ok(format!("Updated {} record to {}", record_type, address))
+            }
+            None => {
+                call("POST", records_url, Some(record))?;
+                Ok(format!("Created {} record for {}", record_type, address))
+            }
+        }
+    }
+
+    fn update_http(&self, host: &DdnsHost, address: IpAddr) -> std::result::Result<String, Failure> {
+        let Provider::Http { url, method, headers, body, username, password, success } = &host.provider else {
+            return Err(Failure::Fatal("Not an HTTP template host".to_string()));
+        };
+        let fill = |template: &str| {
+            template
+                .replace("{hostname}", &host.hostname)
+                .replace("{ip}", &address.to_string())
+                .replace("{username}", username.as_deref().unwrap_or_default())
+                .replace("{password}", password.as_deref().unwrap_or_default())
+        };
+        let request = HttpRequest {
+            method: method.clone(),
+            url: fill(url),
+            headers: headers.iter().map(|h| fill(h)).collect(),
+            user: username.as_ref().map(|u| format!("{}:{}", u, password.as_deref().unwrap_or_default())),
+            body: body.as_deref().map(fill),
+        };
+        let reply = self.request(&request)?;
+        if reply.is_denied() {
+            return Err(Failure::Fatal(reply.summary()));
+        }
+        if !reply.is_success() {
+            return Err(Failure::Retry(reply.summary()));
+        }
+        match success {
+            Some(expected) if !reply.body.contains(expected.as_str()) => Err(Failure::Retry(format!(
+                "Response does not contain '{}': {}",
+                expected,
+                reply.summary()
+            ))),
+            _ => Ok(reply.summary()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    /// 本地 HTTP 服务：按顺序返回预设的响应，并记录收到的请求
+    struct MockServer {
+        address: String,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockServer {
+        fn start(replies: Vec<(u16, &str)>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+            let replies: Vec<(u16, String)> = replies.into_iter().map(|(status, body)| (status, body.to_string())).collect();
+            thread::spawn(move || {
+                for (status, body) in replies {
+                    let Ok((mut stream, _)) = listener.accept() else {
+                        return;
+                    };
+                    recorded.lock().unwrap().push(read_request(&mut stream));
+                    let response = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes());
+                }
+            });
+            MockServer { address, requests }
+        }
+
+        fn url(&self) -> String {
+            format!("http://{}", self.address)
+        }
+
+        fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn read_request(stream: &mut std::net::TcpStream) -> String {
+        let mut data = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let n = stream.read(&mut buf).unwrap_or(0);
+            if n == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&data);
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap_or(0)))
+                    .unwrap_or(0);
+                if body.len() >= length {
+                    break;
+                }
+            }
+        }
+        String::from_utf8_lossy(&data).into_owned()
+    }
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("meowtail-ddns-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn manager(dir: &TempDir) -> DdnsManager {
+        let portmap = Arc::new(PortMapManager::new("iptables", dir.0.join("portmap.toml")).unwrap());
+        let mut manager = DdnsManager::new("curl", dir.0.join("ddns.toml"), portmap);
+        manager.dyndns2_scheme = "http";
+        manager
+    }
+
+    fn dyndns2_host(server: &MockServer) -> DdnsHost {
+        DdnsHost {
+            id: 1,
+            hostname: "home.example.com".to_string(),
+            provider: Provider::Dyndns2 {
+                server: server.address.clone(),
+                username: "user".to_string(),
+                password: "secret".to_string(),
+            },
+        }
+    }
+
+    fn address() -> IpAddr {
+        "203.0.113.7".parse().unwrap()
+    }
+
+    #[test]
+    fn dyndns2_answers() {
+        let dir = TempDir::new("dyndns2");
+        let manager = manager(&dir);
+        let server = MockServer::start(vec![(200, "good 203.0.113.7"), (200, "nochg 203.0.113.7"), (200, "badauth")]);
+        let host = dyndns2_host(&server);
+
+        assert_eq!(manager.update_host_record(&host, address()).ok().as_deref(), Some("good 203.0.113.7"));
+        assert_eq!(manager.update_host_record(&host, address()).ok().as_deref(), Some("nochg 203.0.113.7"));
+        assert!(matches!(manager.update_host_record(&host, address()), Err(Failure::Fatal(m)) if m == "badauth"));
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /nic/update?hostname=home.example.com&myip=203.0.113.7 HTTP/1.1\r\n"));
+        // user:secret
+        assert!(requests[0].contains("Authorization: Basic dXNlcjpzZWNyZXQ=\r\n"));
+    }
+
+    #[test]
+    fn cloudflare_creates_missing_record() {
+        let dir = TempDir::new("cloudflare");
+        let mut manager = manager(&dir);
+        let server = MockServer::start(vec![
+            (200, r#"{"success":true,"result":[]}"#),
+            (200, r#"{"success":true,"result":{"id":"abc"}}"#),
+        ]);
+        manager.cloudflare_api = server.url();
+        let host = DdnsHost {
+            id: 1,
+            hostname: "home.example.com".to_string(),
+            provider: Provider::Cloudflare {
+                zone_id: "zone1".to_string(),
+                api_token: "token".to_string(),
+                proxied: false,
+            },
+        };
+
+        let message = manager.update_host_record(&host, address()).ok().unwrap();
+        assert_eq!(message, "Created A record for 203.0.113.7");
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /zones/zone1/dns_records?type=A&name=home.example.com HTTP/1.1\r\n"));
+        assert!(requests[0].contains("Authorization: Bearer token\r\n"));
+        assert!(requests[1].starts_with("POST /zones/zone1/dns_records HTTP/1.1\r\n"));
+        let body: Value = serde_json::from_str(requests[1].split_once("\r\n\r\n").unwrap().1).unwrap();
+        assert_eq!(body["content"], "203.0.113.7");
+        assert_eq!(body["type"], "A");
+    }
+
+    #[test]
+    fn cloudflare_forbidden_is_fatal() {
+        let dir = TempDir::new("cloudflare-403");
+        let mut manager = manager(&dir);
+        let server = MockServer::start(vec![(403, r#"{"success":false,"errors":[{"code":9109,"message":"Invalid access token"}]}"#)]);
+        manager.cloudflare_api = server.url();
+        let host = DdnsHost {
+            id: 1,
+            hostname: "home.example.com".to_string(),
+            provider: Provider::Cloudflare {
+                zone_id: "zone1".to_string(),
+                api_token: "wrong".to_string(),
+                proxied: false,
+            },
+        };
+
+        assert!(matches!(
+            manager.update_host_record(&host, address()),
+            Err(Failure::Fatal(m)) if m == "HTTP 403: Invalid access token"
+        ));
+    }
+
+    #[test]
+    fn http_template_substitutes_placeholders() {
+        let dir = TempDir::new("http");
+        let manager = manager(&dir);
+        let server = MockServer::start(vec![(200, "OK updated"), (200, "ERROR")]);
+        let host = DdnsHost {
+            id: 1,
+            hostname: "home.example.com".to_string(),
+            provider: Provider::Http {
+                url: format!("{}/update/{{hostname}}?ip={{ip}}", server.url()),
+                method: "POST".to_string(),
+                headers: vec!["X-Host: {hostname}".to_string()],
+                body: Some("user={username}&pass={password}&ip={ip}".to_string()),
+                username: Some("user".to_string()),
+                password: Some("secret".to_string()),
+                success: Some("OK".to_string()),
+            },
+        };
+
+        assert_eq!(manager.update_host_record(&host, address()).ok().as_deref(), Some("HTTP 200: OK updated"));
+        assert!(matches!(manager.update_host_record(&host, address()), Err(Failure::Retry(_))));
+
+        let request = &server.requests()[0];
+        assert!(request.starts_with("POST /update/home.example.com?ip=203.0.113.7 HTTP/1.1\r\n"));
+        assert!(request.contains("X-Host: home.example.com\r\n"));
+        assert!(request.contains("Authorization: Basic dXNlcjpzZWNyZXQ=\r\n"));
+        assert!(request.ends_with("\r\n\r\nuser=user&pass=secret&ip=203.0.113.7"));
+    }
+
+    #[test]
+    fn find_address_in_replies() {
+        assert_eq!(find_address("203.0.113.7\n"), Some(address()));
+        assert_eq!(
+            find_address("<html><body>Current IP Address: 203.0.113.7</body></html>"),
+            Some(address())
+        );
+        assert_eq!(find_address("ip=2001:db8::1"), Some("2001:db8::1".parse().unwrap()));
+        assert_eq!(find_address("version 1.2 of the checker: 198.51.100.1."), Some("198.51.100.1".parse().unwrap()));
+        assert_eq!(find_address("no address here"), None);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff_secs(1), 60);
+        assert_eq!(backoff_secs(2), 120);
+        assert_eq!(backoff_secs(3), 240);
+        assert_eq!(backoff_secs(7), RETRY_MAX_SECS);
+        assert_eq!(backoff_secs(u32::MAX), RETRY_MAX_SECS);
+    }
+
+    fn run_once(replies: Vec<(u16, &str)>, name: &str) -> (MockServer, DdnsManager, TempDir) {
+        let dir = TempDir::new(name);
+        let manager = manager(&dir);
+        let server = MockServer::start(replies);
+        let config = DdnsConfig {
+            enabled: true,
+            lookup_url: Some(format!("{}/ip", server.url())),
+            hosts: vec![dyndns2_host(&server)],
+            ..DdnsConfig::default()
+        };
+        manager.run(&config, false);
+        (server, manager, dir)
+    }
+
+    #[test]
+    fn retry_failures_back_off() {
+        let (server, manager, _dir) = run_once(vec![(200, "203.0.113.7"), (500, "911")], "retry");
+        let status = manager.status().unwrap();
+        assert_eq!(status.address, Some(address()));
+        let state = manager.state.lock().unwrap();
+        let host = &state.hosts[&1];
+        assert_eq!(host.failures, 1);
+        assert_eq!(host.stopped, None);
+        assert_eq!(host.last_address, None);
+        let wait = (host.next_attempt.unwrap() - Utc::now()).num_seconds();
+        assert!((RETRY_BASE_SECS - 5..=RETRY_BASE_SECS).contains(&wait));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn fatal_failures_stop_until_forced() {
+        let replies = vec![(200, "203.0.113.7"), (200, "badauth"), (200, "203.0.113.7"), (200, "good 203.0.113.7")];
+        let (server, manager, _dir) = run_once(replies, "fatal");
+        {
+            let state = manager.state.lock().unwrap();
+            let host = &state.hosts[&1];
+            assert_eq!(host.failures, 1);
+            assert_eq!(host.next_attempt, None);
+            assert_eq!(host.stopped.as_deref(), Some("badauth"));
+        }
+
+        // 已停止的主机不会让后台线程再次检测或更新
+        let config = DdnsConfig {
+            lookup_url: Some(format!("{}/ip", server.url())),
+            hosts: vec![dyndns2_host(&server)],
+            ..DdnsConfig::default()
+        };
+        manager.run(&config, false);
+        assert_eq!(server.requests().len(), 2);
+
+        manager.run(&config, true);
+        assert_eq!(server.requests().len(), 4);
+        let state = manager.state.lock().unwrap();
+        let host = &state.hosts[&1];
+        assert_eq!(host.failures, 0);
+        assert_eq!(host.stopped, None);
+        assert_eq!(host.last_address, Some(address()));
+        assert_eq!(host.history.len(), 2);
+    }
+}
@@ -0,0 +1,285 @@
+// src/device_inventory.rs
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result};
+use crate::fs_util::write_atomic;
+use crate::mac_addr::MacAddr;
+use crate::udhcpd_registry::UdhcpdRegistry;
+
+/// 设备清单中保存的一台设备
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceRecord {
+    pub mac: MacAddr,
+    /// 用户起的名字
+    pub name: Option<String>,
+    /// 客户端通过 DHCP 上报的主机名
+    pub hostname: Option<String>,
+    pub notes: Option<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct DeviceFile {
+    #[serde(default)]
+    devices: Vec<DeviceRecord>,
+}
+
+/// 设备在某个 DHCP 实例中的地址（静态租约或当前租约）
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceAddress {
+    pub instance: String,
+    pub ip: Ipv4Addr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// `GET /api/devices` 中的一项：清单记录与静态租约、当前租约合并后的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct Device {
+    pub mac: MacAddr,
+    pub name: Option<String>,
+    pub hostname: Option<String>,
+    pub vendor: Option<&'static str>,
+    /// 本地管理的（通常是随机生成的）MAC 地址
+    pub locally_administered: bool,
+    pub notes: Option<String>,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub static_leases: Vec<DeviceAddress>,
+    pub active_leases: Vec<DeviceAddress>,
+}
+
+impl Device {
+    fn new(mac: MacAddr) -> Self {
+        Device {
+            mac,
+            name: None,
+            hostname: None,
+            vendor: mac.vendor(),
+            locally_administered: mac.is_locally_administered(),
+            notes: None,
+            first_seen: None,
+            last_seen: None,
+            static_leases: vec![],
+            active_leases: vec![],
+        }
+    }
+
+    /// 设备当前的 IPv4 地址：优先取静态租约，其次取当前租约
+    pub fn address(&self) -> Option<Ipv4Addr> {
+        self.static_leases
+            .first()
+            .or_else(|| self.active_leases.first())
+            .map(|a| a.ip)
+    }
+}
+
+/// 从各实例的当前租约中记录设备出现时间的间隔
+const OBSERVE_SECS: u64 = 300;
+
+/// 以 MAC 为键的设备清单，保存在 devices.toml 中
+pub struct DeviceInventory {
+    file_path: PathBuf,
+    devices: Mutex<BTreeMap<MacAddr, DeviceRecord>>,
+}
+
+impl DeviceInventory {
+    pub fn new<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let file_path = path.into();
+        let file: DeviceFile = match fs::read_to_string(&file_path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => DeviceFile::default(),
+            Err(e) => return Err(e),
+        };
+        let devices = file
+            .devices
+            .into_iter()
+            .map(|d| (d.mac, d))
+            .collect();
+        Ok(DeviceInventory {
+            file_path,
+            devices: Mutex::new(devices),
+        })
+    }
+
+    fn save(&self, devices: &BTreeMap<MacAddr, DeviceRecord>) -> Result<()> {
+        let file = DeviceFile {
+            devices: devices.values().cloned().collect(),
+        };
+        let content = toml::to_string_pretty(&file).map_err(|e| AppError::Process(e.to_string()))?;
+        write_atomic(&self.file_path, &content)
+    }
+
+    /// 设置设备的名字和备注，设备不在清单中时新建
+    pub fn update(&self, mac: MacAddr, name: Option<String>, notes: Option<String>) -> Result<DeviceRecord> {
+        if let Some(name) = &name {
+            if let Some(other) = self.find_by_name(name).filter(|d| d.mac != mac) {
+                return Err(AppError::InvalidInput(format!(
+                    "Name '{}' is already used by {}",
+                    name, other.mac
+                )));
+            }
+        }
+
+        let mut devices = self.devices.lock().unwrap();
+        let record = devices.entry(mac).or_insert_with(|| DeviceRecord {
+            mac,
+            name: None,
+            hostname: None,
+            notes: None,
+            first_seen: Utc::now(),
+            last_seen: None,
+        });
+        record.name = name.filter(|n| !n.trim().is_empty());
+        record.notes = notes.filter(|n| !n.trim().is_empty());
+        let record = record.clone();
+        self.save(&devices)?;
+        Ok(record)
+    }
+
+    pub fn remove(&self, mac: MacAddr) -> Result<()> {
+        let mut devices = self.devices.lock().unwrap();
+        if devices.remove(&mac).is_none() {
+            return Err(AppError::NotFound(format!("Device {}", mac)));
+        }
+        self.save(&devices)
+    }
+
+    pub fn get(&self, mac: MacAddr) -> Option<DeviceRecord> {
+        self.devices.lock().unwrap().get(&mac).cloned()
+    }
+
+    /// 按名字（不区分大小写）查找设备
+    pub fn find_by_name(&self, name: &str) -> Option<DeviceRecord> {
+        self.devices
+            .lock()
+            .unwrap()
+            .values()
+            .find(|d| d.name.as_deref().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+            .cloned()
+    }
+
+    /// 把各实例当前租约中出现的设备记入清单，并更新最后出现时间与主机名
+    pub fn observe(&self, registry: &UdhcpdRegistry) -> Result<()> {
+        let now = Utc::now();
+        let mut observed = Vec::new();
+        for (instance, manager) in registry.instances() {
+            match manager.leases() {
+                Ok(leases) => observed.extend(
+                    leases
+                        .into_iter()
+                        .filter_map(|lease| Some((MacAddr::from_str(&lease.mac).ok()?, lease.hostname))),
+                ),
+                Err(e) => eprintln!("Failed to read leases of DHCP instance '{}': {}", instance, e),
+            }
+        }
+        if observed.is_empty() {
+            return Ok(());
+        }
+
+        let mut devices = self.devices.lock().unwrap();
+        for (mac, hostname) in observed {
+            let record = devices.entry(mac).or_insert_with(|| DeviceRecord {
+                mac,
+                name: None,
+                hostname: None,
+                notes: None,
+                first_seen: now,
+                last_seen: None,
+            });
+            record.last_seen = Some(now);
+            if hostname.is_some() {
+                record.hostname = hostname;
+            }
+        }
+        self.save(&devices)
+    }
+
+    /// 在后台定期调用 `observe`
+    pub fn spawn(inventory: Arc<DeviceInventory>, registry: Arc<UdhcpdRegistry>) {
+        thread::spawn(move || loop {
+            if let Err(e) = inventory.observe(&registry) {
+                eprintln!("Failed to update device inventory: {}", e);
+            }
+            thread::sleep(Duration::from_secs(OBSERVE_SECS));
+        });
+    }
+
+    /// 合并清单、各实例的静态租约与当前租约，只读取不写入
+    pub fn list(&self, registry: &UdhcpdRegistry) -> Result<Vec<Device>> {
+        let mut result: BTreeMap<MacAddr, Device> = BTreeMap::new();
+
+        for (instance, manager) in registry.instances() {
+            if let Ok(config) = manager.read_config() {
+                for lease in config.static_leases {
+                    result
+                        .entry(lease.mac)
+                        .or_insert_with(|| Device::new(lease.mac))
+                        .static_leases
+                        .push(DeviceAddress {
+                            instance: instance.clone(),
+                            ip: lease.ip,
+                            expires_at: None,
+                        });
+                }
+            }
+            match manager.leases() {
+                Ok(leases) => {
+                    for lease in leases {
+                        let Ok(mac) = MacAddr::from_str(&lease.mac) else {
+                            continue;
+                        };
+                        result
+                            .entry(mac)
+                            .or_insert_with(|| Device::new(mac))
+                            .active_leases
+                            .push(DeviceAddress {
+                                instance: instance.clone(),
+                                ip: lease.ip,
+                                expires_at: lease.expires_at,
+                            });
+                    }
+                }
+                Err(e) => eprintln!("Failed to read leases of DHCP instance '{}': {}", instance, e),
+            }
+        }
+
+        let devices = self.devices.lock().unwrap();
+        for record in devices.values() {
+            let device = result
+                .entry(record.mac)
+                .or_insert_with(|| Device::new(record.mac));
+            device.name = record.name.clone();
+            device.hostname = record.hostname.clone();
+            device.notes = record.notes.clone();
+            device.first_seen = Some(record.first_seen);
+            device.last_seen = record.last_seen;
+        }
+        Ok(result.into_values().collect())
+    }
+
+    /// 按设备名解析出其当前的 IPv4 地址
+    pub fn resolve_ip(&self, name: &str, registry: &UdhcpdRegistry) -> Result<Ipv4Addr> {
+        let record = self
+            .find_by_name(name)
+            .ok_or_else(|| AppError::NotFound(format!("Device '{}'", name)))?;
+        self.list(registry)?
+            .into_iter()
+            .find(|d| d.mac == record.mac)
+            .and_then(|d| d.address())
+            .ok_or_else(|| AppError::InvalidInput(format!("Device '{}' has no known IPv4 address", name)))
+    }
+}
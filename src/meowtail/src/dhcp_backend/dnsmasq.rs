@@ -0,0 +1,209 @@
+// src/dhcp_backend/dnsmasq.rs
+
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{TimeZone, Utc};
+
+use super::options::DhcpOption;
+use super::{BackendKind, DhcpBackend, DhcpLease};
+use crate::error::{AppError, Result};
+use crate::mac_addr::MacAddr;
+use crate::supervisor::{ProcessSupervisor, SupervisorStatus};
+use crate::udhcpd_manager::{StaticLease, UdhcpdConfig};
+
+/// 每次渲染都会写入的固定行：关闭 DNS 功能并只绑定到所服务的接口
+const FIXED_LINES: &[&str] = &["port=0", "bind-interfaces"];
+
+/// 只提供 DHCP 服务的 dnsmasq
+pub struct DnsmasqBackend {
+    supervisor: ProcessSupervisor,
+}
+
+impl DnsmasqBackend {
+    pub fn new(executable_path: &str, config_path: &Path, pid_path: PathBuf) -> Self {
+        // 配置文件路径单独作为一个参数，监管器靠它区分本机上的多个 dnsmasq（DHCPv6、DNS）
+        let args = vec![
+            "--keep-in-foreground".to_string(),
+            "--log-facility=-".to_string(),
+            "--conf-file".to_string(),
+            config_path.to_string_lossy().into_owned(),
+        ];
+        DnsmasqBackend {
+            supervisor: ProcessSupervisor::new("dnsmasq", executable_path, args, pid_path),
+        }
+    }
+}
+
+impl DhcpBackend for DnsmasqBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Dnsmasq
+    }
+
+    fn start(&self) -> io::Result<()> {
+        self.supervisor.start()
+    }
+
+    fn stop(&self) -> io::Result<()> {
+        self.supervisor.stop()
+    }
+
+    fn adopt(&self) -> Option<u32> {
+        self.supervisor.adopt()
+    }
+
+    fn is_running(&self) -> bool {
+        self.supervisor.is_running()
+    }
+
+    fn status(&self) -> SupervisorStatus {
+        self.supervisor.status()
+    }
+
+    fn set_start_hook(&self, hook: Box<dyn Fn() + Send + Sync>) {
+        self.supervisor.set_spawn_hook(hook);
+    }
+
+    fn default_lease_file(&self) -> &'static str {
+        "/var/lib/misc/dnsmasq.leases"
+    }
+
+    fn parse_config(&self, content: &str) -> Result<UdhcpdConfig> {
+        parse(content)
+    }
+
+    fn render_config(&self, config: &UdhcpdConfig) -> String {
+        render(config)
+    }
+
+    /// 每行: `<expiry> <mac> <ip> <hostname|*> <client-id|*>`，expiry 为 0 表示永不过期
+    fn read_leases(&self, lease_file: &Path) -> Result<Vec<DhcpLease>> {
+        let content = match fs::read_to_string(lease_file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let leases = content
+            .lines()
+            .filter_map(|line| {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() < 4 {
+                    return None;
+                }
+                let ip = Ipv4Addr::from_str(parts[2]).ok()?;
+                let expiry = parts[0].parse::<i64>().ok()?;
+                Some(DhcpLease {
+                    mac: parts[1].to_lowercase(),
+                    ip,
+                    hostname: Some(parts[3].to_string()).filter(|h| h != "*"),
+                    expires_at: if expiry == 0 {
+                        None
+                    } else {
+                        Utc.timestamp_opt(expiry, 0).single()
+                    },
+                })
+            })
+            .collect();
+        Ok(leases)
+    }
+}
+
+/// 解析 meowtail 生成的 dnsmasq 配置，未识别的行原样保留
+pub fn parse(content: &str) -> Result<UdhcpdConfig> {
+    let mut config = UdhcpdConfig::default();
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') || FIXED_LINES.contains(&trimmed) {
+            continue;
+        }
+        let (key, value) = trimmed.split_once('=').unwrap_or((trimmed, ""));
+        let values: Vec<&str> = value.split(',').map(str::trim).collect();
+
+        match key {
+            "interface" => config.interface = Some(value.to_string()),
+            "dhcp-leasefile" => config.lease_file = Some(value.to_string()),
+            "pid-file" => config.pidfile = Some(value.to_string()),
+            "dhcp-lease-max" => config.max_leases = value.parse().ok(),
+            "dhcp-range" if values.len() >= 2 => {
+                config.start = Some(Ipv4Addr::from_str(values[0])?);
+                config.end = Some(Ipv4Addr::from_str(values[1])?);
+                if let Some(mask) = values.get(2).and_then(|v| Ipv4Addr::from_str(v).ok()) {
+                    config.subnet_mask = Some(mask);
+                }
+            }
+            "dhcp-host" if values.len() >= 2 => match MacAddr::from_str(values[0]) {
+                Ok(mac) => config.static_leases.push(StaticLease {
+                    mac,
+                    ip: Ipv4Addr::from_str(values[1])?,
+                }),
+                // 例如按主机名或客户端 ID 匹配的 dhcp-host，原样保留
+                Err(_) => config.remaining_lines.push(line.to_string()),
+            },
+            "dhcp-option" if values.len() >= 2 => match values[0] {
+                "option:router" | "3" => config.router = Some(Ipv4Addr::from_str(values[1])?),
+                "option:dns-server" | "6" => {
+                    config.dns_servers = values[1..]
+                        .iter()
+                        .map(|s| Ipv4Addr::from_str(s))
+                        .collect::<std::result::Result<_, _>>()
+                        .map_err(AppError::from)?;
+                }
+                _ => match DhcpOption::from_dnsmasq(value) {
+                    Some(option) => config.options.push(option),
+                    None => config.remaining_lines.push(line.to_string()),
+                },
+            },
+            _ => config.remaining_lines.push(line.to_string()),
+        }
+    }
+    Ok(config)
+}
+
+/// 渲染为 dnsmasq 配置：`dhcp-range`、`dhcp-host` 与 `dhcp-option` 行
+pub fn render(config: &UdhcpdConfig) -> String {
+    let mut content = String::new();
+
+    for line in FIXED_LINES {
+        content.push_str(&format!("{}\n", line));
+    }
+    if let Some(iface) = &config.interface {
+        content.push_str(&format!("interface={}\n", iface));
+    }
+    if let Some(lease_file) = &config.lease_file {
+        content.push_str(&format!("dhcp-leasefile={}\n", lease_file));
+    }
+    if let Some(pidfile) = &config.pidfile {
+        content.push_str(&format!("pid-file={}\n", pidfile));
+    }
+    if let Some(max_leases) = config.max_leases {
+        content.push_str(&format!("dhcp-lease-max={}\n", max_leases));
+    }
+    if let (Some(start), Some(end)) = (config.start, config.end) {
+        match config.subnet_mask {
+            Some(mask) => content.push_str(&format!("dhcp-range={},{},{}\n", start, end, mask)),
+            None => content.push_str(&format!("dhcp-range={},{}\n", start, end)),
+        }
+    }
+    if let Some(router) = config.router {
+        content.push_str(&format!("dhcp-option=option:router,{}\n", router));
+    }
+    if !config.dns_servers.is_empty() {
+        let dns_list: Vec<String> = config.dns_servers.iter().map(|ip| ip.to_string()).collect();
+        content.push_str(&format!("dhcp-option=option:dns-server,{}\n", dns_list.join(",")));
+    }
+    for option in &config.options {
+        content.push_str(&format!("{}\n", option.dnsmasq_line()));
+    }
+    for lease in &config.static_leases {
+        content.push_str(&format!("dhcp-host={},{}\n", lease.mac, lease.ip));
+    }
+    for line in &config.remaining_lines {
+        content.push_str(&format!("{}\n", line));
+    }
+    content
+}
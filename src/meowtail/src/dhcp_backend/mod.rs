@@ -0,0 +1,138 @@
+// src/dhcp_backend/mod.rs
+
+pub mod dnsmasq;
+pub mod native;
+pub mod options;
+pub mod packet;
+pub mod rogue;
+pub mod udhcpd;
+
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::supervisor::SupervisorStatus;
+use crate::udhcpd_manager::UdhcpdConfig;
+
+/// 可选的 DHCP 服务端实现，在 meowtail.toml 的 `dhcp_backend` 中选择
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Udhcpd,
+    Dnsmasq,
+    /// meowtail 内置的 DHCPv4 服务端，不依赖外部程序
+    Native,
+}
+
+impl BackendKind {
+    /// 配置文件、PID 文件和租约文件名的前缀
+    pub fn file_stem(&self) -> &'static str {
+        match self {
+            BackendKind::Udhcpd => "udhcpd",
+            BackendKind::Dnsmasq => "dnsmasq-dhcp",
+            BackendKind::Native => "meowtail-dhcpd",
+        }
+    }
+}
+
+/// 服务端当前分配出去的一个动态租约
+#[derive(Debug, Clone, Serialize)]
+pub struct DhcpLease {
+    pub mac: String,
+    pub ip: Ipv4Addr,
+    pub hostname: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// 租约事件的类型，对应服务端发出或收到的 DHCP 报文
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaseEventKind {
+    Offer,
+    Ack,
+    Nak,
+    Release,
+    Decline,
+    Inform,
+    /// 在实例的接口上发现了其他 DHCP 服务端
+    #[serde(rename = "rogue-server")]
+    RogueServer,
+}
+
+/// 每个实例保留的最近租约事件数
+pub(crate) const EVENT_LOG_LEN: usize = 100;
+
+/// 一条租约事件。报文交互事件只有内置服务端才会产生，`rogue-server` 事件对所有服务端都会记录
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaseEvent {
+    pub at: DateTime<Utc>,
+    pub kind: LeaseEventKind,
+    pub mac: String,
+    pub ip: Option<Ipv4Addr>,
+    pub hostname: Option<String>,
+    /// 发出报文的服务端（server identifier），只用于 `rogue-server` 事件
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<Ipv4Addr>,
+}
+
+/// DHCP 服务端实现：进程的启停与状态、配置文件的方言以及租约文件格式。
+/// 配置锁、校验、ETag 和自动应用等与实现无关的逻辑留在 `UdhcpdManager` 中。
+pub trait DhcpBackend: Send + Sync {
+    fn kind(&self) -> BackendKind;
+
+    fn start(&self) -> io::Result<()>;
+
+    fn stop(&self) -> io::Result<()>;
+
+    /// 接管已经在运行的同一实例，返回其 PID
+    fn adopt(&self) -> Option<u32>;
+
+    fn is_running(&self) -> bool;
+
+    fn status(&self) -> SupervisorStatus;
+
+    /// 每次服务端（重新）加载配置前调用
+    fn set_start_hook(&self, hook: Box<dyn Fn() + Send + Sync>);
+
+    /// 让服务端把内存中的租约立即写入租约文件；默认实现认为租约文件总是最新的
+    fn flush_leases(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// 配置中未指定租约文件时使用的路径
+    fn default_lease_file(&self) -> &'static str;
+
+    fn parse_config(&self, content: &str) -> Result<UdhcpdConfig>;
+
+    fn render_config(&self, config: &UdhcpdConfig) -> String;
+
+    fn read_leases(&self, lease_file: &Path) -> Result<Vec<DhcpLease>>;
+
+    /// 最近的租约事件，按时间先后排列
+    fn events(&self) -> Vec<LeaseEvent> {
+        Vec::new()
+    }
+}
+
+/// 按类型创建服务端实现
+pub fn create(kind: BackendKind, config_path: &Path, pid_path: PathBuf) -> Box<dyn DhcpBackend> {
+    match kind {
+        BackendKind::Udhcpd => Box::new(udhcpd::UdhcpdBackend::new("udhcpd", config_path, pid_path)),
+        BackendKind::Dnsmasq => Box::new(dnsmasq::DnsmasqBackend::new("dnsmasq", config_path, pid_path)),
+        BackendKind::Native => Box::new(native::NativeBackend::new(config_path)),
+    }
+}
+
+/// 把 6 字节的硬件地址格式化为 `aa:bb:cc:dd:ee:ff`
+pub(crate) fn format_mac(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
@@ -0,0 +1,750 @@
+// src/dhcp_backend/native.rs
+
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use nix::sys::socket::{self, sockopt, AddressFamily, SockFlag, SockType, SockaddrIn};
+use serde::{Deserialize, Serialize};
+
+use super::options::OptionValue;
+use super::packet::*;
+use super::{BackendKind, DhcpBackend, DhcpLease, LeaseEvent, LeaseEventKind, EVENT_LOG_LEN};
+use crate::error::{AppError, Result};
+use crate::supervisor::SupervisorStatus;
+use crate::udhcpd_manager::UdhcpdConfig;
+
+pub(super) const SERVER_PORT: u16 = 67;
+pub(super) const CLIENT_PORT: u16 = 68;
+/// 与 udhcpd 相同的默认租期：10 天
+const DEFAULT_LEASE_TIME: u32 = 864000;
+/// OFFER 发出后为客户端保留地址的时长
+const OFFER_TIMEOUT: u32 = 60;
+/// 接收超时，决定停止服务时的最大等待时间
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const LOG_TAIL_LINES: usize = 20;
+
+/// 租约数据库中一条地址的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LeaseState {
+    /// 已发出 OFFER，等待客户端 REQUEST
+    Offered,
+    Bound,
+    /// 客户端报告地址冲突，在租期内不再分配
+    Declined,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LeaseRecord {
+    mac: String,
+    ip: Ipv4Addr,
+    hostname: Option<String>,
+    expires_at: DateTime<Utc>,
+    state: LeaseState,
+}
+
+/// 以 JSON 保存在租约文件中的租约数据库，每次绑定、释放或拒绝后整体原子写回
+struct LeaseDb {
+    path: PathBuf,
+    records: Vec<LeaseRecord>,
+}
+
+impl LeaseDb {
+    /// 读取租约文件，文件不存在时为空；未完成的 OFFER 不会被持久化
+    fn load(path: &Path) -> Result<Self> {
+        let records = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str::<Vec<LeaseRecord>>(&content)
+                .map_err(|e| AppError::ConfigParse(format!("{:?}: {}", path, e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        Ok(LeaseDb {
+            path: path.to_path_buf(),
+            records: records
+                .into_iter()
+                .filter(|r| r.state != LeaseState::Offered)
+                .collect(),
+        })
+    }
+
+    fn save(&self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let persistent: Vec<&LeaseRecord> = self
+            .records
+            .iter()
+            .filter(|r| r.state != LeaseState::Offered)
+            .collect();
+        let content = serde_json::to_string_pretty(&persistent).map_err(io::Error::other)?;
+        let mut tmp_path = self.path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, &self.path)
+    }
+
+    fn by_mac(&self, mac: &str) -> Option<&LeaseRecord> {
+        self.records.iter().find(|r| r.mac == mac)
+    }
+
+    /// 写入一条记录，替换同一客户端或同一地址上的旧记录
+    fn upsert(&mut self, record: LeaseRecord) {
+        // 被拒绝的地址没有所属客户端，只按地址替换
+        self.records
+            .retain(|r| r.ip != record.ip && (record.mac.is_empty() || r.mac != record.mac));
+        self.records.push(record);
+    }
+
+    fn remove_mac(&mut self, mac: &str) {
+        self.records.retain(|r| r.mac != mac);
+    }
+}
+
+/// DHCPv4 协议处理：根据配置与租约数据库回应客户端报文，不涉及网络 I/O，
+/// 因此可以直接用构造的报文驱动
+pub struct DhcpServer {
+    config: UdhcpdConfig,
+    server_ip: Ipv4Addr,
+    lease_time: u32,
+    db: LeaseDb,
+}
+
+impl DhcpServer {
+    pub fn new(config: UdhcpdConfig, server_ip: Ipv4Addr, lease_file: &Path) -> Result<Self> {
+        if config.start.is_none() || config.end.is_none() {
+            return Err(AppError::InvalidInput(
+                "The address range (start/end) must be configured.".to_string(),
+            ));
+        }
+        Ok(DhcpServer {
+            lease_time: lease_time(&config),
+            db: LeaseDb::load(lease_file)?,
+            config,
+            server_ip,
+        })
+    }
+
+    /// 处理一个客户端报文，返回对应的租约事件和需要发出的回复（RELEASE 与 DECLINE 没有回复）
+    pub fn handle(&mut self, request: &DhcpPacket) -> Option<(LeaseEvent, Option<DhcpPacket>)> {
+        if request.op != BOOTREQUEST {
+            return None;
+        }
+        let mac = request.client_mac();
+        match request.message_type()? {
+            MessageType::Discover => self.discover(request, &mac).map(|(reply, e)| (e, Some(reply))),
+            MessageType::Request => self.request(request, &mac).map(|(reply, e)| (e, Some(reply))),
+            MessageType::Release => self.release(request, &mac).map(|e| (e, None)),
+            MessageType::Decline => self.decline(request, &mac).map(|e| (e, None)),
+            MessageType::Inform => self.inform(request, &mac).map(|(reply, e)| (e, Some(reply))),
+            _ => None,
+        }
+    }
+
+    fn discover(&mut self, request: &DhcpPacket, mac: &str) -> Option<(DhcpPacket, LeaseEvent)> {
+        let requested = request.ip_option(OPT_REQUESTED_IP);
+        let Some(ip) = self.select_address(mac, requested) else {
+            eprintln!("[dhcpd] No free address to offer to {}", mac);
+            return None;
+        };
+
+        let now = Utc::now();
+        // 已经绑定的租约保持原状，客户端重新发现时不应被降级
+        let bound = self
+            .db
+            .by_mac(mac)
+            .is_some_and(|r| r.ip == ip && r.state == LeaseState::Bound && r.expires_at > now);
+        if !bound {
+            self.db.upsert(LeaseRecord {
+                mac: mac.to_string(),
+                ip,
+                hostname: request.hostname(),
+                expires_at: now + chrono::Duration::seconds(OFFER_TIMEOUT as i64),
+                state: LeaseState::Offered,
+            });
+        }
+
+        let mut reply = DhcpPacket::reply(request, MessageType::Offer);
+        reply.yiaddr = ip;
+        self.add_lease_options(&mut reply);
+        Some((reply, event(LeaseEventKind::Offer, mac, Some(ip), request.hostname())))
+    }
+
+    fn request(&mut self, request: &DhcpPacket, mac: &str) -> Option<(DhcpPacket, LeaseEvent)> {
+        let server_id = request.ip_option(OPT_SERVER_ID);
+        if server_id.is_some_and(|id| id != self.server_ip) {
+            // 客户端选择了另一台服务器的 OFFER
+            if self.db.by_mac(mac).is_some_and(|r| r.state == LeaseState::Offered) {
+                self.db.remove_mac(mac);
+            }
+            return None;
+        }
+
+        let ip = request
+            .ip_option(OPT_REQUESTED_IP)
+            .unwrap_or(request.ciaddr);
+        if ip.is_unspecified() {
+            return None;
+        }
+
+        if !self.can_assign(mac, ip) {
+            let mut reply = DhcpPacket::reply(request, MessageType::Nak);
+            reply.set_ip_option(OPT_SERVER_ID, &[self.server_ip]);
+            if !request.giaddr.is_unspecified() {
+                reply.flags |= FLAG_BROADCAST;
+            }
+            return Some((reply, event(LeaseEventKind::Nak, mac, Some(ip), request.hostname())));
+        }
+
+        let hostname = request
+            .hostname()
+            .or_else(|| self.db.by_mac(mac).and_then(|r| r.hostname.clone()));
+        self.db.upsert(LeaseRecord {
+            mac: mac.to_string(),
+            ip,
+            hostname: hostname.clone(),
+            expires_at: Utc::now() + chrono::Duration::seconds(self.lease_time as i64),
+            state: LeaseState::Bound,
+        });
+        self.persist();
+
+        let mut reply = DhcpPacket::reply(request, MessageType::Ack);
+        reply.ciaddr = request.ciaddr;
+        reply.yiaddr = ip;
+        self.add_lease_options(&mut reply);
+        Some((reply, event(LeaseEventKind::Ack, mac, Some(ip), hostname)))
+    }
+
+    fn release(&mut self, request: &DhcpPacket, mac: &str) -> Option<LeaseEvent> {
+        let record = self.db.by_mac(mac).filter(|r| r.ip == request.ciaddr)?;
+        let hostname = record.hostname.clone();
+        self.db.remove_mac(mac);
+        self.persist();
+        Some(event(LeaseEventKind::Release, mac, Some(request.ciaddr), hostname))
+    }
+
+    fn decline(&mut self, request: &DhcpPacket, mac: &str) -> Option<LeaseEvent> {
+        let ip = request.ip_option(OPT_REQUESTED_IP)?;
+        // 被拒绝的地址不属于任何客户端，在一个租期内不再分配
+        self.db.remove_mac(mac);
+        self.db.upsert(LeaseRecord {
+            mac: String::new(),
+            ip,
+            hostname: None,
+            expires_at: Utc::now() + chrono::Duration::seconds(self.lease_time as i64),
+            state: LeaseState::Declined,
+        });
+        self.persist();
+        Some(event(LeaseEventKind::Decline, mac, Some(ip), request.hostname()))
+    }
+
+    fn inform(&mut self, request: &DhcpPacket, mac: &str) -> Option<(DhcpPacket, LeaseEvent)> {
+        // INFORM 的客户端已自行配置地址，只回复网络参数，不带租期
+        let mut reply = DhcpPacket::reply(request, MessageType::Ack);
+        reply.ciaddr = request.ciaddr;
+        self.add_network_options(&mut reply);
+        Some((reply, event(LeaseEventKind::Inform, mac, Some(request.ciaddr), request.hostname())))
+    }
+
+    /// 为客户端挑选地址：静态租约 > 该客户端之前的地址 > 客户端请求的地址 > 地址池中第一个空闲地址
+    fn select_address(&self, mac: &str, requested: Option<Ipv4Addr>) -> Option<Ipv4Addr> {
+        if let Some(ip) = self.static_ip(mac) {
+            return Some(ip);
+        }
+        if let Some(record) = self.db.by_mac(mac) {
+            if self.can_assign(mac, record.ip) {
+                return Some(record.ip);
+            }
+        }
+        if let Some(ip) = requested {
+            if self.can_assign(mac, ip) {
+                return Some(ip);
+            }
+        }
+        let (start, end) = self.range();
+        (start..=end).map(Ipv4Addr::from).find(|ip| self.can_assign(mac, *ip))
+    }
+
+    /// `ip` 能否分配给 `mac`
+    fn can_assign(&self, mac: &str, ip: Ipv4Addr) -> bool {
+        if let Some(static_ip) = self.static_ip(mac) {
+            return ip == static_ip;
+        }
+        let (start, end) = self.range();
+        if !(start..=end).contains(&u32::from(ip)) {
+            return false;
+        }
+        if ip == self.server_ip || Some(ip) == self.config.router {
+            return false;
+        }
+        if self.config.static_leases.iter().any(|l| l.ip == ip) {
+            return false;
+        }
+        let now = Utc::now();
+        !self
+            .db
+            .records
+            .iter()
+            .any(|r| r.ip == ip && r.mac != mac && r.expires_at > now)
+    }
+
+    fn static_ip(&self, mac: &str) -> Option<Ipv4Addr> {
+        self.config
+            .static_leases
+            .iter()
+            .find(|l| l.mac.to_string() == mac)
+            .map(|l| l.ip)
+    }
+
+    fn range(&self) -> (u32, u32) {
+        (
+            self.config.start.map(u32::from).unwrap_or_default(),
+            self.config.end.map(u32::from).unwrap_or_default(),
+        )
+    }
+
+    fn add_lease_options(&self, reply: &mut DhcpPacket) {
+        reply.set_option(OPT_LEASE_TIME, self.lease_time.to_be_bytes().to_vec());
+        reply.set_option(OPT_RENEWAL_TIME, (self.lease_time / 2).to_be_bytes().to_vec());
+        reply.set_option(OPT_REBINDING_TIME, (self.lease_time / 8 * 7).to_be_bytes().to_vec());
+        self.add_network_options(reply);
+    }
+
+    fn add_network_options(&self, reply: &mut DhcpPacket) {
+        reply.set_ip_option(OPT_SERVER_ID, &[self.server_ip]);
+        if let Some(mask) = self.config.subnet_mask {
+            reply.set_ip_option(OPT_SUBNET_MASK, &[mask]);
+        }
+        if let Some(router) = self.config.router {
+            reply.set_ip_option(OPT_ROUTER, &[router]);
+        }
+        if !self.config.dns_servers.is_empty() {
+            reply.set_ip_option(OPT_DNS_SERVER, &self.config.dns_servers);
+        }
+        // 租期相关的选项由 add_lease_options 单独处理
+        for option in self.config.options.iter().filter(|o| o.code != OPT_LEASE_TIME) {
+            reply.set_option(option.code, option.value.encode());
+        }
+    }
+
+    fn persist(&self) {
+        if let Err(e) = self.db.save() {
+            eprintln!("[dhcpd] Failed to save leases to {:?}: {}", self.db.path, e);
+        }
+    }
+}
+
+/// 租期来自选项 51（udhcpd 语法的 `option lease <秒>`）
+fn lease_time(config: &UdhcpdConfig) -> u32 {
+    config
+        .options
+        .iter()
+        .find_map(|option| match option.value {
+            OptionValue::U32(secs) if option.code == OPT_LEASE_TIME => Some(secs),
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_LEASE_TIME)
+}
+
+fn event(kind: LeaseEventKind, mac: &str, ip: Option<Ipv4Addr>, hostname: Option<String>) -> LeaseEvent {
+    LeaseEvent {
+        at: Utc::now(),
+        kind,
+        mac: mac.to_string(),
+        ip,
+        hostname,
+        server: None,
+    }
+}
+
+/// 回复的目的地址（RFC 2131 4.1）：经中继时发给中继，续租时单播给客户端，否则广播
+fn reply_destination(request: &DhcpPacket, reply: &DhcpPacket) -> SocketAddr {
+    if !request.giaddr.is_unspecified() {
+        return SocketAddrV4::new(request.giaddr, SERVER_PORT).into();
+    }
+    if !request.ciaddr.is_unspecified() && reply.message_type() != Some(MessageType::Nak) {
+        return SocketAddrV4::new(request.ciaddr, CLIENT_PORT).into();
+    }
+    SocketAddrV4::new(Ipv4Addr::BROADCAST, CLIENT_PORT).into()
+}
+
+/// 在 `socket` 上服务，直到 `stop` 被置位。每个事件写入 `events` 并打印一行日志
+pub fn serve(socket: UdpSocket, mut server: DhcpServer, stop: Arc<AtomicBool>, events: Arc<Mutex<VecDeque<LeaseEvent>>>) {
+    let mut buf = [0u8; 1500];
+    while !stop.load(Ordering::SeqCst) {
+        let len = match socket.recv_from(&mut buf) {
+            Ok((len, _)) => len,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+            Err(e) => {
+                eprintln!("[dhcpd] Receive failed: {}", e);
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+        };
+        let Some(request) = DhcpPacket::parse(&buf[..len]) else {
+            continue;
+        };
+        let Some((event, reply)) = server.handle(&request) else {
+            continue;
+        };
+
+        if let Some(reply) = reply {
+            let dest = reply_destination(&request, &reply);
+            if let Err(e) = socket.send_to(&reply.to_bytes(), dest) {
+                eprintln!("[dhcpd] Failed to send {:?} to {}: {}", event.kind, dest, e);
+                continue;
+            }
+        }
+        println!(
+            "[dhcpd] {:?} {} {}{}",
+            event.kind,
+            event.ip.map(|ip| ip.to_string()).unwrap_or_default(),
+            event.mac,
+            event.hostname.as_deref().map(|h| format!(" ({})", h)).unwrap_or_default()
+        );
+        let mut events = events.lock().unwrap();
+        if events.len() >= EVENT_LOG_LEN {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+}
+
+/// 绑定到 `interface` 上的 67 端口。SO_BINDTODEVICE 让多个实例可以各自服务一个接口，
+/// 广播回复也只从该接口发出
+pub(super) fn bind_socket(interface: &str, port: u16) -> io::Result<UdpSocket> {
+    let fd = socket::socket(AddressFamily::Inet, SockType::Datagram, SockFlag::SOCK_CLOEXEC, None)?;
+    socket::setsockopt(&fd, sockopt::ReuseAddr, &true)?;
+    socket::setsockopt(&fd, sockopt::Broadcast, &true)?;
+    socket::setsockopt(&fd, sockopt::BindToDevice, &OsString::from(interface))?;
+    socket::bind(
+        std::os::fd::AsRawFd::as_raw_fd(&fd),
+        &SockaddrIn::from(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)),
+    )?;
+    let socket = UdpSocket::from(fd);
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+    Ok(socket)
+}
+
+/// 接口上的第一个 IPv4 地址，作为 server identifier
+pub(super) fn interface_address(interface: &str) -> Option<Ipv4Addr> {
+    nix::ifaddrs::getifaddrs()
+        .ok()?
+        .filter(|ifa| ifa.interface_name == interface)
+        .find_map(|ifa| ifa.address?.as_sockaddr_in().map(|a| a.ip()))
+}
+
+struct Running {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+    started_at: DateTime<Utc>,
+}
+
+/// 在 meowtail 进程内运行的 DHCPv4 服务端，配置使用 udhcpd 的语法
+pub struct NativeBackend {
+    config_path: PathBuf,
+    running: Mutex<Option<Running>>,
+    start_hook: Mutex<Option<Box<dyn Fn() + Send + Sync>>>,
+    events: Arc<Mutex<VecDeque<LeaseEvent>>>,
+}
+
+impl NativeBackend {
+    pub fn new(config_path: &Path) -> Self {
+        NativeBackend {
+            config_path: config_path.to_path_buf(),
+            running: Mutex::new(None),
+            start_hook: Mutex::new(None),
+            events: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+}
+
+impl DhcpBackend for NativeBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Native
+    }
+
+    fn start(&self) -> io::Result<()> {
+        let mut running = self.running.lock().unwrap();
+        if running.as_ref().is_some_and(|r| !r.handle.is_finished()) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "DHCP server is already running"));
+        }
+
+        if let Some(hook) = self.start_hook.lock().unwrap().as_ref() {
+            hook();
+        }
+        let content = fs::read_to_string(&self.config_path)?;
+        let config = UdhcpdConfig::parse(&content).map_err(io::Error::other)?;
+        let interface = config
+            .interface
+            .clone()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No interface configured"))?;
+        let server_ip = interface_address(&interface).or(config.router).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                format!("Interface {} has no IPv4 address and no router is configured", interface),
+            )
+        })?;
+        let lease_file = PathBuf::from(config.lease_file.clone().unwrap_or_else(|| self.default_lease_file().to_string()));
+        let server = DhcpServer::new(config, server_ip, &lease_file).map_err(io::Error::other)?;
+        let socket = bind_socket(&interface, SERVER_PORT)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let events = self.events.clone();
+        let handle = thread::Builder::new()
+            .name(format!("dhcpd-{}", interface))
+            .spawn(move || serve(socket, server, thread_stop, events))?;
+        println!("[dhcpd] Serving DHCP on {} as {}", interface, server_ip);
+
+        *running = Some(Running {
+            stop,
+            handle,
+            started_at: Utc::now(),
+        });
+        Ok(())
+    }
+
+    fn stop(&self) -> io::Result<()> {
+        if let Some(running) = self.running.lock().unwrap().take() {
+            running.stop.store(true, Ordering::SeqCst);
+            let _ = running.handle.join();
+        }
+        Ok(())
+    }
+
+    /// 服务端运行在本进程内，没有可接管的外部进程
+    fn adopt(&self) -> Option<u32> {
+        None
+    }
+
+    fn is_running(&self) -> bool {
+        self.running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|r| !r.handle.is_finished())
+    }
+
+    fn status(&self) -> SupervisorStatus {
+        let started_at = self.running.lock().unwrap().as_ref().map(|r| r.started_at);
+        let events = self.events();
+        SupervisorStatus {
+            running: self.is_running(),
+            pid: None,
+            adopted: false,
+            started_at,
+            restart_count: 0,
+            last_exit: None,
+            restart_pending_ms: None,
+            recent_output: events
+                .iter()
+                .rev()
+                .take(LOG_TAIL_LINES)
+                .rev()
+                .map(|e| format!("{} {:?} {} {}", e.at.to_rfc3339(), e.kind, e.mac, e.ip.map(|ip| ip.to_string()).unwrap_or_default()))
+                .collect(),
+        }
+    }
+
+    fn set_start_hook(&self, hook: Box<dyn Fn() + Send + Sync>) {
+        *self.start_hook.lock().unwrap() = Some(hook);
+    }
+
+    fn default_lease_file(&self) -> &'static str {
+        "/var/lib/misc/meowtail-dhcpd.leases"
+    }
+
+    fn parse_config(&self, content: &str) -> Result<UdhcpdConfig> {
+        UdhcpdConfig::parse(content)
+    }
+
+    fn render_config(&self, config: &UdhcpdConfig) -> String {
+        config.render()
+    }
+
+    fn read_leases(&self, lease_file: &Path) -> Result<Vec<DhcpLease>> {
+        let now = Utc::now();
+        Ok(LeaseDb::load(lease_file)?
+            .records
+            .into_iter()
+            .filter(|r| r.state == LeaseState::Bound && r.expires_at > now)
+            .map(|r| DhcpLease {
+                mac: r.mac,
+                ip: r.ip,
+                hostname: r.hostname,
+                expires_at: Some(r.expires_at),
+            })
+            .collect())
+    }
+
+    fn events(&self) -> Vec<LeaseEvent> {
+        self.events.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mac_addr::MacAddr;
+    use crate::udhcpd_manager::StaticLease;
+    use std::str::FromStr;
+
+    const SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 1);
+
+    /// 通过一对回环 UDP 套接字收发报文，覆盖报文序列化与 `handle` 的完整往返
+    struct Loopback {
+        server: DhcpServer,
+        server_socket: UdpSocket,
+        client_socket: UdpSocket,
+        lease_file: PathBuf,
+    }
+
+    impl Loopback {
+        fn new(name: &str, config: UdhcpdConfig) -> Self {
+            let dir = std::env::temp_dir().join(format!("meowtail-native-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            let lease_file = dir.join("leases.json");
+            let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            client_socket.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+            Loopback {
+                server: DhcpServer::new(config, SERVER_IP, &lease_file).unwrap(),
+                server_socket,
+                client_socket,
+                lease_file,
+            }
+        }
+
+        fn exchange(&mut self, request: &DhcpPacket) -> Option<DhcpPacket> {
+            let server_addr = self.server_socket.local_addr().unwrap();
+            self.client_socket.send_to(&request.to_bytes(), server_addr).unwrap();
+
+            let mut buf = [0u8; 1500];
+            let (len, from) = self.server_socket.recv_from(&mut buf).unwrap();
+            let received = DhcpPacket::parse(&buf[..len]).expect("server failed to parse request");
+            let (_, reply) = self.server.handle(&received)?;
+            self.server_socket.send_to(&reply?.to_bytes(), from).unwrap();
+
+            let (len, _) = self.client_socket.recv_from(&mut buf).unwrap();
+            Some(DhcpPacket::parse(&buf[..len]).expect("client failed to parse reply"))
+        }
+
+        /// DISCOVER + REQUEST，返回绑定到的地址
+        fn bind(&mut self, xid: u32, mac: [u8; 6]) -> Option<Ipv4Addr> {
+            let offer = self.exchange(&DhcpPacket::discover(xid, mac))?;
+            let ack = self.exchange(&request(xid, mac, offer.yiaddr, Some(SERVER_IP)))?;
+            (ack.message_type() == Some(MessageType::Ack)).then_some(ack.yiaddr)
+        }
+    }
+
+    impl Drop for Loopback {
+        fn drop(&mut self) {
+            if let Some(dir) = self.lease_file.parent() {
+                let _ = fs::remove_dir_all(dir);
+            }
+        }
+    }
+
+    fn config() -> UdhcpdConfig {
+        let mut config = UdhcpdConfig::with_defaults("lo");
+        config.start = Some(Ipv4Addr::new(192, 168, 1, 100));
+        config.end = Some(Ipv4Addr::new(192, 168, 1, 102));
+        config
+    }
+
+    fn request(xid: u32, mac: [u8; 6], ip: Ipv4Addr, server_id: Option<Ipv4Addr>) -> DhcpPacket {
+        let mut request = DhcpPacket::discover(xid, mac);
+        request.set_option(OPT_MESSAGE_TYPE, vec![MessageType::Request.as_u8()]);
+        request.set_ip_option(OPT_REQUESTED_IP, &[ip]);
+        if let Some(id) = server_id {
+            request.set_ip_option(OPT_SERVER_ID, &[id]);
+        }
+        request
+    }
+
+    #[test]
+    fn discover_offer_request_ack() {
+        let mut lo = Loopback::new("ack", config());
+        let mac = [0x02, 0, 0, 0, 0, 1];
+
+        let offer = lo.exchange(&DhcpPacket::discover(1, mac)).unwrap();
+        assert_eq!(offer.message_type(), Some(MessageType::Offer));
+        assert_eq!(offer.xid, 1);
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 100));
+        assert_eq!(offer.ip_option(OPT_SERVER_ID), Some(SERVER_IP));
+        assert_eq!(offer.ip_option(OPT_SUBNET_MASK), Some(Ipv4Addr::new(255, 255, 255, 0)));
+        assert_eq!(offer.option(OPT_LEASE_TIME), Some(&DEFAULT_LEASE_TIME.to_be_bytes()[..]));
+
+        let ack = lo.exchange(&request(1, mac, offer.yiaddr, Some(SERVER_IP))).unwrap();
+        assert_eq!(ack.message_type(), Some(MessageType::Ack));
+        assert_eq!(ack.yiaddr, offer.yiaddr);
+
+        // 绑定后的租约写入租约文件，重新发现时仍拿到同一地址
+        let saved = fs::read_to_string(&lo.lease_file).unwrap();
+        assert!(saved.contains("02:00:00:00:00:01") && saved.contains("\"bound\""));
+        let again = lo.exchange(&DhcpPacket::discover(2, mac)).unwrap();
+        assert_eq!(again.yiaddr, offer.yiaddr);
+    }
+
+    #[test]
+    fn request_for_unavailable_address_is_nak() {
+        let mut lo = Loopback::new("nak", config());
+        let first = [0x02, 0, 0, 0, 0, 1];
+        let second = [0x02, 0, 0, 0, 0, 2];
+        let bound = lo.bind(1, first).unwrap();
+
+        // 地址池外的地址与其他客户端已绑定的地址都应被拒绝
+        let outside = lo.exchange(&request(2, second, Ipv4Addr::new(192, 168, 1, 150), None)).unwrap();
+        assert_eq!(outside.message_type(), Some(MessageType::Nak));
+        assert_eq!(outside.ip_option(OPT_SERVER_ID), Some(SERVER_IP));
+        let taken = lo.exchange(&request(3, second, bound, Some(SERVER_IP))).unwrap();
+        assert_eq!(taken.message_type(), Some(MessageType::Nak));
+
+        // 选择了其他服务器的 REQUEST 不回复
+        assert!(lo.exchange(&request(4, second, bound, Some(Ipv4Addr::new(192, 168, 1, 2)))).is_none());
+    }
+
+    #[test]
+    fn static_lease_is_offered_and_enforced() {
+        let mut config = config();
+        let mac = [0x02, 0, 0, 0, 0, 9];
+        let static_ip = Ipv4Addr::new(192, 168, 1, 50);
+        config.static_leases.push(StaticLease {
+            mac: MacAddr::from_str("02:00:00:00:00:09").unwrap(),
+            ip: static_ip,
+        });
+        let mut lo = Loopback::new("static", config);
+
+        assert_eq!(lo.bind(1, mac), Some(static_ip));
+        let other = lo.exchange(&request(2, mac, Ipv4Addr::new(192, 168, 1, 100), None)).unwrap();
+        assert_eq!(other.message_type(), Some(MessageType::Nak));
+        // 其他客户端不能拿到静态租约的地址
+        let stolen = lo.exchange(&request(3, [0x02, 0, 0, 0, 0, 1], static_ip, None)).unwrap();
+        assert_eq!(stolen.message_type(), Some(MessageType::Nak));
+    }
+
+    #[test]
+    fn exhausted_pool_sends_no_offer() {
+        let mut lo = Loopback::new("exhausted", config());
+        for i in 1..=3u8 {
+            assert_eq!(lo.bind(i as u32, [0x02, 0, 0, 0, 0, i]), Some(Ipv4Addr::new(192, 168, 1, 99 + i)));
+        }
+        assert!(lo.exchange(&DhcpPacket::discover(4, [0x02, 0, 0, 0, 0, 4])).is_none());
+
+        // 释放一个地址后可以再分配
+        let mut release = DhcpPacket::discover(5, [0x02, 0, 0, 0, 0, 2]);
+        release.set_option(OPT_MESSAGE_TYPE, vec![MessageType::Release.as_u8()]);
+        release.ciaddr = Ipv4Addr::new(192, 168, 1, 101);
+        assert!(lo.exchange(&release).is_none());
+        assert_eq!(lo.bind(6, [0x02, 0, 0, 0, 0, 4]), Some(Ipv4Addr::new(192, 168, 1, 101)));
+    }
+}
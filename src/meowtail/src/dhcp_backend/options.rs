@@ -0,0 +1,595 @@
+// src/dhcp_backend/options.rs
+
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ValidationIssue;
+
+/// 选项值的类型，决定 JSON 中 `value` 的写法以及在报文中的编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OptionType {
+    Ip,
+    IpList,
+    String,
+    U8,
+    U16,
+    U32,
+    Hex,
+    StaticRoutes,
+}
+
+impl OptionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OptionType::Ip => "ip",
+            OptionType::IpList => "ip-list",
+            OptionType::String => "string",
+            OptionType::U8 => "u8",
+            OptionType::U16 => "u16",
+            OptionType::U32 => "u32",
+            OptionType::Hex => "hex",
+            OptionType::StaticRoutes => "static-routes",
+        }
+    }
+}
+
+/// 已知选项的定义
+#[derive(Debug, Serialize)]
+pub struct OptionDef {
+    pub code: u8,
+    pub name: &'static str,
+    /// udhcpd.conf 中的选项名，没有时以 `option 0xNN <hex>` 形式写入
+    pub udhcpd_name: Option<&'static str>,
+    #[serde(rename = "type")]
+    pub kind: OptionType,
+}
+
+const fn def(code: u8, name: &'static str, udhcpd_name: Option<&'static str>, kind: OptionType) -> OptionDef {
+    OptionDef {
+        code,
+        name,
+        udhcpd_name,
+        kind,
+    }
+}
+
+/// 内置的选项表，名字沿用 dnsmasq 的 `option:` 名
+pub const KNOWN_OPTIONS: &[OptionDef] = &[
+    def(4, "time-server", Some("timesrv"), OptionType::IpList),
+    def(7, "log-server", Some("logsrv"), OptionType::IpList),
+    def(12, "hostname", Some("hostname"), OptionType::String),
+    def(15, "domain-name", Some("domain"), OptionType::String),
+    def(26, "mtu", Some("mtu"), OptionType::U16),
+    def(28, "broadcast", Some("broadcast"), OptionType::Ip),
+    def(42, "ntp-server", Some("ntpsrv"), OptionType::IpList),
+    def(43, "vendor-encap", None, OptionType::Hex),
+    def(44, "netbios-ns", Some("wins"), OptionType::IpList),
+    def(51, "lease-time", Some("lease"), OptionType::U32),
+    def(60, "vendor-class", None, OptionType::String),
+    def(66, "tftp-server", Some("tftp"), OptionType::String),
+    def(67, "bootfile-name", Some("bootfile"), OptionType::String),
+    def(121, "classless-static-route", Some("staticroutes"), OptionType::StaticRoutes),
+    def(150, "tftp-server-address", None, OptionType::IpList),
+    def(249, "ms-classless-static-route", Some("msstaticroutes"), OptionType::StaticRoutes),
+    def(252, "wpad", Some("wpad"), OptionType::String),
+];
+
+/// 不能作为自定义选项设置的代码及原因
+fn reserved_reason(code: u8) -> Option<&'static str> {
+    match code {
+        0 | 255 => Some("is a padding/end marker"),
+        1 => Some("is set through subnet_mask"),
+        3 => Some("is set through router"),
+        6 => Some("is set through dns_servers"),
+        50 | 53 | 54 | 55 | 61 => Some("is managed by the DHCP protocol itself"),
+        _ => None,
+    }
+}
+
+pub fn lookup(code: u8) -> Option<&'static OptionDef> {
+    KNOWN_OPTIONS.iter().find(|d| d.code == code)
+}
+
+/// 按名字（内置名或 udhcpd 名）或十进制/十六进制代码查找选项代码
+pub fn resolve_code(key: &str) -> Option<u8> {
+    let key = key.trim().to_lowercase();
+    let key = key.strip_prefix("option:").unwrap_or(&key);
+    if let Some(hex) = key.strip_prefix("0x") {
+        return u8::from_str_radix(hex, 16).ok();
+    }
+    if let Ok(code) = key.parse::<u8>() {
+        return Some(code);
+    }
+    KNOWN_OPTIONS
+        .iter()
+        .find(|d| d.name == key || d.udhcpd_name == Some(key))
+        .map(|d| d.code)
+}
+
+/// 任意字节串，JSON 中写作十六进制字符串，接受 `0a0b`、`0a:0b`、`0a 0b` 等写法
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct HexBytes(pub Vec<u8>);
+
+impl FromStr for HexBytes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits: String = s.chars().filter(|c| !matches!(c, ':' | '-' | ' ')).collect();
+        if digits.is_empty() || !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("'{}' is not a hex string", s));
+        }
+        Ok(HexBytes(
+            (0..digits.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+                .collect(),
+        ))
+    }
+}
+
+impl TryFrom<String> for HexBytes {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<HexBytes> for String {
+    fn from(bytes: HexBytes) -> String {
+        bytes.to_string()
+    }
+}
+
+impl fmt::Display for HexBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// 无类别静态路由（选项 121/249）中的一条
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StaticRoute {
+    pub destination: Ipv4Addr,
+    pub prefix_len: u8,
+    pub gateway: Ipv4Addr,
+}
+
+impl StaticRoute {
+    /// 解析 `10.0.0.0/8` 与网关两部分
+    fn parse(destination: &str, gateway: &str) -> Option<Self> {
+        let (addr, len) = destination.split_once('/')?;
+        Some(StaticRoute {
+            destination: Ipv4Addr::from_str(addr).ok()?,
+            prefix_len: len.parse().ok()?,
+            gateway: Ipv4Addr::from_str(gateway).ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "kebab-case")]
+pub enum OptionValue {
+    Ip(Ipv4Addr),
+    IpList(Vec<Ipv4Addr>),
+    String(String),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    Hex(HexBytes),
+    StaticRoutes(Vec<StaticRoute>),
+}
+
+impl OptionValue {
+    pub fn kind(&self) -> OptionType {
+        match self {
+            OptionValue::Ip(_) => OptionType::Ip,
+            OptionValue::IpList(_) => OptionType::IpList,
+            OptionValue::String(_) => OptionType::String,
+            OptionValue::U8(_) => OptionType::U8,
+            OptionValue::U16(_) => OptionType::U16,
+            OptionValue::U32(_) => OptionType::U32,
+            OptionValue::Hex(_) => OptionType::Hex,
+            OptionValue::StaticRoutes(_) => OptionType::StaticRoutes,
+        }
+    }
+
+    /// 报文中的编码，静态路由按 RFC 3442 只写出目的网络的有效字节
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            OptionValue::Ip(ip) => ip.octets().to_vec(),
+            OptionValue::IpList(ips) => ips.iter().flat_map(|ip| ip.octets()).collect(),
+            OptionValue::String(s) => s.as_bytes().to_vec(),
+            OptionValue::U8(v) => vec![*v],
+            OptionValue::U16(v) => v.to_be_bytes().to_vec(),
+            OptionValue::U32(v) => v.to_be_bytes().to_vec(),
+            OptionValue::Hex(bytes) => bytes.0.clone(),
+            OptionValue::StaticRoutes(routes) => routes
+                .iter()
+                .flat_map(|r| {
+                    let significant = (r.prefix_len as usize).div_ceil(8).min(4);
+                    let mut bytes = vec![r.prefix_len];
+                    bytes.extend_from_slice(&r.destination.octets()[..significant]);
+                    bytes.extend_from_slice(&r.gateway.octets());
+                    bytes
+                })
+                .collect(),
+        }
+    }
+
+    /// 按类型解码报文中的字节，长度不符时返回 None
+    pub fn decode(kind: OptionType, bytes: &[u8]) -> Option<OptionValue> {
+        let ip = |b: &[u8]| Ipv4Addr::new(b[0], b[1], b[2], b[3]);
+        match kind {
+            OptionType::Ip if bytes.len() == 4 => Some(OptionValue::Ip(ip(bytes))),
+            OptionType::IpList if !bytes.is_empty() && bytes.len().is_multiple_of(4) => {
+                Some(OptionValue::IpList(bytes.chunks(4).map(ip).collect()))
+            }
+            OptionType::String => String::from_utf8(bytes.to_vec()).ok().map(OptionValue::String),
+            OptionType::U8 => Some(OptionValue::U8(u8::from_be_bytes(bytes.try_into().ok()?))),
+            OptionType::U16 => Some(OptionValue::U16(u16::from_be_bytes(bytes.try_into().ok()?))),
+            OptionType::U32 => Some(OptionValue::U32(u32::from_be_bytes(bytes.try_into().ok()?))),
+            OptionType::Hex => Some(OptionValue::Hex(HexBytes(bytes.to_vec()))),
+            OptionType::StaticRoutes => {
+                let mut routes = Vec::new();
+                let mut rest = bytes;
+                while let Some((&prefix_len, tail)) = rest.split_first() {
+                    let significant = (prefix_len as usize).div_ceil(8);
+                    if prefix_len > 32 || tail.len() < significant + 4 {
+                        return None;
+                    }
+                    let mut destination = [0u8; 4];
+                    destination[..significant].copy_from_slice(&tail[..significant]);
+                    routes.push(StaticRoute {
+                        destination: Ipv4Addr::from(destination),
+                        prefix_len,
+                        gateway: ip(&tail[significant..significant + 4]),
+                    });
+                    rest = &tail[significant + 4..];
+                }
+                Some(OptionValue::StaticRoutes(routes))
+            }
+            _ => None,
+        }
+    }
+
+    /// 解析 udhcpd.conf 中选项名之后以空白分隔的部分
+    fn parse_udhcpd(kind: OptionType, parts: &[&str]) -> Option<OptionValue> {
+        let words: Vec<&str> = parts
+            .iter()
+            .flat_map(|p| p.split(','))
+            .filter(|p| !p.is_empty())
+            .collect();
+        match kind {
+            OptionType::String => Some(OptionValue::String(parts.join(" "))).filter(|_| !parts.is_empty()),
+            OptionType::Hex => HexBytes::from_str(&words.concat()).ok().map(OptionValue::Hex),
+            _ => parse_words(kind, &words),
+        }
+    }
+
+    /// udhcpd.conf 中的写法：以空白分隔
+    fn udhcpd_text(&self) -> String {
+        match self {
+            OptionValue::IpList(ips) => join(ips, " "),
+            OptionValue::StaticRoutes(routes) => routes
+                .iter()
+                .map(|r| format!("{}/{} {}", r.destination, r.prefix_len, r.gateway))
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => self.plain_text(),
+        }
+    }
+
+    /// 解析 dnsmasq `dhcp-option=<code>,` 之后的部分
+    fn parse_dnsmasq(kind: OptionType, value: &str) -> Option<OptionValue> {
+        match kind {
+            OptionType::String => Some(OptionValue::String(unquote_dnsmasq(value.trim()))),
+            OptionType::Hex => HexBytes::from_str(value.trim()).ok().map(OptionValue::Hex),
+            // 整数按固定宽度的十六进制字节写出，以免 dnsmasq 自行推断长度
+            OptionType::U8 | OptionType::U16 | OptionType::U32 if value.contains(':') => {
+                OptionValue::decode(kind, &HexBytes::from_str(value.trim()).ok()?.0)
+            }
+            _ => {
+                let words: Vec<&str> = value.split(',').map(str::trim).collect();
+                parse_words(kind, &words)
+            }
+        }
+    }
+
+    /// dnsmasq 中的写法：以逗号分隔，字符串加引号，整数写成十六进制字节
+    fn dnsmasq_text(&self) -> String {
+        let colon_hex = |bytes: Vec<u8>| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(":")
+        };
+        match self {
+            OptionValue::IpList(ips) => join(ips, ","),
+            OptionValue::String(s) => quote_dnsmasq(s),
+            OptionValue::U8(_) | OptionValue::U16(_) | OptionValue::U32(_) | OptionValue::Hex(_) => {
+                colon_hex(self.encode())
+            }
+            OptionValue::StaticRoutes(routes) => routes
+                .iter()
+                .map(|r| format!("{}/{},{}", r.destination, r.prefix_len, r.gateway))
+                .collect::<Vec<_>>()
+                .join(","),
+            _ => self.plain_text(),
+        }
+    }
+
+    fn plain_text(&self) -> String {
+        match self {
+            OptionValue::Ip(ip) => ip.to_string(),
+            OptionValue::String(s) => s.clone(),
+            OptionValue::U8(v) => v.to_string(),
+            OptionValue::U16(v) => v.to_string(),
+            OptionValue::U32(v) => v.to_string(),
+            OptionValue::Hex(bytes) => bytes.to_string(),
+            OptionValue::IpList(_) | OptionValue::StaticRoutes(_) => self.udhcpd_text(),
+        }
+    }
+}
+
+/// dnsmasq 的引号字符串：逗号在引号内按字面处理，`"` 与 `\` 需要转义。
+/// 换行等控制字符无法写入单行配置，由 `validation_issues` 拒绝
+fn quote_dnsmasq(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// `quote_dnsmasq` 的逆操作，没有引号的值原样返回
+fn unquote_dnsmasq(s: &str) -> String {
+    let Some(inner) = s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) else {
+        return s.to_string();
+    };
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unquoted.extend(chars.next()),
+            _ => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+fn join<T: ToString>(items: &[T], separator: &str) -> String {
+    items.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(separator)
+}
+
+fn parse_words(kind: OptionType, words: &[&str]) -> Option<OptionValue> {
+    match (kind, words) {
+        (OptionType::Ip, [ip]) => Ipv4Addr::from_str(ip).ok().map(OptionValue::Ip),
+        (OptionType::IpList, _) if !words.is_empty() => words
+            .iter()
+            .map(|w| Ipv4Addr::from_str(w).ok())
+            .collect::<Option<_>>()
+            .map(OptionValue::IpList),
+        (OptionType::U8, [v]) => v.parse().ok().map(OptionValue::U8),
+        (OptionType::U16, [v]) => v.parse().ok().map(OptionValue::U16),
+        (OptionType::U32, [v]) => v.parse().ok().map(OptionValue::U32),
+        (OptionType::StaticRoutes, _) if !words.is_empty() && words.len().is_multiple_of(2) => words
+            .chunks(2)
+            .map(|pair| StaticRoute::parse(pair[0], pair[1]))
+            .collect::<Option<_>>()
+            .map(OptionValue::StaticRoutes),
+        _ => None,
+    }
+}
+
+/// 配置中的一个附加 DHCP 选项
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpOption {
+    pub code: u8,
+    #[serde(flatten)]
+    pub value: OptionValue,
+}
+
+impl DhcpOption {
+    pub fn def(&self) -> Option<&'static OptionDef> {
+        lookup(self.code)
+    }
+
+    /// 解析 udhcpd.conf 中 `option`/`opt` 之后的部分，无法识别时返回 None
+    pub fn from_udhcpd(parts: &[&str]) -> Option<Self> {
+        let (key, rest) = parts.split_first()?;
+        let code = resolve_code(key)?;
+        if reserved_reason(code).is_some() {
+            return None;
+        }
+        let named = lookup(code).filter(|d| d.udhcpd_name == Some(*key));
+        let value = match (named, lookup(code)) {
+            (Some(d), _) => OptionValue::parse_udhcpd(d.kind, rest)?,
+            // 以代码写出的选项值是十六进制字节，已知选项再按其类型解码
+            (None, known) => {
+                let bytes = HexBytes::from_str(&rest.concat()).ok()?;
+                known
+                    .and_then(|d| OptionValue::decode(d.kind, &bytes.0))
+                    .unwrap_or(OptionValue::Hex(bytes))
+            }
+        };
+        Some(DhcpOption { code, value })
+    }
+
+    /// udhcpd.conf 中的一行。udhcpd 不认识名字的选项写成 `option 0xNN <hex>`
+    pub fn udhcpd_line(&self) -> String {
+        match self.def() {
+            Some(OptionDef { udhcpd_name: Some(name), kind, .. }) if *kind == self.value.kind() => {
+                format!("option {} {}", name, self.value.udhcpd_text())
+            }
+            _ => format!("option 0x{:02x} {}", self.code, HexBytes(self.value.encode())),
+        }
+    }
+
+    /// 解析 dnsmasq 的 `dhcp-option=` 之后的部分
+    pub fn from_dnsmasq(value: &str) -> Option<Self> {
+        let (key, rest) = value.split_once(',')?;
+        let code = resolve_code(key)?;
+        if reserved_reason(code).is_some() {
+            return None;
+        }
+        let kind = lookup(code).map(|d| d.kind).unwrap_or(OptionType::Hex);
+        Some(DhcpOption {
+            code,
+            value: OptionValue::parse_dnsmasq(kind, rest)?,
+        })
+    }
+
+    pub fn dnsmasq_line(&self) -> String {
+        format!("dhcp-option={},{}", self.code, self.value.dnsmasq_text())
+    }
+
+    /// 单个选项的问题：保留代码、与已知类型不符、取值为空或超出单个选项 255 字节的上限
+    pub fn validation_issues(&self, field: &str) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        if let Some(reason) = reserved_reason(self.code) {
+            issues.push(ValidationIssue::new(field, format!("Option {} {}", self.code, reason)));
+        }
+        if let Some(def) = self.def() {
+            if def.kind != self.value.kind() {
+                issues.push(ValidationIssue::new(
+                    field,
+                    format!(
+                        "Option {} ({}) expects a value of type {}",
+                        def.code,
+                        def.name,
+                        def.kind.as_str()
+                    ),
+                ));
+            }
+        }
+        if let OptionValue::String(s) = &self.value {
+            if s.chars().any(char::is_control) {
+                issues.push(ValidationIssue::new(
+                    field,
+                    format!("Option {} must not contain newlines or other control characters", self.code),
+                ));
+            }
+        }
+        if let OptionValue::StaticRoutes(routes) = &self.value {
+            for route in routes {
+                if route.prefix_len > 32 {
+                    issues.push(ValidationIssue::new(
+                        field,
+                        format!("Prefix length /{} is out of range", route.prefix_len),
+                    ));
+                } else if u32::from(route.destination) & !u32::MAX.checked_shl(32 - route.prefix_len as u32).unwrap_or(0) != 0 {
+                    issues.push(ValidationIssue::new(
+                        field,
+                        format!("{}/{} has host bits set", route.destination, route.prefix_len),
+                    ));
+                }
+            }
+        }
+        let len = self.value.encode().len();
+        if len == 0 {
+            issues.push(ValidationIssue::new(field, format!("Option {} has an empty value", self.code)));
+        } else if len > 255 {
+            issues.push(ValidationIssue::new(
+                field,
+                format!("Option {} encodes to {} bytes, more than 255", self.code, len),
+            ));
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(code: u8, value: OptionValue) -> DhcpOption {
+        DhcpOption { code, value }
+    }
+
+    /// 每种类型各取一个内置选项
+    fn samples() -> Vec<DhcpOption> {
+        vec![
+            option(28, OptionValue::Ip(Ipv4Addr::new(192, 168, 1, 255))),
+            option(42, OptionValue::IpList(vec![Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)])),
+            option(15, OptionValue::String("home.lan".to_string())),
+            option(26, OptionValue::U16(1492)),
+            option(51, OptionValue::U32(86400)),
+            option(43, OptionValue::Hex(HexBytes(vec![0x01, 0x04, 0xc0, 0xa8, 0x01, 0x01]))),
+            option(
+                121,
+                OptionValue::StaticRoutes(vec![
+                    StaticRoute {
+                        destination: Ipv4Addr::new(10, 0, 0, 0),
+                        prefix_len: 8,
+                        gateway: Ipv4Addr::new(192, 168, 1, 2),
+                    },
+                    StaticRoute {
+                        destination: Ipv4Addr::new(0, 0, 0, 0),
+                        prefix_len: 0,
+                        gateway: Ipv4Addr::new(192, 168, 1, 1),
+                    },
+                ]),
+            ),
+        ]
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        for sample in samples() {
+            let decoded = OptionValue::decode(sample.value.kind(), &sample.value.encode());
+            assert_eq!(decoded.as_ref(), Some(&sample.value), "option {}", sample.code);
+        }
+        let u8_value = OptionValue::U8(7);
+        assert_eq!(OptionValue::decode(OptionType::U8, &u8_value.encode()), Some(u8_value));
+        assert_eq!(OptionValue::decode(OptionType::U16, &[1]), None);
+        assert_eq!(OptionValue::decode(OptionType::StaticRoutes, &[33, 0, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn udhcpd_round_trip() {
+        for sample in samples() {
+            let line = sample.udhcpd_line();
+            let parts: Vec<&str> = line.split_whitespace().skip(1).collect();
+            assert_eq!(DhcpOption::from_udhcpd(&parts), Some(sample.clone()), "{}", line);
+        }
+    }
+
+    #[test]
+    fn dnsmasq_round_trip() {
+        for sample in samples() {
+            let line = sample.dnsmasq_line();
+            let value = line.strip_prefix("dhcp-option=").unwrap();
+            assert_eq!(DhcpOption::from_dnsmasq(value), Some(sample.clone()), "{}", line);
+        }
+    }
+
+    #[test]
+    fn dnsmasq_strings_are_escaped() {
+        let sample = option(67, OptionValue::String(r#"pxe,"boot"\x.efi"#.to_string()));
+        let line = sample.dnsmasq_line();
+        assert_eq!(line, r#"dhcp-option=67,"pxe,\"boot\"\\x.efi""#);
+        assert_eq!(DhcpOption::from_dnsmasq(line.strip_prefix("dhcp-option=").unwrap()), Some(sample));
+    }
+
+    #[test]
+    fn rejects_control_characters_in_strings() {
+        let sample = option(15, OptionValue::String("lan\nport=53".to_string()));
+        assert_eq!(sample.validation_issues("options[0]").len(), 1);
+        assert!(option(15, OptionValue::String("lan".to_string())).validation_issues("options[0]").is_empty());
+    }
+}
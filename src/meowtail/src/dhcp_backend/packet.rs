@@ -0,0 +1,259 @@
+// src/dhcp_backend/packet.rs
+
+use std::net::Ipv4Addr;
+
+use super::format_mac;
+
+/// BOOTP 固定头部（含 magic cookie）的长度
+const HEADER_LEN: usize = 240;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+/// 部分客户端不接受短于 BOOTP 最小长度的报文，回复时补齐到 300 字节
+const MIN_PACKET_LEN: usize = 300;
+
+pub const BOOTREQUEST: u8 = 1;
+pub const BOOTREPLY: u8 = 2;
+/// flags 中的广播位
+pub const FLAG_BROADCAST: u16 = 0x8000;
+
+pub const OPT_PAD: u8 = 0;
+pub const OPT_SUBNET_MASK: u8 = 1;
+pub const OPT_ROUTER: u8 = 3;
+pub const OPT_DNS_SERVER: u8 = 6;
+pub const OPT_HOSTNAME: u8 = 12;
+pub const OPT_REQUESTED_IP: u8 = 50;
+pub const OPT_LEASE_TIME: u8 = 51;
+pub const OPT_MESSAGE_TYPE: u8 = 53;
+pub const OPT_SERVER_ID: u8 = 54;
+pub const OPT_PARAMETER_LIST: u8 = 55;
+pub const OPT_RENEWAL_TIME: u8 = 58;
+pub const OPT_REBINDING_TIME: u8 = 59;
+pub const OPT_END: u8 = 255;
+
+/// DHCP 报文类型（option 53）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Discover,
+    Offer,
+    Request,
+    Decline,
+    Ack,
+    Nak,
+    Release,
+    Inform,
+}
+
+impl MessageType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => MessageType::Discover,
+            2 => MessageType::Offer,
+            3 => MessageType::Request,
+            4 => MessageType::Decline,
+            5 => MessageType::Ack,
+            6 => MessageType::Nak,
+            7 => MessageType::Release,
+            8 => MessageType::Inform,
+            _ => return None,
+        })
+    }
+
+    pub fn as_u8(self) -> u8 {
+        match self {
+            MessageType::Discover => 1,
+            MessageType::Offer => 2,
+            MessageType::Request => 3,
+            MessageType::Decline => 4,
+            MessageType::Ack => 5,
+            MessageType::Nak => 6,
+            MessageType::Release => 7,
+            MessageType::Inform => 8,
+        }
+    }
+}
+
+/// 一个 DHCPv4 报文。选项按出现顺序保存，同一选项重复出现时（RFC 3396）内容会被拼接
+#[derive(Debug, Clone)]
+pub struct DhcpPacket {
+    pub op: u8,
+    pub htype: u8,
+    pub hlen: u8,
+    pub hops: u8,
+    pub xid: u32,
+    pub secs: u16,
+    pub flags: u16,
+    pub ciaddr: Ipv4Addr,
+    pub yiaddr: Ipv4Addr,
+    pub siaddr: Ipv4Addr,
+    pub giaddr: Ipv4Addr,
+    pub chaddr: [u8; 16],
+    pub options: Vec<(u8, Vec<u8>)>,
+}
+
+impl DhcpPacket {
+    /// 解析收到的报文，不是合法的 DHCP 报文时返回 None
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < HEADER_LEN || data[236..240] != MAGIC_COOKIE {
+            return None;
+        }
+        let ip = |offset: usize| Ipv4Addr::new(data[offset], data[offset + 1], data[offset + 2], data[offset + 3]);
+
+        let mut packet = DhcpPacket {
+            op: data[0],
+            htype: data[1],
+            hlen: data[2],
+            hops: data[3],
+            xid: u32::from_be_bytes(data[4..8].try_into().ok()?),
+            secs: u16::from_be_bytes(data[8..10].try_into().ok()?),
+            flags: u16::from_be_bytes(data[10..12].try_into().ok()?),
+            ciaddr: ip(12),
+            yiaddr: ip(16),
+            siaddr: ip(20),
+            giaddr: ip(24),
+            chaddr: data[28..44].try_into().ok()?,
+            options: vec![],
+        };
+
+        let mut rest = &data[HEADER_LEN..];
+        while let Some((&code, tail)) = rest.split_first() {
+            match code {
+                OPT_PAD => rest = tail,
+                OPT_END => break,
+                _ => {
+                    let (&len, tail) = tail.split_first()?;
+                    let len = len as usize;
+                    if tail.len() < len {
+                        return None;
+                    }
+                    packet.push_option(code, &tail[..len]);
+                    rest = &tail[len..];
+                }
+            }
+        }
+        Some(packet)
+    }
+
+    /// 序列化为线上格式，长选项按 255 字节拆分
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(MIN_PACKET_LEN);
+        data.extend_from_slice(&[self.op, self.htype, self.hlen, self.hops]);
+        data.extend_from_slice(&self.xid.to_be_bytes());
+        data.extend_from_slice(&self.secs.to_be_bytes());
+        data.extend_from_slice(&self.flags.to_be_bytes());
+        for addr in [self.ciaddr, self.yiaddr, self.siaddr, self.giaddr] {
+            data.extend_from_slice(&addr.octets());
+        }
+        data.extend_from_slice(&self.chaddr);
+        // sname(64) + file(128)
+        data.resize(HEADER_LEN - MAGIC_COOKIE.len(), 0);
+        data.extend_from_slice(&MAGIC_COOKIE);
+
+        for (code, value) in &self.options {
+            for chunk in value.chunks(255) {
+                data.push(*code);
+                data.push(chunk.len() as u8);
+                data.extend_from_slice(chunk);
+            }
+            if value.is_empty() {
+                data.extend_from_slice(&[*code, 0]);
+            }
+        }
+        data.push(OPT_END);
+        if data.len() < MIN_PACKET_LEN {
+            data.resize(MIN_PACKET_LEN, 0);
+        }
+        data
+    }
+
+    /// 构造一个要求广播回复的 DHCPDISCOVER
+    pub fn discover(xid: u32, mac: [u8; 6]) -> Self {
+        let mut chaddr = [0u8; 16];
+        chaddr[..6].copy_from_slice(&mac);
+        let mut discover = DhcpPacket {
+            op: BOOTREQUEST,
+            htype: 1,
+            hlen: 6,
+            hops: 0,
+            xid,
+            secs: 0,
+            flags: FLAG_BROADCAST,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: Ipv4Addr::UNSPECIFIED,
+            chaddr,
+            options: vec![],
+        };
+        discover.set_option(OPT_MESSAGE_TYPE, vec![MessageType::Discover.as_u8()]);
+        discover.set_option(OPT_PARAMETER_LIST, vec![OPT_SUBNET_MASK, OPT_ROUTER, OPT_DNS_SERVER]);
+        discover
+    }
+
+    /// 构造对 `request` 的回复，沿用其 xid、flags、giaddr 与 chaddr
+    pub fn reply(request: &DhcpPacket, message_type: MessageType) -> Self {
+        let mut reply = DhcpPacket {
+            op: BOOTREPLY,
+            htype: request.htype,
+            hlen: request.hlen,
+            hops: 0,
+            xid: request.xid,
+            secs: 0,
+            flags: request.flags,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: request.giaddr,
+            chaddr: request.chaddr,
+            options: vec![],
+        };
+        reply.set_option(OPT_MESSAGE_TYPE, vec![message_type.as_u8()]);
+        reply
+    }
+
+    fn push_option(&mut self, code: u8, value: &[u8]) {
+        match self.options.iter_mut().find(|(c, _)| *c == code) {
+            Some((_, existing)) => existing.extend_from_slice(value),
+            None => self.options.push((code, value.to_vec())),
+        }
+    }
+
+    pub fn option(&self, code: u8) -> Option<&[u8]> {
+        self.options
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, v)| v.as_slice())
+    }
+
+    pub fn set_option(&mut self, code: u8, value: Vec<u8>) {
+        match self.options.iter_mut().find(|(c, _)| *c == code) {
+            Some((_, existing)) => *existing = value,
+            None => self.options.push((code, value)),
+        }
+    }
+
+    pub fn set_ip_option(&mut self, code: u8, addrs: &[Ipv4Addr]) {
+        self.set_option(code, addrs.iter().flat_map(|a| a.octets()).collect());
+    }
+
+    pub fn message_type(&self) -> Option<MessageType> {
+        self.option(OPT_MESSAGE_TYPE)
+            .and_then(|v| v.first())
+            .and_then(|v| MessageType::from_u8(*v))
+    }
+
+    pub fn ip_option(&self, code: u8) -> Option<Ipv4Addr> {
+        let value: [u8; 4] = self.option(code)?.try_into().ok()?;
+        Some(Ipv4Addr::from(value))
+    }
+
+    pub fn hostname(&self) -> Option<String> {
+        self.option(OPT_HOSTNAME)
+            .map(|v| String::from_utf8_lossy(v).trim_end_matches('\0').to_string())
+            .filter(|h| !h.is_empty())
+    }
+
+    /// 客户端硬件地址，格式为 `aa:bb:cc:dd:ee:ff`
+    pub fn client_mac(&self) -> String {
+        let len = (self.hlen as usize).clamp(1, 16);
+        format_mac(&self.chaddr[..len])
+    }
+}
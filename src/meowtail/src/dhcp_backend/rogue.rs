@@ -0,0 +1,325 @@
+// src/dhcp_backend/rogue.rs
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::native::{bind_socket, CLIENT_PORT, SERVER_PORT};
+use super::packet::*;
+use super::{LeaseEvent, LeaseEventKind};
+use crate::udhcpd_manager::UdhcpdManager;
+use crate::udhcpd_registry::UdhcpdRegistry;
+
+/// 一次探测中等待 OFFER 的时间
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+/// 超过这段时间没有再出现的服务端不再报告
+const FORGET_AFTER: chrono::Duration = chrono::Duration::hours(24);
+
+/// 对探测报文的一个 OFFER
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    pub server_id: Ipv4Addr,
+    pub offered_ip: Ipv4Addr,
+    pub router: Option<Ipv4Addr>,
+}
+
+/// 在受管接口上发现的其他 DHCP 服务端
+#[derive(Debug, Clone, Serialize)]
+pub struct RogueServer {
+    pub interface: String,
+    pub server_id: Ipv4Addr,
+    /// OFFER 的源地址，经过中继时与 server_id 不同
+    pub source: Ipv4Addr,
+    pub offered_ip: Ipv4Addr,
+    pub router: Option<Ipv4Addr>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub offers: u64,
+}
+
+impl RogueServer {
+    /// 实例事件日志中的一条 `rogue-server` 事件：`ip` 为对方提供的地址，`server` 为其 server identifier
+    fn event(&self) -> LeaseEvent {
+        LeaseEvent {
+            at: self.first_seen,
+            kind: LeaseEventKind::RogueServer,
+            mac: String::new(),
+            ip: Some(self.offered_ip),
+            hostname: None,
+            server: Some(self.server_id),
+        }
+    }
+}
+
+/// 判断收到的报文是否为其他服务端对本次探测（`xid`）的 OFFER。
+/// server identifier 属于本机地址（`own`）的 OFFER 来自我们自己的服务端，不算在内。
+pub fn classify_offer(reply: &DhcpPacket, xid: u32, own: &[Ipv4Addr], source: Ipv4Addr) -> Option<Offer> {
+    if reply.op != BOOTREPLY || reply.xid != xid || reply.message_type() != Some(MessageType::Offer) {
+        return None;
+    }
+    let server_id = reply.ip_option(OPT_SERVER_ID).unwrap_or(source);
+    if own.contains(&server_id) || own.contains(&source) {
+        return None;
+    }
+    Some(Offer {
+        server_id,
+        offered_ip: reply.yiaddr,
+        router: reply.ip_option(OPT_ROUTER),
+    })
+}
+
+/// 在接口上广播一个 DHCPDISCOVER，收集 `timeout` 内其他服务端的 OFFER 及其源地址
+pub fn probe(interface: &str, timeout: Duration) -> io::Result<Vec<(Offer, Ipv4Addr)>> {
+    let mac = interface_mac(interface).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("No hardware address on interface {}", interface))
+    })?;
+    let own = local_addresses();
+    let xid: u32 = rand::random();
+
+    let socket = bind_socket(interface, CLIENT_PORT)?;
+    socket.send_to(
+        &DhcpPacket::discover(xid, mac).to_bytes(),
+        SocketAddrV4::new(Ipv4Addr::BROADCAST, SERVER_PORT),
+    )?;
+
+    let deadline = Instant::now() + timeout;
+    let mut offers = Vec::new();
+    let mut buf = [0u8; 1500];
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) {
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv_from(&mut buf) {
+            Ok((len, SocketAddr::V4(from))) => {
+                let offer = DhcpPacket::parse(&buf[..len])
+                    .and_then(|reply| classify_offer(&reply, xid, &own, *from.ip()));
+                if let Some(offer) = offer {
+                    offers.push((offer, *from.ip()));
+                }
+            }
+            Ok(_) => {}
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(offers)
+}
+
+fn interface_mac(interface: &str) -> Option<[u8; 6]> {
+    nix::ifaddrs::getifaddrs()
+        .ok()?
+        .filter(|ifa| ifa.interface_name == interface)
+        .find_map(|ifa| ifa.address?.as_link_addr()?.addr())
+}
+
+fn local_addresses() -> Vec<Ipv4Addr> {
+    nix::ifaddrs::getifaddrs()
+        .map(|addrs| {
+            addrs
+                .filter_map(|ifa| ifa.address?.as_sockaddr_in().map(|a| a.ip()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 定期在每个 DHCP 实例所服务的接口上探测其他 DHCP 服务端
+pub struct RogueDetector {
+    interval: Duration,
+    servers: Mutex<Vec<RogueServer>>,
+    last_probe: Mutex<Option<DateTime<Utc>>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RogueReport {
+    pub interval_secs: u64,
+    pub last_probe: Option<DateTime<Utc>>,
+    pub servers: Vec<RogueServer>,
+}
+
+impl RogueDetector {
+    /// interval 为 0 时不做定期探测，只响应手动触发
+    pub fn new(interval: Duration) -> Self {
+        RogueDetector {
+            interval,
+            servers: Mutex::new(vec![]),
+            last_probe: Mutex::new(None),
+        }
+    }
+
+    pub fn report(&self) -> RogueReport {
+        RogueReport {
+            interval_secs: self.interval.as_secs(),
+            last_probe: *self.last_probe.lock().unwrap(),
+            servers: self.servers.lock().unwrap().clone(),
+        }
+    }
+
+    /// 探测所有实例的接口，返回更新后的报告。新发现的服务端记入该接口上各实例的事件日志
+    pub fn probe_all(&self, registry: &UdhcpdRegistry) -> RogueReport {
+        let mut interfaces: BTreeMap<String, Vec<Arc<UdhcpdManager>>> = BTreeMap::new();
+        for (_, manager) in registry.instances() {
+            if let Some(interface) = manager.read_config().ok().and_then(|c| c.interface) {
+                interfaces.entry(interface).or_default().push(manager);
+            }
+        }
+
+        let now = Utc::now();
+        for (interface, managers) in interfaces {
+            match probe(&interface, PROBE_TIMEOUT) {
+                Ok(offers) => {
+                    for server in self.record(&interface, offers, now) {
+                        for manager in &managers {
+                            manager.record_event(server.event());
+                        }
+                    }
+                }
+                Err(e) => eprintln!("[rogue-dhcp] Failed to probe {}: {}", interface, e),
+            }
+        }
+        self.servers.lock().unwrap().retain(|s| now - s.last_seen < FORGET_AFTER);
+        *self.last_probe.lock().unwrap() = Some(now);
+        self.report()
+    }
+
+    /// 合并一次探测的结果，返回新发现的服务端
+    fn record(&self, interface: &str, offers: Vec<(Offer, Ipv4Addr)>, now: DateTime<Utc>) -> Vec<RogueServer> {
+        let mut servers = self.servers.lock().unwrap();
+        let mut found = Vec::new();
+        for (offer, source) in offers {
+            match servers
+                .iter_mut()
+                .find(|s| s.interface == interface && s.server_id == offer.server_id)
+            {
+                Some(server) => {
+                    server.source = source;
+                    server.offered_ip = offer.offered_ip;
+                    server.router = offer.router;
+                    server.last_seen = now;
+                    server.offers += 1;
+                }
+                None => {
+                    eprintln!(
+                        "[rogue-dhcp] Unexpected DHCP server {} on {} offered {} (router {})",
+                        offer.server_id,
+                        interface,
+                        offer.offered_ip,
+                        offer.router.map(|r| r.to_string()).unwrap_or_else(|| "none".to_string()),
+                    );
+                    let server = RogueServer {
+                        interface: interface.to_string(),
+                        server_id: offer.server_id,
+                        source,
+                        offered_ip: offer.offered_ip,
+                        router: offer.router,
+                        first_seen: now,
+                        last_seen: now,
+                        offers: 1,
+                    };
+                    found.push(server.clone());
+                    servers.push(server);
+                }
+            }
+        }
+        found
+    }
+
+    /// 启动后台探测线程
+    pub fn spawn(detector: Arc<RogueDetector>, registry: Arc<UdhcpdRegistry>) {
+        let interval = detector.interval;
+        if interval.is_zero() {
+            return;
+        }
+        thread::spawn(move || loop {
+            detector.probe_all(&registry);
+            thread::sleep(interval);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XID: u32 = 0x1234_5678;
+    const OWN: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 1);
+    const FOREIGN: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 254);
+
+    /// 构造 `server_id` 对本次探测的 OFFER，并经过一次序列化与解析
+    fn offer(xid: u32, server_id: Option<Ipv4Addr>) -> DhcpPacket {
+        let discover = DhcpPacket::discover(xid, [0x02, 0, 0, 0, 0, 1]);
+        let mut reply = DhcpPacket::reply(&discover, MessageType::Offer);
+        reply.yiaddr = Ipv4Addr::new(192, 168, 1, 50);
+        if let Some(id) = server_id {
+            reply.set_ip_option(OPT_SERVER_ID, &[id]);
+        }
+        reply.set_ip_option(OPT_ROUTER, &[FOREIGN]);
+        DhcpPacket::parse(&reply.to_bytes()).unwrap()
+    }
+
+    #[test]
+    fn ignores_own_server() {
+        assert_eq!(classify_offer(&offer(XID, Some(OWN)), XID, &[OWN], OWN), None);
+        // 经过中继时源地址是中继，server identifier 仍然是本机
+        assert_eq!(classify_offer(&offer(XID, Some(OWN)), XID, &[OWN], FOREIGN), None);
+    }
+
+    #[test]
+    fn reports_foreign_server() {
+        let expected = Offer {
+            server_id: FOREIGN,
+            offered_ip: Ipv4Addr::new(192, 168, 1, 50),
+            router: Some(FOREIGN),
+        };
+        assert_eq!(classify_offer(&offer(XID, Some(FOREIGN)), XID, &[OWN], FOREIGN), Some(expected.clone()));
+        // 没有 server identifier 时以源地址代替
+        assert_eq!(classify_offer(&offer(XID, None), XID, &[OWN], FOREIGN), Some(expected));
+    }
+
+    #[test]
+    fn ignores_other_transactions_and_messages() {
+        assert_eq!(classify_offer(&offer(XID + 1, Some(FOREIGN)), XID, &[OWN], FOREIGN), None);
+
+        let mut ack = offer(XID, Some(FOREIGN));
+        ack.set_option(OPT_MESSAGE_TYPE, vec![MessageType::Ack.as_u8()]);
+        assert_eq!(classify_offer(&ack, XID, &[OWN], FOREIGN), None);
+
+        let mut request = offer(XID, Some(FOREIGN));
+        request.op = BOOTREQUEST;
+        assert_eq!(classify_offer(&request, XID, &[OWN], FOREIGN), None);
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let bytes = offer(XID, Some(FOREIGN)).to_bytes();
+        // 截断的报文头
+        assert!(DhcpPacket::parse(&bytes[..100]).is_none());
+        // 错误的 magic cookie
+        let mut bad_cookie = bytes.clone();
+        bad_cookie[236] = 0;
+        assert!(DhcpPacket::parse(&bad_cookie).is_none());
+        // 没有消息类型选项
+        let mut no_type = offer(XID, Some(FOREIGN));
+        no_type.options.retain(|(code, _)| *code != OPT_MESSAGE_TYPE);
+        assert_eq!(classify_offer(&no_type, XID, &[OWN], FOREIGN), None);
+    }
+
+    #[test]
+    fn records_each_server_once() {
+        let detector = RogueDetector::new(Duration::ZERO);
+        let found = Offer {
+            server_id: FOREIGN,
+            offered_ip: Ipv4Addr::new(192, 168, 1, 50),
+            router: None,
+        };
+        let now = Utc::now();
+        let first = detector.record("eth0", vec![(found.clone(), FOREIGN)], now);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].event().server, Some(FOREIGN));
+        assert!(detector.record("eth0", vec![(found, FOREIGN)], now).is_empty());
+        assert_eq!(detector.report().servers[0].offers, 2);
+    }
+}
@@ -0,0 +1,114 @@
+// src/dhcp_backend/udhcpd.rs
+
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+
+use chrono::{TimeZone, Utc};
+use nix::sys::signal::Signal;
+
+use super::{format_mac, BackendKind, DhcpBackend, DhcpLease};
+use crate::error::Result;
+use crate::supervisor::{ProcessSupervisor, SupervisorStatus};
+use crate::udhcpd_manager::UdhcpdConfig;
+
+/// BusyBox udhcpd 租约文件中每条记录的长度：
+/// expires(4) + ip(4) + mac(6) + hostname(20) + pad(2)
+const LEASE_RECORD_LEN: usize = 36;
+
+/// BusyBox udhcpd
+pub struct UdhcpdBackend {
+    supervisor: ProcessSupervisor,
+}
+
+impl UdhcpdBackend {
+    pub fn new(executable_path: &str, config_path: &Path, pid_path: PathBuf) -> Self {
+        // -f: 保持在前台运行，由 supervisor 持有并回收子进程
+        let args = vec!["-f".to_string(), config_path.to_string_lossy().into_owned()];
+        UdhcpdBackend {
+            supervisor: ProcessSupervisor::new("udhcpd", executable_path, args, pid_path),
+        }
+    }
+}
+
+impl DhcpBackend for UdhcpdBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Udhcpd
+    }
+
+    fn start(&self) -> io::Result<()> {
+        self.supervisor.start()
+    }
+
+    fn stop(&self) -> io::Result<()> {
+        self.supervisor.stop()
+    }
+
+    fn adopt(&self) -> Option<u32> {
+        self.supervisor.adopt()
+    }
+
+    fn is_running(&self) -> bool {
+        self.supervisor.is_running()
+    }
+
+    fn status(&self) -> SupervisorStatus {
+        self.supervisor.status()
+    }
+
+    fn set_start_hook(&self, hook: Box<dyn Fn() + Send + Sync>) {
+        self.supervisor.set_spawn_hook(hook);
+    }
+
+    /// SIGUSR1 让 udhcpd 立即写出租约文件
+    fn flush_leases(&self) -> io::Result<()> {
+        self.supervisor.signal(Signal::SIGUSR1)
+    }
+
+    fn default_lease_file(&self) -> &'static str {
+        "/var/lib/misc/udhcpd.leases"
+    }
+
+    fn parse_config(&self, content: &str) -> Result<UdhcpdConfig> {
+        UdhcpdConfig::parse(content)
+    }
+
+    fn render_config(&self, config: &UdhcpdConfig) -> String {
+        config.render()
+    }
+
+    /// 租约文件是二进制格式：开头是 8 字节的写入时间（网络字节序的 Unix 时间戳），
+    /// 之后每条记录的 expires 是相对写入时间的剩余秒数
+    fn read_leases(&self, lease_file: &Path) -> Result<Vec<DhcpLease>> {
+        let data = match fs::read(lease_file) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        if data.len() < 8 {
+            return Ok(vec![]);
+        }
+
+        let written_at = i64::from_be_bytes(data[0..8].try_into().unwrap());
+        let leases = data[8..]
+            .chunks_exact(LEASE_RECORD_LEN)
+            .filter_map(|record| {
+                let expires = u32::from_be_bytes(record[0..4].try_into().unwrap());
+                let ip = Ipv4Addr::new(record[4], record[5], record[6], record[7]);
+                if ip.is_unspecified() {
+                    return None;
+                }
+                let hostname: Vec<u8> = record[14..34].iter().take_while(|b| **b != 0).copied().collect();
+                let hostname = String::from_utf8_lossy(&hostname).into_owned();
+                Some(DhcpLease {
+                    mac: format_mac(&record[8..14]),
+                    ip,
+                    hostname: Some(hostname).filter(|h| !h.is_empty()),
+                    expires_at: Utc.timestamp_opt(written_at + expires as i64, 0).single(),
+                })
+            })
+            .collect();
+        Ok(leases)
+    }
+}
@@ -0,0 +1,529 @@
+// src/dhcpv6_manager.rs
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::Ipv6Addr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::fs_util::{content_etag, etag_matches, write_atomic};
+use crate::interfaces::check_interface;
+use crate::supervisor::{ProcessSupervisor, SupervisorStatus};
+
+/// dnsmasq 的 DHCPv6 租约文件与 PID 文件，与作为 DHCPv4 后端运行的 dnsmasq 分开
+const LEASE_FILE: &str = "/var/lib/misc/dnsmasq-dhcpv6.leases";
+const SERVER_PID_FILE: &str = "/var/run/dnsmasq-dhcpv6.pid";
+
+/// 一个 IPv6 前缀，例如 `2001:db8:1::/64`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Ipv6Prefix {
+    pub addr: Ipv6Addr,
+    pub len: u8,
+}
+
+impl Ipv6Prefix {
+    fn mask(&self) -> u128 {
+        if self.len == 0 {
+            0
+        } else {
+            u128::MAX << (128 - self.len as u32)
+        }
+    }
+
+    /// 用前缀替换 `host` 的网络部分，只保留其接口标识部分
+    pub fn combine(&self, host: Ipv6Addr) -> Ipv6Addr {
+        let mask = self.mask();
+        Ipv6Addr::from((u128::from(self.addr) & mask) | (u128::from(host) & !mask))
+    }
+}
+
+impl FromStr for Ipv6Prefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let (addr, len) = s
+            .split_once('/')
+            .ok_or_else(|| format!("'{}' is not in <address>/<length> form", s))?;
+        let addr = Ipv6Addr::from_str(addr).map_err(|e| format!("'{}': {}", addr, e))?;
+        let len = len
+            .parse::<u8>()
+            .ok()
+            .filter(|l| *l <= 128)
+            .ok_or_else(|| format!("Invalid prefix length '{}'", len))?;
+        Ok(Ipv6Prefix { addr, len })
+    }
+}
+
+impl TryFrom<String> for Ipv6Prefix {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<Ipv6Prefix> for String {
+    fn from(prefix: Ipv6Prefix) -> String {
+        prefix.to_string()
+    }
+}
+
+impl fmt::Display for Ipv6Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.len)
+    }
+}
+
+/// LAN 客户端获取 IPv6 地址的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressMode {
+    /// 只通过 RA 做 SLAAC，DHCPv6 仅提供 DNS 等参数（无状态）
+    Slaac,
+    /// RA 置 M 标志，地址只由 DHCPv6 从地址池分配
+    Stateful,
+    /// SLAAC 与有状态 DHCPv6 同时提供
+    #[default]
+    Both,
+}
+
+/// 按 DUID 固定分配的地址
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuidReservation {
+    pub duid: String,
+    /// 只使用其接口标识部分（前缀之外的位），例如 `::10`
+    pub ip: Ipv6Addr,
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+/// DHCPv6/RA 配置，保存在 dhcpv6.toml 中，启动时渲染为 dnsmasq 配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Dhcpv6Config {
+    pub interface: String,
+    /// 通告的前缀；为空时跟随接口上的地址（例如 ISP 通过 DHCPv6-PD 下发的前缀）
+    pub prefix: Option<Ipv6Prefix>,
+    pub mode: AddressMode,
+    /// 有状态地址池的起止接口标识，例如 `::1000` 与 `::1fff`
+    pub range_start: Option<Ipv6Addr>,
+    pub range_end: Option<Ipv6Addr>,
+    /// 通过 RA (RDNSS) 与 DHCPv6 下发的 DNS 服务器
+    pub dns_servers: Vec<Ipv6Addr>,
+    /// 租期（秒）
+    pub lease_time: u32,
+    pub reservations: Vec<DuidReservation>,
+}
+
+impl Default for Dhcpv6Config {
+    fn default() -> Self {
+        Dhcpv6Config {
+            interface: "eth0".to_string(),
+            prefix: None,
+            mode: AddressMode::default(),
+            range_start: Some(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0x1000)),
+            range_end: Some(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0x1fff)),
+            dns_servers: vec![],
+            lease_time: 43200,
+            reservations: vec![],
+        }
+    }
+}
+
+impl Dhcpv6Config {
+    pub fn validate(&self) -> Result<()> {
+        let issues = self.validation_issues();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        if self.interface.trim().is_empty() {
+            issues.push(ValidationIssue::new("interface", "Interface name cannot be empty"));
+        }
+
+        if let Some(prefix) = self.prefix {
+            // SLAAC 要求 /64
+            if self.mode != AddressMode::Stateful && prefix.len != 64 {
+                issues.push(ValidationIssue::new(
+                    "prefix",
+                    format!("SLAAC requires a /64 prefix, got /{}", prefix.len),
+                ));
+            } else if prefix.len > 120 {
+                issues.push(ValidationIssue::new("prefix", format!("/{} is too long for an address pool", prefix.len)));
+            }
+        }
+
+        if self.mode != AddressMode::Slaac {
+            match (self.range_start, self.range_end) {
+                (Some(start), Some(end)) => {
+                    if self.pool_address(start) > self.pool_address(end) {
+                        issues.push(ValidationIssue::new(
+                            "range_start",
+                            format!("Range start {} is above range end {}", start, end),
+                        ));
+                    }
+                }
+                _ => issues.push(ValidationIssue::new(
+                    "range_start",
+                    "Stateful DHCPv6 requires range_start and range_end",
+                )),
+            }
+        }
+
+        if self.lease_time < 120 {
+            issues.push(ValidationIssue::new("lease_time", "Lease time must be at least 120 seconds"));
+        }
+
+        let mut duids = HashSet::new();
+        let mut ips = HashSet::new();
+        for (i, r) in self.reservations.iter().enumerate() {
+            let field = format!("reservations[{}]", i);
+            if !is_valid_duid(&r.duid) {
+                issues.push(ValidationIssue::new(
+                    format!("{}.duid", field),
+                    format!("'{}' is not a DUID (hex bytes separated by ':')", r.duid),
+                ));
+            }
+            if !duids.insert(r.duid.to_lowercase()) {
+                issues.push(ValidationIssue::new(
+                    format!("{}.duid", field),
+                    format!("DUID {} is reserved more than once", r.duid),
+                ));
+            }
+            if !ips.insert(self.pool_address(r.ip)) {
+                issues.push(ValidationIssue::new(
+                    format!("{}.ip", field),
+                    format!("{} is reserved more than once", r.ip),
+                ));
+            }
+            if let Some(hostname) = &r.hostname {
+                if hostname.is_empty() || hostname.contains(|c: char| c.is_whitespace() || c == ',') {
+                    issues.push(ValidationIssue::new(
+                        format!("{}.hostname", field),
+                        format!("'{}' is not a valid hostname", hostname),
+                    ));
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// 地址池中的地址：有固定前缀时拼上前缀，否则保持为接口标识
+    fn pool_address(&self, host: Ipv6Addr) -> Ipv6Addr {
+        match self.prefix {
+            Some(prefix) => prefix.combine(host),
+            None => host,
+        }
+    }
+
+    /// 渲染为只提供 RA 与 DHCPv6 的 dnsmasq 配置
+    pub fn render_dnsmasq(&self) -> String {
+        let mut content = String::from("# Generated by meowtail from dhcpv6.toml, do not edit\n");
+        content.push_str("port=0\nbind-interfaces\nenable-ra\n");
+        content.push_str(&format!("interface={}\n", self.interface));
+        content.push_str(&format!("dhcp-leasefile={}\n", LEASE_FILE));
+        content.push_str(&format!("pid-file={}\n", SERVER_PID_FILE));
+
+        // 没有固定前缀时由 dnsmasq 按接口上的地址构造（constructor:），ISP 换前缀后自动跟随
+        let constructor = match self.prefix {
+            Some(_) => String::new(),
+            None => format!(",constructor:{}", self.interface),
+        };
+        let prefix_len = self.prefix.map(|p| p.len).unwrap_or(64);
+        let range = match (self.mode, self.range_start, self.range_end) {
+            (AddressMode::Slaac, _, _) | (_, None, _) | (_, _, None) => {
+                let base = self.prefix.map(|p| p.combine(Ipv6Addr::UNSPECIFIED)).unwrap_or(Ipv6Addr::UNSPECIFIED);
+                format!("{}{},ra-stateless", base, constructor)
+            }
+            (mode, Some(start), Some(end)) => {
+                let slaac = if mode == AddressMode::Both { ",slaac" } else { "" };
+                format!("{},{}{}{}", self.pool_address(start), self.pool_address(end), constructor, slaac)
+            }
+        };
+        content.push_str(&format!("dhcp-range={},{},{}\n", range, prefix_len, self.lease_time));
+
+        if !self.dns_servers.is_empty() {
+            let servers: Vec<String> = self.dns_servers.iter().map(|s| format!("[{}]", s)).collect();
+            content.push_str(&format!("dhcp-option=option6:dns-server,{}\n", servers.join(",")));
+        }
+        for r in &self.reservations {
+            let mut line = format!("dhcp-host=id:{},[{}]", r.duid, self.pool_address(r.ip));
+            if let Some(hostname) = &r.hostname {
+                line.push_str(&format!(",{}", hostname));
+            }
+            content.push_str(&line);
+            content.push('\n');
+        }
+        content
+    }
+}
+
+/// DUID：3 到 130 个以 ':' 分隔的十六进制字节
+fn is_valid_duid(duid: &str) -> bool {
+    let octets: Vec<&str> = duid.split(':').collect();
+    (3..=130).contains(&octets.len())
+        && octets
+            .iter()
+            .all(|o| o.len() == 2 && o.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// dnsmasq 当前分配出去的一个 DHCPv6 租约
+#[derive(Debug, Clone, Serialize)]
+pub struct Dhcpv6Lease {
+    pub duid: String,
+    pub iaid: u32,
+    pub ip: Ipv6Addr,
+    pub hostname: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Dhcpv6Status {
+    #[serde(flatten)]
+    pub process: SupervisorStatus,
+    pub config_dirty: Option<bool>,
+}
+
+/// 管理 RA/DHCPv6 服务：配置保存在 dhcpv6.toml，生成 dnsmasq 配置并监管 dnsmasq 进程
+pub struct Dhcpv6Manager {
+    config_path: PathBuf,
+    server_config_path: PathBuf,
+    config_lock: Mutex<()>,
+    supervisor: ProcessSupervisor,
+    /// 运行中的 dnsmasq 启动时加载的配置的 ETag
+    applied_etag: Arc<Mutex<Option<String>>>,
+}
+
+impl Dhcpv6Manager {
+    pub fn new<P1: Into<PathBuf>, P2: Into<PathBuf>, P3: Into<PathBuf>>(
+        executable_path: &str,
+        config_path: P1,
+        server_config_path: P2,
+        pid_path: P3,
+    ) -> Self {
+        let server_config_path = server_config_path.into();
+        // 配置文件路径单独作为一个参数，只接管使用 DHCPv6 配置的 dnsmasq，不会误接管 DHCPv4 或 DNS 的实例
+        let args = vec![
+            "--keep-in-foreground".to_string(),
+            "--log-facility=-".to_string(),
+            "--conf-file".to_string(),
+            server_config_path.to_string_lossy().into_owned(),
+        ];
+        let supervisor = ProcessSupervisor::new("dnsmasq-dhcpv6", executable_path, args, pid_path);
+
+        let applied_etag = Arc::new(Mutex::new(None));
+        let hook_etag = applied_etag.clone();
+        let hook_path = server_config_path.clone();
+        supervisor.set_spawn_hook(move || {
+            let etag = fs::read_to_string(&hook_path).ok().map(|c| content_etag(&c));
+            *hook_etag.lock().unwrap() = etag;
+        });
+
+        Dhcpv6Manager {
+            config_path: config_path.into(),
+            server_config_path,
+            config_lock: Mutex::new(()),
+            supervisor,
+            applied_etag,
+        }
+    }
+
+    pub fn start(&self) -> Result<()> {
+        if self.is_running() {
+            return Err(AppError::Process("DHCPv6 server is already running.".to_string()));
+        }
+        // 以当前配置重新生成 dnsmasq 配置，dhcpv6.toml 被手工修改过也能生效
+        let config = self.read_config()?;
+        config.validate()?;
+        write_atomic(&self.server_config_path, &config.render_dnsmasq())?;
+
+        if let Some(pid) = self.supervisor.adopt() {
+            println!("Found running DHCPv6 dnsmasq with PID {}, supervising it instead of starting a new one.", pid);
+            return Ok(());
+        }
+        self.supervisor.start()?;
+        Ok(())
+    }
+
+    pub fn stop(&self) -> Result<()> {
+        self.supervisor.stop()?;
+        *self.applied_etag.lock().unwrap() = None;
+        Ok(())
+    }
+
+    pub fn restart(&self) -> Result<()> {
+        self.stop()?;
+        thread::sleep(Duration::from_millis(200));
+        self.start()
+    }
+
+    pub fn adopt_running(&self) -> Option<u32> {
+        self.supervisor.adopt()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.supervisor.is_running()
+    }
+
+    pub fn status(&self) -> Dhcpv6Status {
+        Dhcpv6Status {
+            process: self.supervisor.status(),
+            config_dirty: self.config_dirty(),
+        }
+    }
+
+    pub fn config_dirty(&self) -> Option<bool> {
+        if !self.is_running() {
+            return Some(false);
+        }
+        let applied = self.applied_etag.lock().unwrap().clone()?;
+        let current = fs::read_to_string(&self.server_config_path).ok().map(|c| content_etag(&c));
+        Some(current.as_deref() != Some(applied.as_str()))
+    }
+
+    /// 读取配置，文件不存在时返回默认配置
+    pub fn read_config(&self) -> Result<Dhcpv6Config> {
+        Ok(self.read_config_with_etag()?.0)
+    }
+
+    pub fn read_config_with_etag(&self) -> Result<(Dhcpv6Config, String)> {
+        let content = match fs::read_to_string(&self.config_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let config = toml::from_str(&content)
+            .map_err(|e| AppError::ConfigParse(format!("{:?}: {}", self.config_path, e)))?;
+        Ok((config, content_etag(&content)))
+    }
+
+    /// 写入 dhcpv6.toml 并同步生成 dnsmasq 配置，返回新的 ETag
+    fn write_config(&self, config: &Dhcpv6Config) -> Result<String> {
+        let content = toml::to_string_pretty(config).map_err(|e| AppError::Process(e.to_string()))?;
+        write_atomic(&self.config_path, &content)?;
+        write_atomic(&self.server_config_path, &config.render_dnsmasq())?;
+        Ok(content_etag(&content))
+    }
+
+    /// 整体替换配置，`if_match` 与当前 ETag 不一致时拒绝写入
+    pub fn replace_config(&self, config: Dhcpv6Config, if_match: Option<&str>) -> Result<String> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+
+        let (_, etag) = self.read_config_with_etag()?;
+        if let Some(expected) = if_match {
+            if !etag_matches(expected, &etag) {
+                return Err(AppError::PreconditionFailed(
+                    "Configuration was modified since it was read.".to_string(),
+                ));
+            }
+        }
+        config.validate()?;
+        self.write_config(&config)
+    }
+
+    fn update_config<F: FnOnce(&mut Dhcpv6Config)>(&self, update: F) -> Result<()> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let mut config = self.read_config()?;
+        update(&mut config);
+        config.validate()?;
+        self.write_config(&config)?;
+        Ok(())
+    }
+
+    pub fn set_interface(&self, interface: String) -> Result<()> {
+        check_interface(&interface)?;
+        self.update_config(|c| c.interface = interface)
+    }
+
+    pub fn set_prefix(&self, prefix: Option<Ipv6Prefix>) -> Result<()> {
+        self.update_config(|c| c.prefix = prefix)
+    }
+
+    pub fn set_mode(&self, mode: AddressMode) -> Result<()> {
+        self.update_config(|c| c.mode = mode)
+    }
+
+    pub fn set_range(&self, start: Ipv6Addr, end: Ipv6Addr) -> Result<()> {
+        self.update_config(|c| {
+            c.range_start = Some(start);
+            c.range_end = Some(end);
+        })
+    }
+
+    pub fn set_dns_servers(&self, servers: Vec<Ipv6Addr>) -> Result<()> {
+        self.update_config(|c| c.dns_servers = servers)
+    }
+
+    pub fn add_or_update_reservation(&self, reservation: DuidReservation) -> Result<()> {
+        self.update_config(|c| {
+            match c.reservations.iter_mut().find(|r| r.duid.eq_ignore_ascii_case(&reservation.duid)) {
+                Some(existing) => *existing = reservation,
+                None => c.reservations.push(reservation),
+            }
+        })
+    }
+
+    pub fn remove_reservation(&self, duid: &str) -> Result<()> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let mut config = self.read_config()?;
+        let before = config.reservations.len();
+        config.reservations.retain(|r| !r.duid.eq_ignore_ascii_case(duid));
+        if config.reservations.len() == before {
+            return Err(AppError::NotFound(format!("Reservation for DUID {}", duid)));
+        }
+        self.write_config(&config)?;
+        Ok(())
+    }
+
+    /// 解析 dnsmasq 租约文件中的 IPv6 部分：`duid <server-duid>` 行之后每行为
+    /// `<expiry> <iaid> <ipv6> <hostname|*> <client-duid>`
+    pub fn leases(&self) -> Result<Vec<Dhcpv6Lease>> {
+        let content = match fs::read_to_string(LEASE_FILE) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let leases = content
+            .lines()
+            .skip_while(|line| !line.starts_with("duid "))
+            .skip(1)
+            .filter_map(|line| {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() < 5 {
+                    return None;
+                }
+                let expiry = parts[0].parse::<i64>().ok()?;
+                Some(Dhcpv6Lease {
+                    duid: parts[4].to_lowercase(),
+                    iaid: parts[1].parse().ok()?,
+                    ip: Ipv6Addr::from_str(parts[2]).ok()?,
+                    hostname: Some(parts[3].to_string()).filter(|h| h != "*"),
+                    expires_at: if expiry == 0 {
+                        None
+                    } else {
+                        Utc.timestamp_opt(expiry, 0).single()
+                    },
+                })
+            })
+            .collect();
+        Ok(leases)
+    }
+}
@@ -0,0 +1,934 @@
+// src/dns_manager.rs
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use nix::sys::signal::Signal;
+use serde::{Deserialize, Serialize};
+
+use crate::device_inventory::DeviceInventory;
+use crate::dhcpv6_manager::Dhcpv6Manager;
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::fs_util::{content_etag, etag_matches, write_atomic};
+use crate::interfaces;
+use crate::mac_addr::MacAddr;
+use crate::supervisor::{ProcessSupervisor, SupervisorStatus};
+use crate::udhcpd_registry::UdhcpdRegistry;
+
+/// 租约主机名写入的 hosts 文件，内容变化后向 dnsmasq 发送 SIGHUP 重新读取
+const HOSTS_FILE: &str = "/tmp/meowtail_dns.hosts";
+const SERVER_PID_FILE: &str = "/var/run/dnsmasq-dns.pid";
+/// 后台刷新租约主机名的间隔
+const REFRESH_SECS: u64 = 30;
+
+/// 查询上游服务器的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpstreamMode {
+    /// dnsmasq 默认：优先使用最近响应最快的服务器
+    #[default]
+    Auto,
+    /// 按列表顺序，前一个无响应时才使用下一个
+    StrictOrder,
+    /// 同时查询所有服务器，使用最先返回的结果
+    AllServers,
+}
+
+/// 上游 DNS 服务器
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Upstream {
+    pub address: IpAddr,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// 只把这些域名的查询发给该服务器，为空时用于所有查询
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+}
+
+/// 自定义记录的内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "UPPERCASE")]
+pub enum RecordData {
+    A { address: Ipv4Addr },
+    Aaaa { address: Ipv6Addr },
+    /// 目标必须是本服务已知的名字（自定义记录或租约主机名）
+    Cname { target: String },
+}
+
+impl RecordData {
+    fn type_name(&self) -> &'static str {
+        match self {
+            RecordData::A { .. } => "A",
+            RecordData::Aaaa { .. } => "AAAA",
+            RecordData::Cname { .. } => "CNAME",
+        }
+    }
+
+    fn is_cname(&self) -> bool {
+        matches!(self, RecordData::Cname { .. })
+    }
+}
+
+/// 一条自定义记录，不带点的名字同时发布在本地域名下
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsRecord {
+    pub name: String,
+    #[serde(flatten)]
+    pub data: RecordData,
+}
+
+/// 本地 DNS 配置，保存在 dns.toml 中，启动时渲染为只提供 DNS 的 dnsmasq 配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnsConfig {
+    /// 租约主机名与不带点的自定义记录所在的本地域名
+    pub domain: String,
+    /// 提供 DNS 服务的接口
+    pub interfaces: Vec<String>,
+    /// 为空时使用 /etc/resolv.conf 中的服务器（例如 WAN 口 DHCP 获取的）
+    pub upstreams: Vec<Upstream>,
+    pub upstream_mode: UpstreamMode,
+    pub cache_size: u32,
+    /// 发布静态租约与动态租约的主机名
+    pub publish_leases: bool,
+    /// 把监听接口上 DHCP 实例下发的 DNS 服务器改为路由器自身的地址，关闭后恢复原来的值
+    pub advertise: bool,
+    pub records: Vec<DnsRecord>,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        DnsConfig {
+            domain: "lan".to_string(),
+            interfaces: vec!["eth0".to_string()],
+            upstreams: vec![],
+            upstream_mode: UpstreamMode::default(),
+            cache_size: 1000,
+            publish_leases: true,
+            advertise: false,
+            records: vec![],
+        }
+    }
+}
+
+fn is_valid_domain(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// 把客户端上报的主机名转换为 DNS 标签，无法转换时返回 None
+fn host_label(name: &str) -> Option<String> {
+    let label: String = name
+        .split('.')
+        .next()?
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .take(63)
+        .collect();
+    let label = label.trim_matches('-');
+    (!label.is_empty()).then(|| label.to_string())
+}
+
+impl DnsConfig {
+    pub fn validate(&self) -> Result<()> {
+        let issues = self.validation_issues();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        if !is_valid_domain(&self.domain) {
+            issues.push(ValidationIssue::new("domain", format!("'{}' is not a valid domain name", self.domain)));
+        }
+        if self.interfaces.is_empty() {
+            issues.push(ValidationIssue::new("interfaces", "At least one interface is required"));
+        }
+        for (i, interface) in self.interfaces.iter().enumerate() {
+            if !interfaces::is_valid_name(interface) {
+                issues.push(ValidationIssue::new(
+                    format!("interfaces[{}]", i),
+                    format!("'{}' is not a valid interface name", interface),
+                ));
+            }
+        }
+        for (i, upstream) in self.upstreams.iter().enumerate() {
+            if upstream.address.is_unspecified() || upstream.address.is_multicast() {
+                issues.push(ValidationIssue::new(
+                    format!("upstreams[{}].address", i),
+                    format!("{} is not a usable DNS server address", upstream.address),
+                ));
+            }
+            if upstream.port == Some(0) {
+                issues.push(ValidationIssue::new(format!("upstreams[{}].port", i), "Port cannot be 0"));
+            }
+            for (j, domain) in upstream.domains.iter().enumerate() {
+                if !is_valid_domain(domain) {
+                    issues.push(ValidationIssue::new(
+                        format!("upstreams[{}].domains[{}]", i, j),
+                        format!("'{}' is not a valid domain name", domain),
+                    ));
+                }
+            }
+        }
+        if self.cache_size > 10000 {
+            issues.push(ValidationIssue::new("cache_size", "Cache size cannot exceed 10000"));
+        }
+        for (i, record) in self.records.iter().enumerate() {
+            let field = |name: &str| format!("records[{}].{}", i, name);
+            if !is_valid_domain(&record.name) {
+                issues.push(ValidationIssue::new(field("name"), format!("'{}' is not a valid name", record.name)));
+            }
+            let same_name = |r: &&DnsRecord| r.name.eq_ignore_ascii_case(&record.name);
+            if self.records[..i].iter().filter(same_name).any(|r| r.data == record.data) {
+                issues.push(ValidationIssue::new(field("name"), format!("{} {} is defined more than once", record.data.type_name(), record.name)));
+            }
+            if record.data.is_cname() && self.records.iter().filter(same_name).count() > 1 {
+                issues.push(ValidationIssue::new(field("name"), format!("{} has a CNAME and cannot have other records", record.name)));
+            }
+            if let RecordData::Cname { target } = &record.data {
+                if !is_valid_domain(target) {
+                    issues.push(ValidationIssue::new(field("target"), format!("'{}' is not a valid name", target)));
+                } else if target.eq_ignore_ascii_case(&record.name) {
+                    issues.push(ValidationIssue::new(field("target"), "A CNAME cannot point to itself"));
+                }
+            }
+        }
+        issues
+    }
+
+    /// 名字本身，不带点时再加上本地域名
+    fn qualified_names(&self, name: &str) -> Vec<String> {
+        let name = name.to_ascii_lowercase();
+        if name.contains('.') {
+            vec![name]
+        } else {
+            let fqdn = format!("{}.{}", name, self.domain);
+            vec![name, fqdn]
+        }
+    }
+
+    /// 渲染为只提供 DNS 的 dnsmasq 配置
+    pub fn render_dnsmasq(&self) -> String {
+        let mut content = String::from("# Generated by meowtail from dns.toml, do not edit\n");
+        content.push_str("bind-dynamic\ndomain-needed\nbogus-priv\n");
+        for interface in &self.interfaces {
+            content.push_str(&format!("interface={}\n", interface));
+        }
+        content.push_str(&format!("pid-file={}\n", SERVER_PID_FILE));
+        // 本地域名的查询不转发给上游
+        content.push_str(&format!("domain={}\nlocal=/{}/\n", self.domain, self.domain));
+        content.push_str(&format!("cache-size={}\n", self.cache_size));
+        content.push_str(&format!("addn-hosts={}\n", HOSTS_FILE));
+
+        match self.upstream_mode {
+            UpstreamMode::Auto => {}
+            UpstreamMode::StrictOrder => content.push_str("strict-order\n"),
+            UpstreamMode::AllServers => content.push_str("all-servers\n"),
+        }
+        if !self.upstreams.is_empty() {
+            content.push_str("no-resolv\n");
+        }
+        for upstream in &self.upstreams {
+            let mut line = String::from("server=");
+            if !upstream.domains.is_empty() {
+                line.push_str(&format!("/{}/", upstream.domains.join("/")));
+            }
+            line.push_str(&upstream.address.to_string());
+            if let Some(port) = upstream.port {
+                line.push_str(&format!("#{}", port));
+            }
+            content.push_str(&line);
+            content.push('\n');
+        }
+
+        for record in &self.records {
+            let names = self.qualified_names(&record.name).join(",");
+            let line = match &record.data {
+                RecordData::A { address } => format!("host-record={},{}", names, address),
+                RecordData::Aaaa { address } => format!("host-record={},{}", names, address),
+                RecordData::Cname { target } => {
+                    // 不带点的目标指向本地域名下的名字
+                    let target = self.qualified_names(target).pop().unwrap_or_default();
+                    format!("cname={},{}", names, target)
+                }
+            };
+            content.push_str(&line);
+            content.push('\n');
+        }
+        content
+    }
+}
+
+/// 主机名的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostSource {
+    Static,
+    Dynamic,
+    Dhcpv6,
+}
+
+/// 从租约发布的一个名字
+#[derive(Debug, Clone, Serialize)]
+pub struct PublishedHost {
+    pub name: String,
+    pub address: IpAddr,
+    pub source: HostSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac: Option<MacAddr>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DnsStatus {
+    #[serde(flatten)]
+    pub process: SupervisorStatus,
+    pub config_dirty: Option<bool>,
+    /// 当前 hosts 文件中发布的名字数
+    pub published_hosts: usize,
+}
+
+/// 启用 `advertise` 时改动过的 DHCP 实例，关闭 `advertise` 或停止服务时恢复原来的 DNS 服务器
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AdvertisedInstance {
+    original: Vec<Ipv4Addr>,
+    advertised: Vec<Ipv4Addr>,
+}
+
+impl AdvertisedInstance {
+    /// 实例当前下发 `current` 时，改为通告 `address` 后的记录；已经在通告该地址时返回 None。
+    /// 实例还没有被改动过，或之后被手动修改过时，当前值就是需要恢复的值
+    fn next(previous: Option<&AdvertisedInstance>, current: &[Ipv4Addr], address: Ipv4Addr) -> Option<AdvertisedInstance> {
+        if current == [address] {
+            return None;
+        }
+        let original = match previous {
+            Some(entry) if entry.advertised == current => entry.original.clone(),
+            _ => current.to_vec(),
+        };
+        Some(AdvertisedInstance {
+            original,
+            advertised: vec![address],
+        })
+    }
+
+    /// 恢复时写回的服务器；实例在此期间被手动修改过时返回 None
+    fn restore_to(&self, current: &[Ipv4Addr]) -> Option<&[Ipv4Addr]> {
+        (self.advertised == current).then_some(self.original.as_slice())
+    }
+}
+
+/// 管理本地 DNS 服务：配置保存在 dns.toml，生成 dnsmasq 配置并监管 dnsmasq 进程
+pub struct DnsManager {
+    config_path: PathBuf,
+    server_config_path: PathBuf,
+    config_lock: Mutex<()>,
+    supervisor: ProcessSupervisor,
+    /// 运行中的 dnsmasq 启动时加载的配置的 ETag
+    applied_etag: Arc<Mutex<Option<String>>>,
+    registry: Arc<UdhcpdRegistry>,
+    inventory: Arc<DeviceInventory>,
+    dhcpv6: Arc<Dhcpv6Manager>,
+    published: Mutex<Vec<PublishedHost>>,
+    /// 保存在 dns.toml 旁的 dns-advertised.toml，meowtail 重启后仍能恢复
+    advertised_path: PathBuf,
+    advertised: Mutex<BTreeMap<String, AdvertisedInstance>>,
+}
+
+impl DnsManager {
+    pub fn new<P1: Into<PathBuf>, P2: Into<PathBuf>, P3: Into<PathBuf>>(
+        executable_path: &str,
+        config_path: P1,
+        server_config_path: P2,
+        pid_path: P3,
+        registry: Arc<UdhcpdRegistry>,
+        inventory: Arc<DeviceInventory>,
+        dhcpv6: Arc<Dhcpv6Manager>,
+    ) -> Self {
+        let server_config_path = server_config_path.into();
+        // 配置文件路径单独作为一个参数，只接管使用 DNS 配置的 dnsmasq，不会误接管 DHCP 的实例
+        let args = vec![
+            "--keep-in-foreground".to_string(),
+            "--log-facility=-".to_string(),
+            "--conf-file".to_string(),
+            server_config_path.to_string_lossy().into_owned(),
+        ];
+        let supervisor = ProcessSupervisor::new("dnsmasq-dns", executable_path, args, pid_path);
+
+        let applied_etag = Arc::new(Mutex::new(None));
+        let hook_etag = applied_etag.clone();
+        let hook_path = server_config_path.clone();
+        supervisor.set_spawn_hook(move || {
+            let etag = fs::read_to_string(&hook_path).ok().map(|c| content_etag(&c));
+            *hook_etag.lock().unwrap() = etag;
+        });
+
+        let config_path = config_path.into();
+        let advertised_path = config_path.with_file_name("dns-advertised.toml");
+        let advertised = match fs::read_to_string(&advertised_path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("[dns] Ignoring {:?}: {}", advertised_path, e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+
+        DnsManager {
+            config_path,
+            server_config_path,
+            config_lock: Mutex::new(()),
+            supervisor,
+            applied_etag,
+            registry,
+            inventory,
+            dhcpv6,
+            published: Mutex::new(vec![]),
+            advertised_path,
+            advertised: Mutex::new(advertised),
+        }
+    }
+
+    pub fn start(&self) -> Result<()> {
+        if self.is_running() {
+            return Err(AppError::Process("DNS server is already running.".to_string()));
+        }
+        let config = self.read_config()?;
+        config.validate()?;
+        write_atomic(&self.server_config_path, &config.render_dnsmasq())?;
+        // dnsmasq 启动时 hosts 文件必须存在
+        self.write_hosts(&config)?;
+
+        if let Some(pid) = self.supervisor.adopt() {
+            println!("Found running DNS dnsmasq with PID {}, supervising it instead of starting a new one.", pid);
+        } else {
+            self.supervisor.start()?;
+        }
+        if config.advertise {
+            self.advertise(&config);
+        } else {
+            self.withdraw(|_| true);
+        }
+        Ok(())
+    }
+
+    /// 停止服务，并恢复 DHCP 实例原来的 DNS 服务器
+    pub fn stop(&self) -> Result<()> {
+        self.stop_server()?;
+        self.withdraw(|_| true);
+        Ok(())
+    }
+
+    fn stop_server(&self) -> Result<()> {
+        self.supervisor.stop()?;
+        *self.applied_etag.lock().unwrap() = None;
+        Ok(())
+    }
+
+    /// 重启时不恢复 DHCP 实例的 DNS 服务器，避免它们连续重启两次
+    pub fn restart(&self) -> Result<()> {
+        self.stop_server()?;
+        thread::sleep(Duration::from_millis(200));
+        self.start()
+    }
+
+    pub fn adopt_running(&self) -> Option<u32> {
+        self.supervisor.adopt()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.supervisor.is_running()
+    }
+
+    pub fn status(&self) -> DnsStatus {
+        DnsStatus {
+            process: self.supervisor.status(),
+            config_dirty: self.config_dirty(),
+            published_hosts: self.published.lock().unwrap().len(),
+        }
+    }
+
+    pub fn config_dirty(&self) -> Option<bool> {
+        if !self.is_running() {
+            return Some(false);
+        }
+        let applied = self.applied_etag.lock().unwrap().clone()?;
+        let current = fs::read_to_string(&self.server_config_path).ok().map(|c| content_etag(&c));
+        Some(current.as_deref() != Some(applied.as_str()))
+    }
+
+    /// 读取配置，文件不存在时返回默认配置
+    pub fn read_config(&self) -> Result<DnsConfig> {
+        Ok(self.read_config_with_etag()?.0)
+    }
+
+    pub fn read_config_with_etag(&self) -> Result<(DnsConfig, String)> {
+        let content = match fs::read_to_string(&self.config_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let config = DnsConfig {
+                    interfaces: vec![interfaces::default_lan()],
+                    ..DnsConfig::default()
+                };
+                return Ok((config, content_etag("")));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let config = toml::from_str(&content)
+            .map_err(|e| AppError::ConfigParse(format!("{:?}: {}", self.config_path, e)))?;
+        Ok((config, content_etag(&content)))
+    }
+
+    /// 写入 dns.toml 与 dnsmasq 配置，服务在运行时重启使其生效，返回新的 ETag
+    fn write_config(&self, config: &DnsConfig) -> Result<String> {
+        let content = toml::to_string_pretty(config).map_err(|e| AppError::Process(e.to_string()))?;
+        write_atomic(&self.config_path, &content)?;
+        write_atomic(&self.server_config_path, &config.render_dnsmasq())?;
+        if self.is_running() {
+            self.restart()
+                .map_err(|e| AppError::Process(format!("Configuration saved, but applying it failed: {}", e)))?;
+        }
+        Ok(content_etag(&content))
+    }
+
+    /// 整体替换配置，`if_match` 与当前 ETag 不一致时拒绝写入
+    pub fn replace_config(&self, config: DnsConfig, if_match: Option<&str>) -> Result<String> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+
+        let (_, etag) = self.read_config_with_etag()?;
+        if let Some(expected) = if_match {
+            if !etag_matches(expected, &etag) {
+                return Err(AppError::PreconditionFailed(
+                    "Configuration was modified since it was read.".to_string(),
+                ));
+            }
+        }
+        config.validate()?;
+        self.write_config(&config)
+    }
+
+    /// 添加或替换同名同类型的记录；CNAME 会替换同名的所有记录
+    pub fn add_or_update_record(&self, record: DnsRecord) -> Result<()> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let mut config = self.read_config()?;
+        config.records.retain(|r| {
+            !r.name.eq_ignore_ascii_case(&record.name)
+                || (r.data.type_name() != record.data.type_name() && !r.data.is_cname() && !record.data.is_cname())
+        });
+        config.records.push(record);
+        config.validate()?;
+        self.write_config(&config)?;
+        Ok(())
+    }
+
+    /// 删除某个名字的某类记录
+    pub fn remove_record(&self, name: &str, record_type: &str) -> Result<()> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let mut config = self.read_config()?;
+        let before = config.records.len();
+        config
+            .records
+            .retain(|r| !(r.name.eq_ignore_ascii_case(name) && r.data.type_name().eq_ignore_ascii_case(record_type)));
+        if config.records.len() == before {
+            return Err(AppError::NotFound(format!("{} record for {}", record_type.to_ascii_uppercase(), name)));
+        }
+        self.write_config(&config)?;
+        Ok(())
+    }
+
+    /// 当前从租约发布的名字
+    pub fn published_hosts(&self) -> Vec<PublishedHost> {
+        self.published.lock().unwrap().clone()
+    }
+
+    /// 收集各 DHCP 实例的静态租约、动态租约与 DHCPv6 租约的主机名。
+    /// 静态租约优先使用设备清单中的名字；同一名字只发布第一个，自定义记录优先
+    fn collect_hosts(&self, config: &DnsConfig) -> Vec<PublishedHost> {
+        let mut hosts: Vec<PublishedHost> = Vec::new();
+        let suffix = format!(".{}", config.domain);
+        let mut taken: HashSet<(String, bool)> = config
+            .records
+            .iter()
+            .map(|r| r.name.to_ascii_lowercase())
+            .filter_map(|name| match name.strip_suffix(&suffix) {
+                Some(label) => Some(label.to_string()),
+                None => (!name.contains('.')).then_some(name),
+            })
+            .flat_map(|label| [(label.clone(), true), (label, false)])
+            .collect();
+        let mut publish = |name: Option<String>, address: IpAddr, source: HostSource, mac: Option<MacAddr>| {
+            let Some(label) = name.as_deref().and_then(host_label) else {
+                return;
+            };
+            if taken.insert((label.clone(), address.is_ipv4())) {
+                hosts.push(PublishedHost {
+                    name: label,
+                    address,
+                    source,
+                    mac,
+                });
+            }
+        };
+
+        let instances: Vec<_> = self
+            .registry
+            .instances()
+            .into_iter()
+            .map(|(_, manager)| {
+                let static_leases = manager.read_config().map(|c| c.static_leases).unwrap_or_default();
+                let leases = manager.leases().unwrap_or_default();
+                (static_leases, leases)
+            })
+            .collect();
+        let reported = |mac: MacAddr| {
+            instances
+                .iter()
+                .flat_map(|(_, leases)| leases)
+                .find(|l| MacAddr::from_str(&l.mac).ok() == Some(mac))
+                .and_then(|l| l.hostname.clone())
+        };
+
+        for (static_leases, _) in &instances {
+            for lease in static_leases {
+                let record = self.inventory.get(lease.mac);
+                let name = record
+                    .as_ref()
+                    .and_then(|r| r.name.clone())
+                    .or_else(|| reported(lease.mac))
+                    .or_else(|| record.and_then(|r| r.hostname));
+                publish(name, IpAddr::V4(lease.ip), HostSource::Static, Some(lease.mac));
+            }
+        }
+        for (static_leases, leases) in &instances {
+            for lease in leases {
+                let Ok(mac) = MacAddr::from_str(&lease.mac) else {
+                    continue;
+                };
+                if static_leases.iter().any(|l| l.mac == mac) {
+                    continue;
+                }
+                let name = self.inventory.get(mac).and_then(|r| r.name).or_else(|| lease.hostname.clone());
+                publish(name, IpAddr::V4(lease.ip), HostSource::Dynamic, Some(mac));
+            }
+        }
+        for lease in self.dhcpv6.leases().unwrap_or_default() {
+            publish(lease.hostname, IpAddr::V6(lease.ip), HostSource::Dhcpv6, None);
+        }
+        hosts
+    }
+
+    /// 重写 hosts 文件，返回内容是否有变化
+    fn write_hosts(&self, config: &DnsConfig) -> Result<bool> {
+        let hosts = if config.publish_leases { self.collect_hosts(config) } else { vec![] };
+        let mut content = String::from("# Generated by meowtail from DHCP leases, do not edit\n");
+        for host in &hosts {
+            content.push_str(&format!("{} {}.{} {}\n", host.address, host.name, config.domain, host.name));
+        }
+        *self.published.lock().unwrap() = hosts;
+        if fs::read_to_string(HOSTS_FILE).is_ok_and(|current| current == content) {
+            return Ok(false);
+        }
+        write_atomic(PathBuf::from(HOSTS_FILE).as_path(), &content)?;
+        Ok(true)
+    }
+
+    /// 把监听接口上各 DHCP 实例的 `option dns` 改为该接口的 IPv4 地址，
+    /// 改动前记下原来的值；不再位于监听接口上的实例恢复原值
+    fn advertise(&self, config: &DnsConfig) {
+        let mut advertised = self.advertised.lock().unwrap();
+        let mut current = HashSet::new();
+        for (name, manager) in self.registry.instances() {
+            let Ok(dhcp_config) = manager.read_config() else {
+                continue;
+            };
+            let Some(interface) = dhcp_config.interface.filter(|i| config.interfaces.contains(i)) else {
+                continue;
+            };
+            // 接口暂时没有地址时保持原状
+            current.insert(name.clone());
+            let Some(address) = interfaces::get(&interface).ok().and_then(|i| {
+                i.ipv4.iter().find_map(|a| match a.address {
+                    IpAddr::V4(ip) => Some(ip),
+                    IpAddr::V6(_) => None,
+                })
+            }) else {
+                continue;
+            };
+            let Some(entry) = AdvertisedInstance::next(advertised.get(&name), &dhcp_config.dns_servers, address) else {
+                continue;
+            };
+            advertised.insert(name.clone(), entry);
+            // 先保存原来的值，再修改 DHCP 配置
+            if let Err(e) = self.write_advertised(&advertised) {
+                eprintln!("[dns] Failed to write {:?}: {}", self.advertised_path, e);
+                continue;
+            }
+            match manager.set_dns_servers(vec![address]) {
+                Ok(()) => println!("[dns] DHCP instance '{}' now advertises {} as DNS server", name, address),
+                Err(e) => eprintln!("[dns] Failed to set DNS server of DHCP instance '{}': {}", name, e),
+            }
+        }
+        drop(advertised);
+        self.withdraw(|name| !current.contains(name));
+    }
+
+    /// 恢复满足条件的实例原来的 DNS 服务器。之后被手动修改过或已删除的实例只丢弃记录
+    fn withdraw<F: Fn(&str) -> bool>(&self, wanted: F) {
+        let mut advertised = self.advertised.lock().unwrap();
+        let names: Vec<String> = advertised.keys().filter(|name| wanted(name)).cloned().collect();
+        if names.is_empty() {
+            return;
+        }
+        for name in names {
+            let Some(entry) = advertised.remove(&name) else {
+                continue;
+            };
+            let Ok(manager) = self.registry.get(&name) else {
+                continue;
+            };
+            let result = match manager.read_config() {
+                Ok(c) => match entry.restore_to(&c.dns_servers) {
+                    Some(original) => manager.set_dns_servers(original.to_vec()),
+                    None => continue,
+                },
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => println!("[dns] Restored the DNS servers of DHCP instance '{}'", name),
+                Err(e) => {
+                    eprintln!("[dns] Failed to restore the DNS servers of DHCP instance '{}': {}", name, e);
+                    // 保留记录，下次再试
+                    advertised.insert(name, entry);
+                }
+            }
+        }
+        if let Err(e) = self.write_advertised(&advertised) {
+            eprintln!("[dns] Failed to write {:?}: {}", self.advertised_path, e);
+        }
+    }
+
+    fn write_advertised(&self, advertised: &BTreeMap<String, AdvertisedInstance>) -> Result<()> {
+        if advertised.is_empty() {
+            return match fs::remove_file(&self.advertised_path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        let content = toml::to_string_pretty(advertised).map_err(|e| AppError::Process(e.to_string()))?;
+        write_atomic(&self.advertised_path, &content)
+    }
+
+    /// 后台线程：服务运行时定期刷新租约主机名，并保持 `option dns` 指向路由器
+    pub fn spawn(manager: Arc<DnsManager>) {
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(REFRESH_SECS));
+            if !manager.is_running() {
+                continue;
+            }
+            let config = match manager.read_config() {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("[dns] {}", e);
+                    continue;
+                }
+            };
+            match manager.write_hosts(&config) {
+                Ok(true) => {
+                    if let Err(e) = manager.supervisor.signal(Signal::SIGHUP) {
+                        eprintln!("[dns] Failed to reload hosts: {}", e);
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("[dns] Failed to write {}: {}", HOSTS_FILE, e),
+            }
+            if config.advertise {
+                manager.advertise(&config);
+            } else {
+                manager.withdraw(|_| true);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(config: &DnsConfig) -> Vec<String> {
+        config.validation_issues().into_iter().map(|i| i.field).collect()
+    }
+
+    fn record(name: &str, data: RecordData) -> DnsRecord {
+        DnsRecord {
+            name: name.to_string(),
+            data,
+        }
+    }
+
+    #[test]
+    fn host_labels_are_sanitised() {
+        assert_eq!(host_label("Johns-iPhone").as_deref(), Some("johns-iphone"));
+        assert_eq!(host_label("Living Room TV").as_deref(), Some("living-room-tv"));
+        assert_eq!(host_label("laptop.example.com").as_deref(), Some("laptop"));
+        assert_eq!(host_label("_printer_").as_deref(), Some("printer"));
+        assert_eq!(host_label(&"a".repeat(80)).map(|l| l.len()), Some(63));
+        assert_eq!(host_label("---"), None);
+        assert_eq!(host_label(""), None);
+        assert_eq!(host_label(".hidden"), None);
+    }
+
+    #[test]
+    fn renders_dnsmasq_lines() {
+        let config = DnsConfig {
+            domain: "home".to_string(),
+            interfaces: vec!["br-lan".to_string()],
+            upstreams: vec![
+                Upstream {
+                    address: "1.1.1.1".parse().unwrap(),
+                    port: None,
+                    domains: vec![],
+                },
+                Upstream {
+                    address: "10.0.0.53".parse().unwrap(),
+                    port: Some(5353),
+                    domains: vec!["corp.example".to_string(), "vpn.example".to_string()],
+                },
+            ],
+            upstream_mode: UpstreamMode::StrictOrder,
+            cache_size: 500,
+            publish_leases: true,
+            advertise: false,
+            records: vec![
+                record("NAS", RecordData::A { address: Ipv4Addr::new(192, 168, 1, 5) }),
+                record("nas", RecordData::Aaaa { address: "fd00::5".parse().unwrap() }),
+                record("files", RecordData::Cname { target: "nas".to_string() }),
+                record("www.example.org", RecordData::Cname { target: "nas.home".to_string() }),
+            ],
+        };
+        let lines: Vec<String> = config.render_dnsmasq().lines().map(str::to_string).collect();
+        let expected = [
+            "interface=br-lan",
+            "domain=home",
+            "local=/home/",
+            "cache-size=500",
+            "strict-order",
+            "no-resolv",
+            "server=1.1.1.1",
+            "server=/corp.example/vpn.example/10.0.0.53#5353",
+            "host-record=nas,nas.home,192.168.1.5",
+            "host-record=nas,nas.home,fd00::5",
+            "cname=files,files.home,nas.home",
+            "cname=www.example.org,nas.home",
+        ];
+        for line in expected {
+            assert!(lines.iter().any(|l| l == line), "missing '{}' in {:?}", line, lines);
+        }
+    }
+
+    #[test]
+    fn no_upstreams_use_resolv_conf() {
+        let content = DnsConfig::default().render_dnsmasq();
+        assert!(!content.contains("no-resolv"));
+        assert!(!content.contains("server="));
+        assert!(!content.contains("strict-order"));
+    }
+
+    #[test]
+    fn cname_must_be_alone() {
+        let config = DnsConfig {
+            records: vec![
+                record("nas", RecordData::A { address: Ipv4Addr::new(192, 168, 1, 5) }),
+                record("NAS", RecordData::Cname { target: "files".to_string() }),
+                record("files", RecordData::Cname { target: "FILES".to_string() }),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(fields(&config), vec!["records[1].name", "records[2].target"]);
+    }
+
+    #[test]
+    fn rejects_duplicates_and_bad_names() {
+        let config = DnsConfig {
+            domain: "-lan".to_string(),
+            upstreams: vec![Upstream {
+                address: "0.0.0.0".parse().unwrap(),
+                port: Some(0),
+                domains: vec!["".to_string()],
+            }],
+            records: vec![
+                record("nas", RecordData::A { address: Ipv4Addr::new(192, 168, 1, 5) }),
+                record("Nas", RecordData::A { address: Ipv4Addr::new(192, 168, 1, 5) }),
+                record("nas", RecordData::A { address: Ipv4Addr::new(192, 168, 1, 6) }),
+                record("bad name", RecordData::A { address: Ipv4Addr::new(192, 168, 1, 7) }),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            fields(&config),
+            vec![
+                "domain",
+                "upstreams[0].address",
+                "upstreams[0].port",
+                "upstreams[0].domains[0]",
+                "records[1].name",
+                "records[3].name",
+            ]
+        );
+    }
+
+    #[test]
+    fn advertise_remembers_the_original_servers() {
+        let router = Ipv4Addr::new(192, 168, 1, 1);
+        let original = vec![Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(1, 1, 1, 1)];
+
+        let entry = AdvertisedInstance::next(None, &original, router).unwrap();
+        assert_eq!(entry.original, original);
+        assert_eq!(entry.advertised, vec![router]);
+        // 已经在通告路由器地址
+        assert!(AdvertisedInstance::next(Some(&entry), &[router], router).is_none());
+
+        // 接口地址变化后仍记得最初的服务器
+        let moved = Ipv4Addr::new(192, 168, 2, 1);
+        let entry = AdvertisedInstance::next(Some(&entry), &[router], moved).unwrap();
+        assert_eq!(entry.original, original);
+        assert_eq!(entry.restore_to(&[moved]), Some(original.as_slice()));
+
+        // 之后被手动修改过：新值成为需要恢复的值，恢复时也不覆盖手动修改
+        let manual = vec![Ipv4Addr::new(9, 9, 9, 9)];
+        assert_eq!(entry.restore_to(&manual), None);
+        let entry = AdvertisedInstance::next(Some(&entry), &manual, moved).unwrap();
+        assert_eq!(entry.original, manual);
+    }
+
+    #[test]
+    fn advertised_file_round_trips() {
+        let mut advertised = BTreeMap::new();
+        advertised.insert(
+            "lan".to_string(),
+            AdvertisedInstance {
+                original: vec![],
+                advertised: vec![Ipv4Addr::new(192, 168, 1, 1)],
+            },
+        );
+        let content = toml::to_string_pretty(&advertised).unwrap();
+        let parsed: BTreeMap<String, AdvertisedInstance> = toml::from_str(&content).unwrap();
+        assert_eq!(parsed["lan"].original, Vec::<Ipv4Addr>::new());
+        assert_eq!(parsed["lan"].restore_to(&[Ipv4Addr::new(192, 168, 1, 1)]), Some(&[][..]));
+    }
+}
@@ -0,0 +1,82 @@
+// src/error.rs
+
+use std::io;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::mac_addr::MacAddrError;
+
+/// 各子系统共用的错误类型，handler 直接把它转换为 HTTP 响应
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("I/O Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Process Error: {0}")]
+    Process(String),
+    #[error("Configuration parsing error on line: {0}")]
+    ConfigParse(String),
+    #[error("Nix (Unix-like system call) error: {0}")]
+    Nix(#[from] nix::Error),
+    #[error("Invalid IP address: {0}")]
+    InvalidIp(#[from] std::net::AddrParseError),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("{0}")]
+    InvalidMac(#[from] MacAddrError),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+    #[error("Configuration validation failed with {} issue(s)", .0.len())]
+    Validation(Vec<ValidationIssue>),
+}
+
+// 实现 ResponseError 以便 Actix-web 可以自动将我们的错误转换为 HTTP 响应
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            AppError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Process(_) => StatusCode::CONFLICT,
+            AppError::ConfigParse(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Nix(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::InvalidIp(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidMac(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            AppError::Validation(issues) => HttpResponse::build(self.status_code())
+                .json(serde_json::json!({ "error": self.to_string(), "issues": issues })),
+            _ => HttpResponse::build(self.status_code())
+                .json(serde_json::json!({ "error": self.to_string() })),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, AppError>;
+
+/// 配置校验发现的单个问题，`field` 指向出问题的配置项
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationIssue {
+    pub field: String,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    pub(crate) fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        ValidationIssue {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
@@ -0,0 +1,705 @@
+// src/firewall_manager.rs
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::fs_util::write_atomic;
+use crate::interfaces;
+use crate::ip_cidr::IpCidr;
+
+/// 从 INPUT/FORWARD 跳转到的顶层链，区域链以 `MT_` 开头
+const INPUT_CHAIN: &str = "MEOWTAIL_INPUT";
+const FORWARD_CHAIN: &str = "MEOWTAIL_FORWARD";
+const ZONE_CHAIN_PREFIX: &str = "MT_";
+const MAX_ZONE_NAME_LEN: usize = 16;
+
+/// 未匹配任何规则时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    Accept,
+    Drop,
+    Reject,
+}
+
+impl Policy {
+    fn target(&self) -> &'static str {
+        match self {
+            Policy::Accept => "ACCEPT",
+            Policy::Drop => "DROP",
+            Policy::Reject => "REJECT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Protocol {
+    #[default]
+    Any,
+    Tcp,
+    Udp,
+    TcpUdp,
+    Icmp,
+}
+
+/// 目的端口或端口范围，写作 `22` 或 `8000-8100`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, String> {
+        let parse = |p: &str| p.trim().parse::<u16>().ok().filter(|p| *p > 0);
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (parse(start), parse(end)),
+            None => (parse(s), parse(s)),
+        };
+        match (start, end) {
+            (Some(start), Some(end)) if start <= end => Ok(PortRange { start, end }),
+            _ => Err(format!("'{}' is not a port or port range", s)),
+        }
+    }
+}
+
+impl PortRange {
+    pub fn contains(&self, port: u16) -> bool {
+        (self.start..=self.end).contains(&port)
+    }
+}
+
+impl TryFrom<String> for PortRange {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<PortRange> for String {
+    fn from(range: PortRange) -> String {
+        range.to_string()
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+/// 一个防火墙区域，例如 wan、lan、guest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    /// 属于该区域的接口，可用 iptables 的 `+` 通配，例如 `ppp+`
+    #[serde(default)]
+    pub interfaces: Vec<String>,
+    /// 发往本机的流量的默认处理
+    pub input: Policy,
+    /// 从本区域转发出去、未被转发规则放行的流量的默认处理
+    pub forward: Policy,
+}
+
+/// 允许从 `from` 区域转发到 `to` 区域的新连接
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Forwarding {
+    pub from: String,
+    pub to: String,
+}
+
+/// 一条显式的放行或丢弃规则，在区域的默认处理之前匹配
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterRule {
+    /// 由 meowtail 分配
+    #[serde(default)]
+    pub id: u32,
+    /// 说明，写入 iptables 的 comment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// 来源区域
+    pub from: String,
+    /// 目的区域，未设置时规则作用于发往本机的流量
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub protocol: Protocol,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<IpCidr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<IpCidr>,
+    /// 目的端口，只用于 tcp/udp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ports: Option<PortRange>,
+    pub action: Policy,
+}
+
+impl FilterRule {
+    /// 规则中的地址限定了地址族时，只渲染到对应的 iptables/ip6tables
+    fn applies_to(&self, family: Family) -> bool {
+        [self.source, self.destination]
+            .iter()
+            .flatten()
+            .all(|cidr| cidr.is_ipv4() == (family == Family::V4))
+    }
+}
+
+/// firewall.toml 的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FirewallConfig {
+    /// 关闭时删除 meowtail 的所有链，filter 表恢复为系统原有的规则
+    pub enabled: bool,
+    pub zones: Vec<Zone>,
+    pub forwardings: Vec<Forwarding>,
+    pub rules: Vec<FilterRule>,
+}
+
+impl Default for FirewallConfig {
+    fn default() -> Self {
+        let zone = |name: &str, input, forward| Zone {
+            name: name.to_string(),
+            interfaces: vec![],
+            input,
+            forward,
+        };
+        let guest_rule = |id, name: &str, protocol, port: u16| FilterRule {
+            id,
+            name: Some(name.to_string()),
+            from: "guest".to_string(),
+            to: None,
+            protocol,
+            source: None,
+            destination: None,
+            ports: Some(PortRange { start: port, end: port }),
+            action: Policy::Accept,
+        };
+        FirewallConfig {
+            enabled: false,
+            zones: vec![
+                zone("wan", Policy::Drop, Policy::Drop),
+                zone("lan", Policy::Accept, Policy::Accept),
+                zone("guest", Policy::Reject, Policy::Reject),
+            ],
+            forwardings: vec![
+                Forwarding { from: "lan".to_string(), to: "wan".to_string() },
+                Forwarding { from: "guest".to_string(), to: "wan".to_string() },
+            ],
+            rules: vec![
+                guest_rule(1, "Guest DHCP", Protocol::Udp, 67),
+                guest_rule(2, "Guest DNS", Protocol::TcpUdp, 53),
+            ],
+        }
+    }
+}
+
+impl FirewallConfig {
+    pub fn validate(&self) -> Result<()> {
+        let issues = self.validation_issues();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let zone_exists = |name: &str| self.zones.iter().any(|z| z.name == name);
+        // 已出现过的接口及其所在区域
+        let mut seen: Vec<(&str, &str)> = Vec::new();
+
+        for (i, zone) in self.zones.iter().enumerate() {
+            let field = |name: &str| format!("zones[{}].{}", i, name);
+            let valid_name = !zone.name.is_empty()
+                && zone.name.len() <= MAX_ZONE_NAME_LEN
+                && zone.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid_name {
+                issues.push(ValidationIssue::new(
+                    field("name"),
+                    format!("Zone names are 1 to {} letters, digits or '_'", MAX_ZONE_NAME_LEN),
+                ));
+            }
+            if self.zones[..i].iter().any(|z| z.name == zone.name) {
+                issues.push(ValidationIssue::new(field("name"), format!("Zone {} is defined more than once", zone.name)));
+            }
+            for (j, interface) in zone.interfaces.iter().enumerate() {
+                let field = format!("zones[{}].interfaces[{}]", i, j);
+                if !interfaces::is_valid_name(interface.strip_suffix('+').unwrap_or(interface)) {
+                    issues.push(ValidationIssue::new(field, format!("'{}' is not a valid interface name", interface)));
+                } else if let Some((_, other)) = seen.iter().find(|(name, _)| name == interface) {
+                    issues.push(ValidationIssue::new(field, format!("{} is already in zone {}", interface, other)));
+                } else {
+                    seen.push((interface, &zone.name));
+                }
+            }
+        }
+
+        for (i, forwarding) in self.forwardings.iter().enumerate() {
+            for (name, zone) in [("from", &forwarding.from), ("to", &forwarding.to)] {
+                if !zone_exists(zone) {
+                    issues.push(ValidationIssue::new(
+                        format!("forwardings[{}].{}", i, name),
+                        format!("Unknown zone '{}'", zone),
+                    ));
+                }
+            }
+            if forwarding.from == forwarding.to {
+                issues.push(ValidationIssue::new(format!("forwardings[{}].to", i), "A zone cannot forward to itself"));
+            }
+        }
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            let field = |name: &str| format!("rules[{}].{}", i, name);
+            if self.rules[..i].iter().any(|r| r.id == rule.id) {
+                issues.push(ValidationIssue::new(field("id"), format!("Rule ID {} is used more than once", rule.id)));
+            }
+            if !zone_exists(&rule.from) {
+                issues.push(ValidationIssue::new(field("from"), format!("Unknown zone '{}'", rule.from)));
+            }
+            if let Some(to) = rule.to.as_ref().filter(|to| !zone_exists(to)) {
+                issues.push(ValidationIssue::new(field("to"), format!("Unknown zone '{}'", to)));
+            }
+            if rule.ports.is_some() && !matches!(rule.protocol, Protocol::Tcp | Protocol::Udp | Protocol::TcpUdp) {
+                issues.push(ValidationIssue::new(field("ports"), "Ports require protocol tcp, udp or tcp-udp"));
+            }
+            if let (Some(source), Some(destination)) = (rule.source, rule.destination) {
+                if source.is_ipv4() != destination.is_ipv4() {
+                    issues.push(ValidationIssue::new(
+                        field("destination"),
+                        "Source and destination must be of the same address family",
+                    ));
+                }
+            }
+            if let Some(name) = &rule.name {
+                if name.len() > 200 || name.chars().any(|c| c.is_control() || c == '"' || c == '\\') {
+                    issues.push(ValidationIssue::new(
+                        field("name"),
+                        "Name must be at most 200 characters without quotes, backslashes or control characters",
+                    ));
+                }
+            }
+        }
+
+        issues
+    }
+
+    fn zone(&self, name: &str) -> Option<&Zone> {
+        self.zones.iter().find(|z| z.name == name)
+    }
+
+    /// 渲染为 `iptables-restore --noflush` 的输入，只包含 meowtail 自己的链
+    pub fn render(&self, family: Family) -> String {
+        let mut out = String::from("*filter\n");
+        out.push_str(&format!(":{} - [0:0]\n:{} - [0:0]\n", INPUT_CHAIN, FORWARD_CHAIN));
+        for zone in &self.zones {
+            out.push_str(&format!(":{} - [0:0]\n:{} - [0:0]\n", input_chain(zone), forward_chain(zone)));
+        }
+
+        // 已建立的连接始终放行，应用新规则不会断开当前会话
+        for chain in [INPUT_CHAIN, FORWARD_CHAIN] {
+            out.push_str(&format!("-A {} -m conntrack --ctstate ESTABLISHED,RELATED -j ACCEPT\n", chain));
+            out.push_str(&format!("-A {} -m conntrack --ctstate INVALID -j DROP\n", chain));
+        }
+        out.push_str(&format!("-A {} -i lo -j ACCEPT\n", INPUT_CHAIN));
+        match family {
+            // DHCP 客户端收到的应答不一定能被 conntrack 识别为已建立的连接
+            Family::V4 => {
+                out.push_str(&format!("-A {} -p udp --dport 68 -j ACCEPT\n", INPUT_CHAIN));
+                // 端口映射（DNAT）的流量
+                out.push_str(&format!("-A {} -m conntrack --ctstate DNAT -j ACCEPT\n", FORWARD_CHAIN));
+            }
+            // 邻居发现与 DHCPv6 客户端
+            Family::V6 => {
+                out.push_str(&format!("-A {} -p ipv6-icmp -j ACCEPT\n", INPUT_CHAIN));
+                out.push_str(&format!("-A {} -p udp --dport 546 -j ACCEPT\n", INPUT_CHAIN));
+            }
+        }
+        for zone in &self.zones {
+            for interface in &zone.interfaces {
+                out.push_str(&format!("-A {} -i {} -j {}\n", INPUT_CHAIN, interface, input_chain(zone)));
+                out.push_str(&format!("-A {} -i {} -j {}\n", FORWARD_CHAIN, interface, forward_chain(zone)));
+            }
+        }
+
+        for zone in &self.zones {
+            for rule in self.rules.iter().filter(|r| r.from == zone.name && r.applies_to(family)) {
+                match &rule.to {
+                    None => self.render_rule(&mut out, &input_chain(zone), rule, None, family),
+                    Some(to) => {
+                        for interface in self.zone(to).map(|z| z.interfaces.as_slice()).unwrap_or_default() {
+                            self.render_rule(&mut out, &forward_chain(zone), rule, Some(interface), family);
+                        }
+                    }
+                }
+            }
+            for forwarding in self.forwardings.iter().filter(|f| f.from == zone.name) {
+                for interface in self.zone(&forwarding.to).map(|z| z.interfaces.as_slice()).unwrap_or_default() {
+                    out.push_str(&format!("-A {} -o {} -j ACCEPT\n", forward_chain(zone), interface));
+                }
+            }
+            out.push_str(&format!("-A {} -j {}\n", input_chain(zone), zone.input.target()));
+            out.push_str(&format!("-A {} -j {}\n", forward_chain(zone), zone.forward.target()));
+        }
+
+        out.push_str("COMMIT\n");
+        out
+    }
+
+    fn render_rule(&self, out: &mut String, chain: &str, rule: &FilterRule, out_interface: Option<&String>, family: Family) {
+        let protocols: &[&str] = match (rule.protocol, family) {
+            (Protocol::Any, _) => &[""],
+            (Protocol::Tcp, _) => &["tcp"],
+            (Protocol::Udp, _) => &["udp"],
+            (Protocol::TcpUdp, _) => &["tcp", "udp"],
+            (Protocol::Icmp, Family::V4) => &["icmp"],
+            (Protocol::Icmp, Family::V6) => &["ipv6-icmp"],
+        };
+        for protocol in protocols {
+            let mut line = format!("-A {}", chain);
+            if let Some(interface) = out_interface {
+                line.push_str(&format!(" -o {}", interface));
+            }
+            if !protocol.is_empty() {
+                line.push_str(&format!(" -p {}", protocol));
+            }
+            if let Some(source) = rule.source {
+                line.push_str(&format!(" -s {}", source));
+            }
+            if let Some(destination) = rule.destination {
+                line.push_str(&format!(" -d {}", destination));
+            }
+            if let Some(ports) = rule.ports {
+                line.push_str(&format!(" --dport {}", ports.to_string().replace('-', ":")));
+            }
+            let comment = rule.name.clone().unwrap_or_else(|| format!("rule {}", rule.id));
+            line.push_str(&format!(" -m comment --comment \"{}\" -j {}\n", comment, rule.action.target()));
+            out.push_str(&line);
+        }
+    }
+}
+
+fn input_chain(zone: &Zone) -> String {
+    format!("{}IN_{}", ZONE_CHAIN_PREFIX, zone.name)
+}
+
+fn forward_chain(zone: &Zone) -> String {
+    format!("{}FWD_{}", ZONE_CHAIN_PREFIX, zone.name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    fn iptables(&self) -> &'static str {
+        match self {
+            Family::V4 => "iptables",
+            Family::V6 => "ip6tables",
+        }
+    }
+
+    fn restore(&self) -> &'static str {
+        match self {
+            Family::V4 => "iptables-restore",
+            Family::V6 => "ip6tables-restore",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FirewallStatus {
+    pub enabled: bool,
+    /// INPUT/FORWARD 中是否有跳转到 meowtail 链的规则
+    pub ipv4_installed: bool,
+    pub ipv6_installed: bool,
+}
+
+/// 管理 filter 表中的区域与规则，配置保存在 firewall.toml。
+/// 规则写入 meowtail 自己的链，由 INPUT/FORWARD 的第一条规则跳转过去，其他规则保持不变。
+pub struct FirewallManager {
+    config_path: PathBuf,
+    config_lock: Mutex<()>,
+}
+
+impl FirewallManager {
+    pub fn new<P: Into<PathBuf>>(config_path: P) -> Self {
+        FirewallManager {
+            config_path: config_path.into(),
+            config_lock: Mutex::new(()),
+        }
+    }
+
+    /// 读取配置，文件不存在时返回默认的 wan/lan/guest 区域（未启用）
+    pub fn read_config(&self) -> Result<FirewallConfig> {
+        let content = match fs::read_to_string(&self.config_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(FirewallConfig::default()),
+            Err(e) => return Err(e.into()),
+        };
+        toml::from_str(&content).map_err(|e| AppError::ConfigParse(format!("{:?}: {}", self.config_path, e)))
+    }
+
+    fn write_config(&self, config: &FirewallConfig) -> Result<()> {
+        let content = toml::to_string_pretty(config).map_err(|e| AppError::Process(e.to_string()))?;
+        write_atomic(&self.config_path, &content)
+    }
+
+    /// 在锁内修改配置：校验、应用到 iptables 成功后再保存
+    pub fn update_config<T, F: FnOnce(&mut FirewallConfig) -> Result<T>>(&self, update: F) -> Result<T> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let mut config = self.read_config()?;
+        let result = update(&mut config)?;
+        config.validate()?;
+        apply(&config)?;
+        self.write_config(&config)?;
+        Ok(result)
+    }
+
+    pub fn replace_config(&self, config: FirewallConfig) -> Result<()> {
+        self.update_config(|current| {
+            *current = config;
+            Ok(())
+        })
+    }
+
+    pub fn set_enabled(&self, enabled: bool) -> Result<()> {
+        self.update_config(|c| {
+            c.enabled = enabled;
+            Ok(())
+        })
+    }
+
+    pub fn set_zone(&self, zone: Zone) -> Result<()> {
+        self.update_config(|c| {
+            match c.zones.iter_mut().find(|z| z.name == zone.name) {
+                Some(existing) => *existing = zone,
+                None => c.zones.push(zone),
+            }
+            Ok(())
+        })
+    }
+
+    /// 删除区域，引用它的转发与规则一并删除
+    pub fn remove_zone(&self, name: &str) -> Result<()> {
+        self.update_config(|c| {
+            if c.zone(name).is_none() {
+                return Err(AppError::NotFound(format!("Zone {}", name)));
+            }
+            c.zones.retain(|z| z.name != name);
+            c.forwardings.retain(|f| f.from != name && f.to != name);
+            c.rules.retain(|r| r.from != name && r.to.as_deref() != Some(name));
+            Ok(())
+        })
+    }
+
+    pub fn add_forwarding(&self, forwarding: Forwarding) -> Result<()> {
+        self.update_config(|c| {
+            if !c.forwardings.contains(&forwarding) {
+                c.forwardings.push(forwarding);
+            }
+            Ok(())
+        })
+    }
+
+    pub fn remove_forwarding(&self, forwarding: &Forwarding) -> Result<()> {
+        self.update_config(|c| {
+            if !c.forwardings.contains(forwarding) {
+                return Err(AppError::NotFound(format!("Forwarding {} -> {}", forwarding.from, forwarding.to)));
+            }
+            c.forwardings.retain(|f| f != forwarding);
+            Ok(())
+        })
+    }
+
+    /// 添加规则并返回分配的 ID
+    pub fn add_rule(&self, mut rule: FilterRule) -> Result<FilterRule> {
+        self.update_config(|c| {
+            rule.id = c.rules.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+            c.rules.push(rule.clone());
+            Ok(rule)
+        })
+    }
+
+    pub fn update_rule(&self, id: u32, mut rule: FilterRule) -> Result<FilterRule> {
+        rule.id = id;
+        self.update_config(|c| {
+            let existing = c
+                .rules
+                .iter_mut()
+                .find(|r| r.id == id)
+                .ok_or_else(|| AppError::NotFound(format!("Rule {}", id)))?;
+            *existing = rule.clone();
+            Ok(rule)
+        })
+    }
+
+    pub fn remove_rule(&self, id: u32) -> Result<()> {
+        self.update_config(|c| {
+            if !c.rules.iter().any(|r| r.id == id) {
+                return Err(AppError::NotFound(format!("Rule {}", id)));
+            }
+            c.rules.retain(|r| r.id != id);
+            Ok(())
+        })
+    }
+
+    pub fn status(&self) -> Result<FirewallStatus> {
+        Ok(FirewallStatus {
+            enabled: self.read_config()?.enabled,
+            ipv4_installed: jump_installed(Family::V4, "filter", "INPUT", INPUT_CHAIN),
+            ipv6_installed: jump_installed(Family::V6, "filter", "INPUT", INPUT_CHAIN),
+        })
+    }
+
+    /// 启动时按 firewall.toml 重建规则
+    pub fn apply_all(&self) -> Result<()> {
+        let config = self.read_config()?;
+        config.validate()?;
+        apply(&config)
+    }
+}
+
+/// 应用配置：启用时替换 meowtail 的链并确保跳转存在，关闭时删除它们
+fn apply(config: &FirewallConfig) -> Result<()> {
+    for family in [Family::V4, Family::V6] {
+        if config.enabled {
+            restore(family, &config.render(family))?;
+            for (builtin, chain) in [("INPUT", INPUT_CHAIN), ("FORWARD", FORWARD_CHAIN)] {
+                if !jump_installed(family, "filter", builtin, chain) {
+                    run_iptables(family, &["-I", builtin, "1", "-j", chain])?;
+                }
+            }
+            // 管理端口的保护需要排在 MEOWTAIL_INPUT 之前
+            crate::management_access::keep_first(family)?;
+            let rendered: Vec<String> = config.zones.iter().flat_map(|z| [input_chain(z), forward_chain(z)]).collect();
+            remove_chains(family, "filter", |chain| chain.starts_with(ZONE_CHAIN_PREFIX) && !rendered.iter().any(|r| r == chain));
+        } else {
+            for (builtin, chain) in [("INPUT", INPUT_CHAIN), ("FORWARD", FORWARD_CHAIN)] {
+                while jump_installed(family, "filter", builtin, chain) {
+                    run_iptables(family, &["-D", builtin, "-j", chain])?;
+                }
+            }
+            remove_chains(family, "filter", |chain| {
+                chain == INPUT_CHAIN || chain == FORWARD_CHAIN || chain.starts_with(ZONE_CHAIN_PREFIX)
+            });
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn run_iptables(family: Family, args: &[&str]) -> Result<()> {
+    let output = Command::new(family.iptables()).args(args).output()?;
+    if !output.status.success() {
+        return Err(AppError::Process(format!(
+            "{} {} failed: {}",
+            family.iptables(),
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn jump_installed(family: Family, table: &str, builtin: &str, chain: &str) -> bool {
+    Command::new(family.iptables())
+        .args(["-t", table, "-C", builtin, "-j", chain])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// 内置链中跳转到 `chain` 的规则的位置，从 1 开始
+pub(crate) fn jump_position(family: Family, builtin: &str, chain: &str) -> Option<usize> {
+    let output = Command::new(family.iptables()).args(["-t", "filter", "-S", builtin]).output().ok()?;
+    parse_jump_position(&String::from_utf8_lossy(&output.stdout), builtin, chain)
+}
+
+/// 解析 `iptables -S <builtin>` 的输出，只有 `-A` 行占用位置
+fn parse_jump_position(rules: &str, builtin: &str, chain: &str) -> Option<usize> {
+    let jump = format!("-A {} -j {}", builtin, chain);
+    rules
+        .lines()
+        .filter(|line| line.starts_with("-A "))
+        .position(|line| line.trim() == jump)
+        .map(|i| i + 1)
+}
+
+/// 用 `iptables-restore --noflush` 一次性替换 meowtail 的链，其他链不受影响
+pub(crate) fn restore(family: Family, rules: &str) -> Result<()> {
+    let mut child = Command::new(family.restore())
+        .arg("--noflush")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(rules.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(AppError::Process(format!(
+            "{} failed: {}",
+            family.restore(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// 清空并删除表中满足条件的自定义链；全部清空后再删除，链之间的跳转不会阻止删除
+pub(crate) fn remove_chains<F: Fn(&str) -> bool>(family: Family, table: &str, wanted: F) {
+    let Ok(output) = Command::new(family.iptables()).args(["-t", table, "-S"]).output() else {
+        return;
+    };
+    let chains: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.strip_prefix("-N "))
+        .filter(|chain| wanted(chain))
+        .map(str::to_string)
+        .collect();
+    for chain in &chains {
+        let _ = run_iptables(family, &["-t", table, "-F", chain]);
+    }
+    for chain in &chains {
+        if let Err(e) = run_iptables(family, &["-t", table, "-X", chain]) {
+            eprintln!("[firewall] {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jump_position_counts_only_appended_rules() {
+        let rules = "-P INPUT ACCEPT\n\
+                     -A INPUT -j MEOWTAIL_INPUT\n\
+                     -A INPUT -i lo -j ACCEPT\n\
+                     -A INPUT -j MEOWTAIL_MGMT\n";
+        assert_eq!(parse_jump_position(rules, "INPUT", "MEOWTAIL_INPUT"), Some(1));
+        assert_eq!(parse_jump_position(rules, "INPUT", "MEOWTAIL_MGMT"), Some(3));
+        assert_eq!(parse_jump_position(rules, "INPUT", "MEOWTAIL"), None);
+        assert_eq!(parse_jump_position(rules, "FORWARD", "MEOWTAIL_INPUT"), None);
+    }
+}
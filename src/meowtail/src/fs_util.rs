@@ -0,0 +1,93 @@
+// src/fs_util.rs
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use crate::error::Result;
+
+/// 先写入临时文件再 rename，避免进程读到写了一半的配置
+pub(crate) fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// 与 write_atomic 相同，但文件权限为 0600，用于保存密码与 token 的文件
+pub(crate) fn write_secret(path: &Path, content: &str) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    // 残留的临时文件可能有更宽的权限，OpenOptions 的 mode 只在创建时生效
+    let _ = fs::remove_file(&tmp_path);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp_path)?;
+    file.write_all(content.as_bytes())?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// 以内容的 FNV-1a 哈希作为配置文件的 ETag，跨进程与版本保持稳定
+pub(crate) fn content_etag(content: &str) -> String {
+    let hash = content.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// 判断 If-Match 头是否匹配当前 ETag：支持 `*`、逗号分隔的多个值、引号与弱校验前缀 W/
+pub(crate) fn etag_matches(if_match: &str, etag: &str) -> bool {
+    let mut tags = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in if_match.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => tags.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    tags.push(current);
+    tags.iter()
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag).trim() == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn etag_is_stable() {
+        // FNV-1a 的公开测试向量
+        assert_eq!(content_etag(""), "cbf29ce484222325");
+        assert_eq!(content_etag("a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn if_match_accepts_lists_and_wildcard() {
+        let etag = content_etag("interface eth0\n");
+        assert!(etag_matches(&format!("\"{}\"", etag), &etag));
+        assert!(etag_matches(&format!("W/\"{}\"", etag), &etag));
+        assert!(etag_matches(&format!("\"0000\", \"{}\"", etag), &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"0000\", \"1111\"", &etag));
+        assert!(!etag_matches("", &etag));
+    }
+
+    #[test]
+    fn secrets_are_private() {
+        let path = std::env::temp_dir().join(format!("meowtail-secret-{}", std::process::id()));
+        write_secret(&path, "password").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "password");
+        fs::remove_file(&path).unwrap();
+    }
+}
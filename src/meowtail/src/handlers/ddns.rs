@@ -0,0 +1,81 @@
+// src/handlers/ddns.rs
+
+use crate::ddns_manager::{DdnsConfig, DdnsHost, DdnsManager};
+use crate::error::AppError;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder, Scope};
+use serde_json::json;
+
+// 密码与 API token 不会返回，提交时留空表示不修改
+#[get("")]
+async fn get_ddns(manager: web::Data<DdnsManager>) -> Result<impl Responder, AppError> {
+    let config = web::block(move || manager.read_config())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(config.redacted()))
+}
+
+#[put("")]
+async fn replace_ddns(manager: web::Data<DdnsManager>, payload: web::Json<DdnsConfig>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.replace_config(payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "DDNS updated"})))
+}
+
+#[get("/status")]
+async fn ddns_status(manager: web::Data<DdnsManager>) -> Result<impl Responder, AppError> {
+    let status = web::block(move || manager.status())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+// 立即检测地址并更新所有主机，返回更新后的状态
+#[post("/update")]
+async fn update_ddns_now(manager: web::Data<DdnsManager>) -> Result<impl Responder, AppError> {
+    let status = web::block(move || manager.update_now())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+#[post("/hosts")]
+async fn add_ddns_host(manager: web::Data<DdnsManager>, payload: web::Json<DdnsHost>) -> Result<impl Responder, AppError> {
+    let host = web::block(move || manager.add_host(payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Created().json(host.redacted()))
+}
+
+#[put("/hosts/{id}")]
+async fn update_ddns_host(
+    manager: web::Data<DdnsManager>,
+    path: web::Path<u32>,
+    payload: web::Json<DdnsHost>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let host = web::block(move || manager.update_host(id, payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(host.redacted()))
+}
+
+#[delete("/hosts/{id}")]
+async fn delete_ddns_host(manager: web::Data<DdnsManager>, path: web::Path<u32>) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    web::block(move || manager.remove_host(id))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "DDNS host deleted"})))
+}
+
+pub fn service() -> Scope {
+    web::scope("/ddns")
+        .service(get_ddns)
+        .service(replace_ddns)
+        .service(ddns_status)
+        .service(update_ddns_now)
+        .service(add_ddns_host)
+        .service(update_ddns_host)
+        .service(delete_ddns_host)
+}
@@ -0,0 +1,72 @@
+// src/handlers/devices.rs
+
+use crate::device_inventory::DeviceInventory;
+use crate::error::AppError;
+use crate::mac_addr::MacAddr;
+use crate::udhcpd_registry::UdhcpdRegistry;
+use actix_web::{delete, get, put, web, HttpResponse, Responder, Scope};
+use serde::Deserialize;
+use serde_json::json;
+use std::str::FromStr;
+
+#[derive(Deserialize)]
+struct DevicePayload {
+    name: Option<String>,
+    notes: Option<String>,
+}
+
+#[get("")]
+async fn list_devices(
+    inventory: web::Data<DeviceInventory>,
+    registry: web::Data<UdhcpdRegistry>,
+) -> Result<impl Responder, AppError> {
+    let devices = web::block(move || inventory.list(&registry))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(devices))
+}
+
+#[put("/{mac}")]
+async fn update_device(
+    inventory: web::Data<DeviceInventory>,
+    path: web::Path<String>,
+    payload: web::Json<DevicePayload>,
+) -> Result<impl Responder, AppError> {
+    let DevicePayload { name, notes } = payload.into_inner();
+    let mac = MacAddr::from_str(&path)?;
+    let record = web::block(move || inventory.update(mac, name, notes))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(record))
+}
+
+#[delete("/{mac}")]
+async fn delete_device(
+    inventory: web::Data<DeviceInventory>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let mac = MacAddr::from_str(&path)?;
+    web::block(move || inventory.remove(mac))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "Device removed"})))
+}
+
+// 按内置 OUI 表查询任意 MAC 地址的厂商
+#[get("/{mac}/vendor")]
+async fn vendor(path: web::Path<String>) -> Result<impl Responder, AppError> {
+    let mac = MacAddr::from_str(&path)?;
+    Ok(HttpResponse::Ok().json(json!({
+        "mac": mac,
+        "vendor": mac.vendor(),
+        "locally_administered": mac.is_locally_administered(),
+    })))
+}
+
+pub fn service() -> Scope {
+    web::scope("/devices")
+        .service(list_devices)
+        .service(vendor)
+        .service(update_device)
+        .service(delete_device)
+}
@@ -0,0 +1,278 @@
+// src/handlers/dhcpv6.rs
+
+use crate::config::AppConfig;
+use crate::dhcpv6_manager::{AddressMode, Dhcpv6Config, Dhcpv6Manager, DuidReservation, Ipv6Prefix};
+use crate::error::AppError;
+use actix_web::{delete, get, http::header, post, put, web, HttpRequest, HttpResponse, Responder, Scope};
+use serde::Deserialize;
+use serde_json::json;
+use std::net::Ipv6Addr;
+use std::str::FromStr;
+
+// --- 请求体 (Payloads) 定义 ---
+
+#[derive(Deserialize)]
+struct PrefixPayload {
+    // null 表示跟随接口上的前缀
+    prefix: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ModePayload {
+    mode: AddressMode,
+}
+
+#[derive(Deserialize)]
+struct RangePayload {
+    start: String,
+    end: String,
+}
+
+#[derive(Deserialize)]
+struct DnsPayload {
+    servers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct InterfacePayload {
+    interface: String,
+}
+
+#[derive(Deserialize)]
+struct ReservationPayload {
+    duid: String,
+    ip: String,
+    hostname: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RemoveReservationPayload {
+    duid: String,
+}
+
+#[derive(Deserialize)]
+struct ReplaceConfigPayload {
+    #[serde(flatten)]
+    config: Dhcpv6Config,
+    #[serde(default)]
+    restart: bool,
+}
+
+#[derive(Deserialize)]
+struct AutostartPayload {
+    enabled: bool,
+}
+
+fn parse_ipv6(value: &str, what: &str) -> Result<Ipv6Addr, AppError> {
+    Ipv6Addr::from_str(value).map_err(|_| AppError::InvalidInput(format!("Invalid {}: {}", what, value)))
+}
+
+// --- 服务控制 ---
+
+#[post("/start")]
+async fn start(manager: web::Data<Dhcpv6Manager>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.start())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "DHCPv6 server started"})))
+}
+
+#[post("/stop")]
+async fn stop(manager: web::Data<Dhcpv6Manager>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.stop())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "DHCPv6 server stopped"})))
+}
+
+#[post("/restart")]
+async fn restart(manager: web::Data<Dhcpv6Manager>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.restart())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "DHCPv6 server restarted"})))
+}
+
+#[get("/status")]
+async fn status(manager: web::Data<Dhcpv6Manager>) -> Result<impl Responder, AppError> {
+    let status = web::block(move || manager.status())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+#[get("/leases")]
+async fn leases(manager: web::Data<Dhcpv6Manager>) -> Result<impl Responder, AppError> {
+    let leases = web::block(move || manager.leases())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(leases))
+}
+
+// 设置开机自启，并保存到 meowtail.toml
+#[post("/autostart")]
+async fn set_autostart(
+    config: web::Data<AppConfig>,
+    payload: web::Json<AutostartPayload>,
+) -> Result<impl Responder, AppError> {
+    let mut app_config = config.lock().unwrap();
+    app_config.dhcpv6_autostart = payload.enabled;
+    app_config.save()?;
+    Ok(HttpResponse::Ok().json(json!({ "autostart": payload.enabled })))
+}
+
+// --- 配置 ---
+
+#[get("/config")]
+async fn get_config(manager: web::Data<Dhcpv6Manager>) -> Result<impl Responder, AppError> {
+    let (config, etag) = manager.read_config_with_etag()?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, format!("\"{}\"", etag)))
+        .json(config))
+}
+
+#[put("/config")]
+async fn replace_config(
+    req: HttpRequest,
+    manager: web::Data<Dhcpv6Manager>,
+    payload: web::Json<ReplaceConfigPayload>,
+) -> Result<impl Responder, AppError> {
+    let if_match = super::if_match(&req)?;
+    let ReplaceConfigPayload { config, restart: restart_after } = payload.into_inner();
+
+    let etag = web::block(move || {
+        let etag = manager.replace_config(config, if_match.as_deref())?;
+        if restart_after {
+            manager.restart()?;
+        }
+        Ok::<_, AppError>(etag)
+    })
+    .await
+    .map_err(|e| AppError::Process(e.to_string()))??;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, format!("\"{}\"", etag)))
+        .json(json!({ "status": "Configuration replaced", "restarted": restart_after })))
+}
+
+#[post("/config/validate")]
+async fn validate_config(payload: web::Json<Dhcpv6Config>) -> Result<impl Responder, AppError> {
+    let issues = payload.validation_issues();
+    Ok(HttpResponse::Ok().json(json!({ "valid": issues.is_empty(), "issues": issues })))
+}
+
+#[post("/config/prefix")]
+async fn set_prefix(
+    manager: web::Data<Dhcpv6Manager>,
+    payload: web::Json<PrefixPayload>,
+) -> Result<impl Responder, AppError> {
+    let prefix = match &payload.prefix {
+        Some(p) => Some(Ipv6Prefix::from_str(p).map_err(AppError::InvalidInput)?),
+        None => None,
+    };
+    web::block(move || manager.set_prefix(prefix))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "Prefix updated"})))
+}
+
+#[post("/config/mode")]
+async fn set_mode(
+    manager: web::Data<Dhcpv6Manager>,
+    payload: web::Json<ModePayload>,
+) -> Result<impl Responder, AppError> {
+    let mode = payload.mode;
+    web::block(move || manager.set_mode(mode))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "Address mode updated"})))
+}
+
+#[post("/config/range")]
+async fn set_range(
+    manager: web::Data<Dhcpv6Manager>,
+    payload: web::Json<RangePayload>,
+) -> Result<impl Responder, AppError> {
+    let start_ip = parse_ipv6(&payload.start, "range start")?;
+    let end_ip = parse_ipv6(&payload.end, "range end")?;
+    web::block(move || manager.set_range(start_ip, end_ip))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "DHCPv6 range updated"})))
+}
+
+#[post("/config/dns")]
+async fn set_dns(
+    manager: web::Data<Dhcpv6Manager>,
+    payload: web::Json<DnsPayload>,
+) -> Result<impl Responder, AppError> {
+    let servers: Vec<Ipv6Addr> = payload
+        .servers
+        .iter()
+        .map(|s| parse_ipv6(s, "DNS server address"))
+        .collect::<Result<_, _>>()?;
+    web::block(move || manager.set_dns_servers(servers))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "DNS servers updated"})))
+}
+
+#[post("/config/interface")]
+async fn set_interface(
+    manager: web::Data<Dhcpv6Manager>,
+    payload: web::Json<InterfacePayload>,
+) -> Result<impl Responder, AppError> {
+    let interface = payload.interface.clone();
+    web::block(move || manager.set_interface(interface))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "Interface updated"})))
+}
+
+#[post("/config/reservation")]
+async fn add_reservation(
+    manager: web::Data<Dhcpv6Manager>,
+    payload: web::Json<ReservationPayload>,
+) -> Result<impl Responder, AppError> {
+    let reservation = DuidReservation {
+        duid: payload.duid.to_lowercase(),
+        ip: parse_ipv6(&payload.ip, "reservation address")?,
+        hostname: payload.hostname.clone(),
+    };
+    web::block(move || manager.add_or_update_reservation(reservation))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "Reservation added/updated"})))
+}
+
+#[delete("/config/reservation")]
+async fn remove_reservation(
+    manager: web::Data<Dhcpv6Manager>,
+    payload: web::Json<RemoveReservationPayload>,
+) -> Result<impl Responder, AppError> {
+    let duid = payload.duid.clone();
+    web::block(move || manager.remove_reservation(&duid))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "Reservation removed"})))
+}
+
+pub fn service() -> Scope {
+    web::scope("/dhcpv6")
+        .service(start)
+        .service(stop)
+        .service(restart)
+        .service(status)
+        .service(leases)
+        .service(set_autostart)
+        .service(get_config)
+        .service(replace_config)
+        .service(validate_config)
+        .service(set_prefix)
+        .service(set_mode)
+        .service(set_range)
+        .service(set_dns)
+        .service(set_interface)
+        .service(add_reservation)
+        .service(remove_reservation)
+}
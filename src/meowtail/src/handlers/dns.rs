@@ -0,0 +1,128 @@
+// src/handlers/dns.rs
+
+use crate::config::AppConfig;
+use crate::dns_manager::{DnsConfig, DnsManager, DnsRecord};
+use crate::error::AppError;
+use actix_web::{delete, get, http::header, post, put, web, HttpRequest, HttpResponse, Responder, Scope};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+struct AutostartPayload {
+    enabled: bool,
+}
+
+// --- 服务控制 ---
+
+#[post("/start")]
+async fn start_dns(manager: web::Data<DnsManager>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.start())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "DNS server started"})))
+}
+
+#[post("/stop")]
+async fn stop_dns(manager: web::Data<DnsManager>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.stop())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "DNS server stopped"})))
+}
+
+#[post("/restart")]
+async fn restart_dns(manager: web::Data<DnsManager>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.restart())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "DNS server restarted"})))
+}
+
+#[get("/status")]
+async fn dns_status(manager: web::Data<DnsManager>) -> Result<impl Responder, AppError> {
+    let status = web::block(move || manager.status())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+// 从 DHCP 租约发布的名字，服务运行时每 30 秒刷新
+#[get("/hosts")]
+async fn published_hosts(manager: web::Data<DnsManager>) -> impl Responder {
+    HttpResponse::Ok().json(manager.published_hosts())
+}
+
+// 设置开机自启，并保存到 meowtail.toml
+#[post("/autostart")]
+async fn set_dns_autostart(
+    config: web::Data<AppConfig>,
+    payload: web::Json<AutostartPayload>,
+) -> Result<impl Responder, AppError> {
+    let mut app_config = config.lock().unwrap();
+    app_config.dns_autostart = payload.enabled;
+    app_config.save()?;
+    Ok(HttpResponse::Ok().json(json!({ "autostart": payload.enabled })))
+}
+
+// --- 配置，运行中的服务会自动重启以加载新配置 ---
+
+#[get("/config")]
+async fn get_dns_config(manager: web::Data<DnsManager>) -> Result<impl Responder, AppError> {
+    let (config, etag) = manager.read_config_with_etag()?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, format!("\"{}\"", etag)))
+        .json(config))
+}
+
+#[put("/config")]
+async fn replace_dns_config(
+    req: HttpRequest,
+    manager: web::Data<DnsManager>,
+    payload: web::Json<DnsConfig>,
+) -> Result<impl Responder, AppError> {
+    let if_match = super::if_match(&req)?;
+    let etag = web::block(move || manager.replace_config(payload.into_inner(), if_match.as_deref()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, format!("\"{}\"", etag)))
+        .json(json!({ "status": "Configuration replaced" })))
+}
+
+#[post("/config/validate")]
+async fn validate_dns_config(payload: web::Json<DnsConfig>) -> Result<impl Responder, AppError> {
+    let issues = payload.validation_issues();
+    Ok(HttpResponse::Ok().json(json!({ "valid": issues.is_empty(), "issues": issues })))
+}
+
+#[post("/records")]
+async fn add_record(manager: web::Data<DnsManager>, payload: web::Json<DnsRecord>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.add_or_update_record(payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "Record added/updated"})))
+}
+
+#[delete("/records/{type}/{name}")]
+async fn remove_record(manager: web::Data<DnsManager>, path: web::Path<(String, String)>) -> Result<impl Responder, AppError> {
+    let (record_type, name) = path.into_inner();
+    web::block(move || manager.remove_record(&name, &record_type))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "Record removed"})))
+}
+
+pub fn service() -> Scope {
+    web::scope("/dns")
+        .service(start_dns)
+        .service(stop_dns)
+        .service(restart_dns)
+        .service(dns_status)
+        .service(published_hosts)
+        .service(set_dns_autostart)
+        .service(get_dns_config)
+        .service(replace_dns_config)
+        .service(validate_dns_config)
+        .service(add_record)
+        .service(remove_record)
+}
@@ -0,0 +1,182 @@
+// src/handlers/firewall.rs
+
+use crate::error::AppError;
+use crate::firewall_manager::{Family, FilterRule, FirewallConfig, FirewallManager, Forwarding, Policy, Zone};
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder, Scope};
+use serde::Deserialize;
+use serde_json::json;
+
+// 区域名来自路径
+#[derive(Deserialize)]
+struct ZonePayload {
+    #[serde(default)]
+    interfaces: Vec<String>,
+    input: Policy,
+    forward: Policy,
+}
+
+#[get("")]
+async fn get_firewall(manager: web::Data<FirewallManager>) -> Result<impl Responder, AppError> {
+    let config = web::block(move || manager.read_config())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(config))
+}
+
+// 整体替换配置，应用成功后才保存
+#[put("")]
+async fn replace_firewall(
+    manager: web::Data<FirewallManager>,
+    payload: web::Json<FirewallConfig>,
+) -> Result<impl Responder, AppError> {
+    web::block(move || manager.replace_config(payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "firewall updated"})))
+}
+
+#[get("/status")]
+async fn firewall_status(manager: web::Data<FirewallManager>) -> Result<impl Responder, AppError> {
+    let status = web::block(move || manager.status())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+// 当前配置渲染出的 iptables-restore 输入，便于在启用前检查
+#[get("/preview")]
+async fn preview(manager: web::Data<FirewallManager>) -> Result<impl Responder, AppError> {
+    let config = web::block(move || manager.read_config())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({
+        "ipv4": config.render(Family::V4),
+        "ipv6": config.render(Family::V6),
+    })))
+}
+
+#[post("/enable")]
+async fn enable(manager: web::Data<FirewallManager>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.set_enabled(true))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "firewall enabled"})))
+}
+
+#[post("/disable")]
+async fn disable(manager: web::Data<FirewallManager>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.set_enabled(false))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "firewall disabled"})))
+}
+
+// --- 区域 ---
+
+#[put("/zones/{name}")]
+async fn set_zone(
+    manager: web::Data<FirewallManager>,
+    path: web::Path<String>,
+    payload: web::Json<ZonePayload>,
+) -> Result<impl Responder, AppError> {
+    let ZonePayload { interfaces, input, forward } = payload.into_inner();
+    let zone = Zone {
+        name: path.into_inner(),
+        interfaces,
+        input,
+        forward,
+    };
+    web::block(move || manager.set_zone(zone))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "zone saved"})))
+}
+
+// 同时删除引用该区域的转发和规则
+#[delete("/zones/{name}")]
+async fn delete_zone(manager: web::Data<FirewallManager>, path: web::Path<String>) -> Result<impl Responder, AppError> {
+    let name = path.into_inner();
+    web::block(move || manager.remove_zone(&name))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "zone deleted"})))
+}
+
+// --- 区域间转发 ---
+
+#[post("/forwardings")]
+async fn add_forwarding(
+    manager: web::Data<FirewallManager>,
+    payload: web::Json<Forwarding>,
+) -> Result<impl Responder, AppError> {
+    let forwarding = payload.into_inner();
+    let created = forwarding.clone();
+    web::block(move || manager.add_forwarding(forwarding))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Created().json(created))
+}
+
+#[delete("/forwardings/{from}/{to}")]
+async fn delete_forwarding(
+    manager: web::Data<FirewallManager>,
+    path: web::Path<(String, String)>,
+) -> Result<impl Responder, AppError> {
+    let (from, to) = path.into_inner();
+    web::block(move || manager.remove_forwarding(&Forwarding { from, to }))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "forwarding deleted"})))
+}
+
+// --- 规则 ---
+
+#[post("/rules")]
+async fn add_filter_rule(
+    manager: web::Data<FirewallManager>,
+    payload: web::Json<FilterRule>,
+) -> Result<impl Responder, AppError> {
+    let rule = web::block(move || manager.add_rule(payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Created().json(rule))
+}
+
+#[put("/rules/{id}")]
+async fn update_filter_rule(
+    manager: web::Data<FirewallManager>,
+    path: web::Path<u32>,
+    payload: web::Json<FilterRule>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let rule = web::block(move || manager.update_rule(id, payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[delete("/rules/{id}")]
+async fn delete_filter_rule(manager: web::Data<FirewallManager>, path: web::Path<u32>) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    web::block(move || manager.remove_rule(id))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "rule deleted"})))
+}
+
+pub fn service() -> Scope {
+    web::scope("/firewall")
+        .service(get_firewall)
+        .service(replace_firewall)
+        .service(firewall_status)
+        .service(preview)
+        .service(enable)
+        .service(disable)
+        .service(set_zone)
+        .service(delete_zone)
+        .service(add_forwarding)
+        .service(delete_forwarding)
+        .service(add_filter_rule)
+        .service(update_filter_rule)
+        .service(delete_filter_rule)
+}
@@ -0,0 +1,33 @@
+// src/handlers/interfaces.rs
+
+use crate::error::AppError;
+use crate::interfaces;
+use actix_web::{get, web, HttpResponse, Responder, Scope};
+use std::io;
+
+#[get("")]
+async fn list_interfaces() -> Result<impl Responder, AppError> {
+    let list = web::block(interfaces::list)
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(list))
+}
+
+#[get("/{name}")]
+async fn get_interface(path: web::Path<String>) -> Result<impl Responder, AppError> {
+    let name = path.into_inner();
+    let interface = web::block(move || interfaces::get(&name))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))?
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidInput => AppError::NotFound(e.to_string()),
+            _ => AppError::Io(e),
+        })?;
+    Ok(HttpResponse::Ok().json(interface))
+}
+
+pub fn service() -> Scope {
+    web::scope("/interfaces")
+        .service(list_interfaces)
+        .service(get_interface)
+}
@@ -0,0 +1,63 @@
+// src/handlers/management.rs
+
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::management_access::{self, LocalAddr, ManagementAccess};
+use actix_web::{get, put, web, HttpRequest, HttpResponse, Responder, Scope};
+use serde_json::json;
+
+// 监听地址只在启动时生效，这里只读
+#[get("")]
+async fn get_management(config: web::Data<AppConfig>) -> impl Responder {
+    let app_config = config.lock().unwrap();
+    HttpResponse::Ok().json(json!({
+        "listen_addresses": app_config.listen_addresses,
+        "listen_port": app_config.listen_port,
+        "access": app_config.management,
+    }))
+}
+
+// 修改访问限制并保存到 meowtail.toml；会把当前客户端拒之门外的修改返回 409。
+// 规则安装或保存失败时重新应用原来的设置，不留下只装了一部分的规则
+#[put("")]
+async fn set_management(
+    req: HttpRequest,
+    config: web::Data<AppConfig>,
+    payload: web::Json<ManagementAccess>,
+) -> Result<impl Responder, AppError> {
+    let access = payload.into_inner();
+    access.validate()?;
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    let local = req.conn_data::<LocalAddr>().map(|addr| addr.0);
+    let access = web::block(move || {
+        if let Some(peer) = peer.filter(|peer| !access.keeps_access(*peer, local)) {
+            return Err(AppError::Conflict(format!("This change would block access from {}", peer)));
+        }
+        let (previous, port) = {
+            let app_config = config.lock().unwrap();
+            (app_config.management.clone(), app_config.listen_port)
+        };
+        let result = management_access::apply_firewall(&access, port).and_then(|_| {
+            let mut app_config = config.lock().unwrap();
+            app_config.management = access.clone();
+            app_config.save().map_err(|e| {
+                app_config.management = previous.clone();
+                AppError::from(e)
+            })
+        });
+        if let Err(e) = result {
+            if let Err(restore) = management_access::apply_firewall(&previous, port) {
+                eprintln!("[management] Failed to restore the previous firewall rules: {}", restore);
+            }
+            return Err(e);
+        }
+        Ok(access)
+    })
+    .await
+    .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"access": access})))
+}
+
+pub fn service() -> Scope {
+    web::scope("/management").service(get_management).service(set_management)
+}
@@ -2,4 +2,33 @@
 
 pub mod auth;
 pub mod udhcpd;
+pub mod dhcpv6;
+pub mod devices;
 pub mod portmap;
+pub mod tools;
+pub mod interfaces;
+pub mod network;
+pub mod routes;
+pub mod firewall;
+pub mod nat;
+pub mod management;
+pub mod upnp;
+pub mod ddns;
+pub mod dns;
+
+use actix_web::{http::header, HttpRequest};
+
+use crate::error::AppError;
+
+/// 取出 If-Match 头的原始值，由各管理器按 ETag 列表规则比较
+pub(crate) fn if_match(req: &HttpRequest) -> Result<Option<String>, AppError> {
+    req.headers()
+        .get(header::IF_MATCH)
+        .map(|value| {
+            value
+                .to_str()
+                .map(str::to_string)
+                .map_err(|_| AppError::InvalidInput("Invalid If-Match header".to_string()))
+        })
+        .transpose()
+}
@@ -0,0 +1,89 @@
+// src/handlers/nat.rs
+
+use crate::error::AppError;
+use crate::nat_manager::{NatConfig, NatManager, NatRule};
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder, Scope};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+struct IpForwardPayload {
+    enabled: bool,
+}
+
+#[get("")]
+async fn get_nat(manager: web::Data<NatManager>) -> Result<impl Responder, AppError> {
+    let config = web::block(move || manager.read_config())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(config))
+}
+
+#[put("")]
+async fn replace_nat(manager: web::Data<NatManager>, payload: web::Json<NatConfig>) -> Result<impl Responder, AppError> {
+    web::block(move || manager.replace_config(payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "NAT updated"})))
+}
+
+#[get("/status")]
+async fn nat_status(manager: web::Data<NatManager>) -> Result<impl Responder, AppError> {
+    let status = web::block(move || manager.status())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+#[post("/ip-forward")]
+async fn set_ip_forward(
+    manager: web::Data<NatManager>,
+    payload: web::Json<IpForwardPayload>,
+) -> Result<impl Responder, AppError> {
+    let enabled = payload.enabled;
+    web::block(move || manager.set_ip_forward(enabled))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "IP forwarding updated", "enabled": enabled})))
+}
+
+#[post("/rules")]
+async fn add_nat_rule(manager: web::Data<NatManager>, payload: web::Json<NatRule>) -> Result<impl Responder, AppError> {
+    let rule = web::block(move || manager.add_rule(payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Created().json(rule))
+}
+
+#[put("/rules/{id}")]
+async fn update_nat_rule(
+    manager: web::Data<NatManager>,
+    path: web::Path<u32>,
+    payload: web::Json<NatRule>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let rule = web::block(move || manager.update_rule(id, payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[delete("/rules/{id}")]
+async fn delete_nat_rule(manager: web::Data<NatManager>, path: web::Path<u32>) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    web::block(move || manager.remove_rule(id))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "NAT rule deleted"})))
+}
+
+pub fn service() -> Scope {
+    web::scope("/nat")
+        .service(get_nat)
+        .service(replace_nat)
+        .service(nat_status)
+        .service(set_ip_forward)
+        .service(add_nat_rule)
+        .service(update_nat_rule)
+        .service(delete_nat_rule)
+}
@@ -0,0 +1,342 @@
+// src/handlers/network.rs
+
+use crate::addressing_manager::{Addressing, AddressingManager, InterfaceAddressing, InterfaceRole};
+use crate::dhcpv6_manager::Dhcpv6Manager;
+use crate::dns_manager::DnsManager;
+use crate::error::AppError;
+use crate::portmap_manager::PortMapManager;
+use crate::topology_manager::{Bridge, TopologyManager, Vlan};
+use crate::udhcpd_registry::UdhcpdRegistry;
+use crate::upnp::UpnpManager;
+use actix_web::dev::Payload;
+use actix_web::{delete, get, post, put, web, FromRequest, HttpRequest, HttpResponse, Responder, Scope};
+use serde::Deserialize;
+use serde_json::json;
+use std::future::{ready, Ready};
+use std::net::IpAddr;
+
+// 接口名来自路径，其余字段与 interfaces.toml 中的一项相同
+#[derive(Deserialize)]
+struct AddressingPayload {
+    #[serde(default)]
+    role: InterfaceRole,
+    #[serde(flatten)]
+    addressing: Addressing,
+    #[serde(default)]
+    check_host: Option<IpAddr>,
+}
+
+#[derive(Deserialize)]
+struct BridgePayload {
+    name: String,
+    #[serde(default)]
+    ports: Vec<String>,
+    #[serde(default)]
+    stp: bool,
+}
+
+#[derive(Deserialize)]
+struct BridgeUpdatePayload {
+    #[serde(default)]
+    ports: Vec<String>,
+    #[serde(default)]
+    stp: bool,
+}
+
+#[derive(Deserialize)]
+struct PortPayload {
+    port: String,
+}
+
+#[derive(Deserialize)]
+struct VlanPayload {
+    parent: String,
+    id: u16,
+    // 默认为 <parent>.<id>
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DeleteQuery {
+    // 接口仍被 DHCP、端口映射等使用时也删除
+    #[serde(default)]
+    force: bool,
+}
+
+fn app_data<T: ?Sized + 'static>(req: &HttpRequest) -> Result<web::Data<T>, AppError> {
+    req.app_data::<web::Data<T>>()
+        .cloned()
+        .ok_or_else(|| AppError::Process(format!("{} not found in application state.", std::any::type_name::<T>())))
+}
+
+// 可能在配置中引用网桥或 VLAN 的子系统
+struct InterfaceUsers {
+    registry: web::Data<UdhcpdRegistry>,
+    dhcpv6: web::Data<Dhcpv6Manager>,
+    portmap: web::Data<PortMapManager>,
+    addressing: web::Data<AddressingManager>,
+    dns: web::Data<DnsManager>,
+    upnp: web::Data<UpnpManager>,
+}
+
+impl InterfaceUsers {
+    // 其他子系统的配置中引用了该接口的地方
+    fn of(&self, name: &str) -> Vec<String> {
+        let mut users: Vec<String> = self
+            .registry
+            .instances()
+            .into_iter()
+            .filter(|(_, manager)| manager.read_config().is_ok_and(|c| c.interface.as_deref() == Some(name)))
+            .map(|(instance, _)| format!("DHCP instance '{}'", instance))
+            .collect();
+        if self.dhcpv6.read_config().is_ok_and(|c| c.interface == name) {
+            users.push("DHCPv6 server".to_string());
+        }
+        if self.portmap.config().external_interface == name {
+            users.push("port mapping".to_string());
+        }
+        if self.addressing.read_config().is_ok_and(|c| c.get(name).is_some()) {
+            users.push("interface addressing".to_string());
+        }
+        if self.dns.read_config().is_ok_and(|c| c.interfaces.iter().any(|i| i == name)) {
+            users.push("DNS server".to_string());
+        }
+        if self.upnp.config().interfaces.iter().any(|i| i == name) {
+            users.push("UPnP".to_string());
+        }
+        users
+    }
+
+    fn from_app_data(req: &HttpRequest) -> Result<Self, AppError> {
+        Ok(InterfaceUsers {
+            registry: app_data(req)?,
+            dhcpv6: app_data(req)?,
+            portmap: app_data(req)?,
+            addressing: app_data(req)?,
+            dns: app_data(req)?,
+            upnp: app_data(req)?,
+        })
+    }
+
+    // 删除前检查引用，`force` 时跳过
+    fn check_unused(&self, name: &str, force: bool) -> Result<(), AppError> {
+        let users = self.of(name);
+        if force || users.is_empty() {
+            return Ok(());
+        }
+        Err(AppError::Conflict(format!("{} is used by {}", name, users.join(", "))))
+    }
+}
+
+impl FromRequest for InterfaceUsers {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(InterfaceUsers::from_app_data(req))
+    }
+}
+
+// --- 接口地址 ---
+
+#[get("/addressing")]
+async fn list_addressing(manager: web::Data<AddressingManager>) -> Result<impl Responder, AppError> {
+    let list = web::block(move || manager.list())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(list))
+}
+
+#[get("/addressing/{name}")]
+async fn get_addressing(
+    manager: web::Data<AddressingManager>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let name = path.into_inner();
+    let status = web::block(move || manager.get(&name))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+// 应用并检查连通性，可能需要数十秒；失败时已回滚，返回 409
+#[put("/addressing/{name}")]
+async fn set_addressing(
+    manager: web::Data<AddressingManager>,
+    path: web::Path<String>,
+    payload: web::Json<AddressingPayload>,
+) -> Result<impl Responder, AppError> {
+    let AddressingPayload { role, addressing, check_host } = payload.into_inner();
+    let entry = InterfaceAddressing {
+        name: path.into_inner(),
+        role,
+        addressing,
+        check_host,
+    };
+    let status = web::block(move || manager.set(entry))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+#[delete("/addressing/{name}")]
+async fn remove_addressing(
+    manager: web::Data<AddressingManager>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let name = path.into_inner();
+    web::block(move || manager.remove(&name))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "addressing removed"})))
+}
+
+// --- 网桥 ---
+
+#[get("/bridges")]
+async fn list_bridges(manager: web::Data<TopologyManager>) -> Result<impl Responder, AppError> {
+    let list = web::block(move || manager.bridges())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(list))
+}
+
+#[get("/bridges/{name}")]
+async fn get_bridge(manager: web::Data<TopologyManager>, path: web::Path<String>) -> Result<impl Responder, AppError> {
+    let name = path.into_inner();
+    let bridge = web::block(move || manager.bridge(&name))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(bridge))
+}
+
+#[post("/bridges")]
+async fn create_bridge(
+    manager: web::Data<TopologyManager>,
+    payload: web::Json<BridgePayload>,
+) -> Result<impl Responder, AppError> {
+    let BridgePayload { name, ports, stp } = payload.into_inner();
+    let bridge = web::block(move || manager.create_bridge(Bridge { name, ports, stp }))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Created().json(bridge))
+}
+
+#[put("/bridges/{name}")]
+async fn update_bridge(
+    manager: web::Data<TopologyManager>,
+    path: web::Path<String>,
+    payload: web::Json<BridgeUpdatePayload>,
+) -> Result<impl Responder, AppError> {
+    let BridgeUpdatePayload { ports, stp } = payload.into_inner();
+    let name = path.into_inner();
+    let bridge = web::block(move || manager.update_bridge(Bridge { name, ports, stp }))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(bridge))
+}
+
+#[post("/bridges/{name}/ports")]
+async fn add_bridge_port(
+    manager: web::Data<TopologyManager>,
+    path: web::Path<String>,
+    payload: web::Json<PortPayload>,
+) -> Result<impl Responder, AppError> {
+    let name = path.into_inner();
+    let port = payload.into_inner().port;
+    let bridge = web::block(move || manager.add_bridge_port(&name, &port))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(bridge))
+}
+
+#[delete("/bridges/{name}/ports/{port}")]
+async fn remove_bridge_port(
+    manager: web::Data<TopologyManager>,
+    path: web::Path<(String, String)>,
+) -> Result<impl Responder, AppError> {
+    let (name, port) = path.into_inner();
+    let bridge = web::block(move || manager.remove_bridge_port(&name, &port))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(bridge))
+}
+
+#[delete("/bridges/{name}")]
+async fn delete_bridge(
+    manager: web::Data<TopologyManager>,
+    users: InterfaceUsers,
+    path: web::Path<String>,
+    query: web::Query<DeleteQuery>,
+) -> Result<impl Responder, AppError> {
+    let name = path.into_inner();
+    web::block(move || {
+        users.check_unused(&name, query.force)?;
+        manager.delete_bridge(&name)
+    })
+    .await
+    .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "bridge deleted"})))
+}
+
+// --- VLAN ---
+
+#[get("/vlans")]
+async fn list_vlans(manager: web::Data<TopologyManager>) -> Result<impl Responder, AppError> {
+    let list = web::block(move || manager.vlans())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(list))
+}
+
+#[post("/vlans")]
+async fn create_vlan(
+    manager: web::Data<TopologyManager>,
+    payload: web::Json<VlanPayload>,
+) -> Result<impl Responder, AppError> {
+    let VlanPayload { parent, id, name } = payload.into_inner();
+    let vlan = Vlan {
+        name: name.unwrap_or_else(|| Vlan::default_name(&parent, id)),
+        parent,
+        id,
+    };
+    let vlan = web::block(move || manager.create_vlan(vlan))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Created().json(vlan))
+}
+
+#[delete("/vlans/{name}")]
+async fn delete_vlan(
+    manager: web::Data<TopologyManager>,
+    users: InterfaceUsers,
+    path: web::Path<String>,
+    query: web::Query<DeleteQuery>,
+) -> Result<impl Responder, AppError> {
+    let name = path.into_inner();
+    web::block(move || {
+        users.check_unused(&name, query.force)?;
+        manager.delete_vlan(&name)
+    })
+    .await
+    .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "VLAN deleted"})))
+}
+
+pub fn service() -> Scope {
+    web::scope("/network")
+        .service(list_addressing)
+        .service(get_addressing)
+        .service(set_addressing)
+        .service(remove_addressing)
+        .service(list_bridges)
+        .service(get_bridge)
+        .service(create_bridge)
+        .service(update_bridge)
+        .service(add_bridge_port)
+        .service(remove_bridge_port)
+        .service(delete_bridge)
+        .service(list_vlans)
+        .service(create_vlan)
+        .service(delete_vlan)
+}
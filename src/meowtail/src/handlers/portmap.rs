@@ -1,14 +1,20 @@
-use actix_web::{delete, get, post, web, HttpResponse, Responder, Scope};
+use actix_web::{delete, get, post, web, HttpResponse, Responder, ResponseError, Scope};
 use serde::Deserialize;
 
+use crate::device_inventory::DeviceInventory;
+use crate::error::AppError;
 use crate::portmap_manager::{PortMapManager, PortMapRule};
+use crate::udhcpd_registry::UdhcpdRegistry;
 
+// 添加规则时可以用 device（设备清单中的名字）代替 internal_ip
 #[derive(Deserialize)]
 struct RulePayload {
     protocol: String,
     external_port: u16,
+    #[serde(default)]
     internal_ip: String,
     internal_port: u16,
+    device: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -24,16 +30,35 @@ async fn get_config(manager: web::Data<PortMapManager>) -> impl Responder {
 #[post("/rule")]
 async fn add_rule(
     manager: web::Data<PortMapManager>,
+    inventory: web::Data<DeviceInventory>,
+    registry: web::Data<UdhcpdRegistry>,
     payload: web::Json<RulePayload>,
 ) -> impl Responder {
+    let internal_ip = match &payload.device {
+        Some(name) => {
+            let name = name.clone();
+            let resolved = web::block(move || inventory.resolve_ip(&name, &registry))
+                .await
+                .map_err(|e| AppError::Process(e.to_string()))
+                .and_then(|r| r);
+            match resolved {
+                Ok(ip) => ip.to_string(),
+                Err(e) => return e.error_response(),
+            }
+        }
+        None => payload.internal_ip.clone(),
+    };
     let rule = PortMapRule {
         protocol: payload.protocol.clone(),
         external_port: payload.external_port,
-        internal_ip: payload.internal_ip.clone(),
+        internal_ip,
         internal_port: payload.internal_port,
     };
 
     if let Err(e) = manager.add_rule(rule) {
+        if e.kind() == std::io::ErrorKind::AlreadyExists {
+            return HttpResponse::Conflict().json(serde_json::json!({"error": e.to_string()}));
+        }
         return HttpResponse::InternalServerError()
             .json(serde_json::json!({"error": e.to_string()}));
     }
@@ -65,6 +90,9 @@ async fn set_interface(
     payload: web::Json<InterfacePayload>,
 ) -> impl Responder {
     if let Err(e) = manager.set_interface(payload.interface.clone()) {
+        if e.kind() == std::io::ErrorKind::InvalidInput {
+            return HttpResponse::BadRequest().json(serde_json::json!({"error": e.to_string()}));
+        }
         return HttpResponse::InternalServerError()
             .json(serde_json::json!({"error": e.to_string()}));
     }
@@ -0,0 +1,108 @@
+// src/handlers/routes.rs
+
+use crate::error::AppError;
+use crate::route_manager::{PolicyRule, Route, RouteManager};
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder, Scope};
+use serde_json::json;
+
+// --- 静态路由 ---
+
+#[get("")]
+async fn list_routes(manager: web::Data<RouteManager>) -> Result<impl Responder, AppError> {
+    let list = web::block(move || manager.routes())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(list))
+}
+
+#[post("")]
+async fn add_route(manager: web::Data<RouteManager>, payload: web::Json<Route>) -> Result<impl Responder, AppError> {
+    let route = web::block(move || manager.add_route(payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Created().json(route))
+}
+
+#[put("/{id}")]
+async fn update_route(
+    manager: web::Data<RouteManager>,
+    path: web::Path<u32>,
+    payload: web::Json<Route>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let route = web::block(move || manager.update_route(id, payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(route))
+}
+
+#[delete("/{id}")]
+async fn delete_route(manager: web::Data<RouteManager>, path: web::Path<u32>) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    web::block(move || manager.delete_route(id))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "route deleted"})))
+}
+
+// --- 策略规则 ---
+
+#[get("/rules")]
+async fn list_rules(manager: web::Data<RouteManager>) -> Result<impl Responder, AppError> {
+    let list = web::block(move || manager.rules())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(list))
+}
+
+#[post("/rules")]
+async fn add_rule(manager: web::Data<RouteManager>, payload: web::Json<PolicyRule>) -> Result<impl Responder, AppError> {
+    let rule = web::block(move || manager.add_rule(payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Created().json(rule))
+}
+
+#[put("/rules/{id}")]
+async fn update_rule(
+    manager: web::Data<RouteManager>,
+    path: web::Path<u32>,
+    payload: web::Json<PolicyRule>,
+) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    let rule = web::block(move || manager.update_rule(id, payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+#[delete("/rules/{id}")]
+async fn delete_rule(manager: web::Data<RouteManager>, path: web::Path<u32>) -> Result<impl Responder, AppError> {
+    let id = path.into_inner();
+    web::block(move || manager.delete_rule(id))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "rule deleted"})))
+}
+
+// 重新安装被删掉的路由和规则，例如接口重新启用之后
+#[post("/reconcile")]
+async fn reconcile(manager: web::Data<RouteManager>) -> Result<impl Responder, AppError> {
+    let report = web::block(move || manager.reconcile())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub fn service() -> Scope {
+    web::scope("/routes")
+        .service(list_routes)
+        .service(add_route)
+        .service(list_rules)
+        .service(add_rule)
+        .service(update_rule)
+        .service(delete_rule)
+        .service(reconcile)
+        .service(update_route)
+        .service(delete_route)
+}
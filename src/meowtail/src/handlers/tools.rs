@@ -0,0 +1,35 @@
+// src/handlers/tools.rs
+
+use crate::arp_probe;
+use crate::error::AppError;
+use actix_web::{post, web, HttpResponse, Responder, Scope};
+use serde::Deserialize;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+#[derive(Deserialize)]
+struct ArpProbePayload {
+    interface: String,
+    ip: String,
+    // 等待应答的秒数，默认 1
+    wait_secs: Option<u32>,
+}
+
+#[post("/arp-probe")]
+async fn probe_address(payload: web::Json<ArpProbePayload>) -> Result<impl Responder, AppError> {
+    let ArpProbePayload { interface, ip, wait_secs } = payload.into_inner();
+    if interface.is_empty() {
+        return Err(AppError::InvalidInput("Interface name cannot be empty".to_string()));
+    }
+    let ip = Ipv4Addr::from_str(&ip)?;
+    let wait_secs = wait_secs.unwrap_or(1).min(10);
+
+    let result = web::block(move || arp_probe::probe(&interface, ip, wait_secs))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub fn service() -> Scope {
+    web::scope("/tools").service(probe_address)
+}
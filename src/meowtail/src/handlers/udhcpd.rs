@@ -1,11 +1,53 @@
 // src/handlers/udhcpd.rs
 
-use crate::udhcpd_manager::{StaticLease, UdhcpdError, UdhcpdManager};
-use actix_web::{delete, get, post, web, HttpResponse, Responder, Scope};
+use crate::config::{AppConfig, UdhcpdInstanceConfig};
+use crate::device_inventory::DeviceInventory;
+use crate::dhcp_backend::options::{self, DhcpOption, OptionType, OptionValue};
+use crate::dhcp_backend::rogue::RogueDetector;
+use crate::error::AppError;
+use crate::lease_import::{self, ImportMode};
+use crate::mac_addr::MacAddr;
+use crate::udhcpd_manager::{StaticLease, UdhcpdConfig, UdhcpdManager};
+use crate::udhcpd_registry::UdhcpdRegistry;
+use actix_web::dev::Payload;
+use actix_web::{delete, get, http::header, post, put, web, FromRequest, HttpRequest, HttpResponse, Responder, Scope};
 use serde::Deserialize;
 use serde_json::json;
+use std::future::{ready, Ready};
 use std::net::Ipv4Addr;
+use std::ops::Deref;
 use std::str::FromStr;
+use std::sync::Arc;
+
+// --- 实例提取器 ---
+
+/// 按路径中的 `{instance}` 从 UdhcpdRegistry 取出对应的实例
+struct Instance {
+    name: String,
+    manager: Arc<UdhcpdManager>,
+}
+
+impl Deref for Instance {
+    type Target = UdhcpdManager;
+
+    fn deref(&self) -> &UdhcpdManager {
+        &self.manager
+    }
+}
+
+impl FromRequest for Instance {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let name = req.match_info().get("instance").unwrap_or_default().to_string();
+        let result = match req.app_data::<web::Data<UdhcpdRegistry>>() {
+            Some(registry) => registry.get(&name).map(|manager| Instance { name, manager }),
+            None => Err(AppError::Process("UdhcpdRegistry not found in application state.".to_string())),
+        };
+        ready(result)
+    }
+}
 
 // --- 请求体 (Payloads) 定义 ---
 
@@ -25,10 +67,15 @@ struct DnsPayload {
     servers: Vec<String>,
 }
 
+// mac 与 device（设备清单中的名字）二选一
 #[derive(Deserialize)]
 struct LeasePayload {
-    mac: String,
+    mac: Option<String>,
+    device: Option<String>,
     ip: String,
+    // 写入前先用 ARP 确认地址没有被其他主机占用
+    #[serde(default)]
+    probe: bool,
 }
 
 #[derive(Deserialize)]
@@ -36,11 +83,69 @@ struct RemoveLeasePayload {
     mac: String,
 }
 
+// code 可以是数字或选项名；type 省略时取已知选项的类型，未知选项按 hex 处理
+#[derive(Deserialize)]
+struct OptionPayload {
+    code: serde_json::Value,
+    #[serde(rename = "type")]
+    kind: Option<OptionType>,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum LeaseFormat {
+    Csv,
+    Json,
+}
+
+// 未指定 format 时按 Content-Type 判断，text/csv 为 CSV，其余为 JSON
+#[derive(Deserialize)]
+struct ImportQuery {
+    format: Option<LeaseFormat>,
+    #[serde(default)]
+    mode: ImportMode,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: Option<LeaseFormat>,
+}
+
 #[derive(Deserialize)]
 struct InterfacePayload {
     interface: String,
 }
 
+// 整体替换配置的请求体：完整的 UdhcpdConfig 加上可选的 restart 标志
+#[derive(Deserialize)]
+struct ReplaceConfigPayload {
+    #[serde(flatten)]
+    config: UdhcpdConfig,
+    #[serde(default)]
+    restart: bool,
+}
+
+#[derive(Deserialize)]
+struct AutoApplyPayload {
+    enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct AutostartPayload {
+    enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct CreateInstancePayload {
+    name: String,
+    interface: String,
+    #[serde(default)]
+    autostart: bool,
+}
+
 // 新增: 修改子网掩码的请求体
 #[derive(Deserialize)]
 struct SubnetPayload {
@@ -48,72 +153,249 @@ struct SubnetPayload {
 }
 
 
+// --- 其他 DHCP 服务端探测 ---
+
+#[get("/rogue-servers")]
+async fn rogue_servers(detector: web::Data<RogueDetector>) -> impl Responder {
+    HttpResponse::Ok().json(detector.report())
+}
+
+// 立即在所有实例的接口上探测一次
+#[post("/rogue-servers/probe")]
+async fn probe_rogue_servers(
+    detector: web::Data<RogueDetector>,
+    registry: web::Data<UdhcpdRegistry>,
+) -> Result<impl Responder, AppError> {
+    let report = web::block(move || detector.probe_all(&registry))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// --- 实例管理 ---
+
+#[get("/instances")]
+async fn list_instances(registry: web::Data<UdhcpdRegistry>) -> Result<impl Responder, AppError> {
+    let instances = web::block(move || registry.list())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))?;
+    Ok(HttpResponse::Ok().json(instances))
+}
+
+#[post("/instances")]
+async fn create_instance(
+    registry: web::Data<UdhcpdRegistry>,
+    config: web::Data<AppConfig>,
+    payload: web::Json<CreateInstancePayload>,
+) -> Result<impl Responder, AppError> {
+    let CreateInstancePayload { name, interface, autostart } = payload.into_inner();
+    if interface.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Interface name cannot be empty".to_string(),
+        ));
+    }
+
+    let created = name.clone();
+    web::block(move || registry.create(&created, &interface))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+
+    let mut app_config = config.lock().unwrap();
+    app_config.udhcpd_instances.retain(|i| i.name != name);
+    app_config.udhcpd_instances.push(UdhcpdInstanceConfig {
+        name: name.clone(),
+        autostart,
+        auto_apply: false,
+    });
+    app_config.save()?;
+
+    Ok(HttpResponse::Ok().json(json!({ "status": "udhcpd instance created", "name": name })))
+}
+
+#[delete("/instances/{name}")]
+async fn delete_instance(
+    registry: web::Data<UdhcpdRegistry>,
+    config: web::Data<AppConfig>,
+    path: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    let name = path.into_inner();
+    let removed = name.clone();
+    web::block(move || registry.remove(&removed))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+
+    let mut app_config = config.lock().unwrap();
+    app_config.udhcpd_instances.retain(|i| i.name != name);
+    app_config.save()?;
+
+    Ok(HttpResponse::Ok().json(json!({ "status": "udhcpd instance deleted" })))
+}
+
 // --- 处理器 (Handlers) ---
 
+// 开关开机自动启动，并保存到 meowtail.toml
+#[post("/autostart")]
+async fn set_autostart(
+    manager: Instance,
+    config: web::Data<AppConfig>,
+    payload: web::Json<AutostartPayload>,
+) -> Result<impl Responder, AppError> {
+    let mut app_config = config.lock().unwrap();
+    match app_config.udhcpd_instance_mut(&manager.name) {
+        Some(instance) => instance.autostart = payload.enabled,
+        None => app_config.udhcpd_instances.push(UdhcpdInstanceConfig {
+            name: manager.name.clone(),
+            autostart: payload.enabled,
+            auto_apply: manager.auto_apply(),
+        }),
+    }
+    app_config.save()?;
+    Ok(HttpResponse::Ok().json(json!({ "autostart": payload.enabled })))
+}
+
 #[post("/start")]
-async fn start(manager: web::Data<UdhcpdManager>) -> Result<impl Responder, UdhcpdError> {
+async fn start(manager: Instance) -> Result<impl Responder, AppError> {
     web::block(move || manager.start())
         .await
-        .map_err(|e| UdhcpdError::Process(e.to_string()))??;
+        .map_err(|e| AppError::Process(e.to_string()))??;
     Ok(HttpResponse::Ok().json(json!({"status": "udhcpd service started"})))
 }
 
 #[post("/stop")]
-async fn stop(manager: web::Data<UdhcpdManager>) -> Result<impl Responder, UdhcpdError> {
+async fn stop(manager: Instance) -> Result<impl Responder, AppError> {
     web::block(move || manager.stop())
         .await
-        .map_err(|e| UdhcpdError::Process(e.to_string()))??;
+        .map_err(|e| AppError::Process(e.to_string()))??;
     Ok(HttpResponse::Ok().json(json!({"status": "udhcpd service stopped"})))
 }
 
 #[post("/restart")]
-async fn restart(manager: web::Data<UdhcpdManager>) -> Result<impl Responder, UdhcpdError> {
+async fn restart(manager: Instance) -> Result<impl Responder, AppError> {
     web::block(move || manager.restart())
         .await
-        .map_err(|e| UdhcpdError::Process(e.to_string()))??;
+        .map_err(|e| AppError::Process(e.to_string()))??;
     Ok(HttpResponse::Ok().json(json!({"status": "udhcpd service restarted"})))
 }
 
 #[get("/status")]
-async fn status(manager: web::Data<UdhcpdManager>) -> Result<impl Responder, UdhcpdError> {
-    let is_running = manager.is_running();
-    Ok(HttpResponse::Ok().json(json!({ "running": is_running })))
+async fn status(manager: Instance) -> Result<impl Responder, AppError> {
+    let status = web::block(move || manager.status())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+// 当前分配出去的动态租约
+#[get("/leases")]
+async fn leases(manager: Instance) -> Result<impl Responder, AppError> {
+    let leases = web::block(move || manager.leases())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(leases))
+}
+
+// 最近的租约事件
+#[get("/events")]
+async fn events(manager: Instance) -> Result<impl Responder, AppError> {
+    Ok(HttpResponse::Ok().json(manager.events()))
+}
+
+// ARP 探测动态地址池
+#[post("/pool/check")]
+async fn check_pool(manager: Instance) -> Result<impl Responder, AppError> {
+    let report = web::block(move || manager.check_pool())
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// 开关自动应用模式，并保存到 meowtail.toml
+#[post("/auto-apply")]
+async fn set_auto_apply(
+    manager: Instance,
+    config: web::Data<AppConfig>,
+    payload: web::Json<AutoApplyPayload>,
+) -> Result<impl Responder, AppError> {
+    {
+        let mut app_config = config.lock().unwrap();
+        if let Some(instance) = app_config.udhcpd_instance_mut(&manager.name) {
+            instance.auto_apply = payload.enabled;
+        }
+        app_config.save()?;
+    }
+    manager.set_auto_apply(payload.enabled);
+    Ok(HttpResponse::Ok().json(json!({ "auto_apply": payload.enabled })))
 }
 
 #[get("/config")]
-async fn get_config(manager: web::Data<UdhcpdManager>) -> Result<impl Responder, UdhcpdError> {
-    let config = manager.read_config()?;
-    Ok(HttpResponse::Ok().json(config))
+async fn get_config(manager: Instance) -> Result<impl Responder, AppError> {
+    let (config, etag) = manager.read_config_with_etag()?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, format!("\"{}\"", etag)))
+        .json(config))
+}
+
+#[put("/config")]
+async fn replace_config(
+    req: HttpRequest,
+    manager: Instance,
+    payload: web::Json<ReplaceConfigPayload>,
+) -> Result<impl Responder, AppError> {
+    let if_match = super::if_match(&req)?;
+    let ReplaceConfigPayload { config, restart: restart_after } = payload.into_inner();
+
+    let (etag, restarted, applied) = web::block(move || {
+        let etag = manager.replace_config(config, if_match.as_deref())?;
+        // 自动应用模式下 replace_config 可能已经重启过，运行中的进程已加载新配置时不再重启
+        let restarted = restart_after && manager.config_dirty() != Some(false);
+        if restarted {
+            manager.restart()?;
+        }
+        Ok::<_, AppError>((etag, restarted, manager.config_dirty() == Some(false)))
+    })
+    .await
+    .map_err(|e| AppError::Process(e.to_string()))??;
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, format!("\"{}\"", etag)))
+        .json(json!({ "status": "Configuration replaced", "restarted": restarted, "applied": applied })))
+}
+
+// 只校验提交的配置，不写入文件
+#[post("/config/validate")]
+async fn validate_config(payload: web::Json<UdhcpdConfig>) -> Result<impl Responder, AppError> {
+    let issues = payload.validation_issues();
+    Ok(HttpResponse::Ok().json(json!({ "valid": issues.is_empty(), "issues": issues })))
 }
 
 #[post("/config/range")]
 async fn set_range(
-    manager: web::Data<UdhcpdManager>,
+    manager: Instance,
     payload: web::Json<RangePayload>,
-) -> Result<impl Responder, UdhcpdError> {
+) -> Result<impl Responder, AppError> {
     let start_ip = Ipv4Addr::from_str(&payload.start)
-        .map_err(|_| UdhcpdError::InvalidInput("Invalid start IP address".to_string()))?;
+        .map_err(|_| AppError::InvalidInput("Invalid start IP address".to_string()))?;
     let end_ip = Ipv4Addr::from_str(&payload.end)
-        .map_err(|_| UdhcpdError::InvalidInput("Invalid end IP address".to_string()))?;
+        .map_err(|_| AppError::InvalidInput("Invalid end IP address".to_string()))?;
 
     web::block(move || manager.set_dhcp_range(start_ip, end_ip))
         .await
-        .map_err(|e| UdhcpdError::Process(e.to_string()))??;
+        .map_err(|e| AppError::Process(e.to_string()))??;
 
     Ok(HttpResponse::Ok().json(json!({"status": "DHCP range updated"})))
 }
 
 #[post("/config/gateway")]
 async fn set_gateway(
-    manager: web::Data<UdhcpdManager>,
+    manager: Instance,
     payload: web::Json<GatewayPayload>,
-) -> Result<impl Responder, UdhcpdError> {
+) -> Result<impl Responder, AppError> {
     let gateway_ip = Ipv4Addr::from_str(&payload.gateway)
-        .map_err(|_| UdhcpdError::InvalidInput("Invalid gateway IP address".to_string()))?;
+        .map_err(|_| AppError::InvalidInput("Invalid gateway IP address".to_string()))?;
 
     web::block(move || manager.set_gateway(gateway_ip))
         .await
-        .map_err(|e| UdhcpdError::Process(e.to_string()))??;
+        .map_err(|e| AppError::Process(e.to_string()))??;
 
     Ok(HttpResponse::Ok().json(json!({"status": "Gateway updated"})))
 }
@@ -121,15 +403,15 @@ async fn set_gateway(
 // 新增: 修改子网掩码的处理器
 #[post("/config/subnet")]
 async fn set_subnet_mask(
-    manager: web::Data<UdhcpdManager>,
+    manager: Instance,
     payload: web::Json<SubnetPayload>,
-) -> Result<impl Responder, UdhcpdError> {
+) -> Result<impl Responder, AppError> {
     let subnet_mask = Ipv4Addr::from_str(&payload.subnet)
-        .map_err(|_| UdhcpdError::InvalidInput("Invalid subnet mask".to_string()))?;
+        .map_err(|_| AppError::InvalidInput("Invalid subnet mask".to_string()))?;
 
     web::block(move || manager.set_subnet_mask(subnet_mask))
         .await
-        .map_err(|e| UdhcpdError::Process(e.to_string()))??;
+        .map_err(|e| AppError::Process(e.to_string()))??;
 
     Ok(HttpResponse::Ok().json(json!({"status": "Subnet mask updated"})))
 }
@@ -137,83 +419,239 @@ async fn set_subnet_mask(
 
 #[post("/config/interface")]
 async fn set_interface(
-    manager: web::Data<UdhcpdManager>,
+    manager: Instance,
     payload: web::Json<InterfacePayload>,
-) -> Result<impl Responder, UdhcpdError> {
+) -> Result<impl Responder, AppError> {
     let interface_name = payload.interface.clone();
     if interface_name.is_empty() {
-        return Err(UdhcpdError::InvalidInput(
+        return Err(AppError::InvalidInput(
             "Interface name cannot be empty".to_string(),
         ));
     }
 
     web::block(move || manager.set_interface(interface_name))
         .await
-        .map_err(|e| UdhcpdError::Process(e.to_string()))??;
+        .map_err(|e| AppError::Process(e.to_string()))??;
 
     Ok(HttpResponse::Ok().json(json!({ "status": "Interface updated" })))
 }
 
 #[post("/config/dns")]
 async fn set_dns(
-    manager: web::Data<UdhcpdManager>,
+    manager: Instance,
     payload: web::Json<DnsPayload>,
-) -> Result<impl Responder, UdhcpdError> {
+) -> Result<impl Responder, AppError> {
     let servers: Vec<Ipv4Addr> = payload
         .servers
         .iter()
         .map(|s| {
             Ipv4Addr::from_str(s)
-                .map_err(|_| UdhcpdError::InvalidInput(format!("Invalid DNS IP address: {}", s)))
+                .map_err(|_| AppError::InvalidInput(format!("Invalid DNS IP address: {}", s)))
         })
         .collect::<Result<_, _>>()?;
 
     web::block(move || manager.set_dns_servers(servers))
         .await
-        .map_err(|e| UdhcpdError::Process(e.to_string()))??;
+        .map_err(|e| AppError::Process(e.to_string()))??;
 
     Ok(HttpResponse::Ok().json(json!({"status": "DNS servers updated"})))
 }
 
 #[post("/config/lease")]
 async fn add_lease(
-    manager: web::Data<UdhcpdManager>,
+    manager: Instance,
+    inventory: web::Data<DeviceInventory>,
     payload: web::Json<LeasePayload>,
-) -> Result<impl Responder, UdhcpdError> {
+) -> Result<impl Responder, AppError> {
     let ip = Ipv4Addr::from_str(&payload.ip)
-        .map_err(|_| UdhcpdError::InvalidInput("Invalid lease IP address".to_string()))?;
-    let lease = StaticLease {
-        mac: payload.mac.clone(),
-        ip,
+        .map_err(|_| AppError::InvalidInput("Invalid lease IP address".to_string()))?;
+    let mac = match (&payload.mac, &payload.device) {
+        (Some(mac), _) => MacAddr::from_str(mac)?,
+        (None, Some(name)) => inventory
+            .find_by_name(name)
+            .map(|d| d.mac)
+            .ok_or_else(|| AppError::NotFound(format!("Device '{}'", name)))?,
+        (None, None) => return Err(AppError::InvalidInput("Either mac or device is required".to_string())),
     };
-
-    web::block(move || manager.add_or_update_static_lease(lease))
+    let lease = StaticLease { mac, ip };
+    let probe = payload.probe;
+
+    web::block(move || {
+        if probe {
+            manager.check_lease_address(&lease)?;
+        }
+        manager.add_or_update_static_lease(lease)
+    })
         .await
-        .map_err(|e| UdhcpdError::Process(e.to_string()))??;
+        .map_err(|e| AppError::Process(e.to_string()))??;
 
     Ok(HttpResponse::Ok().json(json!({"status": "Static lease added/updated"})))
 }
 
 #[delete("/config/lease")]
 async fn remove_lease(
-    manager: web::Data<UdhcpdManager>,
+    manager: Instance,
     payload: web::Json<RemoveLeasePayload>,
-) -> Result<impl Responder, UdhcpdError> {
-    let mac = payload.mac.clone();
-    web::block(move || manager.remove_static_lease(&mac))
+) -> Result<impl Responder, AppError> {
+    let mac = MacAddr::from_str(&payload.mac)?;
+    web::block(move || manager.remove_static_lease(mac))
         .await
-        .map_err(|e| UdhcpdError::Process(e.to_string()))??;
+        .map_err(|e| AppError::Process(e.to_string()))??;
 
     Ok(HttpResponse::Ok().json(json!({"status": "Static lease removed"})))
 }
 
+#[get("/config/leases/export")]
+async fn export_leases(manager: Instance, query: web::Query<ExportQuery>) -> Result<impl Responder, AppError> {
+    let static_leases = manager.read_config()?.static_leases;
+    match query.format.unwrap_or(LeaseFormat::Json) {
+        LeaseFormat::Csv => Ok(HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}-leases.csv\"", manager.name),
+            ))
+            .body(lease_import::to_csv(&static_leases))),
+        LeaseFormat::Json => Ok(HttpResponse::Ok().json(static_leases)),
+    }
+}
+
+#[post("/config/leases/import")]
+async fn import_leases(
+    req: HttpRequest,
+    manager: Instance,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+) -> Result<impl Responder, AppError> {
+    let content = std::str::from_utf8(&body)
+        .map_err(|_| AppError::InvalidInput("Request body is not valid UTF-8".to_string()))?;
+    let format = query.format.unwrap_or_else(|| {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        if content_type.starts_with("text/csv") {
+            LeaseFormat::Csv
+        } else {
+            LeaseFormat::Json
+        }
+    });
+    let rows = match format {
+        LeaseFormat::Csv => lease_import::parse_csv(content),
+        LeaseFormat::Json => lease_import::parse_json(content),
+    }
+    .map_err(|issue| AppError::Validation(vec![issue]))?;
+    let (mode, dry_run) = (query.mode, query.dry_run);
+
+    let report = web::block(move || manager.import_static_leases(&rows, mode, dry_run))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// --- DHCP 选项 ---
+
+fn parse_option_code(key: &str) -> Result<u8, AppError> {
+    options::resolve_code(key).ok_or_else(|| AppError::InvalidInput(format!("Unknown DHCP option '{}'", key)))
+}
+
+fn option_json(option: &DhcpOption) -> serde_json::Value {
+    json!({
+        "code": option.code,
+        "name": option.def().map(|d| d.name),
+        "type": option.value.kind(),
+        "value": option.value,
+    })
+}
+
+#[get("/config/options")]
+async fn list_options(manager: Instance) -> Result<impl Responder, AppError> {
+    let config = manager.read_config()?;
+    let list: Vec<_> = config.options.iter().map(option_json).collect();
+    Ok(HttpResponse::Ok().json(list))
+}
+
+// 内置的已知选项表
+#[get("/config/options/known")]
+async fn known_options() -> impl Responder {
+    HttpResponse::Ok().json(options::KNOWN_OPTIONS)
+}
+
+#[get("/config/options/{code}")]
+async fn get_option(manager: Instance, path: web::Path<(String, String)>) -> Result<impl Responder, AppError> {
+    let code = parse_option_code(&path.1)?;
+    let config = manager.read_config()?;
+    let option = config
+        .options
+        .iter()
+        .find(|o| o.code == code)
+        .ok_or_else(|| AppError::NotFound(format!("Option {}", code)))?;
+    Ok(HttpResponse::Ok().json(option_json(option)))
+}
+
+#[post("/config/options")]
+async fn set_option(manager: Instance, payload: web::Json<OptionPayload>) -> Result<impl Responder, AppError> {
+    let OptionPayload { code, kind, value } = payload.into_inner();
+    let code = match &code {
+        serde_json::Value::Number(n) => n
+            .as_u64()
+            .and_then(|n| u8::try_from(n).ok())
+            .ok_or_else(|| AppError::InvalidInput(format!("Invalid DHCP option code {}", n)))?,
+        serde_json::Value::String(s) => parse_option_code(s)?,
+        _ => return Err(AppError::InvalidInput("code must be a number or an option name".to_string())),
+    };
+    let kind = kind
+        .or_else(|| options::lookup(code).map(|d| d.kind))
+        .unwrap_or(OptionType::Hex);
+    let value: OptionValue = serde_json::from_value(json!({ "type": kind, "value": value }))
+        .map_err(|e| AppError::InvalidInput(format!("Invalid value for option {}: {}", code, e)))?;
+    let option = DhcpOption { code, value };
+    let response = option_json(&option);
+
+    web::block(move || manager.set_option(option))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[delete("/config/options/{code}")]
+async fn remove_option(manager: Instance, path: web::Path<(String, String)>) -> Result<impl Responder, AppError> {
+    let code = parse_option_code(&path.1)?;
+    web::block(move || manager.remove_option(code))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+
+    Ok(HttpResponse::Ok().json(json!({"status": "Option removed"})))
+}
+
 pub fn service() -> Scope {
+    // 实例管理路由必须在 /{instance} 之前注册
     web::scope("/udhcpd")
+        .service(list_instances)
+        .service(create_instance)
+        .service(delete_instance)
+        .service(rogue_servers)
+        .service(probe_rogue_servers)
+        .service(instance_service())
+}
+
+fn instance_service() -> Scope {
+    web::scope("/{instance}")
         .service(start)
         .service(stop)
         .service(restart)
         .service(status)
+        .service(leases)
+        .service(events)
+        .service(check_pool)
+        .service(set_auto_apply)
+        .service(set_autostart)
         .service(get_config)
+        .service(replace_config)
+        .service(validate_config)
         .service(set_range)
         .service(set_gateway)
         .service(set_interface)
@@ -221,4 +659,11 @@ pub fn service() -> Scope {
         .service(set_dns)
         .service(add_lease)
         .service(remove_lease)
+        .service(export_leases)
+        .service(import_leases)
+        .service(list_options)
+        .service(known_options)
+        .service(get_option)
+        .service(set_option)
+        .service(remove_option)
 }
\ No newline at end of file
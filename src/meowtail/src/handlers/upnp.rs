@@ -0,0 +1,49 @@
+// src/handlers/upnp.rs
+
+use crate::error::AppError;
+use crate::upnp::{UpnpConfig, UpnpManager};
+use actix_web::{delete, get, put, web, HttpResponse, Responder, Scope};
+use serde_json::json;
+
+#[get("/config")]
+async fn get_upnp_config(manager: web::Data<UpnpManager>) -> impl Responder {
+    HttpResponse::Ok().json(manager.config())
+}
+
+// ACL、secure_mode 与 max_lease_secs 立即生效，其他选项需要重启
+#[put("/config")]
+async fn set_upnp_config(
+    manager: web::Data<UpnpManager>,
+    payload: web::Json<UpnpConfig>,
+) -> Result<impl Responder, AppError> {
+    web::block(move || manager.set_config(payload.into_inner()))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "UPnP config updated"})))
+}
+
+// 客户端通过 UPnP、NAT-PMP 和 PCP 申请的映射，不含 portmap.toml 中的规则
+#[get("/mappings")]
+async fn list_mappings(manager: web::Data<UpnpManager>) -> impl Responder {
+    HttpResponse::Ok().json(manager.mappings())
+}
+
+#[delete("/mappings/{protocol}/{port}")]
+async fn delete_mapping(
+    manager: web::Data<UpnpManager>,
+    path: web::Path<(String, u16)>,
+) -> Result<impl Responder, AppError> {
+    let (protocol, port) = path.into_inner();
+    web::block(move || manager.remove_mapping(&protocol.to_ascii_lowercase(), port))
+        .await
+        .map_err(|e| AppError::Process(e.to_string()))??;
+    Ok(HttpResponse::Ok().json(json!({"status": "mapping deleted"})))
+}
+
+pub fn service() -> Scope {
+    web::scope("/upnp")
+        .service(get_upnp_config)
+        .service(set_upnp_config)
+        .service(list_mappings)
+        .service(delete_mapping)
+}
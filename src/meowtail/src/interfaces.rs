@@ -0,0 +1,266 @@
+// src/interfaces.rs
+
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Serialize;
+
+use crate::error::{AppError, Result};
+
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+const IFF_UP: u32 = 0x1;
+
+/// 接口名的最大长度（IFNAMSIZ - 1）
+pub const MAX_NAME_LEN: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterfaceKind {
+    Ethernet,
+    Bridge,
+    Vlan,
+    Wireless,
+    Loopback,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceAddress {
+    pub address: IpAddr,
+    pub prefix_len: u8,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+}
+
+/// 一个网络接口的当前状态，来自 /sys/class/net 与 getifaddrs
+#[derive(Debug, Clone, Serialize)]
+pub struct NetInterface {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: InterfaceKind,
+    pub mac: Option<String>,
+    /// 管理状态（IFF_UP）
+    pub up: bool,
+    /// 内核的 operstate：up、down、dormant、unknown 等
+    pub state: String,
+    pub carrier: Option<bool>,
+    pub mtu: u32,
+    /// 所属的网桥
+    pub master: Option<String>,
+    pub ipv4: Vec<InterfaceAddress>,
+    pub ipv6: Vec<InterfaceAddress>,
+    pub counters: InterfaceCounters,
+}
+
+fn sys_path(name: &str) -> PathBuf {
+    Path::new(SYS_CLASS_NET).join(name)
+}
+
+fn read_attr(name: &str, attr: &str) -> Option<String> {
+    fs::read_to_string(sys_path(name).join(attr))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+fn read_number(name: &str, attr: &str) -> Option<u64> {
+    read_attr(name, attr)?.parse().ok()
+}
+
+fn kind(name: &str) -> InterfaceKind {
+    let path = sys_path(name);
+    let devtype = read_attr(name, "uevent").and_then(|uevent| {
+        uevent
+            .lines()
+            .find_map(|l| l.strip_prefix("DEVTYPE=").map(str::to_string))
+    });
+    match devtype.as_deref() {
+        Some("bridge") => return InterfaceKind::Bridge,
+        Some("vlan") => return InterfaceKind::Vlan,
+        Some("wlan") => return InterfaceKind::Wireless,
+        _ => {}
+    }
+    if path.join("bridge").exists() {
+        InterfaceKind::Bridge
+    } else if path.join("wireless").exists() || path.join("phy80211").exists() {
+        InterfaceKind::Wireless
+    } else {
+        // ARPHRD_ETHER = 1, ARPHRD_LOOPBACK = 772
+        match read_number(name, "type") {
+            Some(1) => InterfaceKind::Ethernet,
+            Some(772) => InterfaceKind::Loopback,
+            _ => InterfaceKind::Other,
+        }
+    }
+}
+
+fn counters(name: &str) -> InterfaceCounters {
+    let stat = |attr: &str| read_number(name, &format!("statistics/{}", attr)).unwrap_or(0);
+    InterfaceCounters {
+        rx_bytes: stat("rx_bytes"),
+        rx_packets: stat("rx_packets"),
+        rx_errors: stat("rx_errors"),
+        rx_dropped: stat("rx_dropped"),
+        tx_bytes: stat("tx_bytes"),
+        tx_packets: stat("tx_packets"),
+        tx_errors: stat("tx_errors"),
+        tx_dropped: stat("tx_dropped"),
+    }
+}
+
+fn read_interface(name: &str) -> NetInterface {
+    let flags = read_attr(name, "flags")
+        .and_then(|f| u32::from_str_radix(f.trim_start_matches("0x"), 16).ok())
+        .unwrap_or(0);
+    let kind = kind(name);
+    NetInterface {
+        name: name.to_string(),
+        kind,
+        mac: read_attr(name, "address").filter(|_| kind != InterfaceKind::Loopback),
+        up: flags & IFF_UP != 0,
+        state: read_attr(name, "operstate").unwrap_or_else(|| "unknown".to_string()),
+        // 接口关闭时读取 carrier 会失败
+        carrier: read_number(name, "carrier").map(|c| c == 1),
+        mtu: read_number(name, "mtu").unwrap_or(0) as u32,
+        master: fs::read_link(sys_path(name).join("master"))
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string())),
+        ipv4: vec![],
+        ipv6: vec![],
+        counters: counters(name),
+    }
+}
+
+/// 把 getifaddrs 中的地址填入对应接口
+fn fill_addresses(interfaces: &mut [NetInterface]) {
+    let Ok(addrs) = nix::ifaddrs::getifaddrs() else {
+        return;
+    };
+    for ifa in addrs {
+        let Some(interface) = interfaces.iter_mut().find(|i| i.name == ifa.interface_name) else {
+            continue;
+        };
+        let Some(address) = ifa.address else {
+            continue;
+        };
+        if let Some(v4) = address.as_sockaddr_in() {
+            let prefix_len = ifa
+                .netmask
+                .and_then(|m| m.as_sockaddr_in().map(|m| u32::from(m.ip()).count_ones() as u8))
+                .unwrap_or(32);
+            interface.ipv4.push(InterfaceAddress {
+                address: IpAddr::V4(v4.ip()),
+                prefix_len,
+            });
+        } else if let Some(v6) = address.as_sockaddr_in6() {
+            let prefix_len = ifa
+                .netmask
+                .and_then(|m| m.as_sockaddr_in6().map(|m| u128::from(m.ip()).count_ones() as u8))
+                .unwrap_or(128);
+            interface.ipv6.push(InterfaceAddress {
+                address: IpAddr::V6(v6.ip()),
+                prefix_len,
+            });
+        }
+    }
+}
+
+/// 列出所有网络接口，按名字排序
+pub fn list() -> io::Result<Vec<NetInterface>> {
+    let mut names: Vec<String> = fs::read_dir(SYS_CLASS_NET)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    let mut interfaces: Vec<NetInterface> = names.iter().map(|n| read_interface(n)).collect();
+    fill_addresses(&mut interfaces);
+    Ok(interfaces)
+}
+
+pub fn get(name: &str) -> io::Result<NetInterface> {
+    check(name)?;
+    let mut interfaces = vec![read_interface(name)];
+    fill_addresses(&mut interfaces);
+    Ok(interfaces.remove(0))
+}
+
+/// 内核接受的接口名：1 到 15 个字符，不含 `/`、空白与 `:`
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name != "."
+        && name != ".."
+        && !name.contains(|c: char| c == '/' || c == ':' || c.is_whitespace())
+}
+
+/// 接口名合法且接口存在时返回 Ok，否则返回 InvalidInput 错误
+pub fn check(name: &str) -> io::Result<()> {
+    if !is_valid_name(name) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' is not a valid interface name", name),
+        ));
+    }
+    if !sys_path(name).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Interface '{}' does not exist", name),
+        ));
+    }
+    Ok(())
+}
+
+/// 与 check 相同，错误转换为 AppError::InvalidInput
+pub(crate) fn check_interface(name: &str) -> Result<()> {
+    check(name).map_err(|e| AppError::InvalidInput(e.to_string()))
+}
+
+/// 网桥当前的成员端口，按名字排序；不是网桥时为空
+pub fn bridge_ports(name: &str) -> Vec<String> {
+    let mut ports: Vec<String> = fs::read_dir(sys_path(name).join("brif"))
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    ports.sort();
+    ports
+}
+
+/// 新建 DHCP 配置时默认使用的接口：优先网桥，其次以太网接口，都没有时为 eth0
+pub fn default_lan() -> String {
+    let interfaces = list().unwrap_or_default();
+    [InterfaceKind::Bridge, InterfaceKind::Ethernet]
+        .iter()
+        .find_map(|kind| interfaces.iter().find(|i| i.kind == *kind))
+        .map(|i| i.name.clone())
+        .unwrap_or_else(|| "eth0".to_string())
+}
+
+/// 运行 `ip` 命令，失败时把 stderr 放进错误信息
+pub(crate) fn run_ip(args: &[&str]) -> Result<()> {
+    let output = Command::new("ip").args(args).output()?;
+    if !output.status.success() {
+        return Err(AppError::Process(format!(
+            "ip {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+// src/ip_cidr.rs
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// 带前缀长度的 IPv4/IPv6 地址，例如 `192.168.1.1/24`、`2001:db8::1/64`。
+/// 既可以表示接口地址（主机位非零），也可以表示网段。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IpCidr {
+    pub addr: IpAddr,
+    pub len: u8,
+}
+
+impl IpCidr {
+    pub fn new(addr: IpAddr, len: u8) -> Result<Self, String> {
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        if len > max {
+            return Err(format!("Prefix length /{} is too long for {}", len, addr));
+        }
+        Ok(IpCidr { addr, len })
+    }
+
+    pub fn is_ipv4(&self) -> bool {
+        self.addr.is_ipv4()
+    }
+
+    /// 去掉主机位后的网段
+    pub fn network(&self) -> IpCidr {
+        let addr = match self.addr {
+            IpAddr::V4(a) => {
+                let mask = if self.len == 0 { 0 } else { u32::MAX << (32 - self.len as u32) };
+                IpAddr::V4(Ipv4Addr::from(u32::from(a) & mask))
+            }
+            IpAddr::V6(a) => {
+                let mask = if self.len == 0 { 0 } else { u128::MAX << (128 - self.len as u32) };
+                IpAddr::V6(Ipv6Addr::from(u128::from(a) & mask))
+            }
+        };
+        IpCidr { addr, len: self.len }
+    }
+
+    /// `ip` 是否落在本网段内（地址族不同时为 false）
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        ip.is_ipv4() == self.is_ipv4() && IpCidr { addr: ip, len: self.len }.network() == self.network()
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = String;
+
+    /// 省略前缀长度时视为单个主机（/32 或 /128）
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let (addr, len) = match s.split_once('/') {
+            Some((addr, len)) => (addr, Some(len)),
+            None => (s, None),
+        };
+        let addr = IpAddr::from_str(addr).map_err(|_| format!("'{}' is not a valid IP address", addr))?;
+        let len = match len {
+            Some(len) => len.parse::<u8>().map_err(|_| format!("Invalid prefix length '{}'", len))?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+        IpCidr::new(addr, len)
+    }
+}
+
+impl TryFrom<String> for IpCidr {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        s.parse()
+    }
+}
+
+impl From<IpCidr> for String {
+    fn from(cidr: IpCidr) -> String {
+        cidr.to_string()
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.len)
+    }
+}
@@ -0,0 +1,380 @@
+// src/lease_import.rs
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ValidationIssue;
+use crate::mac_addr::MacAddr;
+use crate::udhcpd_manager::{StaticLease, UdhcpdConfig};
+
+/// 导入文件中的一行，保留原始文本以便逐行报告错误
+#[derive(Debug, Clone, Deserialize)]
+pub struct LeaseRow {
+    #[serde(skip)]
+    pub row: usize,
+    pub mac: String,
+    pub ip: String,
+}
+
+/// merge 在现有静态租约上新增或更新；replace 用导入的内容整体替换
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    #[default]
+    Merge,
+    Replace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RowAction {
+    Added,
+    Updated,
+    Unchanged,
+}
+
+/// 一行导入会对配置产生的变化
+#[derive(Debug, Clone, Serialize)]
+pub struct RowChange {
+    pub row: usize,
+    pub mac: MacAddr,
+    pub ip: Ipv4Addr,
+    pub action: RowAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_ip: Option<Ipv4Addr>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    pub dry_run: bool,
+    pub rows: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    /// replace 模式下被删除的租约
+    pub removed: Vec<StaticLease>,
+    pub changes: Vec<RowChange>,
+    pub issues: Vec<ValidationIssue>,
+}
+
+/// 解析 CSV。第一行含 `mac` 时视为表头并按列名取值，否则按 `mac,ip` 顺序；空行和 `#` 开头的行被忽略
+pub fn parse_csv(content: &str) -> Result<Vec<LeaseRow>, ValidationIssue> {
+    let mut rows = Vec::new();
+    let mut columns = (0, 1);
+
+    for (i, line) in content.lines().enumerate() {
+        let row = i + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let fields = split_csv_line(trimmed).map_err(|e| ValidationIssue::new(format!("row {}", row), e))?;
+
+        if rows.is_empty() && fields.iter().any(|f| f.eq_ignore_ascii_case("mac")) {
+            let position = |name: &str| fields.iter().position(|f| f.eq_ignore_ascii_case(name));
+            columns = match (position("mac"), position("ip")) {
+                (Some(mac), Some(ip)) => (mac, ip),
+                _ => return Err(ValidationIssue::new("row 1", "Header must contain 'mac' and 'ip' columns")),
+            };
+            continue;
+        }
+
+        rows.push(LeaseRow {
+            row,
+            mac: fields.get(columns.0).cloned().unwrap_or_default(),
+            ip: fields.get(columns.1).cloned().unwrap_or_default(),
+        });
+    }
+    Ok(rows)
+}
+
+/// 拆分一行 CSV，支持 `"..."` 引用的字段与 `""` 转义
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' | ';' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quoted field".to_string());
+    }
+    fields.push(field.trim().to_string());
+    Ok(fields)
+}
+
+/// 解析 JSON 数组 `[{ "mac", "ip" }, ...]`，行号从 1 开始按数组下标计
+pub fn parse_json(content: &str) -> Result<Vec<LeaseRow>, ValidationIssue> {
+    let mut rows: Vec<LeaseRow> =
+        serde_json::from_str(content).map_err(|e| ValidationIssue::new("body", format!("Invalid JSON: {}", e)))?;
+    for (i, row) in rows.iter_mut().enumerate() {
+        row.row = i + 1;
+    }
+    Ok(rows)
+}
+
+/// 计算导入后的配置与报告，不做任何写入。
+/// 有问题的行不会进入新配置；调用方在 `issues` 非空时应放弃整个导入。
+pub fn plan(current: &UdhcpdConfig, rows: &[LeaseRow], mode: ImportMode, dry_run: bool) -> (UdhcpdConfig, ImportReport) {
+    let mut issues = Vec::new();
+    let mut accepted: Vec<(usize, StaticLease)> = Vec::new();
+    let mut seen_mac: HashMap<MacAddr, usize> = HashMap::new();
+    let mut seen_ip: HashMap<Ipv4Addr, usize> = HashMap::new();
+
+    for row in rows {
+        let field = |name: &str| format!("row {}.{}", row.row, name);
+        let mac = MacAddr::from_str(&row.mac).map_err(|e| ValidationIssue::new(field("mac"), e.to_string()));
+        let ip = Ipv4Addr::from_str(row.ip.trim())
+            .map_err(|_| ValidationIssue::new(field("ip"), format!("Invalid IP address '{}'", row.ip)));
+        let (mac, ip) = match (mac, ip) {
+            (Ok(mac), Ok(ip)) => (mac, ip),
+            (mac, ip) => {
+                issues.extend(mac.err());
+                issues.extend(ip.err());
+                continue;
+            }
+        };
+
+        if let Some(first) = seen_mac.get(&mac) {
+            issues.push(ValidationIssue::new(field("mac"), format!("MAC {} already appears in row {}", mac, first)));
+            continue;
+        }
+        if let Some(first) = seen_ip.get(&ip) {
+            issues.push(ValidationIssue::new(field("ip"), format!("IP {} already appears in row {}", ip, first)));
+            continue;
+        }
+        seen_mac.insert(mac, row.row);
+        seen_ip.insert(ip, row.row);
+        accepted.push((row.row, StaticLease { mac, ip }));
+    }
+
+    // merge 模式下，导入中未出现的现有租约保持不变，其 IP 不能再分给别的 MAC
+    if mode == ImportMode::Merge {
+        accepted.retain(|(row, lease)| {
+            let holder = current
+                .static_leases
+                .iter()
+                .find(|l| l.ip == lease.ip && l.mac != lease.mac && !seen_mac.contains_key(&l.mac));
+            match holder {
+                Some(holder) => {
+                    issues.push(ValidationIssue::new(
+                        format!("row {}.ip", row),
+                        format!("IP {} is already reserved for {}", lease.ip, holder.mac),
+                    ));
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+
+    let mut config = current.clone();
+    let mut changes = Vec::new();
+    let mut removed = Vec::new();
+    // 新配置中每个静态租约对应的导入行
+    let mut origin: HashMap<usize, usize> = HashMap::new();
+
+    if mode == ImportMode::Replace {
+        removed = current
+            .static_leases
+            .iter()
+            .filter(|l| !seen_mac.contains_key(&l.mac))
+            .cloned()
+            .collect();
+        config.static_leases.clear();
+    }
+    for (row, lease) in accepted {
+        let previous = current.static_leases.iter().find(|l| l.mac == lease.mac).map(|l| l.ip);
+        let action = match previous {
+            None => RowAction::Added,
+            Some(ip) if ip == lease.ip => RowAction::Unchanged,
+            Some(_) => RowAction::Updated,
+        };
+        let index = match config.static_leases.iter().position(|l| l.mac == lease.mac) {
+            Some(index) => {
+                config.static_leases[index] = lease.clone();
+                index
+            }
+            None => {
+                config.static_leases.push(lease.clone());
+                config.static_leases.len() - 1
+            }
+        };
+        origin.insert(index, row);
+        changes.push(RowChange {
+            row,
+            mac: lease.mac,
+            ip: lease.ip,
+            action,
+            previous_ip: previous.filter(|_| action == RowAction::Updated),
+        });
+    }
+
+    // 整体校验（子网、网关等），把落在导入租约上的问题归到对应的行；
+    // 与单项修改一样，导入前文件中已有的问题不阻止导入
+    let existing = current.validation_issues();
+    for issue in config.validation_issues().into_iter().filter(|i| !existing.contains(i)) {
+        let row = issue
+            .field
+            .strip_prefix("static_leases[")
+            .and_then(|rest| rest.strip_suffix(']'))
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| origin.get(&index));
+        match row {
+            Some(row) => issues.push(ValidationIssue::new(format!("row {}", row), issue.message)),
+            None => issues.push(issue),
+        }
+    }
+
+    let count = |action| changes.iter().filter(|c| c.action == action).count();
+    let report = ImportReport {
+        mode,
+        dry_run,
+        rows: rows.len(),
+        added: count(RowAction::Added),
+        updated: count(RowAction::Updated),
+        unchanged: count(RowAction::Unchanged),
+        removed,
+        changes,
+        issues,
+    };
+    (config, report)
+}
+
+/// 导出为 `mac,ip` 的 CSV，带表头
+pub fn to_csv(leases: &[StaticLease]) -> String {
+    let mut content = String::from("mac,ip\n");
+    for lease in leases {
+        content.push_str(&format!("{},{}\n", lease.mac, lease.ip));
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lease(mac: &str, ip: &str) -> StaticLease {
+        StaticLease {
+            mac: MacAddr::from_str(mac).unwrap(),
+            ip: Ipv4Addr::from_str(ip).unwrap(),
+        }
+    }
+
+    fn row(row: usize, mac: &str, ip: &str) -> LeaseRow {
+        LeaseRow {
+            row,
+            mac: mac.to_string(),
+            ip: ip.to_string(),
+        }
+    }
+
+    fn fields(report: &ImportReport) -> Vec<String> {
+        report.issues.iter().map(|i| i.field.clone()).collect()
+    }
+
+    #[test]
+    fn csv_with_header_and_quotes() {
+        let content = "# exported\nip;\"MAC\"\n\n192.168.1.10;\"aa:bb:cc:00:11:22\"\n192.168.1.11,aa-bb-cc-00-11-23\n";
+        let rows = parse_csv(content).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].row, rows[0].mac.as_str(), rows[0].ip.as_str()), (4, "aa:bb:cc:00:11:22", "192.168.1.10"));
+        assert_eq!((rows[1].row, rows[1].mac.as_str(), rows[1].ip.as_str()), (5, "aa-bb-cc-00-11-23", "192.168.1.11"));
+    }
+
+    #[test]
+    fn csv_without_header_uses_mac_ip_order() {
+        let rows = parse_csv("aa:bb:cc:00:11:22,192.168.1.10\n\"a\"\"b\",x\n").unwrap();
+        assert_eq!(rows[0].mac, "aa:bb:cc:00:11:22");
+        assert_eq!(rows[0].ip, "192.168.1.10");
+        assert_eq!(rows[1].mac, "a\"b");
+    }
+
+    #[test]
+    fn csv_errors_name_the_row() {
+        assert_eq!(parse_csv("mac,address\n").unwrap_err().field, "row 1");
+        assert_eq!(parse_csv("aa:bb:cc:00:11:22,192.168.1.10\n\"aa,192.168.1.11\n").unwrap_err().field, "row 2");
+    }
+
+    #[test]
+    fn json_rows_are_numbered() {
+        let rows = parse_json(r#"[{"mac": "aa:bb:cc:00:11:22", "ip": "192.168.1.10"}, {"mac": "x", "ip": "y"}]"#).unwrap();
+        assert_eq!(rows.iter().map(|r| r.row).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(parse_json(r#"{"mac": "x"}"#).unwrap_err().field, "body");
+    }
+
+    #[test]
+    fn plan_reports_bad_and_duplicate_rows() {
+        let current = UdhcpdConfig::with_defaults("eth0");
+        let rows = [
+            row(1, "aa:bb:cc:00:11:22", "192.168.1.10"),
+            row(2, "not-a-mac", "192.168.1.300"),
+            row(3, "AA:BB:CC:00:11:22", "192.168.1.11"),
+            row(4, "aa:bb:cc:00:11:23", "192.168.1.10"),
+            row(5, "aa:bb:cc:00:11:24", "10.0.0.5"),
+            row(6, "aa:bb:cc:00:11:25", "192.168.1.1"),
+        ];
+        let (_, report) = plan(&current, &rows, ImportMode::Merge, true);
+        assert_eq!(fields(&report), vec!["row 2.mac", "row 2.ip", "row 3.mac", "row 4.ip", "row 5", "row 6"]);
+        assert_eq!(report.rows, 6);
+    }
+
+    #[test]
+    fn merge_keeps_other_leases_and_protects_their_addresses() {
+        let mut current = UdhcpdConfig::with_defaults("eth0");
+        current.static_leases = vec![lease("aa:bb:cc:00:00:01", "192.168.1.10"), lease("aa:bb:cc:00:00:02", "192.168.1.20")];
+
+        let rows = [
+            row(1, "aa:bb:cc:00:00:01", "192.168.1.11"),
+            row(2, "aa:bb:cc:00:00:03", "192.168.1.30"),
+        ];
+        let (config, report) = plan(&current, &rows, ImportMode::Merge, false);
+        assert!(report.issues.is_empty());
+        assert_eq!((report.added, report.updated, report.unchanged), (1, 1, 0));
+        assert_eq!(report.changes[0].previous_ip, Some(Ipv4Addr::new(192, 168, 1, 10)));
+        assert_eq!(config.static_leases.len(), 3);
+
+        let (_, report) = plan(&current, &[row(1, "aa:bb:cc:00:00:03", "192.168.1.20")], ImportMode::Merge, false);
+        assert_eq!(fields(&report), vec!["row 1.ip"]);
+    }
+
+    #[test]
+    fn replace_removes_missing_leases() {
+        let mut current = UdhcpdConfig::with_defaults("eth0");
+        current.static_leases = vec![lease("aa:bb:cc:00:00:01", "192.168.1.10"), lease("aa:bb:cc:00:00:02", "192.168.1.20")];
+
+        // 被替换掉的租约的地址可以分给别的设备
+        let rows = [
+            row(1, "aa:bb:cc:00:00:01", "192.168.1.10"),
+            row(2, "aa:bb:cc:00:00:03", "192.168.1.20"),
+        ];
+        let (config, report) = plan(&current, &rows, ImportMode::Replace, false);
+        assert!(report.issues.is_empty());
+        assert_eq!((report.added, report.updated, report.unchanged), (1, 0, 1));
+        assert_eq!(report.removed, vec![lease("aa:bb:cc:00:00:02", "192.168.1.20")]);
+        assert_eq!(config.static_leases, vec![lease("aa:bb:cc:00:00:01", "192.168.1.10"), lease("aa:bb:cc:00:00:03", "192.168.1.20")]);
+    }
+
+    #[test]
+    fn csv_export_round_trips() {
+        let leases = vec![lease("aa:bb:cc:00:00:01", "192.168.1.10")];
+        let rows = parse_csv(&to_csv(&leases)).unwrap();
+        let (config, report) = plan(&UdhcpdConfig::with_defaults("eth0"), &rows, ImportMode::Replace, true);
+        assert!(report.issues.is_empty());
+        assert_eq!(config.static_leases, leases);
+    }
+}
@@ -0,0 +1,177 @@
+// src/mac_addr.rs
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::oui;
+
+#[derive(Error, Debug)]
+#[error("Invalid MAC address '{input}': {reason}")]
+pub struct MacAddrError {
+    input: String,
+    reason: &'static str,
+}
+
+/// 单播的以太网 MAC 地址，规范形式为小写冒号分隔（`aa:bb:cc:00:11:22`）。
+///
+/// 解析时接受常见写法：`AA:BB:CC:00:11:22`、`aa-bb-cc-00-11-22`、
+/// `aabb.cc00.1122`（Cisco）以及不带分隔符的 `aabbcc001122`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MacAddr([u8; 6]);
+
+impl MacAddr {
+    /// 从原始字节构造，与解析时一样拒绝全零、组播和广播地址
+    pub fn from_octets(octets: [u8; 6]) -> Result<Self, MacAddrError> {
+        let error = |reason| MacAddrError {
+            input: MacAddr(octets).to_string(),
+            reason,
+        };
+        if octets == [0; 6] {
+            return Err(error("the all-zero address is not a valid client address"));
+        }
+        // 组播位，也包括广播地址 ff:ff:ff:ff:ff:ff
+        if octets[0] & 0x01 != 0 {
+            return Err(error("multicast and broadcast addresses are not valid client addresses"));
+        }
+        Ok(MacAddr(octets))
+    }
+
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+
+    /// 本地管理的地址，例如手机和笔记本为保护隐私使用的随机 MAC
+    pub fn is_locally_administered(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+
+    /// 按内置 OUI 表查找厂商
+    pub fn vendor(&self) -> Option<&'static str> {
+        oui::lookup(&self.0)
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = MacAddrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |reason| MacAddrError {
+            input: s.to_string(),
+            reason,
+        };
+        let trimmed = s.trim();
+
+        // 按分隔符拆分后每组的十六进制位数必须一致：6 组 2 位或 3 组 4 位
+        let groups: Vec<&str> = trimmed.split([':', '-', '.']).collect();
+        let group_len = match groups.len() {
+            6 => 2,
+            3 => 4,
+            1 => 12,
+            _ => return Err(error("expected 6 groups of 2 or 3 groups of 4 hex digits")),
+        };
+        if groups.iter().any(|g| g.len() != group_len) {
+            return Err(error("expected 6 groups of 2 or 3 groups of 4 hex digits"));
+        }
+        let separators: Vec<char> = trimmed.chars().filter(|c| !c.is_ascii_hexdigit()).collect();
+        if separators.windows(2).any(|w| w[0] != w[1]) {
+            return Err(error("mixed separators"));
+        }
+
+        let digits: String = groups.concat();
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(error("contains non-hexadecimal characters"));
+        }
+        let mut octets = [0u8; 6];
+        for (i, octet) in octets.iter_mut().enumerate() {
+            *octet = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).map_err(|_| error("invalid hex digits"))?;
+        }
+
+        MacAddr::from_octets(octets).map_err(|e| error(e.reason))
+    }
+}
+
+impl TryFrom<String> for MacAddr {
+    type Error = MacAddrError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<MacAddr> for String {
+    fn from(mac: MacAddr) -> String {
+        mac.to_string()
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let o = self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            o[0], o[1], o[2], o[3], o[4], o[5]
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_common_notations() {
+        let expected = "aa:bb:cc:00:11:22";
+        for input in [
+            "aa:bb:cc:00:11:22",
+            "AA:BB:CC:00:11:22",
+            "aa-bb-cc-00-11-22",
+            "aabb.cc00.1122",
+            "aabbcc001122",
+            "  aa:bb:cc:00:11:22\n",
+        ] {
+            assert_eq!(MacAddr::from_str(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for input in [
+            "",
+            "aa:bb:cc:00:11",
+            "aa:bb:cc:00:11:22:33",
+            "aa:bb-cc:00:11:22",
+            "aabb.cc00-1122",
+            "a:bb:cc:00:11:222",
+            "aa:bb:cc:00:11:zz",
+            "aabbcc00112",
+        ] {
+            assert!(MacAddr::from_str(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_non_client_addresses() {
+        for input in ["00:00:00:00:00:00", "01:00:5e:00:00:01", "ff:ff:ff:ff:ff:ff", "33:33:00:00:00:01"] {
+            assert!(MacAddr::from_str(input).is_err(), "{}", input);
+        }
+        assert!(MacAddr::from_octets([0x01, 0, 0x5e, 0, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn flags_locally_administered_addresses() {
+        assert!(MacAddr::from_str("da:a1:19:00:00:01").unwrap().is_locally_administered());
+        assert!(!MacAddr::from_str("00:1a:2b:00:00:01").unwrap().is_locally_administered());
+    }
+
+    #[test]
+    fn serde_uses_the_canonical_form() {
+        let mac: MacAddr = serde_json::from_str("\"AA-BB-CC-00-11-22\"").unwrap();
+        assert_eq!(serde_json::to_string(&mac).unwrap(), "\"aa:bb:cc:00:11:22\"");
+        assert!(serde_json::from_str::<MacAddr>("\"ff:ff:ff:ff:ff:ff\"").is_err());
+    }
+}
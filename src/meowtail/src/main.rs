@@ -4,7 +4,8 @@ use std::fs::File;
 use std::process;
 use std::env;
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use actix_web::{web, App, HttpServer};
 use daemonize::Daemonize;
@@ -12,15 +13,48 @@ use nix::unistd::getuid;
 use actix_files as fs;
 
 // 引入模块
+mod addressing_manager;
+mod arp_probe;
+mod ddns_manager;
+mod dhcp_backend;
+mod dhcpv6_manager;
+mod dns_manager;
+mod device_inventory;
+mod error;
+mod firewall_manager;
+mod fs_util;
+mod lease_import;
+mod mac_addr;
+mod management_access;
+mod oui;
 mod handlers;
+mod interfaces;
+mod ip_cidr;
 mod middleware;
 mod models;
+mod nat_manager;
 mod udhcpd_manager;
+mod udhcpd_registry;
 mod portmap_manager;
+mod route_manager;
+mod supervisor;
+mod topology_manager;
+mod upnp;
 mod config; // 引入新的 config 模块
 
-use crate::udhcpd_manager::UdhcpdManager;
+use crate::addressing_manager::AddressingManager;
+use crate::ddns_manager::DdnsManager;
+use crate::device_inventory::DeviceInventory;
+use crate::dhcp_backend::rogue::RogueDetector;
+use crate::dhcpv6_manager::Dhcpv6Manager;
+use crate::dns_manager::DnsManager;
+use crate::firewall_manager::FirewallManager;
+use crate::nat_manager::NatManager;
+use crate::topology_manager::TopologyManager;
+use crate::udhcpd_registry::UdhcpdRegistry;
+use crate::upnp::UpnpManager;
 use crate::portmap_manager::PortMapManager;
+use crate::route_manager::RouteManager;
 use crate::config::Config;
 
 fn main() {
@@ -80,34 +114,130 @@ fn main() {
             
             sys.block_on(async {
                 let startup_config = app_config.lock().unwrap().clone();
-                // --- UdhcpdManager 初始化 (保持不变) ---
-                let config_path = "./udhcpd.conf";
-                let pid_path = "/tmp/meowtail_udhcpd.pid";
-                let executable_path = "udhcpd"; 
-
-                let manager = UdhcpdManager::new(executable_path, config_path, pid_path);
-                if let Err(e) = manager.create_config_with_defaults("eth0", false) {
-                    if e.to_string().contains("already exists") {
-                        println!("Configuration file '{}' already exists, using it.", config_path);
-                    } else {
-                        eprintln!("Failed to create default config file: {}", e);
-                        process::exit(1);
+                // --- 网桥与 VLAN：先于接口地址、DHCP 服务与端口映射创建 ---
+                let topology_manager = TopologyManager::new("./topology.toml");
+                topology_manager.apply_all();
+                let topology_data = web::Data::new(topology_manager);
+
+                // --- 接口地址：先于 DHCP 服务与端口映射配置 ---
+                let addressing_manager = AddressingManager::new("./interfaces.toml");
+                addressing_manager.apply_all();
+                let addressing_data = web::Data::new(addressing_manager);
+
+                // --- 静态路由与策略规则：依赖接口地址 ---
+                let route_manager = RouteManager::new("ip", "./routes.toml");
+                match route_manager.reconcile() {
+                    Ok(report) => {
+                        for error in &report.errors {
+                            eprintln!("[routes] {}", error);
+                        }
+                    }
+                    Err(e) => eprintln!("Failed to reconcile routes: {}", e),
+                }
+                let route_data = web::Data::new(route_manager);
+
+                // --- 防火墙区域与规则：在服务开始监听之前生效 ---
+                let firewall_manager = FirewallManager::new("./firewall.toml");
+                if let Err(e) = firewall_manager.apply_all() {
+                    eprintln!("Failed to apply firewall rules: {}", e);
+                }
+                let firewall_data = web::Data::new(firewall_manager);
+
+                // --- 出站 NAT 与 IPv4 转发 ---
+                let nat_manager = NatManager::new("./nat.toml");
+                if let Err(e) = nat_manager.apply_all() {
+                    eprintln!("Failed to apply NAT rules: {}", e);
+                }
+                let nat_data = web::Data::new(nat_manager);
+
+                // --- 管理端口的访问限制规则 ---
+                if let Err(e) = management_access::apply_firewall(&startup_config.management, startup_config.listen_port) {
+                    eprintln!("Failed to apply management access rules: {}", e);
+                }
+
+                // --- udhcpd 实例初始化 ---
+                let registry = UdhcpdRegistry::new(startup_config.dhcp_backend);
+                // 配置文件不存在时，新配置使用的接口
+                let default_interface = interfaces::default_lan();
+                for instance in &startup_config.udhcpd_instances {
+                    let manager = match registry.open(&instance.name, &default_interface) {
+                        Ok(m) => m,
+                        Err(e) => {
+                            eprintln!("Failed to open udhcpd instance '{}': {}", instance.name, e);
+                            process::exit(1);
+                        }
+                    };
+                    manager.set_auto_apply(instance.auto_apply);
+                    if let Some(pid) = manager.adopt_running() {
+                        println!("udhcpd instance '{}' is already running with PID {}, supervising it.", instance.name, pid);
+                    } else if instance.autostart {
+                        println!("udhcpd instance '{}' was enabled, attempting to start...", instance.name);
+                        if let Err(e) = manager.start() {
+                            eprintln!("Failed to auto-start udhcpd instance '{}': {}", instance.name, e);
+                        }
+                    }
+                }
+                let registry_data = web::Data::new(registry);
+
+                // --- 其他 DHCP 服务端探测 ---
+                let rogue_detector = Arc::new(RogueDetector::new(Duration::from_secs(
+                    startup_config.rogue_dhcp_interval_secs,
+                )));
+                RogueDetector::spawn(rogue_detector.clone(), registry_data.clone().into_inner());
+                let rogue_data = web::Data::from(rogue_detector);
+
+                // --- DHCPv6/RA 初始化 ---
+                let dhcpv6_manager = Dhcpv6Manager::new(
+                    "dnsmasq",
+                    "./dhcpv6.toml",
+                    "./dnsmasq-dhcpv6.conf",
+                    "/tmp/meowtail_dhcpv6.pid",
+                );
+                if let Some(pid) = dhcpv6_manager.adopt_running() {
+                    println!("DHCPv6 server is already running with PID {}, supervising it.", pid);
+                } else if startup_config.dhcpv6_autostart {
+                    println!("DHCPv6 server was enabled, attempting to start...");
+                    if let Err(e) = dhcpv6_manager.start() {
+                        eprintln!("Failed to auto-start DHCPv6 server: {}", e);
                     }
-                } else {
-                    println!("Created default configuration file at '{}'.", config_path);
                 }
+                let dhcpv6_data = web::Data::new(dhcpv6_manager);
+
+                // --- 设备清单 ---
+                let inventory = match DeviceInventory::new("./devices.toml") {
+                    Ok(i) => i,
+                    Err(e) => {
+                        eprintln!("Failed to load device inventory: {}", e);
+                        process::exit(1);
+                    }
+                };
+                let inventory_data = web::Data::new(inventory);
+                DeviceInventory::spawn(inventory_data.clone().into_inner(), registry_data.clone().into_inner());
 
-                let manager_data = web::Data::new(manager);
-                if startup_config.udhcpd_enabled {
-                    println!("udhcpd was enabled, attempting to start...");
-                    if let Err(e) = manager_data.start() {
-                        eprintln!("Failed to auto-start udhcpd: {}", e);
+                // --- 本地 DNS：发布 DHCP 租约中的主机名 ---
+                let dns_manager = Arc::new(DnsManager::new(
+                    "dnsmasq",
+                    "./dns.toml",
+                    "./dnsmasq-dns.conf",
+                    "/tmp/meowtail_dns.pid",
+                    registry_data.clone().into_inner(),
+                    inventory_data.clone().into_inner(),
+                    dhcpv6_data.clone().into_inner(),
+                ));
+                if let Some(pid) = dns_manager.adopt_running() {
+                    println!("DNS server is already running with PID {}, supervising it.", pid);
+                } else if startup_config.dns_autostart {
+                    println!("DNS server was enabled, attempting to start...");
+                    if let Err(e) = dns_manager.start() {
+                        eprintln!("Failed to auto-start DNS server: {}", e);
                     }
                 }
+                DnsManager::spawn(dns_manager.clone());
+                let dns_data = web::Data::from(dns_manager);
 
                 // --- PortMapManager 初始化并载入规则 ---
                 let portmap_path = "./portmap.toml";
-                let portmap_manager = match PortMapManager::new(portmap_path) {
+                let portmap_manager = match PortMapManager::new("iptables", portmap_path) {
                     Ok(m) => m,
                     Err(e) => {
                         eprintln!("Failed to load port map config: {}", e);
@@ -119,13 +249,42 @@ fn main() {
                 }
                 let portmap_data = web::Data::new(portmap_manager);
 
-                let listen_addr = format!("{}:{}", startup_config.listen_address, startup_config.listen_port);
-                println!("Starting web server at http://{}", listen_addr);
+                // --- UPnP IGD 与 NAT-PMP/PCP：映射作为租约交给 PortMapManager ---
+                let upnp_manager = match UpnpManager::new("./upnp.toml", portmap_data.clone().into_inner()) {
+                    Ok(m) => Arc::new(m),
+                    Err(e) => {
+                        eprintln!("Failed to load UPnP config: {}", e);
+                        process::exit(1);
+                    }
+                };
+                UpnpManager::spawn(upnp_manager.clone());
+                if let Some(igd_server) = upnp::igd::server(upnp_manager.clone()) {
+                    actix_web::rt::spawn(igd_server);
+                }
+                let upnp_data = web::Data::from(upnp_manager);
+
+                // --- DDNS：跟随端口映射外部接口的地址 ---
+                let ddns_manager = Arc::new(DdnsManager::new("curl", "./ddns.toml", portmap_data.clone().into_inner()));
+                DdnsManager::spawn(ddns_manager.clone());
+                let ddns_data = web::Data::from(ddns_manager);
 
-                if let Err(e) = HttpServer::new(move || {
+                let server = HttpServer::new(move || {
                     App::new()
-                        .app_data(manager_data.clone())
+                        // 最外层：来源不被允许时在 JWT 校验之前返回 403
+                        .wrap(middleware::access::AccessMiddleware)
+                        .app_data(registry_data.clone())
+                        .app_data(rogue_data.clone())
+                        .app_data(dhcpv6_data.clone())
+                        .app_data(inventory_data.clone())
+                        .app_data(dns_data.clone())
                         .app_data(portmap_data.clone())
+                        .app_data(addressing_data.clone())
+                        .app_data(topology_data.clone())
+                        .app_data(route_data.clone())
+                        .app_data(firewall_data.clone())
+                        .app_data(nat_data.clone())
+                        .app_data(upnp_data.clone())
+                        .app_data(ddns_data.clone())
                         .app_data(app_config.clone())
                         // 公开的 API 路由
                         .service(handlers::auth::login)
@@ -136,18 +295,39 @@ fn main() {
                                 .service(handlers::auth::logined)
                                 .service(handlers::auth::change_password)
                                 .service(handlers::udhcpd::service())
-                                .service(handlers::portmap::service()),
+                                .service(handlers::dhcpv6::service())
+                                .service(handlers::dns::service())
+                                .service(handlers::devices::service())
+                                .service(handlers::portmap::service())
+                                .service(handlers::tools::service())
+                                .service(handlers::interfaces::service())
+                                .service(handlers::network::service())
+                                .service(handlers::routes::service())
+                                .service(handlers::firewall::service())
+                                .service(handlers::nat::service())
+                                .service(handlers::management::service())
+                                .service(handlers::upnp::service())
+                                .service(handlers::ddns::service()),
                         )
                         // --- 关键修改：在这里添加静态文件服务 ---
                         // 这个服务应该在所有 API 路由之后注册，以避免冲突
                         .service(fs::Files::new("/", "./static").index_file("index.html"))
-                })
-                .bind(listen_addr)
-                .unwrap()
-                .run()
-                .await
-                {
-                    eprintln!("Web server failed to start: {}", e);
+                });
+                // 记录每个连接的本机地址，修改管理访问限制时用于判断入接口
+                let server = server.on_connect(management_access::record_local_addr);
+                // 每个监听地址都使用 listen_port
+                let port = startup_config.listen_port;
+                let server = startup_config.listen_addresses.iter().try_fold(server, |server, address| {
+                    println!("Starting web server on {} port {}", address, port);
+                    server.bind((address.as_str(), port))
+                });
+                match server {
+                    Ok(server) => {
+                        if let Err(e) = server.run().await {
+                            eprintln!("Web server failed: {}", e);
+                        }
+                    }
+                    Err(e) => eprintln!("Web server failed to start: {}", e),
                 }
             });
         }
@@ -0,0 +1,208 @@
+// src/management_access.rs
+
+use std::any::Any;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::dev::Extensions;
+use actix_web::rt::net::TcpStream;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::firewall_manager::{self, Family};
+use crate::interfaces;
+use crate::ip_cidr::IpCidr;
+
+/// INPUT 中保护管理端口的链
+const MANAGEMENT_CHAIN: &str = "MEOWTAIL_MGMT";
+
+/// 接口地址缓存的有效期，避免每个请求都扫描一遍 /sys/class/net
+const NETWORKS_TTL: Duration = Duration::from_secs(10);
+
+/// 各接口的直连网络
+type Networks = Vec<(String, Vec<IpCidr>)>;
+
+/// 缓存的直连网络，以及读取的时间
+static NETWORKS: Mutex<Option<(Instant, Networks)>> = Mutex::new(None);
+
+/// 连接所访问的本机地址，由 `record_local_addr` 在建立连接时记录
+#[derive(Clone, Copy, Debug)]
+pub struct LocalAddr(pub IpAddr);
+
+/// 用于 `HttpServer::on_connect`。监听 0.0.0.0 时只能从连接本身得到本机地址
+pub fn record_local_addr(connection: &dyn Any, data: &mut Extensions) {
+    if let Some(address) = connection.downcast_ref::<TcpStream>().and_then(|s| s.local_addr().ok()) {
+        data.insert(LocalAddr(address.ip()));
+    }
+}
+
+/// 允许访问管理界面的来源，保存在 meowtail.toml 的 `[management]` 中。
+/// 两个列表都不为空时，请求需要同时满足两者；列表为空表示不限制。
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ManagementAccess {
+    pub allowed_sources: Vec<IpCidr>,
+    /// 客户端所在的直连网络属于这些接口之一
+    pub allowed_interfaces: Vec<String>,
+    /// 同时在 INPUT 中安装规则，拒绝其他来源对管理端口的连接
+    pub firewall: bool,
+}
+
+impl ManagementAccess {
+    pub fn validate(&self) -> Result<()> {
+        let issues: Vec<ValidationIssue> = self
+            .allowed_interfaces
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| !interfaces::is_valid_name(name))
+            .map(|(i, name)| {
+                ValidationIssue::new(
+                    format!("allowed_interfaces[{}]", i),
+                    format!("'{}' is not a valid interface name", name),
+                )
+            })
+            .collect();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        !self.allowed_sources.is_empty() || !self.allowed_interfaces.is_empty()
+    }
+
+    /// 本机发起的连接总是允许
+    pub fn allows(&self, peer: IpAddr) -> bool {
+        let peer = peer.to_canonical();
+        if peer.is_loopback() || !self.is_restricted() {
+            return true;
+        }
+        let source_allowed =
+            self.allowed_sources.is_empty() || self.allowed_sources.iter().any(|cidr| cidr.contains(peer));
+        source_allowed
+            && (self.allowed_interfaces.is_empty()
+                || peer_interface(peer).is_some_and(|name| self.allowed_interfaces.contains(&name)))
+    }
+
+    /// 修改后从 `peer` 到本机 `local` 的连接是否仍然可以访问。防火墙按真正的入接口匹配，
+    /// 这里无法得到入接口，只有客户端所在的直连网络与它访问的本机地址属于同一个接口时，
+    /// 才认为连接是从该接口进来的；其他情况视为会被拒绝
+    pub fn keeps_access(&self, peer: IpAddr, local: Option<IpAddr>) -> bool {
+        let peer = peer.to_canonical();
+        if !self.allows(peer) {
+            return false;
+        }
+        if peer.is_loopback() || !self.firewall || self.allowed_interfaces.is_empty() {
+            return true;
+        }
+        let ingress = peer_interface(peer);
+        ingress.is_some() && local.and_then(|local| address_interface(local.to_canonical())) == ingress
+    }
+}
+
+/// 地址所在的直连网络属于哪个接口；经网关到达的客户端没有对应的接口。
+/// 这里按接口地址的网段推断，不是数据包真正的入接口
+fn peer_interface(peer: IpAddr) -> Option<String> {
+    find_network(|cidrs| cidrs.iter().any(|cidr| cidr.contains(peer)))
+}
+
+/// 拥有本机地址 `address` 的接口
+fn address_interface(address: IpAddr) -> Option<String> {
+    find_network(|cidrs| cidrs.iter().any(|cidr| cidr.addr == address))
+}
+
+/// 第一个直连网络满足 `matches` 的接口，接口列表缓存 `NETWORKS_TTL`
+fn find_network(matches: impl Fn(&[IpCidr]) -> bool) -> Option<String> {
+    let mut cache = NETWORKS.lock().unwrap();
+    let fresh = cache.as_ref().is_some_and(|(read_at, _)| read_at.elapsed() < NETWORKS_TTL);
+    if !fresh {
+        let networks = interfaces::list()
+            .ok()?
+            .into_iter()
+            .map(|interface| {
+                let cidrs = interface
+                    .ipv4
+                    .iter()
+                    .chain(interface.ipv6.iter())
+                    .filter_map(|a| IpCidr::new(a.address, a.prefix_len).ok())
+                    .collect();
+                (interface.name, cidrs)
+            })
+            .collect();
+        *cache = Some((Instant::now(), networks));
+    }
+    let (_, networks) = cache.as_ref()?;
+    networks.iter().find(|(_, cidrs)| matches(cidrs)).map(|(name, _)| name.clone())
+}
+
+/// 按配置安装或删除保护管理端口的 iptables 规则
+pub fn apply_firewall(access: &ManagementAccess, port: u16) -> Result<()> {
+    for family in [Family::V4, Family::V6] {
+        if access.firewall && access.is_restricted() {
+            firewall_manager::restore(family, &render(access, port, family))?;
+            if !firewall_manager::jump_installed(family, "filter", "INPUT", MANAGEMENT_CHAIN) {
+                firewall_manager::run_iptables(family, &["-I", "INPUT", "1", "-j", MANAGEMENT_CHAIN])?;
+            }
+            keep_first(family)?;
+        } else {
+            while firewall_manager::jump_installed(family, "filter", "INPUT", MANAGEMENT_CHAIN) {
+                firewall_manager::run_iptables(family, &["-D", "INPUT", "-j", MANAGEMENT_CHAIN])?;
+            }
+            firewall_manager::remove_chains(family, "filter", |chain| chain == MANAGEMENT_CHAIN);
+        }
+    }
+    Ok(())
+}
+
+/// 把管理链的跳转移回 INPUT 的第一条。其他链（例如 MEOWTAIL_INPUT）也插入到第一条，
+/// 之后安装的会排在前面并先放行管理端口，所以每次应用防火墙后都要调用。
+/// 先插入新的跳转再删除旧的，中间不会失去保护；跳转不存在时什么也不做
+pub(crate) fn keep_first(family: Family) -> Result<()> {
+    match firewall_manager::jump_position(family, "INPUT", MANAGEMENT_CHAIN) {
+        Some(position) if position > 1 => {
+            firewall_manager::run_iptables(family, &["-I", "INPUT", "1", "-j", MANAGEMENT_CHAIN])?;
+            firewall_manager::run_iptables(family, &["-D", "INPUT", &(position + 1).to_string()])
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 允许的来源 RETURN 回 INPUT 继续匹配，其余对管理端口的连接丢弃
+fn render(access: &ManagementAccess, port: u16, family: Family) -> String {
+    let mut out = format!("*filter\n:{} - [0:0]\n", MANAGEMENT_CHAIN);
+    let allow = |matches: &str| format!("-A {} -p tcp --dport {}{} -j RETURN\n", MANAGEMENT_CHAIN, port, matches);
+    out.push_str(&allow(" -i lo"));
+
+    let sources: Vec<Option<&IpCidr>> = if access.allowed_sources.is_empty() {
+        vec![None]
+    } else {
+        access
+            .allowed_sources
+            .iter()
+            .filter(|cidr| cidr.is_ipv4() == (family == Family::V4))
+            .map(Some)
+            .collect()
+    };
+    let interfaces: Vec<Option<&String>> = if access.allowed_interfaces.is_empty() {
+        vec![None]
+    } else {
+        access.allowed_interfaces.iter().map(Some).collect()
+    };
+    for source in &sources {
+        for interface in &interfaces {
+            let mut matches = String::new();
+            if let Some(interface) = interface {
+                matches.push_str(&format!(" -i {}", interface));
+            }
+            if let Some(source) = source {
+                matches.push_str(&format!(" -s {}", source));
+            }
+            out.push_str(&allow(&matches));
+        }
+    }
+    out.push_str(&format!("-A {} -p tcp --dport {} -j DROP\nCOMMIT\n", MANAGEMENT_CHAIN, port));
+    out
+}
@@ -0,0 +1,78 @@
+// src/middleware/access.rs
+
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, web,
+};
+use futures_util::future::LocalBoxFuture;
+use crate::config::AppConfig;
+
+/// 按 meowtail.toml 的 `[management]` 限制客户端来源，在 JWT 校验之前执行，也覆盖 /login 与静态文件
+pub struct AccessMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for AccessMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AccessMiddlewareService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessMiddlewareService { service: Rc::new(service) }))
+    }
+}
+
+pub struct AccessMiddlewareService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let config = req.app_data::<web::Data<AppConfig>>().cloned();
+        let peer = req.peer_addr().map(|addr| addr.ip());
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let config = match config {
+                Some(c) => c,
+                None => {
+                    eprintln!("Critical: AppConfig not found in application state.");
+                    return Err(actix_web::error::ErrorInternalServerError("Server configuration error."));
+                }
+            };
+
+            let access = config.lock().unwrap().management.clone();
+            if !access.is_restricted() {
+                return service.call(req).await;
+            }
+            // 查找客户端所在的接口需要读取 /sys，放到阻塞线程池中
+            let allowed = match peer {
+                Some(peer) => web::block(move || access.allows(peer)).await.unwrap_or(false),
+                None => false,
+            };
+            if allowed {
+                service.call(req).await
+            } else {
+                Err(actix_web::error::ErrorForbidden("Management access is not allowed from this address."))
+            }
+        })
+    }
+}
@@ -1,3 +1,4 @@
 // src/middleware/mod.rs
 
-pub mod jwt;
\ No newline at end of file
+pub mod jwt;
+pub mod access;
\ No newline at end of file
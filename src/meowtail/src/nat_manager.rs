@@ -0,0 +1,403 @@
+// src/nat_manager.rs
+
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::firewall_manager::{self, Family};
+use crate::fs_util::write_atomic;
+use crate::interfaces;
+use crate::ip_cidr::IpCidr;
+
+/// nat 表中从 POSTROUTING 跳转到的链
+const POSTROUTING_CHAIN: &str = "MEOWTAIL_POSTROUTING";
+const IP_FORWARD_PATH: &str = "/proc/sys/net/ipv4/ip_forward";
+
+/// 出站地址转换方式
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum NatAction {
+    /// 使用出口接口当前的地址，适合 DHCP/PPPoE 获取地址的 WAN
+    Masquerade,
+    /// 转换为固定地址
+    Snat { to: Ipv4Addr },
+}
+
+/// 一条出站 NAT 规则：从 `sources` 经 `out_interface` 发出的流量做地址转换
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NatRule {
+    /// 由 meowtail 分配
+    #[serde(default)]
+    pub id: u32,
+    /// 出口接口，可用 iptables 的 `+` 通配，例如 `ppp+`
+    pub out_interface: String,
+    /// 只转换这些源网段，为空时转换所有流量
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<IpCidr>,
+    #[serde(flatten)]
+    pub action: NatAction,
+}
+
+impl NatRule {
+    fn comment(&self) -> String {
+        format!("nat {}", self.id)
+    }
+}
+
+/// nat.toml 的内容
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NatConfig {
+    /// 设置 net.ipv4.ip_forward，未设置时保持内核当前的值
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_forward: Option<bool>,
+    pub rules: Vec<NatRule>,
+}
+
+impl NatConfig {
+    pub fn validate(&self) -> Result<()> {
+        let mut issues = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            let field = |name: &str| format!("rules[{}].{}", i, name);
+            if self.rules[..i].iter().any(|r| r.id == rule.id) {
+                issues.push(ValidationIssue::new(field("id"), format!("Rule ID {} is used more than once", rule.id)));
+            }
+            let interface = &rule.out_interface;
+            if !interfaces::is_valid_name(interface.strip_suffix('+').unwrap_or(interface)) {
+                issues.push(ValidationIssue::new(
+                    field("out_interface"),
+                    format!("'{}' is not a valid interface name", interface),
+                ));
+            }
+            for (j, source) in rule.sources.iter().enumerate() {
+                if !source.is_ipv4() {
+                    issues.push(ValidationIssue::new(format!("rules[{}].sources[{}]", i, j), "Only IPv4 networks can be translated"));
+                }
+            }
+            if let NatAction::Snat { to } = &rule.action {
+                if to.is_unspecified() || to.is_broadcast() || to.is_multicast() {
+                    issues.push(ValidationIssue::new(field("to"), format!("{} cannot be used as a source address", to)));
+                }
+            }
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+
+    /// 渲染为 `iptables-restore --noflush` 的输入，只包含 meowtail 的 nat 链
+    pub fn render(&self) -> String {
+        let mut out = format!("*nat\n:{} - [0:0]\n", POSTROUTING_CHAIN);
+        for rule in &self.rules {
+            let target = match &rule.action {
+                NatAction::Masquerade => "MASQUERADE".to_string(),
+                NatAction::Snat { to } => format!("SNAT --to-source {}", to),
+            };
+            // 没有源网段时渲染一条不限制源地址的规则
+            let sources: Vec<Option<&IpCidr>> = if rule.sources.is_empty() {
+                vec![None]
+            } else {
+                rule.sources.iter().map(Some).collect()
+            };
+            for source in sources {
+                let mut line = format!("-A {}", POSTROUTING_CHAIN);
+                if let Some(source) = source {
+                    line.push_str(&format!(" -s {}", source));
+                }
+                line.push_str(&format!(" -o {} -m comment --comment \"{}\" -j {}\n", rule.out_interface, rule.comment(), target));
+                out.push_str(&line);
+            }
+        }
+        out.push_str("COMMIT\n");
+        out
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NatRuleStatus {
+    #[serde(flatten)]
+    pub rule: NatRule,
+    /// 出口接口是否存在，通配的接口名总是视为存在
+    pub interface_present: bool,
+    /// 规则匹配过的连接数与字节数（只统计每个连接的第一个包）
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NatStatus {
+    /// 内核当前的 net.ipv4.ip_forward
+    pub ip_forward: bool,
+    /// POSTROUTING 中是否有跳转到 meowtail 链的规则
+    pub installed: bool,
+    pub rules: Vec<NatRuleStatus>,
+}
+
+/// 管理出站 NAT 与 IPv4 转发开关，配置保存在 nat.toml
+pub struct NatManager {
+    config_path: PathBuf,
+    config_lock: Mutex<()>,
+}
+
+impl NatManager {
+    pub fn new<P: Into<PathBuf>>(config_path: P) -> Self {
+        NatManager {
+            config_path: config_path.into(),
+            config_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn read_config(&self) -> Result<NatConfig> {
+        let content = match fs::read_to_string(&self.config_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(NatConfig::default()),
+            Err(e) => return Err(e.into()),
+        };
+        toml::from_str(&content).map_err(|e| AppError::ConfigParse(format!("{:?}: {}", self.config_path, e)))
+    }
+
+    fn write_config(&self, config: &NatConfig) -> Result<()> {
+        let content = toml::to_string_pretty(config).map_err(|e| AppError::Process(e.to_string()))?;
+        write_atomic(&self.config_path, &content)
+    }
+
+    /// 在锁内修改配置：校验、应用成功后再保存。应用或保存失败时恢复原来的规则与转发开关，
+    /// 内核状态与 nat.toml 保持一致
+    pub fn update_config<T, F: FnOnce(&mut NatConfig) -> Result<T>>(&self, update: F) -> Result<T> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let previous = self.read_config()?;
+        let mut config = previous.clone();
+        let result = update(&mut config)?;
+        config.validate()?;
+        let ip_forward = fs::read_to_string(IP_FORWARD_PATH).ok();
+        if let Err(e) = apply(&config).and_then(|_| self.write_config(&config)) {
+            if let Err(e) = apply(&previous) {
+                eprintln!("[nat] Failed to restore the previous rules: {}", e);
+            }
+            if let Some(value) = ip_forward {
+                let _ = fs::write(IP_FORWARD_PATH, value);
+            }
+            return Err(e);
+        }
+        Ok(result)
+    }
+
+    pub fn replace_config(&self, config: NatConfig) -> Result<()> {
+        self.update_config(|current| {
+            *current = config;
+            Ok(())
+        })
+    }
+
+    pub fn set_ip_forward(&self, enabled: bool) -> Result<()> {
+        self.update_config(|c| {
+            c.ip_forward = Some(enabled);
+            Ok(())
+        })
+    }
+
+    /// 添加规则并返回分配的 ID
+    pub fn add_rule(&self, mut rule: NatRule) -> Result<NatRule> {
+        self.update_config(|c| {
+            rule.id = c.rules.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+            c.rules.push(rule.clone());
+            Ok(rule)
+        })
+    }
+
+    pub fn update_rule(&self, id: u32, mut rule: NatRule) -> Result<NatRule> {
+        rule.id = id;
+        self.update_config(|c| {
+            let existing = c
+                .rules
+                .iter_mut()
+                .find(|r| r.id == id)
+                .ok_or_else(|| AppError::NotFound(format!("NAT rule {}", id)))?;
+            *existing = rule.clone();
+            Ok(rule)
+        })
+    }
+
+    pub fn remove_rule(&self, id: u32) -> Result<()> {
+        self.update_config(|c| {
+            if !c.rules.iter().any(|r| r.id == id) {
+                return Err(AppError::NotFound(format!("NAT rule {}", id)));
+            }
+            c.rules.retain(|r| r.id != id);
+            Ok(())
+        })
+    }
+
+    pub fn status(&self) -> Result<NatStatus> {
+        let config = self.read_config()?;
+        let counters = rule_counters();
+        let rules = config
+            .rules
+            .into_iter()
+            .map(|rule| {
+                let (packets, bytes) = counters
+                    .iter()
+                    .filter(|(comment, _, _)| *comment == rule.comment())
+                    .fold((0, 0), |(p, b), (_, packets, bytes)| (p + packets, b + bytes));
+                let interface_present = rule.out_interface.ends_with('+')
+                    || interfaces::check(&rule.out_interface).is_ok();
+                NatRuleStatus {
+                    rule,
+                    interface_present,
+                    packets,
+                    bytes,
+                }
+            })
+            .collect();
+        Ok(NatStatus {
+            ip_forward: fs::read_to_string(IP_FORWARD_PATH).is_ok_and(|v| v.trim() == "1"),
+            installed: firewall_manager::jump_installed(Family::V4, "nat", "POSTROUTING", POSTROUTING_CHAIN),
+            rules,
+        })
+    }
+
+    /// 启动时按 nat.toml 设置转发开关并重建规则
+    pub fn apply_all(&self) -> Result<()> {
+        let config = self.read_config()?;
+        config.validate()?;
+        apply(&config)
+    }
+}
+
+fn apply(config: &NatConfig) -> Result<()> {
+    if let Some(enabled) = config.ip_forward {
+        fs::write(IP_FORWARD_PATH, if enabled { "1\n" } else { "0\n" })?;
+    }
+    firewall_manager::restore(Family::V4, &config.render())?;
+    if !firewall_manager::jump_installed(Family::V4, "nat", "POSTROUTING", POSTROUTING_CHAIN) {
+        firewall_manager::run_iptables(Family::V4, &["-t", "nat", "-A", "POSTROUTING", "-j", POSTROUTING_CHAIN])?;
+    }
+    Ok(())
+}
+
+/// 从 `iptables -t nat -S -v` 中读出每条规则的 comment 与计数
+fn rule_counters() -> Vec<(String, u64, u64)> {
+    let Ok(output) = Command::new("iptables")
+        .args(["-t", "nat", "-S", POSTROUTING_CHAIN, "-v"])
+        .output()
+    else {
+        return Vec::new();
+    };
+    parse_counters(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// `-A ... --comment "nat 1" -c <packets> <bytes> -j ...` 形式的行，没有 comment 的规则被跳过
+fn parse_counters(rules: &str) -> Vec<(String, u64, u64)> {
+    rules
+        .lines()
+        .filter_map(|line| {
+            let comment = line.split("--comment \"").nth(1)?.split('"').next()?;
+            let mut counters = line.split(" -c ").nth(1)?.split_whitespace();
+            let packets = counters.next()?.parse().ok()?;
+            let bytes = counters.next()?.parse().ok()?;
+            Some((comment.to_string(), packets, bytes))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: u32, out_interface: &str, sources: &[&str], action: NatAction) -> NatRule {
+        NatRule {
+            id,
+            out_interface: out_interface.to_string(),
+            sources: sources.iter().map(|s| s.parse().unwrap()).collect(),
+            action,
+        }
+    }
+
+    fn fields(config: &NatConfig) -> Vec<String> {
+        match config.validate() {
+            Ok(()) => vec![],
+            Err(AppError::Validation(issues)) => issues.into_iter().map(|i| i.field).collect(),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn renders_one_line_per_source() {
+        let config = NatConfig {
+            ip_forward: Some(true),
+            rules: vec![
+                rule(1, "ppp+", &[], NatAction::Masquerade),
+                rule(2, "eth0", &["192.168.1.0/24", "192.168.2.0/24"], NatAction::Snat { to: Ipv4Addr::new(203, 0, 113, 7) }),
+            ],
+        };
+        assert_eq!(
+            config.render(),
+            "*nat\n\
+             :MEOWTAIL_POSTROUTING - [0:0]\n\
+             -A MEOWTAIL_POSTROUTING -o ppp+ -m comment --comment \"nat 1\" -j MASQUERADE\n\
+             -A MEOWTAIL_POSTROUTING -s 192.168.1.0/24 -o eth0 -m comment --comment \"nat 2\" -j SNAT --to-source 203.0.113.7\n\
+             -A MEOWTAIL_POSTROUTING -s 192.168.2.0/24 -o eth0 -m comment --comment \"nat 2\" -j SNAT --to-source 203.0.113.7\n\
+             COMMIT\n"
+        );
+    }
+
+    #[test]
+    fn empty_config_flushes_the_chain() {
+        assert_eq!(NatConfig::default().render(), "*nat\n:MEOWTAIL_POSTROUTING - [0:0]\nCOMMIT\n");
+    }
+
+    #[test]
+    fn accepts_wildcard_interfaces() {
+        let config = NatConfig {
+            ip_forward: None,
+            rules: vec![rule(1, "ppp+", &[], NatAction::Masquerade), rule(2, "eth0.10", &["10.0.0.0/8"], NatAction::Masquerade)],
+        };
+        assert!(fields(&config).is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        let config = NatConfig {
+            ip_forward: None,
+            rules: vec![
+                rule(1, "+", &[], NatAction::Masquerade),
+                rule(1, "eth0", &["fd00::/64"], NatAction::Masquerade),
+                rule(3, "eth/0", &[], NatAction::Snat { to: Ipv4Addr::UNSPECIFIED }),
+                rule(4, "eth0", &[], NatAction::Snat { to: Ipv4Addr::BROADCAST }),
+                rule(5, "eth0", &[], NatAction::Snat { to: Ipv4Addr::new(224, 0, 0, 1) }),
+            ],
+        };
+        assert_eq!(
+            fields(&config),
+            vec![
+                "rules[0].out_interface",
+                "rules[1].id",
+                "rules[1].sources[0]",
+                "rules[2].out_interface",
+                "rules[2].to",
+                "rules[3].to",
+                "rules[4].to",
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_rule_counters() {
+        let output = "-N MEOWTAIL_POSTROUTING\n\
+                      -A MEOWTAIL_POSTROUTING -o ppp+ -m comment --comment \"nat 1\" -c 12 3456 -j MASQUERADE\n\
+                      -A MEOWTAIL_POSTROUTING -s 192.168.1.0/24 -o eth0 -m comment --comment \"nat 2\" -c 0 0 -j SNAT --to-source 203.0.113.7\n\
+                      -A MEOWTAIL_POSTROUTING -o eth1 -c 5 500 -j MASQUERADE\n";
+        assert_eq!(
+            parse_counters(output),
+            vec![("nat 1".to_string(), 12, 3456), ("nat 2".to_string(), 0, 0)]
+        );
+    }
+}
@@ -0,0 +1,104 @@
+// src/oui.rs
+
+/// 内置的 OUI（MAC 地址前 3 字节）到厂商名的对照表，只收录家庭与小型办公网络中常见的厂商
+const OUI_TABLE: &[([u8; 3], &str)] = &[
+    ([0x00, 0x03, 0x93], "Apple"),
+    ([0x00, 0x0D, 0x93], "Apple"),
+    ([0x00, 0x16, 0xCB], "Apple"),
+    ([0x00, 0x1B, 0x63], "Apple"),
+    ([0x00, 0x1C, 0xB3], "Apple"),
+    ([0x3C, 0x07, 0x54], "Apple"),
+    ([0xA4, 0x5E, 0x60], "Apple"),
+    ([0xAC, 0xBC, 0x32], "Apple"),
+    ([0xF0, 0x18, 0x98], "Apple"),
+    ([0x00, 0x12, 0xFB], "Samsung"),
+    ([0x00, 0x1A, 0x11], "Google"),
+    ([0x3C, 0x5A, 0xB4], "Google"),
+    ([0xF4, 0xF5, 0xD8], "Google"),
+    ([0x18, 0xB4, 0x30], "Nest Labs"),
+    ([0x44, 0x65, 0x0D], "Amazon"),
+    ([0x74, 0xC2, 0x46], "Amazon"),
+    ([0xF0, 0x27, 0x2D], "Amazon"),
+    ([0x00, 0x15, 0x5D], "Microsoft Hyper-V"),
+    ([0x00, 0x14, 0x22], "Dell"),
+    ([0x00, 0x1A, 0xA0], "Dell"),
+    ([0x00, 0x1E, 0xC9], "Dell"),
+    ([0x00, 0x26, 0xB9], "Dell"),
+    ([0xF8, 0xBC, 0x12], "Dell"),
+    ([0x3C, 0xD9, 0x2B], "Hewlett Packard"),
+    ([0x00, 0x1B, 0x21], "Intel"),
+    ([0x00, 0xE0, 0x4C], "Realtek"),
+    ([0x00, 0x04, 0x4B], "NVIDIA"),
+    ([0x00, 0x25, 0x90], "Super Micro"),
+    ([0x00, 0x30, 0x48], "Super Micro"),
+    ([0x00, 0x0D, 0xB9], "PC Engines"),
+    ([0xB8, 0x27, 0xEB], "Raspberry Pi"),
+    ([0xDC, 0xA6, 0x32], "Raspberry Pi"),
+    ([0xE4, 0x5F, 0x01], "Raspberry Pi"),
+    ([0x28, 0xCD, 0xC1], "Raspberry Pi"),
+    ([0x18, 0xFE, 0x34], "Espressif"),
+    ([0x24, 0x0A, 0xC4], "Espressif"),
+    ([0x30, 0xAE, 0xA4], "Espressif"),
+    ([0x60, 0x01, 0x94], "Espressif"),
+    ([0x84, 0xF3, 0xEB], "Espressif"),
+    ([0xA4, 0xCF, 0x12], "Espressif"),
+    ([0x00, 0x17, 0x88], "Philips Lighting"),
+    ([0x00, 0x0E, 0x58], "Sonos"),
+    ([0x5C, 0xAA, 0xFD], "Sonos"),
+    ([0x00, 0x1F, 0xA7], "Sony Interactive Entertainment"),
+    ([0x00, 0x09, 0xBF], "Nintendo"),
+    ([0x00, 0x17, 0xAB], "Nintendo"),
+    ([0x00, 0x9E, 0xC8], "Xiaomi"),
+    ([0x28, 0x6C, 0x07], "Xiaomi"),
+    ([0x64, 0x09, 0x80], "Xiaomi"),
+    ([0x00, 0xE0, 0xFC], "Huawei"),
+    ([0x00, 0x1D, 0x0F], "TP-Link"),
+    ([0x50, 0xC7, 0xBF], "TP-Link"),
+    ([0xF4, 0xF2, 0x6D], "TP-Link"),
+    ([0x00, 0x1C, 0xF0], "D-Link"),
+    ([0x00, 0x24, 0x01], "D-Link"),
+    ([0x00, 0x14, 0xBF], "Linksys"),
+    ([0x00, 0x09, 0x5B], "Netgear"),
+    ([0x00, 0x0F, 0xB5], "Netgear"),
+    ([0x00, 0x1E, 0x2A], "Netgear"),
+    ([0x00, 0x15, 0x6D], "Ubiquiti"),
+    ([0x24, 0xA4, 0x3C], "Ubiquiti"),
+    ([0x44, 0xD9, 0xE7], "Ubiquiti"),
+    ([0x80, 0x2A, 0xA8], "Ubiquiti"),
+    ([0x00, 0x0C, 0x42], "MikroTik"),
+    ([0x4C, 0x5E, 0x0C], "MikroTik"),
+    ([0x00, 0x18, 0x0A], "Cisco Meraki"),
+    ([0x00, 0x11, 0x32], "Synology"),
+    ([0x00, 0x08, 0x9B], "QNAP"),
+    ([0x24, 0x5E, 0xBE], "QNAP"),
+    ([0x00, 0x90, 0xA9], "Western Digital"),
+    ([0x00, 0x05, 0x69], "VMware"),
+    ([0x00, 0x0C, 0x29], "VMware"),
+    ([0x00, 0x50, 0x56], "VMware"),
+    ([0x08, 0x00, 0x27], "VirtualBox"),
+    ([0x00, 0x1C, 0x42], "Parallels"),
+    ([0x00, 0x16, 0x3E], "Xen"),
+    ([0x52, 0x54, 0x00], "QEMU/KVM"),
+];
+
+/// 按 MAC 地址查找厂商。本地管理的地址（如手机的随机 MAC）除表中列出的虚拟化前缀外没有厂商
+pub fn lookup(mac: &[u8; 6]) -> Option<&'static str> {
+    let prefix = [mac[0], mac[1], mac[2]];
+    OUI_TABLE
+        .iter()
+        .find(|(oui, _)| *oui == prefix)
+        .map(|(_, vendor)| *vendor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locally_administered_has_no_vendor() {
+        assert_eq!(lookup(&[0x00, 0x0C, 0x29, 0x01, 0x02, 0x03]), Some("VMware"));
+        // QEMU 的前缀本身是本地管理地址，但在表中
+        assert_eq!(lookup(&[0x52, 0x54, 0x00, 0x01, 0x02, 0x03]), Some("QEMU/KVM"));
+        assert_eq!(lookup(&[0xDA, 0x12, 0x34, 0x56, 0x78, 0x9A]), None);
+    }
+}
@@ -1,10 +1,16 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::{self, Write};
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::sync::Mutex;
 
+use crate::interfaces;
+
+/// 租约规则的 iptables 注释
+const LEASE_COMMENT: &str = "meowtail-lease";
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct PortMapRule {
     pub protocol: String,
@@ -19,13 +25,42 @@ pub struct PortMapConfig {
     pub rules: Vec<PortMapRule>,
 }
 
+/// 租约由哪个协议创建
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LeaseOrigin {
+    Upnp,
+    NatPmp,
+    Pcp,
+}
+
+/// 客户端通过 UPnP/NAT-PMP/PCP 申请的映射，只保存在内存中，到期后自动删除
+#[derive(Debug, Clone, Serialize)]
+pub struct PortMapLease {
+    #[serde(flatten)]
+    pub rule: PortMapRule,
+    pub description: String,
+    pub origin: LeaseOrigin,
+    /// 为空表示不过期
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl PortMapRule {
+    fn same_external(&self, other: &PortMapRule) -> bool {
+        self.protocol.eq_ignore_ascii_case(&other.protocol) && self.external_port == other.external_port
+    }
+}
+
 pub struct PortMapManager {
     config: Mutex<PortMapConfig>,
+    // 与手动配置的 rules 分开，不写入 portmap.toml
+    leases: Mutex<Vec<PortMapLease>>,
     file_path: PathBuf,
+    iptables_path: String,
 }
 
 impl PortMapManager {
-    pub fn new<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+    pub fn new<P: Into<PathBuf>>(iptables_path: &str, path: P) -> io::Result<Self> {
         let file_path = path.into();
         let config = if file_path.exists() {
             let content = fs::read_to_string(&file_path)?;
@@ -33,7 +68,12 @@ impl PortMapManager {
         } else {
             PortMapConfig::default()
         };
-        Ok(Self { config: Mutex::new(config), file_path })
+        Ok(Self {
+            config: Mutex::new(config),
+            leases: Mutex::new(Vec::new()),
+            file_path,
+            iptables_path: iptables_path.to_string(),
+        })
     }
 
     fn save(&self) -> io::Result<()> {
@@ -44,55 +84,196 @@ impl PortMapManager {
         Ok(())
     }
 
-    fn run_iptables(args: &[&str]) -> io::Result<()> {
-        Command::new("iptables").args(args).status()?;
+    fn run_iptables(&self, args: &[String]) -> io::Result<()> {
+        let output = Command::new(&self.iptables_path).args(args).output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "iptables {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
         Ok(())
     }
 
-    fn apply_rule_internal(rule: &PortMapRule, iface: &str, action: &str) -> io::Result<()> {
+    /// 用 `-C` 检查 `-D` 参数描述的规则是否存在
+    fn rule_exists(&self, delete_args: &[String]) -> bool {
+        let args: Vec<&str> = delete_args.iter().map(|a| if a == "-D" { "-C" } else { a.as_str() }).collect();
+        Command::new(&self.iptables_path)
+            .args(args)
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    /// PREROUTING 与 POSTROUTING 两条规则的参数；租约的规则带有注释，启动时据此清理
+    fn rule_args(rule: &PortMapRule, iface: &str, action: &str, lease: bool) -> [Vec<String>; 2] {
+        let comment = if lease { vec!["-m", "comment", "--comment", LEASE_COMMENT] } else { vec![] };
+        let external_port = rule.external_port.to_string();
+        let internal_port = rule.internal_port.to_string();
+        let destination = format!("{}:{}", rule.internal_ip, rule.internal_port);
         let prerouting = [
             "-t", "nat", action, "PREROUTING",
             "-i", iface,
             "-p", &rule.protocol,
-            "--dport", &rule.external_port.to_string(),
-            "-j", "DNAT",
-            "--to-destination",
-            &format!("{}:{}", rule.internal_ip, rule.internal_port),
-        ];
-        Self::run_iptables(&prerouting)?;
+            "--dport", &external_port,
+        ]
+        .into_iter()
+        .chain(comment.iter().copied())
+        .chain(["-j", "DNAT", "--to-destination", &destination])
+        .map(str::to_string)
+        .collect();
         let postrouting = [
             "-t", "nat", action, "POSTROUTING",
             "-o", iface,
             "-p", &rule.protocol,
-            "--dport", &rule.internal_port.to_string(),
+            "--dport", &internal_port,
             "-d", &rule.internal_ip,
-            "-j", "MASQUERADE",
-        ];
-        Self::run_iptables(&postrouting)
+        ]
+        .into_iter()
+        .chain(comment.iter().copied())
+        .chain(["-j", "MASQUERADE"])
+        .map(str::to_string)
+        .collect();
+        [prerouting, postrouting]
+    }
+
+    fn apply_rule_internal(&self, rule: &PortMapRule, iface: &str, action: &str, lease: bool) -> io::Result<()> {
+        let [prerouting, postrouting] = Self::rule_args(rule, iface, action, lease);
+        if action == "-D" {
+            // 规则可能已经不存在（重启后、手动清空或更换接口后），只删除还在的
+            for args in [prerouting, postrouting] {
+                if self.rule_exists(&args) {
+                    self.run_iptables(&args)?;
+                }
+            }
+            return Ok(());
+        }
+        self.run_iptables(&prerouting)?;
+        if let Err(e) = self.run_iptables(&postrouting) {
+            // 不留下没有记录的 DNAT 规则
+            let [prerouting, _] = Self::rule_args(rule, iface, "-D", lease);
+            let _ = self.run_iptables(&prerouting);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// 删除所有带租约注释的规则。租约只保存在内存中，上次运行留下的规则已经没有记录
+    fn remove_lease_rules(&self) {
+        for chain in ["PREROUTING", "POSTROUTING"] {
+            let Ok(output) = Command::new(&self.iptables_path).args(["-t", "nat", "-S", chain]).output() else {
+                continue;
+            };
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for rule in stdout
+                .lines()
+                .filter(|line| line.split_whitespace().any(|w| w == LEASE_COMMENT))
+                .filter_map(|line| line.strip_prefix("-A "))
+            {
+                let args: Vec<String> = ["-t", "nat", "-D"]
+                    .into_iter()
+                    .chain(rule.split_whitespace())
+                    .map(str::to_string)
+                    .collect();
+                if let Err(e) = self.run_iptables(&args) {
+                    eprintln!("[portmap] {}", e);
+                }
+            }
+        }
     }
 
     pub fn apply_rule(&self, rule: &PortMapRule) -> io::Result<()> {
         let cfg = self.config.lock().unwrap();
-        Self::apply_rule_internal(rule, &cfg.external_interface, "-A")
+        self.apply_rule_internal(rule, &cfg.external_interface, "-A", false)
     }
 
     pub fn remove_rule(&self, rule: &PortMapRule) -> io::Result<()> {
         let cfg = self.config.lock().unwrap();
-        Self::apply_rule_internal(rule, &cfg.external_interface, "-D")
+        self.apply_rule_internal(rule, &cfg.external_interface, "-D", false)
     }
 
+    /// 安装所有规则与租约；先清除残留的租约规则，启动时不会留下无人管理的映射
     pub fn apply_all(&self) -> io::Result<()> {
         let cfg = self.config.lock().unwrap();
+        let leases = self.leases.lock().unwrap();
+        self.remove_lease_rules();
         for r in &cfg.rules {
-            let _ = Self::apply_rule_internal(r, &cfg.external_interface, "-A");
+            let _ = self.apply_rule_internal(r, &cfg.external_interface, "-A", false);
+        }
+        for lease in leases.iter() {
+            let _ = self.apply_rule_internal(&lease.rule, &cfg.external_interface, "-A", true);
+        }
+        Ok(())
+    }
+
+    /// 添加或续期租约。同一内部地址再次申请同一外部端口时替换原租约，
+    /// 端口被手动规则或其他地址占用时返回 AlreadyExists
+    pub fn add_lease(&self, lease: PortMapLease) -> io::Result<()> {
+        let cfg = self.config.lock().unwrap();
+        let mut leases = self.leases.lock().unwrap();
+        if cfg.rules.iter().any(|r| r.same_external(&lease.rule)) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "port is used by a configured rule"));
+        }
+        if let Some(pos) = leases.iter().position(|l| l.rule.same_external(&lease.rule)) {
+            if leases[pos].rule.internal_ip != lease.rule.internal_ip {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, "port is leased to another client"));
+            }
+            if leases[pos].rule != lease.rule {
+                self.apply_rule_internal(&leases[pos].rule, &cfg.external_interface, "-D", true)?;
+                self.apply_rule_internal(&lease.rule, &cfg.external_interface, "-A", true)?;
+            }
+            leases[pos] = lease;
+            return Ok(());
         }
+        self.apply_rule_internal(&lease.rule, &cfg.external_interface, "-A", true)?;
+        leases.push(lease);
         Ok(())
     }
 
+    /// 删除租约并返回它，不存在时返回 None
+    pub fn remove_lease(&self, protocol: &str, external_port: u16) -> io::Result<Option<PortMapLease>> {
+        let cfg = self.config.lock().unwrap();
+        let mut leases = self.leases.lock().unwrap();
+        let Some(pos) = leases
+            .iter()
+            .position(|l| l.rule.protocol.eq_ignore_ascii_case(protocol) && l.rule.external_port == external_port)
+        else {
+            return Ok(None);
+        };
+        self.apply_rule_internal(&leases[pos].rule, &cfg.external_interface, "-D", true)?;
+        Ok(Some(leases.remove(pos)))
+    }
+
+    pub fn leases(&self) -> Vec<PortMapLease> {
+        self.leases.lock().unwrap().clone()
+    }
+
+    /// 删除已到期的租约，返回被删除的租约
+    pub fn expire_leases(&self) -> Vec<PortMapLease> {
+        let cfg = self.config.lock().unwrap();
+        let mut leases = self.leases.lock().unwrap();
+        let now = Utc::now();
+        let (expired, kept): (Vec<_>, Vec<_>) =
+            leases.drain(..).partition(|l| l.expires_at.is_some_and(|t| t <= now));
+        *leases = kept;
+        for lease in &expired {
+            let _ = self.apply_rule_internal(&lease.rule, &cfg.external_interface, "-D", true);
+        }
+        expired
+    }
+
+    /// 外部端口已被其他规则或租约占用时返回 AlreadyExists，需要先删除租约
     pub fn add_rule(&self, rule: PortMapRule) -> io::Result<()> {
         {
             let mut cfg = self.config.lock().unwrap();
-            Self::apply_rule_internal(&rule, &cfg.external_interface, "-A")?;
+            if cfg.rules.iter().any(|r| r.same_external(&rule)) {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, "port is used by a configured rule"));
+            }
+            if self.leases.lock().unwrap().iter().any(|l| l.rule.same_external(&rule)) {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, "port is leased to a client"));
+            }
+            self.apply_rule_internal(&rule, &cfg.external_interface, "-A", false)?;
             cfg.rules.push(rule);
         }
         self.save()
@@ -102,7 +283,7 @@ impl PortMapManager {
         {
             let mut cfg = self.config.lock().unwrap();
             if let Some(pos) = cfg.rules.iter().position(|r| r == &rule) {
-                Self::apply_rule_internal(&rule, &cfg.external_interface, "-D")?;
+                self.apply_rule_internal(&rule, &cfg.external_interface, "-D", false)?;
                 cfg.rules.remove(pos);
             }
         }
@@ -110,6 +291,7 @@ impl PortMapManager {
     }
 
     pub fn set_interface(&self, iface: String) -> io::Result<()> {
+        interfaces::check(&iface)?;
         let old_iface: String;
         let rules_clone: Vec<PortMapRule>;
 
@@ -122,6 +304,7 @@ impl PortMapManager {
             }
 
             // 2. Store the old interface name and a copy of the rules before changing them.
+            //    Lease rules are flushed by apply_all below.
             old_iface = cfg.external_interface.clone();
             rules_clone = cfg.rules.clone();
 
@@ -135,7 +318,7 @@ impl PortMapManager {
             for rule in &rules_clone {
                 // Ignore errors during removal, as a rule might already be gone.
                 // This makes the cleanup process more robust.
-                let _ = Self::apply_rule_internal(rule, &old_iface, "-D");
+                let _ = self.apply_rule_internal(rule, &old_iface, "-D", false);
             }
         }
 
@@ -150,3 +333,69 @@ impl PortMapManager {
         self.config.lock().unwrap().clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            TempFile(std::env::temp_dir().join(format!("meowtail-portmap-{}-{}", std::process::id(), name)))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn rule(protocol: &str, external_port: u16, internal_ip: &str) -> PortMapRule {
+        PortMapRule {
+            protocol: protocol.to_string(),
+            external_port,
+            internal_ip: internal_ip.to_string(),
+            internal_port: 80,
+        }
+    }
+
+    #[test]
+    fn add_rule_refuses_ports_in_use() {
+        let file = TempFile::new("in-use");
+        let manager = PortMapManager::new("iptables", &file.0).unwrap();
+        manager.config.lock().unwrap().rules.push(rule("tcp", 8080, "192.168.1.10"));
+        manager.leases.lock().unwrap().push(PortMapLease {
+            rule: rule("udp", 9000, "192.168.1.20"),
+            description: "game".to_string(),
+            origin: LeaseOrigin::Upnp,
+            expires_at: None,
+        });
+
+        let err = manager.add_rule(rule("TCP", 8080, "192.168.1.30")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        let err = manager.add_rule(rule("udp", 9000, "192.168.1.30")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(manager.config().rules.len(), 1);
+        assert!(!file.0.exists());
+    }
+
+    #[test]
+    fn lease_rules_carry_the_comment() {
+        let lease = rule("tcp", 8080, "192.168.1.10");
+        let [prerouting, postrouting] = PortMapManager::rule_args(&lease, "eth0", "-A", true);
+        assert_eq!(
+            prerouting.join(" "),
+            "-t nat -A PREROUTING -i eth0 -p tcp --dport 8080 -m comment --comment meowtail-lease \
+             -j DNAT --to-destination 192.168.1.10:80"
+        );
+        assert_eq!(
+            postrouting.join(" "),
+            "-t nat -A POSTROUTING -o eth0 -p tcp --dport 80 -d 192.168.1.10 -m comment --comment meowtail-lease \
+             -j MASQUERADE"
+        );
+        let [prerouting, _] = PortMapManager::rule_args(&lease, "eth0", "-D", false);
+        assert!(!prerouting.iter().any(|a| a == LEASE_COMMENT));
+    }
+}
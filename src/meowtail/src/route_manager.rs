@@ -0,0 +1,837 @@
+// src/route_manager.rs
+
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::fs_util::write_atomic;
+use crate::interfaces;
+use crate::interfaces::check_interface;
+use crate::ip_cidr::IpCidr;
+
+/// 内核保留的路由表：0 unspec、253 default、254 main、255 local
+const MAIN_TABLE: u32 = 254;
+/// 策略规则的优先级 0 (local)、32766 (main)、32767 (default) 为内核默认规则所用
+const MAX_RULE_PRIORITY: u32 = 32765;
+
+/// 一条静态路由
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Route {
+    /// 由 meowtail 分配
+    #[serde(default)]
+    pub id: u32,
+    /// 目的网段，默认路由为 `0.0.0.0/0` 或 `::/0`
+    pub destination: IpCidr,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metric: Option<u32>,
+    /// 路由表编号，未设置时为 main (254)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<u32>,
+}
+
+impl Route {
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        if self.destination.network() != self.destination {
+            issues.push(ValidationIssue::new(
+                "destination",
+                format!("{} has host bits set, did you mean {}?", self.destination, self.destination.network()),
+            ));
+        }
+        if self.gateway.is_none() && self.interface.is_none() {
+            issues.push(ValidationIssue::new("gateway", "A route needs a gateway, an interface or both"));
+        }
+        if let Some(gateway) = self.gateway {
+            if gateway.is_ipv4() != self.destination.is_ipv4() {
+                issues.push(ValidationIssue::new("gateway", "Gateway and destination must be of the same address family"));
+            }
+        }
+        if let Some(interface) = &self.interface {
+            if !interfaces::is_valid_name(interface) {
+                issues.push(ValidationIssue::new("interface", format!("'{}' is not a valid interface name", interface)));
+            }
+        }
+        if let Some(table) = self.table {
+            issues.extend(table_issue("table", table));
+        }
+        issues
+    }
+
+    /// `ip route` 中描述这条路由的参数（目的地址之后的部分）
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![self.destination.to_string()];
+        if let Some(gateway) = self.gateway {
+            args.extend(["via".to_string(), gateway.to_string()]);
+        }
+        if let Some(interface) = &self.interface {
+            args.extend(["dev".to_string(), interface.clone()]);
+        }
+        if let Some(metric) = self.metric {
+            args.extend(["metric".to_string(), metric.to_string()]);
+        }
+        args.extend(["table".to_string(), self.table.unwrap_or(MAIN_TABLE).to_string()]);
+        args
+    }
+
+    fn matches(&self, installed: &KernelRoute) -> bool {
+        installed.destination == self.destination
+            && installed.table == self.table.unwrap_or(MAIN_TABLE)
+            && self.metric.is_none_or(|m| m == installed.metric)
+            && self.gateway.is_none_or(|g| installed.gateway == Some(g))
+            && self.interface.as_ref().is_none_or(|i| installed.dev.as_ref() == Some(i))
+    }
+}
+
+/// 一条策略路由规则：来自 `from` 网段或从 `iif` 进入的流量查 `table` 路由表
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyRule {
+    #[serde(default)]
+    pub id: u32,
+    /// 规则按优先级从小到大匹配，同时用于在内核中识别这条规则
+    pub priority: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<IpCidr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iif: Option<String>,
+    pub table: u32,
+    /// 没有 `from` 时规则所属的地址族
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub ipv6: bool,
+}
+
+impl PolicyRule {
+    fn is_ipv6(&self) -> bool {
+        self.from.map(|f| !f.is_ipv4()).unwrap_or(self.ipv6)
+    }
+
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        if !(1..=MAX_RULE_PRIORITY).contains(&self.priority) {
+            issues.push(ValidationIssue::new(
+                "priority",
+                format!("Priority must be between 1 and {}", MAX_RULE_PRIORITY),
+            ));
+        }
+        if self.from.is_none() && self.iif.is_none() {
+            issues.push(ValidationIssue::new("from", "A rule needs a source network, an input interface or both"));
+        }
+        if let Some(from) = self.from {
+            if from.network() != from {
+                issues.push(ValidationIssue::new("from", format!("{} has host bits set", from)));
+            }
+        }
+        if let Some(iif) = &self.iif {
+            if !interfaces::is_valid_name(iif) {
+                issues.push(ValidationIssue::new("iif", format!("'{}' is not a valid interface name", iif)));
+            }
+        }
+        issues.extend(table_issue("table", self.table));
+        issues
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(from) = self.from {
+            args.extend(["from".to_string(), from.to_string()]);
+        }
+        if let Some(iif) = &self.iif {
+            args.extend(["iif".to_string(), iif.clone()]);
+        }
+        args.extend(["lookup".to_string(), self.table.to_string()]);
+        args.extend(["priority".to_string(), self.priority.to_string()]);
+        args
+    }
+
+    fn matches(&self, installed: &KernelRule) -> bool {
+        installed.priority == self.priority
+            && installed.from == self.from
+            && installed.iif == self.iif
+            && installed.table == self.table
+    }
+}
+
+fn table_issue(field: &str, table: u32) -> Option<ValidationIssue> {
+    match table {
+        0 | 253 | 255 => Some(ValidationIssue::new(field, format!("Table {} is reserved", table))),
+        _ => None,
+    }
+}
+
+/// routes.toml 的内容
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingConfig {
+    pub routes: Vec<Route>,
+    pub rules: Vec<PolicyRule>,
+}
+
+impl RoutingConfig {
+    pub fn validate(&self) -> Result<()> {
+        let mut issues = Vec::new();
+        for (i, route) in self.routes.iter().enumerate() {
+            let prefix = format!("routes[{}]", i);
+            if self.routes[..i].iter().any(|r| {
+                r.destination == route.destination && r.table == route.table && r.metric == route.metric
+            }) {
+                issues.push(ValidationIssue::new(
+                    format!("{}.destination", prefix),
+                    format!("A route to {} with the same table and metric already exists", route.destination),
+                ));
+            }
+            issues.extend(
+                route
+                    .validation_issues()
+                    .into_iter()
+                    .map(|issue| ValidationIssue::new(format!("{}.{}", prefix, issue.field), issue.message)),
+            );
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            let prefix = format!("rules[{}]", i);
+            if self.rules[..i].iter().any(|r| r.priority == rule.priority && r.is_ipv6() == rule.is_ipv6()) {
+                issues.push(ValidationIssue::new(
+                    format!("{}.priority", prefix),
+                    format!("Priority {} is already used by another rule", rule.priority),
+                ));
+            }
+            issues.extend(
+                rule.validation_issues()
+                    .into_iter()
+                    .map(|issue| ValidationIssue::new(format!("{}.{}", prefix, issue.field), issue.message)),
+            );
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+}
+
+/// `ip -j route show` 中的一条路由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelRoute {
+    pub destination: IpCidr,
+    pub gateway: Option<IpAddr>,
+    pub dev: Option<String>,
+    pub metric: u32,
+    pub table: u32,
+}
+
+/// `ip -j rule show` 中的一条规则
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelRule {
+    pub priority: u32,
+    pub from: Option<IpCidr>,
+    pub iif: Option<String>,
+    pub table: u32,
+}
+
+/// iproute2 的路由表名定义，`ip` 按这些文件把表编号显示为名字
+const RT_TABLES_FILES: &[&str] = &["/usr/share/iproute2/rt_tables", "/etc/iproute2/rt_tables"];
+const RT_TABLES_DIRS: &[&str] = &["/usr/share/iproute2/rt_tables.d", "/etc/iproute2/rt_tables.d"];
+
+/// 解析 rt_tables 格式的 `<编号> <名字>` 行
+pub fn parse_rt_tables(content: &str) -> Vec<(u32, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let id = words.next()?;
+            let id = match id.strip_prefix("0x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => id.parse().ok()?,
+            };
+            Some((id, words.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// 本机定义的所有路由表名
+fn rt_tables() -> Vec<(u32, String)> {
+    let mut files: Vec<PathBuf> = RT_TABLES_FILES.iter().map(PathBuf::from).collect();
+    for dir in RT_TABLES_DIRS {
+        if let Ok(entries) = fs::read_dir(dir) {
+            let mut conf: Vec<PathBuf> = entries
+                .filter_map(|e| Some(e.ok()?.path()))
+                .filter(|p| p.extension().is_some_and(|e| e == "conf"))
+                .collect();
+            conf.sort();
+            files.extend(conf);
+        }
+    }
+    files
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|content| parse_rt_tables(&content))
+        .collect()
+}
+
+/// 路由表名或编号。`ip` 省略 `table` 时为 main；其他名字按 `tables`（rt_tables）解析
+fn parse_table(value: Option<&Value>, tables: &[(u32, String)]) -> Option<u32> {
+    match value {
+        None => Some(MAIN_TABLE),
+        Some(Value::Number(n)) => n.as_u64().map(|n| n as u32),
+        Some(Value::String(s)) => match s.as_str() {
+            "main" => Some(MAIN_TABLE),
+            "default" => Some(253),
+            "local" => Some(255),
+            other => other
+                .parse()
+                .ok()
+                .or_else(|| tables.iter().rev().find(|(_, name)| name == other).map(|(id, _)| *id)),
+        },
+        _ => None,
+    }
+}
+
+/// 解析 `ip -j [-4|-6] route show table all` 的输出。`ipv6` 用于把 `default` 转为 `::/0`，
+/// 没有前缀长度的目的地址（主机路由）视为 /32 或 /128
+pub fn parse_routes(json: &str, ipv6: bool, tables: &[(u32, String)]) -> Vec<KernelRoute> {
+    let Ok(Value::Array(entries)) = serde_json::from_str::<Value>(json) else {
+        return vec![];
+    };
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let dst = entry.get("dst")?.as_str()?;
+            let destination = match dst {
+                "default" if ipv6 => IpCidr::new(IpAddr::from([0u16; 8]), 0).ok()?,
+                "default" => IpCidr::new(IpAddr::from([0u8; 4]), 0).ok()?,
+                dst => dst.parse().ok()?,
+            };
+            Some(KernelRoute {
+                destination,
+                gateway: entry.get("gateway").and_then(|g| g.as_str()?.parse().ok()),
+                dev: entry.get("dev").and_then(|d| d.as_str()).map(str::to_string),
+                metric: entry.get("metric").and_then(|m| m.as_u64()).unwrap_or(0) as u32,
+                table: parse_table(entry.get("table"), tables)?,
+            })
+        })
+        .collect()
+}
+
+/// 解析 `ip -j [-4|-6] rule show` 的输出
+pub fn parse_rules(json: &str, tables: &[(u32, String)]) -> Vec<KernelRule> {
+    let Ok(Value::Array(entries)) = serde_json::from_str::<Value>(json) else {
+        return vec![];
+    };
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let from = match entry.get("src").and_then(|s| s.as_str()) {
+                None | Some("all") => None,
+                Some(src) => {
+                    let addr: IpAddr = src.parse().ok()?;
+                    let max = if addr.is_ipv4() { 32 } else { 128 };
+                    let len = entry.get("srclen").and_then(|l| l.as_u64()).unwrap_or(max);
+                    Some(IpCidr::new(addr, len as u8).ok()?)
+                }
+            };
+            Some(KernelRule {
+                priority: entry.get("priority")?.as_u64()? as u32,
+                from,
+                iif: entry.get("iif").and_then(|i| i.as_str()).map(str::to_string),
+                table: parse_table(entry.get("table"), tables)?,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteStatus {
+    #[serde(flatten)]
+    pub route: Route,
+    /// 内核路由表中是否有这条路由
+    pub installed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleStatus {
+    #[serde(flatten)]
+    pub rule: PolicyRule,
+    pub installed: bool,
+}
+
+/// 一次对账的结果：补装的路由和规则，以及安装失败的原因
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReconcileReport {
+    pub routes_installed: Vec<u32>,
+    pub rules_installed: Vec<u32>,
+    pub errors: Vec<String>,
+}
+
+/// 管理静态路由与策略规则，配置保存在 routes.toml。
+/// 所有修改都通过 `ip` 命令完成，`ip_path` 可以指向测试用的替身程序。
+/// 只增删 routes.toml 中的路由和规则，其他来源（内核、DHCP 客户端等）的保持不变。
+pub struct RouteManager {
+    ip_path: String,
+    config_path: PathBuf,
+    config_lock: Mutex<()>,
+}
+
+impl RouteManager {
+    pub fn new<P: Into<PathBuf>>(ip_path: &str, config_path: P) -> Self {
+        RouteManager {
+            ip_path: ip_path.to_string(),
+            config_path: config_path.into(),
+            config_lock: Mutex::new(()),
+        }
+    }
+
+    fn run_ip(&self, args: &[String]) -> Result<String> {
+        let output = Command::new(&self.ip_path).args(args).output()?;
+        if !output.status.success() {
+            return Err(AppError::Process(format!(
+                "ip {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn family(ipv6: bool) -> String {
+        if ipv6 { "-6" } else { "-4" }.to_string()
+    }
+
+    /// 内核中所有路由表的路由
+    pub fn kernel_routes(&self) -> Result<Vec<KernelRoute>> {
+        let tables = rt_tables();
+        let mut routes = Vec::new();
+        for ipv6 in [false, true] {
+            let args = ["-j", &Self::family(ipv6), "route", "show", "table", "all"].map(String::from);
+            routes.extend(parse_routes(&self.run_ip(&args)?, ipv6, &tables));
+        }
+        Ok(routes)
+    }
+
+    pub fn kernel_rules(&self) -> Result<Vec<KernelRule>> {
+        let tables = rt_tables();
+        let mut rules = Vec::new();
+        for ipv6 in [false, true] {
+            let args = ["-j", &Self::family(ipv6), "rule", "show"].map(String::from);
+            rules.extend(parse_rules(&self.run_ip(&args)?, &tables));
+        }
+        Ok(rules)
+    }
+
+    fn install_route(&self, route: &Route) -> Result<()> {
+        let mut args = vec![Self::family(!route.destination.is_ipv4()), "route".to_string(), "replace".to_string()];
+        args.extend(route.args());
+        self.run_ip(&args).map(|_| ())
+    }
+
+    fn uninstall_route(&self, route: &Route) -> Result<()> {
+        let mut args = vec![Self::family(!route.destination.is_ipv4()), "route".to_string(), "del".to_string()];
+        args.extend(route.args());
+        self.run_ip(&args).map(|_| ())
+    }
+
+    fn install_rule(&self, rule: &PolicyRule) -> Result<()> {
+        let mut args = vec![Self::family(rule.is_ipv6()), "rule".to_string(), "add".to_string()];
+        args.extend(rule.args());
+        self.run_ip(&args).map(|_| ())
+    }
+
+    fn uninstall_rule(&self, rule: &PolicyRule) -> Result<()> {
+        let mut args = vec![Self::family(rule.is_ipv6()), "rule".to_string(), "del".to_string()];
+        args.extend(rule.args());
+        self.run_ip(&args).map(|_| ())
+    }
+
+    /// 读取配置，文件不存在时为空
+    pub fn read_config(&self) -> Result<RoutingConfig> {
+        let content = match fs::read_to_string(&self.config_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(RoutingConfig::default()),
+            Err(e) => return Err(e.into()),
+        };
+        toml::from_str(&content).map_err(|e| AppError::ConfigParse(format!("{:?}: {}", self.config_path, e)))
+    }
+
+    fn write_config(&self, config: &RoutingConfig) -> Result<()> {
+        let content = toml::to_string_pretty(config).map_err(|e| AppError::Process(e.to_string()))?;
+        write_atomic(&self.config_path, &content)
+    }
+
+    fn update_config<T, F: FnOnce(&mut RoutingConfig) -> Result<T>>(&self, update: F) -> Result<T> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let mut config = self.read_config()?;
+        let result = update(&mut config)?;
+        self.write_config(&config)?;
+        Ok(result)
+    }
+
+    pub fn routes(&self) -> Result<Vec<RouteStatus>> {
+        let installed = self.kernel_routes()?;
+        Ok(self
+            .read_config()?
+            .routes
+            .into_iter()
+            .map(|route| RouteStatus {
+                installed: installed.iter().any(|k| route.matches(k)),
+                route,
+            })
+            .collect())
+    }
+
+    pub fn rules(&self) -> Result<Vec<RuleStatus>> {
+        let installed = self.kernel_rules()?;
+        Ok(self
+            .read_config()?
+            .rules
+            .into_iter()
+            .map(|rule| RuleStatus {
+                installed: installed.iter().any(|k| rule.matches(k)),
+                rule,
+            })
+            .collect())
+    }
+
+    /// 添加路由并立即安装，返回分配的 ID
+    pub fn add_route(&self, mut route: Route) -> Result<Route> {
+        if let Some(interface) = &route.interface {
+            check_interface(interface)?;
+        }
+        self.update_config(|config| {
+            route.id = config.routes.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+            config.routes.push(route.clone());
+            config.validate()?;
+            self.install_route(&route)?;
+            Ok(route)
+        })
+    }
+
+    pub fn update_route(&self, id: u32, mut route: Route) -> Result<Route> {
+        if let Some(interface) = &route.interface {
+            check_interface(interface)?;
+        }
+        route.id = id;
+        self.update_config(|config| {
+            let existing = config
+                .routes
+                .iter_mut()
+                .find(|r| r.id == id)
+                .ok_or_else(|| AppError::NotFound(format!("Route {}", id)))?;
+            let previous = std::mem::replace(existing, route.clone());
+            config.validate()?;
+            // 旧路由可能已被手工删除
+            let _ = self.uninstall_route(&previous);
+            if let Err(e) = self.install_route(&route) {
+                // 新路由装不上时恢复旧路由，配置文件也保持不变
+                let _ = self.install_route(&previous);
+                return Err(e);
+            }
+            Ok(route)
+        })
+    }
+
+    pub fn delete_route(&self, id: u32) -> Result<()> {
+        self.update_config(|config| {
+            let index = config
+                .routes
+                .iter()
+                .position(|r| r.id == id)
+                .ok_or_else(|| AppError::NotFound(format!("Route {}", id)))?;
+            let route = config.routes.remove(index);
+            let _ = self.uninstall_route(&route);
+            Ok(())
+        })
+    }
+
+    pub fn add_rule(&self, mut rule: PolicyRule) -> Result<PolicyRule> {
+        if let Some(iif) = &rule.iif {
+            check_interface(iif)?;
+        }
+        self.update_config(|config| {
+            rule.id = config.rules.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+            config.rules.push(rule.clone());
+            config.validate()?;
+            self.install_rule(&rule)?;
+            Ok(rule)
+        })
+    }
+
+    pub fn update_rule(&self, id: u32, mut rule: PolicyRule) -> Result<PolicyRule> {
+        if let Some(iif) = &rule.iif {
+            check_interface(iif)?;
+        }
+        rule.id = id;
+        self.update_config(|config| {
+            let existing = config
+                .rules
+                .iter_mut()
+                .find(|r| r.id == id)
+                .ok_or_else(|| AppError::NotFound(format!("Rule {}", id)))?;
+            let previous = std::mem::replace(existing, rule.clone());
+            config.validate()?;
+            let _ = self.uninstall_rule(&previous);
+            if let Err(e) = self.install_rule(&rule) {
+                let _ = self.install_rule(&previous);
+                return Err(e);
+            }
+            Ok(rule)
+        })
+    }
+
+    pub fn delete_rule(&self, id: u32) -> Result<()> {
+        self.update_config(|config| {
+            let index = config
+                .rules
+                .iter()
+                .position(|r| r.id == id)
+                .ok_or_else(|| AppError::NotFound(format!("Rule {}", id)))?;
+            let rule = config.rules.remove(index);
+            let _ = self.uninstall_rule(&rule);
+            Ok(())
+        })
+    }
+
+    /// 与内核对账：安装 routes.toml 中有而内核中没有的路由和规则
+    pub fn reconcile(&self) -> Result<ReconcileReport> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let config = self.read_config()?;
+        config.validate()?;
+        let kernel_routes = self.kernel_routes()?;
+        let kernel_rules = self.kernel_rules()?;
+
+        let mut report = ReconcileReport::default();
+        for route in config.routes.iter().filter(|r| !kernel_routes.iter().any(|k| r.matches(k))) {
+            match self.install_route(route) {
+                Ok(()) => report.routes_installed.push(route.id),
+                Err(e) => report.errors.push(format!("Route {} to {}: {}", route.id, route.destination, e)),
+            }
+        }
+        for rule in config.rules.iter().filter(|r| !kernel_rules.iter().any(|k| r.matches(k))) {
+            match self.install_rule(rule) {
+                Ok(()) => report.rules_installed.push(rule.id),
+                Err(e) => report.errors.push(format!("Rule {} (priority {}): {}", rule.id, rule.priority, e)),
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// 真实 `ip -j -4 route show table all` 输出的片段：主机路由没有前缀长度，main 表省略 `table`
+    const ROUTES_V4: &str = r#"[{"dst":"default","gateway":"192.0.2.1","dev":"eth0","flags":[]},
+        {"dst":"192.0.2.0/24","dev":"eth0","protocol":"kernel","scope":"link","prefsrc":"192.0.2.2","flags":[]},
+        {"dst":"10.8.0.0/16","gateway":"192.0.2.9","dev":"eth0","table":"vpn","metric":50,"flags":[]},
+        {"type":"local","dst":"127.0.0.1","dev":"lo","table":"local","protocol":"kernel","scope":"host","prefsrc":"127.0.0.1","flags":[]},
+        {"type":"broadcast","dst":"192.0.2.255","dev":"eth0","table":"local","protocol":"kernel","scope":"link","prefsrc":"192.0.2.2","flags":[]}]"#;
+    const ROUTES_V6: &str = r#"[{"dst":"fe80::/64","dev":"eth0","protocol":"kernel","metric":256,"pref":"medium","flags":[]},
+        {"dst":"default","gateway":"fe80::1","dev":"eth0","table":"100","metric":1024,"flags":[]}]"#;
+    const RULES_V4: &str = r#"[{"priority":0,"src":"all","table":"local"},
+        {"priority":100,"src":"10.1.0.0","srclen":16,"table":"vpn"},
+        {"priority":101,"src":"10.2.0.7","iif":"eth1","table":"200"},
+        {"priority":32766,"src":"all","table":"main"},
+        {"priority":32767,"src":"all","table":"default"}]"#;
+
+    fn tables() -> Vec<(u32, String)> {
+        parse_rt_tables("# reserved values\n255\tlocal\n254\tmain\n\n0x64 vpn\n")
+    }
+
+    fn cidr(s: &str) -> IpCidr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_route_output() {
+        let routes = parse_routes(ROUTES_V4, false, &tables());
+        assert_eq!(routes.len(), 5);
+        assert_eq!(routes[0].destination, cidr("0.0.0.0/0"));
+        assert_eq!(routes[0].table, MAIN_TABLE);
+        assert_eq!(routes[1].gateway, None);
+        assert_eq!(routes[2].table, 100);
+        assert_eq!(routes[2].metric, 50);
+        assert_eq!(routes[3].destination, cidr("127.0.0.1/32"));
+        assert_eq!(routes[3].table, 255);
+
+        let routes = parse_routes(ROUTES_V6, true, &[]);
+        assert_eq!(routes[1].destination, cidr("::/0"));
+        assert_eq!(routes[1].table, 100);
+        // 不认识的表名无法比较，跳过这条路由
+        assert!(parse_routes(ROUTES_V4, false, &[]).iter().all(|r| r.table != 100));
+    }
+
+    #[test]
+    fn parses_rule_output() {
+        let rules = parse_rules(RULES_V4, &tables());
+        assert_eq!(rules.len(), 5);
+        assert_eq!(rules[0], KernelRule { priority: 0, from: None, iif: None, table: 255 });
+        assert_eq!(rules[1].from, Some(cidr("10.1.0.0/16")));
+        assert_eq!(rules[1].table, 100);
+        assert_eq!(rules[2].from, Some(cidr("10.2.0.7/32")));
+        assert_eq!(rules[2].iif.as_deref(), Some("eth1"));
+        assert_eq!(rules[2].table, 200);
+        assert!(parse_rules("not json", &[]).is_empty());
+    }
+
+    /// 代替 `ip` 的脚本：记录每次调用的参数，`show` 输出 `<dir>/{routes,rules}-{4,6}.json`，
+    /// 参数包含 `<dir>/fail` 中的文本时失败
+    struct StubIp {
+        dir: PathBuf,
+        manager: RouteManager,
+    }
+
+    impl StubIp {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("meowtail-routes-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let script = dir.join("ip");
+            fs::write(
+                &script,
+                format!(
+                    r#"#!/bin/sh
+dir='{}'
+echo "$*" >> "$dir/log"
+if [ -s "$dir/fail" ] && echo "$*" | grep -qF -- "$(cat "$dir/fail")"; then
+    echo "RTNETLINK answers: No such process" >&2
+    exit 2
+fi
+case "$*" in
+    *"route show"*) cat "$dir/routes$2.json" 2>/dev/null || echo '[]' ;;
+    *"rule show"*) cat "$dir/rules$2.json" 2>/dev/null || echo '[]' ;;
+esac
+"#,
+                    dir.display()
+                ),
+            )
+            .unwrap();
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+            // 其他测试线程 fork 时可能短暂持有脚本的写句柄，执行会报 ETXTBSY，等它释放
+            for _ in 0..50 {
+                match Command::new(&script).arg("warmup").status() {
+                    Err(e) if e.raw_os_error() == Some(nix::libc::ETXTBSY) => {
+                        std::thread::sleep(std::time::Duration::from_millis(20))
+                    }
+                    _ => break,
+                }
+            }
+            let _ = fs::remove_file(dir.join("log"));
+            let manager = RouteManager::new(script.to_str().unwrap(), dir.join("routes.toml"));
+            StubIp { dir, manager }
+        }
+
+        fn write(&self, name: &str, content: &str) {
+            fs::write(self.dir.join(name), content).unwrap();
+        }
+
+        /// 取出并清空调用记录
+        fn calls(&self) -> Vec<String> {
+            let path = self.dir.join("log");
+            let log = fs::read_to_string(&path).unwrap_or_default();
+            let _ = fs::remove_file(&path);
+            log.lines().filter(|l| !l.contains(" show")).map(str::to_string).collect()
+        }
+    }
+
+    impl Drop for StubIp {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn route(destination: &str, gateway: &str) -> Route {
+        Route {
+            id: 0,
+            destination: cidr(destination),
+            gateway: Some(gateway.parse().unwrap()),
+            interface: None,
+            metric: None,
+            table: None,
+        }
+    }
+
+    #[test]
+    fn reconcile_installs_only_missing_entries() {
+        let stub = StubIp::new("reconcile");
+        let mut config = RoutingConfig::default();
+        config.routes.push(Route { id: 1, ..route("192.0.2.0/24", "192.0.2.1") });
+        config.routes.push(Route { id: 2, ..route("10.9.0.0/16", "192.0.2.9") });
+        config.rules.push(PolicyRule { id: 1, priority: 101, from: Some(cidr("10.2.0.7/32")), iif: Some("eth1".to_string()), table: 200, ipv6: false });
+        config.rules.push(PolicyRule { id: 2, priority: 300, from: Some(cidr("10.3.0.0/16")), iif: None, table: 300, ipv6: false });
+        stub.manager.write_config(&config).unwrap();
+        stub.write("routes-4.json", r#"[{"dst":"192.0.2.0/24","gateway":"192.0.2.1","dev":"eth0","flags":[]}]"#);
+        stub.write("rules-4.json", RULES_V4);
+
+        let report = stub.manager.reconcile().unwrap();
+        assert_eq!(report.routes_installed, vec![2]);
+        assert_eq!(report.rules_installed, vec![2]);
+        assert!(report.errors.is_empty());
+        assert_eq!(
+            stub.calls(),
+            vec![
+                "-4 route replace 10.9.0.0/16 via 192.0.2.9 table 254",
+                "-4 rule add from 10.3.0.0/16 lookup 300 priority 300",
+            ]
+        );
+
+        // 安装失败记入报告，不影响其他条目
+        stub.write("fail", "10.9.0.0/16");
+        let report = stub.manager.reconcile().unwrap();
+        assert!(report.routes_installed.is_empty());
+        assert_eq!(report.rules_installed, vec![2]);
+        assert_eq!(report.errors.len(), 1);
+    }
+
+    #[test]
+    fn update_replaces_the_installed_route() {
+        let stub = StubIp::new("update");
+        let added = stub.manager.add_route(route("10.9.0.0/16", "192.0.2.9")).unwrap();
+        assert_eq!(added.id, 1);
+        assert_eq!(stub.calls(), vec!["-4 route replace 10.9.0.0/16 via 192.0.2.9 table 254"]);
+
+        stub.manager.update_route(1, route("10.9.0.0/16", "192.0.2.10")).unwrap();
+        assert_eq!(
+            stub.calls(),
+            vec![
+                "-4 route del 10.9.0.0/16 via 192.0.2.9 table 254",
+                "-4 route replace 10.9.0.0/16 via 192.0.2.10 table 254",
+            ]
+        );
+        assert_eq!(stub.manager.read_config().unwrap().routes[0].gateway, Some("192.0.2.10".parse().unwrap()));
+    }
+
+    #[test]
+    fn failed_update_rolls_back() {
+        let stub = StubIp::new("rollback");
+        stub.manager.add_route(route("10.9.0.0/16", "192.0.2.9")).unwrap();
+        stub.calls();
+
+        stub.write("fail", "via 192.0.2.66");
+        assert!(stub.manager.update_route(1, route("10.9.0.0/16", "192.0.2.66")).is_err());
+        // 旧路由被重新安装，配置文件保持原样
+        assert_eq!(
+            stub.calls(),
+            vec![
+                "-4 route del 10.9.0.0/16 via 192.0.2.9 table 254",
+                "-4 route replace 10.9.0.0/16 via 192.0.2.66 table 254",
+                "-4 route replace 10.9.0.0/16 via 192.0.2.9 table 254",
+            ]
+        );
+        assert_eq!(stub.manager.read_config().unwrap().routes[0].gateway, Some("192.0.2.9".parse().unwrap()));
+
+        // 新增失败时不写入配置
+        assert!(stub.manager.add_route(route("10.66.0.0/16", "192.0.2.66")).is_err());
+        assert_eq!(stub.manager.read_config().unwrap().routes.len(), 1);
+    }
+}
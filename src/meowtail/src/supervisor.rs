@@ -0,0 +1,552 @@
+// src/supervisor.rs
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+use serde::Serialize;
+
+/// 两次意外退出之间的最小/最大重启间隔
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// 子进程稳定运行超过该时长后，重启间隔重置为最小值
+const STABLE_RUNTIME: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const LOG_TAIL_LINES: usize = 20;
+
+/// 一次子进程退出的记录
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitRecord {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub at: DateTime<Utc>,
+    /// 是否为 stop() 主动停止
+    pub expected: bool,
+}
+
+impl ExitRecord {
+    fn new(status: ExitStatus, expected: bool) -> Self {
+        ExitRecord {
+            code: status.code(),
+            signal: status.signal(),
+            at: Utc::now(),
+            expected,
+        }
+    }
+
+    /// 接管的进程不是我们的子进程，无法取得退出码
+    fn unknown(expected: bool) -> Self {
+        ExitRecord {
+            code: None,
+            signal: None,
+            at: Utc::now(),
+            expected,
+        }
+    }
+}
+
+/// 对外报告的监管状态
+#[derive(Debug, Clone, Serialize)]
+pub struct SupervisorStatus {
+    pub running: bool,
+    pub pid: Option<u32>,
+    /// 进程是否为接管的外部进程（而非本进程启动的子进程）
+    pub adopted: bool,
+    pub started_at: Option<DateTime<Utc>>,
+    pub restart_count: u32,
+    pub last_exit: Option<ExitRecord>,
+    /// 下一次自动重启前还需等待的毫秒数（仅在等待重启时出现）
+    pub restart_pending_ms: Option<u64>,
+    pub recent_output: Vec<String>,
+}
+
+/// 正在运行的被监管进程
+enum Process {
+    /// 由本进程启动，可以 wait() 回收
+    Child(Child),
+    /// 在 meowtail 之外启动、经校验后接管的进程，只能轮询其是否存活
+    Adopted(Pid),
+}
+
+impl Process {
+    fn pid(&self) -> u32 {
+        match self {
+            Process::Child(child) => child.id(),
+            Process::Adopted(pid) => pid.as_raw() as u32,
+        }
+    }
+}
+
+struct State {
+    process: Option<Process>,
+    /// 调用方是否希望进程保持运行
+    desired_running: bool,
+    /// 每次开始监管时递增，用于让旧的监控线程退出
+    generation: u64,
+    started_at: Option<DateTime<Utc>>,
+    started_instant: Option<Instant>,
+    restart_count: u32,
+    backoff: Duration,
+    next_restart: Option<Instant>,
+    last_exit: Option<ExitRecord>,
+    recent_output: VecDeque<String>,
+}
+
+type SpawnHook = Box<dyn Fn() + Send + Sync>;
+
+struct Inner {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    pid_path: PathBuf,
+    state: Mutex<State>,
+    spawn_hook: Mutex<Option<SpawnHook>>,
+}
+
+/// 持有并监管一个长期运行的子进程：回收僵尸进程、意外退出后按退避间隔重启、
+/// 记录退出状态与重启次数，并把子进程的 stderr 转发到本进程的日志中。
+///
+/// 被监管的程序必须在前台运行（例如 udhcpd 需要 `-f`），否则 fork 后父进程立即退出会被视为崩溃。
+///
+/// PID 文件中的进程只有在 `/proc/<pid>/exe`/`cmdline` 与本监管器的程序名和参数中的
+/// 路径（如配置文件）都对得上时才被认为是“我们的”进程，避免 PID 被复用后误杀无关进程。
+pub struct ProcessSupervisor {
+    inner: Arc<Inner>,
+}
+
+impl ProcessSupervisor {
+    pub fn new<P: Into<PathBuf>>(name: &str, program: &str, args: Vec<String>, pid_path: P) -> Self {
+        ProcessSupervisor {
+            inner: Arc::new(Inner {
+                name: name.to_string(),
+                program: program.to_string(),
+                args,
+                pid_path: pid_path.into(),
+                state: Mutex::new(State {
+                    process: None,
+                    desired_running: false,
+                    generation: 0,
+                    started_at: None,
+                    started_instant: None,
+                    restart_count: 0,
+                    backoff: MIN_BACKOFF,
+                    next_restart: None,
+                    last_exit: None,
+                    recent_output: VecDeque::new(),
+                }),
+                spawn_hook: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// 设置每次启动子进程（包括自动重启）前调用的回调，例如记录子进程将要加载的配置
+    pub fn set_spawn_hook<F: Fn() + Send + Sync + 'static>(&self, hook: F) {
+        *self.inner.spawn_hook.lock().unwrap() = Some(Box::new(hook));
+    }
+
+    /// 向正在运行的进程发送信号（发送前校验进程身份）
+    pub fn signal(&self, sig: Signal) -> io::Result<()> {
+        let state = self.inner.state.lock().unwrap();
+        let pid = match &state.process {
+            Some(Process::Child(child)) => Pid::from_raw(child.id() as i32),
+            Some(Process::Adopted(pid)) if self.inner.matches(*pid) => *pid,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} is not running.", self.inner.name),
+                ))
+            }
+        };
+        signal::kill(pid, sig).map_err(io::Error::from)
+    }
+
+    /// 启动子进程并开始监管
+    pub fn start(&self) -> io::Result<()> {
+        let generation = {
+            let mut state = self.inner.state.lock().unwrap();
+            if state.process.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} is already running.", self.inner.name),
+                ));
+            }
+            self.inner.spawn(&mut state)?;
+            self.inner.begin_supervision(&mut state)
+        };
+
+        let inner = self.inner.clone();
+        thread::spawn(move || inner.monitor(generation));
+        Ok(())
+    }
+
+    /// 查找在 meowtail 之外启动（或 meowtail 重启前留下）的同一实例并接管监管。
+    /// 先看 PID 文件，不匹配时清理过期的 PID 文件并扫描 /proc。
+    pub fn adopt(&self) -> Option<u32> {
+        let generation = {
+            let mut state = self.inner.state.lock().unwrap();
+            if let Some(process) = &state.process {
+                return Some(process.pid());
+            }
+
+            let pid = self.inner.pid_from_file().or_else(|| self.inner.scan_proc())?;
+            if let Err(e) = fs::write(&self.inner.pid_path, pid.to_string()) {
+                eprintln!("Failed to write PID file {:?}: {}", self.inner.pid_path, e);
+            }
+            println!("Adopted running {} with PID: {}", self.inner.name, pid);
+            state.process = Some(Process::Adopted(pid));
+            state.started_at = None;
+            state.started_instant = Some(Instant::now());
+            self.inner.begin_supervision(&mut state)
+        };
+
+        let inner = self.inner.clone();
+        thread::spawn(move || inner.monitor(generation));
+        self.pid()
+    }
+
+    /// 停止进程：先发 SIGTERM，3 秒内未退出则 SIGKILL，子进程会被回收
+    pub fn stop(&self) -> io::Result<()> {
+        let process = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.desired_running = false;
+            state.next_restart = None;
+            state.process.take()
+        };
+
+        let record = match process {
+            None => return Ok(()),
+            Some(Process::Child(mut child)) => {
+                let pid = Pid::from_raw(child.id() as i32);
+                let _ = signal::kill(pid, Signal::SIGTERM);
+                let mut status = None;
+                for _ in 0..30 {
+                    if let Some(s) = child.try_wait()? {
+                        status = Some(s);
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(100));
+                }
+                let status = match status {
+                    Some(s) => s,
+                    None => {
+                        println!("{} did not respond to SIGTERM, sending SIGKILL...", self.inner.name);
+                        child.kill()?;
+                        child.wait()?
+                    }
+                };
+                ExitRecord::new(status, true)
+            }
+            Some(Process::Adopted(pid)) => {
+                // 发信号前再校验一次，进程可能已经退出而 PID 被复用
+                if self.inner.matches(pid) {
+                    signal::kill(pid, Signal::SIGTERM)?;
+                    for _ in 0..30 {
+                        if !self.inner.matches(pid) {
+                            break;
+                        }
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                    if self.inner.matches(pid) {
+                        println!("{} did not respond to SIGTERM, sending SIGKILL...", self.inner.name);
+                        signal::kill(pid, Signal::SIGKILL)?;
+                    }
+                }
+                ExitRecord::unknown(true)
+            }
+        };
+
+        let mut state = self.inner.state.lock().unwrap();
+        state.last_exit = Some(record);
+        state.started_at = None;
+        state.started_instant = None;
+        let _ = fs::remove_file(&self.inner.pid_path);
+        Ok(())
+    }
+
+    /// 进程当前是否存活（已退出但尚未被监控线程回收的也视为不在运行）
+    pub fn is_running(&self) -> bool {
+        let mut state = self.inner.state.lock().unwrap();
+        match state.process.as_mut() {
+            Some(Process::Child(child)) => matches!(child.try_wait(), Ok(None)),
+            Some(Process::Adopted(pid)) => self.inner.matches(*pid),
+            None => false,
+        }
+    }
+
+    /// 当前进程的 PID
+    pub fn pid(&self) -> Option<u32> {
+        self.inner.state.lock().unwrap().process.as_ref().map(|p| p.pid())
+    }
+
+    pub fn status(&self) -> SupervisorStatus {
+        let running = self.is_running();
+        let state = self.inner.state.lock().unwrap();
+        SupervisorStatus {
+            running,
+            pid: state.process.as_ref().map(|p| p.pid()),
+            adopted: matches!(state.process, Some(Process::Adopted(_))),
+            started_at: state.started_at,
+            restart_count: state.restart_count,
+            last_exit: state.last_exit.clone(),
+            restart_pending_ms: state
+                .next_restart
+                .map(|at| at.saturating_duration_since(Instant::now()).as_millis() as u64),
+            recent_output: state.recent_output.iter().cloned().collect(),
+        }
+    }
+}
+
+impl Inner {
+    fn spawn(self: &Arc<Self>, state: &mut State) -> io::Result<()> {
+        if let Some(hook) = self.spawn_hook.lock().unwrap().as_ref() {
+            hook();
+        }
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let pid = child.id();
+
+        if let Err(e) = fs::write(&self.pid_path, pid.to_string()) {
+            eprintln!("Failed to write PID file {:?}: {}", self.pid_path, e);
+        }
+
+        if let Some(stderr) = child.stderr.take() {
+            let inner = self.clone();
+            thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(|l| l.ok()) {
+                    eprintln!("[{}] {}", inner.name, line);
+                    let mut state = inner.state.lock().unwrap();
+                    if state.recent_output.len() == LOG_TAIL_LINES {
+                        state.recent_output.pop_front();
+                    }
+                    state.recent_output.push_back(line);
+                }
+            });
+        }
+
+        state.process = Some(Process::Child(child));
+        state.started_at = Some(Utc::now());
+        state.started_instant = Some(Instant::now());
+        println!("Started {} with PID: {}. PID file created at {:?}", self.name, pid, self.pid_path);
+        Ok(())
+    }
+
+    fn begin_supervision(&self, state: &mut State) -> u64 {
+        state.desired_running = true;
+        state.restart_count = 0;
+        state.backoff = MIN_BACKOFF;
+        state.next_restart = None;
+        state.generation += 1;
+        state.generation
+    }
+
+    /// 监控线程：回收退出的子进程，并在非预期退出时按退避间隔重启
+    fn monitor(self: Arc<Self>, generation: u64) {
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let mut state = self.state.lock().unwrap();
+            if state.generation != generation || !state.desired_running {
+                return;
+            }
+
+            let record = match state.process.as_mut() {
+                Some(Process::Child(child)) => match child.try_wait() {
+                    Ok(Some(status)) => Some(ExitRecord::new(status, false)),
+                    Ok(None) => continue,
+                    Err(e) => {
+                        eprintln!("Failed to poll {}: {}", self.name, e);
+                        continue;
+                    }
+                },
+                Some(Process::Adopted(pid)) if self.matches(*pid) => continue,
+                Some(Process::Adopted(_)) => Some(ExitRecord::unknown(false)),
+                None => None,
+            };
+
+            if let Some(record) = record {
+                eprintln!(
+                    "{} exited unexpectedly (code: {:?}, signal: {:?})",
+                    self.name, record.code, record.signal
+                );
+                state.last_exit = Some(record);
+                state.process = None;
+                state.started_at = None;
+                let _ = fs::remove_file(&self.pid_path);
+
+                if state.started_instant.take().is_some_and(|t| t.elapsed() >= STABLE_RUNTIME) {
+                    state.backoff = MIN_BACKOFF;
+                }
+                state.next_restart = Some(Instant::now() + state.backoff);
+                println!("Restarting {} in {:?}", self.name, state.backoff);
+                state.backoff = (state.backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+
+            if state.next_restart.is_some_and(|at| Instant::now() >= at) {
+                state.next_restart = None;
+                state.restart_count += 1;
+                if let Err(e) = self.spawn(&mut state) {
+                    eprintln!("Failed to restart {}: {}", self.name, e);
+                    state.next_restart = Some(Instant::now() + state.backoff);
+                    state.backoff = (state.backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    /// 读取 PID 文件并校验；文件内容无效或进程不匹配时删除这个过期的 PID 文件
+    fn pid_from_file(&self) -> Option<Pid> {
+        let content = fs::read_to_string(&self.pid_path).ok()?;
+        let pid = content.trim().parse::<i32>().ok().filter(|p| *p > 0).map(Pid::from_raw);
+        match pid {
+            Some(pid) if self.matches(pid) => Some(pid),
+            _ => {
+                println!("Removing stale PID file {:?}", self.pid_path);
+                let _ = fs::remove_file(&self.pid_path);
+                None
+            }
+        }
+    }
+
+    /// 在 /proc 中查找与本监管器匹配的进程。
+    /// 参数中没有可以区分实例的值（如配置文件路径）时不扫描，以免接管同名的其他进程。
+    fn scan_proc(&self) -> Option<Pid> {
+        if identifying_args(&self.args).is_empty() {
+            return None;
+        }
+        let own_pid = std::process::id() as i32;
+        fs::read_dir("/proc")
+            .ok()?
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<i32>().ok())
+            .filter(|pid| *pid != own_pid)
+            .map(Pid::from_raw)
+            .find(|pid| self.matches(*pid))
+    }
+
+    /// 校验 PID 对应的进程确实是被监管的程序：
+    /// 可执行文件名（`/proc/<pid>/exe` 或 argv[0]，兼容 BusyBox applet）与程序名一致，
+    /// 且命令行中包含监管参数里的所有标识值（见 `args_match`）。
+    fn matches(&self, pid: Pid) -> bool {
+        let proc_dir = PathBuf::from(format!("/proc/{}", pid));
+        let Ok(cmdline) = fs::read(proc_dir.join("cmdline")) else {
+            return false;
+        };
+        let argv: Vec<String> = cmdline
+            .split(|b| *b == 0)
+            .filter(|a| !a.is_empty())
+            .map(|a| String::from_utf8_lossy(a).into_owned())
+            .collect();
+        let Some(argv0) = argv.first() else {
+            // 僵尸进程或内核线程
+            return false;
+        };
+
+        let program = file_name(&self.program);
+        let exe = fs::read_link(proc_dir.join("exe"))
+            .ok()
+            .map(|p| file_name(p.to_string_lossy().trim_end_matches(" (deleted)")));
+        if file_name(argv0) != program && exe.as_deref() != Some(program.as_str()) {
+            return false;
+        }
+
+        let cwd = fs::read_link(proc_dir.join("cwd")).unwrap_or_default();
+        args_match(&self.args, &argv[1..], &cwd)
+    }
+}
+
+/// 监管参数中用来区分实例的值：普通参数本身，以及 `--opt=value` 中的 `(opt, value)`。
+/// 不带值的开关（如 `-f`、`--keep-in-foreground`）和 `--log-facility=-` 这类以 `-` 开头的值不算。
+fn identifying_args(args: &[String]) -> Vec<(Option<&str>, &str)> {
+    args.iter()
+        .filter_map(|arg| {
+            if !arg.starts_with('-') {
+                return Some((None, arg.as_str()));
+            }
+            arg.split_once('=')
+                .filter(|(_, value)| !value.is_empty() && !value.starts_with('-'))
+                .map(|(opt, value)| (Some(opt), value))
+        })
+        .collect()
+}
+
+/// 进程的命令行 `argv`（不含 argv[0]）是否包含 `args` 中的所有标识值。
+/// 值按路径比较：`argv` 中的相对路径以进程的工作目录 `cwd` 解析，`args` 中的以本进程的工作目录解析。
+/// `--opt=value` 也接受进程以 `--opt value` 的形式传入。
+fn args_match(args: &[String], argv: &[String], cwd: &Path) -> bool {
+    let own_cwd = std::env::current_dir().unwrap_or_default();
+    identifying_args(args).into_iter().all(|(opt, wanted)| {
+        let wanted = canonical(Path::new(wanted), &own_cwd);
+        let same = |value: &str| canonical(Path::new(value), cwd) == wanted;
+        match opt {
+            None => argv.iter().any(|a| same(a)),
+            Some(opt) => argv.iter().enumerate().any(|(i, a)| match a.split_once('=') {
+                Some((o, value)) => o == opt && same(value),
+                None => a == opt && argv.get(i + 1).is_some_and(|v| same(v)),
+            }),
+        }
+    })
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// 以 `base` 解析相对路径并尽量规范化，文件不存在时退回拼接结果
+fn canonical(path: &Path, base: &Path) -> PathBuf {
+    let joined = if path.is_absolute() { path.to_path_buf() } else { base.join(path) };
+    fs::canonicalize(&joined).unwrap_or(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn conf_file_values_are_compared() {
+        let dhcp = strings(&["--keep-in-foreground", "--log-facility=-", "--conf-file=/tmp/meowtail-dhcp.conf"]);
+        let dns = strings(&["--keep-in-foreground", "--log-facility=-", "--conf-file=/tmp/meowtail-dns.conf"]);
+        let cwd = Path::new("/");
+
+        assert!(args_match(&dhcp, &dhcp, cwd));
+        assert!(args_match(&dns, &dns, cwd));
+        assert!(!args_match(&dhcp, &dns, cwd));
+        assert!(!args_match(&dns, &dhcp, cwd));
+        // 以 `--opt value` 形式传入或使用相对路径也能识别
+        assert!(args_match(&dhcp, &strings(&["-k", "--conf-file", "/tmp/meowtail-dhcp.conf"]), cwd));
+        assert!(args_match(&dhcp, &strings(&["--conf-file=meowtail-dhcp.conf"]), Path::new("/tmp")));
+        assert!(!args_match(&dhcp, &strings(&["-k", "/tmp/meowtail-dhcp.conf"]), cwd));
+    }
+
+    #[test]
+    fn plain_path_arguments_are_compared() {
+        let args = strings(&["-f", "/etc/udhcpd.eth0.conf"]);
+        assert!(args_match(&args, &strings(&["-f", "/etc/udhcpd.eth0.conf"]), Path::new("/")));
+        assert!(!args_match(&args, &strings(&["-f", "/etc/udhcpd.eth1.conf"]), Path::new("/")));
+    }
+
+    #[test]
+    fn refuses_scan_without_identifying_arguments() {
+        let args = strings(&["--keep-in-foreground", "--log-facility=-"]);
+        assert!(identifying_args(&args).is_empty());
+        let supervisor = ProcessSupervisor::new("sh", "sh", args, "/nonexistent/sh.pid");
+        assert!(supervisor.inner.scan_proc().is_none());
+    }
+}
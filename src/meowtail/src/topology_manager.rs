@@ -0,0 +1,404 @@
+// src/topology_manager.rs
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::fs_util::write_atomic;
+use crate::interfaces::{self, run_ip, InterfaceKind};
+
+/// 一个网桥及其成员端口
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bridge {
+    pub name: String,
+    #[serde(default)]
+    pub ports: Vec<String>,
+    /// 是否启用生成树协议
+    #[serde(default)]
+    pub stp: bool,
+}
+
+/// 一个 802.1Q VLAN 子接口
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vlan {
+    pub name: String,
+    pub parent: String,
+    pub id: u16,
+}
+
+impl Vlan {
+    /// 未指定名字时使用的 `<parent>.<id>`
+    pub fn default_name(parent: &str, id: u16) -> String {
+        format!("{}.{}", parent, id)
+    }
+}
+
+/// topology.toml 的内容
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Topology {
+    pub bridges: Vec<Bridge>,
+    pub vlans: Vec<Vlan>,
+}
+
+impl Topology {
+    pub fn validate(&self) -> Result<()> {
+        let issues = self.validation_issues();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut names: Vec<&str> = Vec::new();
+
+        for (i, vlan) in self.vlans.iter().enumerate() {
+            let field = |name: &str| format!("vlans[{}].{}", i, name);
+            if !interfaces::is_valid_name(&vlan.name) {
+                issues.push(ValidationIssue::new(field("name"), format!("'{}' is not a valid interface name", vlan.name)));
+            } else if names.contains(&vlan.name.as_str()) {
+                issues.push(ValidationIssue::new(field("name"), format!("{} is defined more than once", vlan.name)));
+            }
+            names.push(&vlan.name);
+            if !(1..=4094).contains(&vlan.id) {
+                issues.push(ValidationIssue::new(field("id"), "VLAN ID must be between 1 and 4094"));
+            }
+            if self.vlans.iter().any(|v| v.name == vlan.parent) {
+                issues.push(ValidationIssue::new(field("parent"), "Stacked VLANs are not supported"));
+            }
+            if self.vlans[..i].iter().any(|v| v.parent == vlan.parent && v.id == vlan.id) {
+                issues.push(ValidationIssue::new(
+                    field("id"),
+                    format!("VLAN {} already exists on {}", vlan.id, vlan.parent),
+                ));
+            }
+        }
+
+        for (i, bridge) in self.bridges.iter().enumerate() {
+            let field = |name: &str| format!("bridges[{}].{}", i, name);
+            if !interfaces::is_valid_name(&bridge.name) {
+                issues.push(ValidationIssue::new(field("name"), format!("'{}' is not a valid interface name", bridge.name)));
+            } else if names.contains(&bridge.name.as_str()) {
+                issues.push(ValidationIssue::new(field("name"), format!("{} is defined more than once", bridge.name)));
+            }
+            names.push(&bridge.name);
+            for (j, port) in bridge.ports.iter().enumerate() {
+                let field = format!("bridges[{}].ports[{}]", i, j);
+                if self.bridges.iter().any(|b| b.name == *port) {
+                    issues.push(ValidationIssue::new(field, format!("Bridge {} cannot be a port of another bridge", port)));
+                } else if bridge.ports[..j].contains(port)
+                    || self.bridges[..i].iter().any(|b| b.ports.contains(port))
+                {
+                    issues.push(ValidationIssue::new(field, format!("{} is already a port of a bridge", port)));
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// 接口是否由拓扑创建
+    pub fn defines(&self, name: &str) -> bool {
+        self.bridges.iter().any(|b| b.name == name) || self.vlans.iter().any(|v| v.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BridgeStatus {
+    #[serde(flatten)]
+    pub config: Bridge,
+    pub exists: bool,
+    pub up: bool,
+    /// 当前实际加入网桥的端口
+    pub attached_ports: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VlanStatus {
+    #[serde(flatten)]
+    pub config: Vlan,
+    pub exists: bool,
+    pub up: bool,
+}
+
+/// 管理网桥与 VLAN 子接口，拓扑保存在 topology.toml，启动时在 DHCP 服务与端口映射之前重建。
+/// 只增删 topology.toml 中定义的接口，系统中其他的网桥和 VLAN 保持不变。
+pub struct TopologyManager {
+    config_path: PathBuf,
+    config_lock: Mutex<()>,
+}
+
+impl TopologyManager {
+    pub fn new<P: Into<PathBuf>>(config_path: P) -> Self {
+        TopologyManager {
+            config_path: config_path.into(),
+            config_lock: Mutex::new(()),
+        }
+    }
+
+    /// 读取拓扑，文件不存在时为空
+    pub fn read_config(&self) -> Result<Topology> {
+        let content = match fs::read_to_string(&self.config_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Topology::default()),
+            Err(e) => return Err(e.into()),
+        };
+        toml::from_str(&content).map_err(|e| AppError::ConfigParse(format!("{:?}: {}", self.config_path, e)))
+    }
+
+    fn write_config(&self, topology: &Topology) -> Result<()> {
+        let content = toml::to_string_pretty(topology).map_err(|e| AppError::Process(e.to_string()))?;
+        write_atomic(&self.config_path, &content)
+    }
+
+    /// 在锁内修改拓扑：校验、修改系统中的接口、再写入文件
+    fn update_config<F: FnOnce(&mut Topology) -> Result<()>>(&self, update: F) -> Result<()> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+        let mut topology = self.read_config()?;
+        update(&mut topology)?;
+        self.write_config(&topology)
+    }
+
+    pub fn bridges(&self) -> Result<Vec<BridgeStatus>> {
+        Ok(self.read_config()?.bridges.into_iter().map(bridge_status).collect())
+    }
+
+    pub fn bridge(&self, name: &str) -> Result<BridgeStatus> {
+        self.read_config()?
+            .bridges
+            .into_iter()
+            .find(|b| b.name == name)
+            .map(bridge_status)
+            .ok_or_else(|| AppError::NotFound(format!("Bridge {}", name)))
+    }
+
+    pub fn vlans(&self) -> Result<Vec<VlanStatus>> {
+        Ok(self.read_config()?.vlans.into_iter().map(vlan_status).collect())
+    }
+
+    pub fn create_bridge(&self, bridge: Bridge) -> Result<BridgeStatus> {
+        self.update_config(|topology| {
+            if topology.bridges.iter().any(|b| b.name == bridge.name) {
+                return Err(AppError::Conflict(format!("Bridge {} already exists", bridge.name)));
+            }
+            check_new_interface(&bridge.name, InterfaceKind::Bridge)?;
+            topology.bridges.push(bridge.clone());
+            topology.validate()?;
+            check_ports(topology, &bridge)?;
+            apply_bridge(&bridge, &[])
+        })?;
+        self.bridge(&bridge.name)
+    }
+
+    /// 更新网桥的端口与 STP 设置，只增删有变化的端口
+    pub fn update_bridge(&self, bridge: Bridge) -> Result<BridgeStatus> {
+        self.update_config(|topology| {
+            let existing = topology
+                .bridges
+                .iter_mut()
+                .find(|b| b.name == bridge.name)
+                .ok_or_else(|| AppError::NotFound(format!("Bridge {}", bridge.name)))?;
+            let previous = std::mem::replace(existing, bridge.clone());
+            topology.validate()?;
+            check_ports(topology, &bridge)?;
+            apply_bridge(&bridge, &previous.ports)
+        })?;
+        self.bridge(&bridge.name)
+    }
+
+    pub fn add_bridge_port(&self, name: &str, port: &str) -> Result<BridgeStatus> {
+        let mut bridge = self.bridge(name)?.config;
+        if !bridge.ports.iter().any(|p| p == port) {
+            bridge.ports.push(port.to_string());
+        }
+        self.update_bridge(bridge)
+    }
+
+    pub fn remove_bridge_port(&self, name: &str, port: &str) -> Result<BridgeStatus> {
+        let mut bridge = self.bridge(name)?.config;
+        if !bridge.ports.iter().any(|p| p == port) {
+            return Err(AppError::NotFound(format!("{} is not a port of bridge {}", port, name)));
+        }
+        bridge.ports.retain(|p| p != port);
+        self.update_bridge(bridge)
+    }
+
+    /// 删除网桥，内核会自动释放其成员端口。其他子系统的引用由调用方检查
+    pub fn delete_bridge(&self, name: &str) -> Result<()> {
+        self.update_config(|topology| {
+            let index = topology
+                .bridges
+                .iter()
+                .position(|b| b.name == name)
+                .ok_or_else(|| AppError::NotFound(format!("Bridge {}", name)))?;
+            if let Some(vlan) = topology.vlans.iter().find(|v| v.parent == name) {
+                return Err(AppError::Conflict(format!("VLAN {} is defined on bridge {}", vlan.name, name)));
+            }
+            topology.bridges.remove(index);
+            delete_link(name)
+        })
+    }
+
+    pub fn create_vlan(&self, vlan: Vlan) -> Result<VlanStatus> {
+        self.update_config(|topology| {
+            if topology.defines(&vlan.name) {
+                return Err(AppError::Conflict(format!("{} already exists", vlan.name)));
+            }
+            check_new_interface(&vlan.name, InterfaceKind::Vlan)?;
+            if !topology.bridges.iter().any(|b| b.name == vlan.parent) {
+                interfaces::check(&vlan.parent).map_err(|e| AppError::InvalidInput(e.to_string()))?;
+            }
+            topology.vlans.push(vlan.clone());
+            topology.validate()?;
+            apply_vlan(&vlan)
+        })?;
+        Ok(vlan_status(vlan))
+    }
+
+    /// 删除 VLAN 子接口。其他子系统的引用由调用方检查
+    pub fn delete_vlan(&self, name: &str) -> Result<()> {
+        self.update_config(|topology| {
+            let index = topology
+                .vlans
+                .iter()
+                .position(|v| v.name == name)
+                .ok_or_else(|| AppError::NotFound(format!("VLAN {}", name)))?;
+            let vlan = topology.vlans.remove(index);
+            if let Some(bridge) = topology.bridges.iter().find(|b| b.ports.contains(&vlan.name)) {
+                return Err(AppError::Conflict(format!("{} is a port of bridge {}", vlan.name, bridge.name)));
+            }
+            delete_link(&vlan.name)
+        })
+    }
+
+    /// 启动时重建拓扑：先建在物理接口上的 VLAN（它们可能是网桥端口），再建网桥，最后建在网桥上的 VLAN
+    pub fn apply_all(&self) {
+        let topology = match self.read_config().and_then(|t| t.validate().map(|_| t)) {
+            Ok(topology) => topology,
+            Err(e) => {
+                eprintln!("[topology] Not applying {:?}: {}", self.config_path, e);
+                return;
+            }
+        };
+        let on_bridge = |v: &&Vlan| topology.bridges.iter().any(|b| b.name == v.parent);
+
+        for vlan in topology.vlans.iter().filter(|v| !on_bridge(v)) {
+            if let Err(e) = apply_vlan(vlan) {
+                eprintln!("[topology] Failed to create VLAN {}: {}", vlan.name, e);
+            }
+        }
+        for bridge in &topology.bridges {
+            let mut bridge = bridge.clone();
+            bridge.ports.retain(|port| match interfaces::check(port) {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("[topology] Skipping port of {}: {}", bridge.name, e);
+                    false
+                }
+            });
+            if let Err(e) = apply_bridge(&bridge, &[]) {
+                eprintln!("[topology] Failed to create bridge {}: {}", bridge.name, e);
+            }
+        }
+        for vlan in topology.vlans.iter().filter(on_bridge) {
+            if let Err(e) = apply_vlan(vlan) {
+                eprintln!("[topology] Failed to create VLAN {}: {}", vlan.name, e);
+            }
+        }
+    }
+}
+
+fn bridge_status(bridge: Bridge) -> BridgeStatus {
+    let link = interfaces::get(&bridge.name).ok();
+    BridgeStatus {
+        exists: link.is_some(),
+        up: link.is_some_and(|l| l.up),
+        attached_ports: interfaces::bridge_ports(&bridge.name),
+        config: bridge,
+    }
+}
+
+fn vlan_status(vlan: Vlan) -> VlanStatus {
+    let link = interfaces::get(&vlan.name).ok();
+    VlanStatus {
+        exists: link.is_some(),
+        up: link.is_some_and(|l| l.up),
+        config: vlan,
+    }
+}
+
+/// 新接口的名字不能被其他类型的现有接口占用（同类型的视为之前创建的，直接沿用）
+fn check_new_interface(name: &str, kind: InterfaceKind) -> Result<()> {
+    match interfaces::get(name) {
+        Ok(existing) if existing.kind != kind => Err(AppError::Conflict(format!(
+            "Interface {} already exists and is not a {}",
+            name,
+            match kind {
+                InterfaceKind::Bridge => "bridge",
+                _ => "VLAN",
+            }
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// 端口必须存在，且没有加入拓扑之外的其他网桥
+fn check_ports(topology: &Topology, bridge: &Bridge) -> Result<()> {
+    for port in &bridge.ports {
+        if !topology.defines(port) {
+            interfaces::check(port).map_err(|e| AppError::InvalidInput(e.to_string()))?;
+        }
+        let master = interfaces::get(port).ok().and_then(|p| p.master);
+        if let Some(master) = master.filter(|m| *m != bridge.name) {
+            if !topology.bridges.iter().any(|b| b.name == master) {
+                return Err(AppError::Conflict(format!("{} is already a port of bridge {}", port, master)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 创建网桥（已存在时沿用），使成员端口与配置一致
+fn apply_bridge(bridge: &Bridge, previous_ports: &[String]) -> Result<()> {
+    if interfaces::get(&bridge.name).is_err() {
+        run_ip(&["link", "add", "name", &bridge.name, "type", "bridge"])?;
+    }
+    let stp = if bridge.stp { "1" } else { "0" };
+    run_ip(&["link", "set", "dev", &bridge.name, "type", "bridge", "stp_state", stp])?;
+
+    // 只释放之前配置过的端口，hostapd 等自行加入网桥的端口保持不变
+    let attached = interfaces::bridge_ports(&bridge.name);
+    for port in previous_ports.iter().filter(|p| !bridge.ports.contains(p) && attached.contains(p)) {
+        run_ip(&["link", "set", "dev", port, "nomaster"])?;
+    }
+    for port in &bridge.ports {
+        if !attached.contains(port) {
+            run_ip(&["link", "set", "dev", port, "master", &bridge.name])?;
+        }
+        run_ip(&["link", "set", "dev", port, "up"])?;
+    }
+    run_ip(&["link", "set", "dev", &bridge.name, "up"])
+}
+
+fn apply_vlan(vlan: &Vlan) -> Result<()> {
+    if interfaces::get(&vlan.name).is_err() {
+        let id = vlan.id.to_string();
+        run_ip(&["link", "add", "link", &vlan.parent, "name", &vlan.name, "type", "vlan", "id", &id])?;
+    }
+    run_ip(&["link", "set", "dev", &vlan.parent, "up"])?;
+    run_ip(&["link", "set", "dev", &vlan.name, "up"])
+}
+
+fn delete_link(name: &str) -> Result<()> {
+    if interfaces::get(name).is_ok() {
+        run_ip(&["link", "del", "dev", name])?;
+    }
+    Ok(())
+}
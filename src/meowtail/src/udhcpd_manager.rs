@@ -1,70 +1,38 @@
 // src/udhcpd_manager.rs
 
-use std::fs::{self, File};
-use std::io::{self, BufRead};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
 use std::net::Ipv4Addr;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 use std::str::FromStr;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
-use actix_web::{http::StatusCode, HttpResponse, ResponseError};
-use nix::sys::signal::{self, Signal};
-use nix::unistd::Pid;
 use serde::{Deserialize, Serialize};
-use thiserror::Error;
-
-/// 自定义错误类型，用于封装模块中可能发生的所有错误。
-#[derive(Error, Debug)]
-pub enum UdhcpdError {
-    #[error("I/O Error: {0}")]
-    Io(#[from] io::Error),
-    #[error("Process Error: {0}")]
-    Process(String),
-    #[error("PID file error: {0}")]
-    PidFile(String),
-    #[error("Configuration parsing error on line: {0}")]
-    ConfigParse(String),
-    #[error("Nix (Unix-like system call) error: {0}")]
-    Nix(#[from] nix::Error),
-    #[error("Invalid IP address: {0}")]
-    InvalidIp(#[from] std::net::AddrParseError),
-    #[error("Invalid input: {0}")]
-    InvalidInput(String),
-}
-
-// 实现 ResponseError 以便 Actix-web 可以自动将我们的错误转换为 HTTP 响应
-impl ResponseError for UdhcpdError {
-    fn status_code(&self) -> StatusCode {
-        match *self {
-            UdhcpdError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            UdhcpdError::Process(_) => StatusCode::CONFLICT,
-            UdhcpdError::PidFile(_) => StatusCode::NOT_FOUND,
-            UdhcpdError::ConfigParse(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            UdhcpdError::Nix(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            UdhcpdError::InvalidIp(_) => StatusCode::BAD_REQUEST,
-            UdhcpdError::InvalidInput(_) => StatusCode::BAD_REQUEST,
-        }
-    }
-
-    fn error_response(&self) -> HttpResponse {
-        HttpResponse::build(self.status_code()).json(serde_json::json!({ "error": self.to_string() }))
-    }
-}
 
-pub type Result<T> = std::result::Result<T, UdhcpdError>;
+use crate::arp_probe;
+use crate::dhcp_backend::options::DhcpOption;
+use crate::dhcp_backend::{self, BackendKind, DhcpBackend, DhcpLease, LeaseEvent, EVENT_LOG_LEN};
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::fs_util::{content_etag, etag_matches, write_atomic};
+use crate::interfaces::check_interface;
+use crate::lease_import::{self, ImportMode, ImportReport, LeaseRow};
+use crate::mac_addr::MacAddr;
+use crate::supervisor::SupervisorStatus;
 
 /// 代表一个静态租约配置 (添加了 Serialize/Deserialize)
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StaticLease {
-    pub mac: String,
+    pub mac: MacAddr,
     pub ip: Ipv4Addr,
 }
 
 /// 代表 udhcpd.conf 文件的完整配置 (添加了 Serialize/Deserialize)
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct UdhcpdConfig {
     pub start: Option<Ipv4Addr>,
     pub end: Option<Ipv4Addr>,
@@ -72,19 +40,24 @@ pub struct UdhcpdConfig {
     pub subnet_mask: Option<Ipv4Addr>,
     pub dns_servers: Vec<Ipv4Addr>,
     pub router: Option<Ipv4Addr>,
+    pub lease_file: Option<String>,
+    pub pidfile: Option<String>,
+    /// 同时分配的租约上限，未设置时使用服务端的默认值
+    pub max_leases: Option<u32>,
     pub static_leases: Vec<StaticLease>,
+    /// 路由、子网掩码和 DNS 之外的其他 DHCP 选项
+    pub options: Vec<DhcpOption>,
     #[serde(skip)]
-    remaining_lines: Vec<String>,
+    pub(crate) remaining_lines: Vec<String>,
 }
 
 impl UdhcpdConfig {
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let file = File::open(path)?;
-        let reader = io::BufReader::new(file);
+    /// 解析 udhcpd.conf 格式的文本
+    pub fn parse(content: &str) -> Result<Self> {
         let mut config = UdhcpdConfig::default();
 
-        for line in reader.lines() {
-            let line = line?;
+        for line in content.lines() {
+            let line = line.to_string();
             let trimmed_line = line.trim();
             if trimmed_line.is_empty() || trimmed_line.starts_with('#') {
                 continue;
@@ -99,35 +72,60 @@ impl UdhcpdConfig {
                 "start" if parts.len() > 1 => config.start = Some(Ipv4Addr::from_str(parts[1])?),
                 "end" if parts.len() > 1 => config.end = Some(Ipv4Addr::from_str(parts[1])?),
                 "interface" if parts.len() > 1 => config.interface = Some(parts[1].to_string()),
-                "option" if parts.len() > 2 => match parts[1] {
+                "lease_file" if parts.len() > 1 => config.lease_file = Some(parts[1].to_string()),
+                "pidfile" if parts.len() > 1 => config.pidfile = Some(parts[1].to_string()),
+                "max_leases" if parts.len() > 1 => {
+                    config.max_leases = Some(parts[1].parse().map_err(|_| {
+                        AppError::InvalidInput(format!("Invalid max_leases '{}'", parts[1]))
+                    })?)
+                }
+                "option" | "opt" if parts.len() > 2 => match parts[1] {
                     "subnet" => config.subnet_mask = Some(Ipv4Addr::from_str(parts[2])?),
                     "dns" => {
                         config.dns_servers = parts[2..]
                             .iter()
-                            .map(|s| Ipv4Addr::from_str(s).unwrap())
-                            .collect()
+                            .map(|s| Ipv4Addr::from_str(s))
+                            .collect::<std::result::Result<_, _>>()?
                     }
                     "router" => config.router = Some(Ipv4Addr::from_str(parts[2])?),
-                    _ => config.remaining_lines.push(line.clone()),
+                    _ => match DhcpOption::from_udhcpd(&parts[1..]) {
+                        Some(option) => config.options.push(option),
+                        None => config.remaining_lines.push(line.clone()),
+                    },
                 },
-                "static_lease" if parts.len() > 2 => {
-                    config.static_leases.push(StaticLease {
-                        mac: parts[1].to_string(),
+                "static_lease" if parts.len() > 2 => match MacAddr::from_str(parts[1]) {
+                    Ok(mac) => config.static_leases.push(StaticLease {
+                        mac,
                         ip: Ipv4Addr::from_str(parts[2])?,
-                    });
-                }
+                    }),
+                    // 保留无法识别的租约行，不让一行手写错误导致整个配置无法读取
+                    Err(e) => {
+                        eprintln!("Ignoring static lease: {}", e);
+                        config.remaining_lines.push(line.clone());
+                    }
+                },
                 _ => config.remaining_lines.push(line.clone()),
             }
         }
         Ok(config)
     }
 
-    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    /// 渲染为 udhcpd.conf 格式的文本
+    pub fn render(&self) -> String {
         let mut content = String::new();
 
         if let Some(iface) = &self.interface {
             content.push_str(&format!("interface {}\n", iface));
         }
+        if let Some(lease_file) = &self.lease_file {
+            content.push_str(&format!("lease_file {}\n", lease_file));
+        }
+        if let Some(pidfile) = &self.pidfile {
+            content.push_str(&format!("pidfile {}\n", pidfile));
+        }
+        if let Some(max_leases) = self.max_leases {
+            content.push_str(&format!("max_leases {}\n", max_leases));
+        }
         if let Some(start) = self.start {
             content.push_str(&format!("start {}\n", start));
         }
@@ -144,133 +142,544 @@ impl UdhcpdConfig {
             let dns_list: Vec<String> = self.dns_servers.iter().map(|ip| ip.to_string()).collect();
             content.push_str(&format!("option dns {}\n", dns_list.join(" ")));
         }
+        for option in &self.options {
+            content.push_str(&format!("{}\n", option.udhcpd_line()));
+        }
         for lease in &self.static_leases {
             content.push_str(&format!("static_lease {} {}\n", lease.mac, lease.ip));
         }
         for line in &self.remaining_lines {
             content.push_str(&format!("{}\n", line));
         }
+        content
+    }
 
-        fs::write(path, content)?;
-        Ok(())
+    /// 新建配置文件时使用的默认配置
+    pub fn with_defaults(interface: &str) -> Self {
+        UdhcpdConfig {
+            interface: Some(interface.to_string()),
+            start: Some(Ipv4Addr::new(192, 168, 1, 100)),
+            end: Some(Ipv4Addr::new(192, 168, 1, 200)),
+            subnet_mask: Some(Ipv4Addr::new(255, 255, 255, 0)),
+            router: Some(Ipv4Addr::new(192, 168, 1, 1)),
+            lease_file: None,
+            pidfile: None,
+            max_leases: None,
+            dns_servers: vec![Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(1, 1, 1, 1)],
+            static_leases: vec![],
+            options: vec![],
+            remaining_lines: vec!["# Auto-generated by UdhcpdManager".to_string()],
+        }
     }
+
+    /// 对配置做语义校验，有任何问题时返回 `AppError::Validation`
+    pub fn validate(&self) -> Result<()> {
+        let issues = self.validation_issues();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+
+    /// 收集配置中的所有语义问题（子网一致性、地址池顺序与大小、重复的 IP/MAC 等）
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        if let Some(iface) = &self.interface {
+            if iface.trim().is_empty() {
+                issues.push(ValidationIssue::new("interface", "Interface name cannot be empty"));
+            }
+        }
+
+        // 子网掩码必须是连续的 1 后接连续的 0，且至少要能容纳两个主机地址
+        let mask = self.subnet_mask.map(u32::from);
+        if let Some(m) = mask {
+            if !is_contiguous_mask(m) {
+                issues.push(ValidationIssue::new(
+                    "subnet_mask",
+                    format!("{} is not a contiguous netmask", Ipv4Addr::from(m)),
+                ));
+            } else if m.count_ones() > 30 || m == 0 {
+                issues.push(ValidationIssue::new(
+                    "subnet_mask",
+                    format!("/{} leaves no usable host addresses", m.count_ones()),
+                ));
+            }
+        }
+        let mask = mask.filter(|m| is_contiguous_mask(*m) && *m != 0 && m.count_ones() <= 30);
+
+        // 以 router（没有时以 start）确定所服务的子网
+        let network = mask.and_then(|m| {
+            self.router
+                .or(self.start)
+                .map(|ip| u32::from(ip) & m)
+        });
+        let in_subnet = |ip: Ipv4Addr| match (network, mask) {
+            (Some(net), Some(m)) => u32::from(ip) & m == net,
+            _ => true,
+        };
+        let is_host_addr = |ip: Ipv4Addr| match (network, mask) {
+            (Some(net), Some(m)) => {
+                let host = u32::from(ip) & !m;
+                u32::from(ip) & m == net && host != 0 && host != !m
+            }
+            _ => true,
+        };
+
+        if let Some(router) = self.router {
+            if !is_host_addr(router) {
+                issues.push(ValidationIssue::new(
+                    "router",
+                    format!("{} is the network or broadcast address of the subnet", router),
+                ));
+            }
+        }
+
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => {
+                if u32::from(start) > u32::from(end) {
+                    issues.push(ValidationIssue::new(
+                        "start",
+                        format!("Range start {} is above range end {}", start, end),
+                    ));
+                }
+                for (field, ip) in [("start", start), ("end", end)] {
+                    if !in_subnet(ip) {
+                        issues.push(ValidationIssue::new(
+                            field,
+                            format!("{} lies outside the subnet", ip),
+                        ));
+                    } else if !is_host_addr(ip) {
+                        issues.push(ValidationIssue::new(
+                            field,
+                            format!("{} is the network or broadcast address of the subnet", ip),
+                        ));
+                    }
+                }
+                // 地址池不能大于子网可用的主机数，也不能超过租约上限
+                let size = u64::from(u32::from(end).saturating_sub(u32::from(start))) + 1;
+                if u32::from(start) <= u32::from(end) {
+                    if let Some(m) = mask {
+                        let hosts = u64::from(!m) - 1;
+                        if size > hosts {
+                            issues.push(ValidationIssue::new(
+                                "end",
+                                format!(
+                                    "Range of {} addresses does not fit in a /{} subnet ({} hosts)",
+                                    size,
+                                    m.count_ones(),
+                                    hosts
+                                ),
+                            ));
+                        }
+                    }
+                    if let Some(max) = self.max_leases.filter(|max| size > u64::from(*max)) {
+                        issues.push(ValidationIssue::new(
+                            "end",
+                            format!("Range of {} addresses exceeds max_leases {}", size, max),
+                        ));
+                    }
+                }
+                if let Some(router) = self.router {
+                    if u32::from(start) <= u32::from(router) && u32::from(router) <= u32::from(end) {
+                        issues.push(ValidationIssue::new(
+                            "router",
+                            format!("Router {} lies inside the dynamic range", router),
+                        ));
+                    }
+                }
+            }
+            (None, None) => {}
+            _ => issues.push(ValidationIssue::new(
+                "start",
+                "Range start and end must be set together",
+            )),
+        }
+
+        if self.max_leases == Some(0) {
+            issues.push(ValidationIssue::new("max_leases", "max_leases must be at least 1"));
+        }
+
+        for (i, dns) in self.dns_servers.iter().enumerate() {
+            if dns.is_unspecified() || dns.is_broadcast() || dns.is_multicast() {
+                issues.push(ValidationIssue::new(
+                    format!("dns_servers[{}]", i),
+                    format!("{} is not a usable DNS server address", dns),
+                ));
+            }
+        }
+
+        for (i, lease) in self.static_leases.iter().enumerate() {
+            let field = format!("static_leases[{}]", i);
+            if !in_subnet(lease.ip) {
+                issues.push(ValidationIssue::new(
+                    field.as_str(),
+                    format!("{} lies outside the subnet", lease.ip),
+                ));
+            } else if !is_host_addr(lease.ip) {
+                issues.push(ValidationIssue::new(
+                    field.as_str(),
+                    format!("{} is the network or broadcast address of the subnet", lease.ip),
+                ));
+            }
+            if Some(lease.ip) == self.router {
+                issues.push(ValidationIssue::new(
+                    field.as_str(),
+                    format!("{} is the router address", lease.ip),
+                ));
+            }
+            let earlier = &self.static_leases[..i];
+            if earlier.iter().any(|l| l.mac == lease.mac) {
+                issues.push(ValidationIssue::new(
+                    field.as_str(),
+                    format!("MAC {} is reserved more than once", lease.mac),
+                ));
+            }
+            if earlier.iter().any(|l| l.ip == lease.ip) {
+                issues.push(ValidationIssue::new(
+                    field.as_str(),
+                    format!("IP {} is reserved more than once", lease.ip),
+                ));
+            }
+        }
+
+        // 解析时无法识别的静态租约行原样保留在 remaining_lines 中，udhcpd 会忽略它们
+        for line in self.remaining_lines.iter().filter(|l| is_static_lease_line(l)) {
+            issues.push(ValidationIssue::new(
+                "static_leases",
+                format!("Ignored static lease line '{}': expected 'static_lease <mac> <ip>'", line.trim()),
+            ));
+        }
+
+        for (i, option) in self.options.iter().enumerate() {
+            let field = format!("options[{}]", i);
+            issues.extend(option.validation_issues(&field));
+            if self.options[..i].iter().any(|o| o.code == option.code) {
+                issues.push(ValidationIssue::new(
+                    field.as_str(),
+                    format!("Option {} is set more than once", option.code),
+                ));
+            }
+        }
+
+        issues
+    }
+}
+
+/// 地址池中一个有主机应答的地址
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolAddress {
+    pub ip: Ipv4Addr,
+    pub mac: Option<MacAddr>,
+    pub vendor: Option<&'static str>,
+    /// 按静态租约或当前租约应当使用该地址的 MAC
+    pub expected_mac: Option<MacAddr>,
+    /// 应答者不是租约的持有者，客户端拿到该地址时会发生冲突
+    pub conflict: bool,
+}
+
+/// 对整个动态地址池做 ARP 探测的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolCheckReport {
+    pub interface: String,
+    pub start: Ipv4Addr,
+    pub end: Ipv4Addr,
+    pub probed: usize,
+    pub conflicts: usize,
+    pub in_use: Vec<PoolAddress>,
+    pub errors: Vec<String>,
+}
+
+/// 一次地址池检查最多探测的地址数
+const MAX_POOL_CHECK: u32 = 1024;
+
+fn is_static_lease_line(line: &str) -> bool {
+    line.split_whitespace().next() == Some("static_lease")
+}
+
+fn is_contiguous_mask(mask: u32) -> bool {
+    (!mask).wrapping_add(1) & !mask == 0
+}
+
+
+/// `GET /status` 返回的完整状态
+#[derive(Debug, Clone, Serialize)]
+pub struct UdhcpdStatus {
+    #[serde(flatten)]
+    pub process: SupervisorStatus,
+    pub backend: BackendKind,
+    /// 磁盘上的配置是否与运行中的 udhcpd 加载的不同；接管的进程无法得知时为 null
+    pub config_dirty: Option<bool>,
+    pub auto_apply: bool,
 }
 
 pub struct UdhcpdManager {
-    executable_path: String,
     config_path: PathBuf,
-    pid_path: PathBuf,
     config_lock: Mutex<()>,
+    backend: Box<dyn DhcpBackend>,
+    /// 运行中的 udhcpd 启动时加载的配置文件的 ETag
+    applied_etag: Arc<Mutex<Option<String>>>,
+    auto_apply: AtomicBool,
+    /// 与服务端实现无关的事件，例如发现其他 DHCP 服务端
+    events: Mutex<VecDeque<LeaseEvent>>,
 }
 
 impl UdhcpdManager {
     pub fn new<P1: Into<PathBuf>, P2: Into<PathBuf>>(
-        executable_path: &str,
+        kind: BackendKind,
         config_path: P1,
         pid_path: P2,
     ) -> Self {
+        let config_path = config_path.into();
+        let backend = dhcp_backend::create(kind, &config_path, pid_path.into());
+
+        // 每次启动（包括自动重启）时记录 udhcpd 即将加载的配置
+        let applied_etag = Arc::new(Mutex::new(None));
+        let hook_etag = applied_etag.clone();
+        let hook_path = config_path.clone();
+        backend.set_start_hook(Box::new(move || {
+            let etag = fs::read_to_string(&hook_path).ok().map(|c| content_etag(&c));
+            *hook_etag.lock().unwrap() = etag;
+        }));
+
         UdhcpdManager {
-            executable_path: executable_path.to_string(),
-            config_path: config_path.into(),
-            pid_path: pid_path.into(),
+            backend,
+            config_path,
             config_lock: Mutex::new(()),
+            applied_etag,
+            auto_apply: AtomicBool::new(false),
+            events: Mutex::new(VecDeque::new()),
         }
     }
 
     pub fn start(&self) -> Result<()> {
         if self.is_running() {
-            return Err(UdhcpdError::Process(
+            return Err(AppError::Process(
                 "udhcpd is already running.".to_string(),
             ));
         }
         if !self.config_path.exists() {
-            return Err(UdhcpdError::Io(io::Error::new(
+            return Err(AppError::Io(io::Error::new(
                 io::ErrorKind::NotFound,
                 "Configuration file not found. Cannot start service.",
             )));
         }
 
-        let child = Command::new(&self.executable_path)
-            .arg(self.config_path.to_str().unwrap())
-            .spawn()
-            .map_err(|e| UdhcpdError::Io(e))?;
-
-        let pid = child.id();
-
-        fs::write(&self.pid_path, pid.to_string())
-            .map_err(|e| UdhcpdError::PidFile(format!("Failed to write PID file: {}", e)))?;
+        // 同一配置的 udhcpd 已在 meowtail 之外运行时直接接管，而不是再启动一个
+        if let Some(pid) = self.backend.adopt() {
+            println!("Found running udhcpd with PID {}, supervising it instead of starting a new one.", pid);
+            return Ok(());
+        }
 
-        println!("Started udhcpd with PID: {}. PID file created at {:?}", pid, self.pid_path);
+        self.backend.start()?;
         Ok(())
     }
 
     pub fn stop(&self) -> Result<()> {
-        let pid = match self.get_pid() {
-            Ok(pid) => pid,
-            Err(_) => return Ok(()),
-        };
+        self.backend.stop()?;
+        *self.applied_etag.lock().unwrap() = None;
+        Ok(())
+    }
 
-        signal::kill(pid, Signal::SIGTERM)?;
+    /// 接管已经在运行的同一配置的 udhcpd（例如 meowtail 重启前启动的）
+    pub fn adopt_running(&self) -> Option<u32> {
+        self.backend.adopt()
+    }
 
-        for _ in 0..30 {
-            if !self.is_process_alive(pid) {
-                let _ = fs::remove_file(&self.pid_path);
-                return Ok(());
+    /// 重启 udhcpd。重启前先让 udhcpd 把租约刷到磁盘并备份，
+    /// 停止后若租约文件丢失或被清空则从备份恢复，避免客户端丢失已分配的地址。
+    pub fn restart(&self) -> Result<()> {
+        let lease_file = self.lease_file();
+        let backup = if self.is_running() {
+            self.backup_leases(&lease_file)
+        } else {
+            None
+        };
+
+        self.stop()?;
+        if let Some(backup) = backup {
+            let lost = fs::metadata(&lease_file).map(|m| m.len() == 0).unwrap_or(true);
+            if lost {
+                println!("Restoring udhcpd leases from {:?}", backup);
+                fs::copy(&backup, &lease_file)?;
             }
-            thread::sleep(Duration::from_millis(100));
         }
+        thread::sleep(Duration::from_millis(200));
+        self.start()
+    }
+
+    fn lease_file(&self) -> PathBuf {
+        self.read_config()
+            .ok()
+            .and_then(|c| c.lease_file)
+            .unwrap_or_else(|| self.backend.default_lease_file().to_string())
+            .into()
+    }
 
-        if self.is_process_alive(pid) {
-            println!("Process did not respond to SIGTERM, sending SIGKILL...");
-            signal::kill(pid, Signal::SIGKILL)?;
+    /// 让服务端立即写出租约文件，等待写入完成后复制一份 .bak
+    fn backup_leases(&self, lease_file: &Path) -> Option<PathBuf> {
+        let modified = || fs::metadata(lease_file).and_then(|m| m.modified()).ok();
+        let before = modified();
+        if self.backend.flush_leases().is_ok() {
+            for _ in 0..10 {
+                thread::sleep(Duration::from_millis(100));
+                if modified() != before {
+                    break;
+                }
+            }
         }
 
-        let _ = fs::remove_file(&self.pid_path);
-        Ok(())
+        let mut backup = lease_file.as_os_str().to_owned();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+        match fs::copy(lease_file, &backup) {
+            Ok(_) => Some(backup),
+            Err(e) => {
+                eprintln!("Failed to back up udhcpd leases {:?}: {}", lease_file, e);
+                None
+            }
+        }
     }
 
-    pub fn restart(&self) -> Result<()> {
-        self.stop()?;
-        thread::sleep(Duration::from_millis(200));
-        self.start()
+    /// 运行状态、PID、退出记录与重启次数，以及配置是否尚未生效
+    pub fn status(&self) -> UdhcpdStatus {
+        UdhcpdStatus {
+            process: self.backend.status(),
+            backend: self.backend.kind(),
+            config_dirty: self.config_dirty(),
+            auto_apply: self.auto_apply(),
+        }
     }
 
-    pub fn is_running(&self) -> bool {
-        match self.get_pid() {
-            Ok(pid) => self.is_process_alive(pid),
-            Err(_) => false,
+    /// 运行中的 udhcpd 是否还在使用旧配置。未运行时新配置会在下次启动时生效，视为 false
+    pub fn config_dirty(&self) -> Option<bool> {
+        if !self.is_running() {
+            return Some(false);
         }
+        let applied = self.applied_etag.lock().unwrap().clone()?;
+        let current = fs::read_to_string(&self.config_path).ok().map(|c| content_etag(&c));
+        Some(current.as_deref() != Some(applied.as_str()))
+    }
+
+    pub fn auto_apply(&self) -> bool {
+        self.auto_apply.load(Ordering::SeqCst)
     }
 
-    fn get_pid(&self) -> Result<Pid> {
-        if !self.pid_path.exists() {
-            return Err(UdhcpdError::PidFile("PID file not found.".to_string()));
+    /// 开启后每次成功写入配置都会自动重启正在运行的 udhcpd
+    pub fn set_auto_apply(&self, enabled: bool) {
+        self.auto_apply.store(enabled, Ordering::SeqCst);
+    }
+
+    fn apply_if_enabled(&self) -> Result<()> {
+        if !self.auto_apply() || self.config_dirty() == Some(false) {
+            return Ok(());
         }
-        let content = fs::read_to_string(&self.pid_path)?;
-        let pid_val = content
-            .trim()
-            .parse::<i32>()
-            .map_err(|_| UdhcpdError::PidFile("Failed to parse PID from file.".to_string()))?;
-        Ok(Pid::from_raw(pid_val))
+        self.restart().map_err(|e| {
+            AppError::Process(format!("Configuration saved, but applying it failed: {}", e))
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.backend.is_running()
+    }
+
+    /// 当前分配出去的动态租约
+    pub fn leases(&self) -> Result<Vec<DhcpLease>> {
+        self.backend.read_leases(&self.lease_file())
     }
 
-    fn is_process_alive(&self, pid: Pid) -> bool {
-        signal::kill(pid, None).is_ok()
+    /// 最近的租约事件，按时间排序：内置服务端记录的报文交互与本实例上的其他事件
+    pub fn events(&self) -> Vec<LeaseEvent> {
+        let mut events = self.backend.events();
+        events.extend(self.events.lock().unwrap().iter().cloned());
+        events.sort_by_key(|e| e.at);
+        events
+    }
+
+    /// 在本实例的事件日志中记录一条事件
+    pub fn record_event(&self, event: LeaseEvent) {
+        let mut events = self.events.lock().unwrap();
+        if events.len() >= EVENT_LOG_LEN {
+            events.pop_front();
+        }
+        events.push_back(event);
     }
 
     pub fn read_config(&self) -> Result<UdhcpdConfig> {
-        UdhcpdConfig::from_file(&self.config_path)
+        let content = fs::read_to_string(&self.config_path)?;
+        self.backend.parse_config(&content)
     }
 
     pub fn write_config(&self, config: &UdhcpdConfig) -> Result<()> {
-        config.write_to_file(&self.config_path)
+        write_atomic(&self.config_path, &self.backend.render_config(config))
     }
 
-    pub fn create_config_with_defaults(&self, interface: &str, overwrite: bool) -> Result<()> {
-        let _guard = self.config_lock.lock().map_err(|e| UdhcpdError::Process(format!("Failed to acquire config lock: {}", e)))?;
+    /// 读取配置及其 ETag（配置文件内容的哈希）
+    pub fn read_config_with_etag(&self) -> Result<(UdhcpdConfig, String)> {
+        let content = fs::read_to_string(&self.config_path)?;
+        Ok((self.backend.parse_config(&content)?, content_etag(&content)))
+    }
+
+    /// 整体替换配置：校验通过后在配置锁内一次性原子写入。
+    /// `if_match` 为客户端上次读取时拿到的 ETag，与当前文件不一致时拒绝写入，避免覆盖他人的修改。
+    /// 返回新配置的 ETag。
+    pub fn replace_config(&self, mut config: UdhcpdConfig, if_match: Option<&str>) -> Result<String> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+
+        let (current, etag) = self.read_config_with_etag()?;
+        if let Some(expected) = if_match {
+            if !etag_matches(expected, &etag) {
+                return Err(AppError::PreconditionFailed(
+                    "Configuration was modified since it was read.".to_string(),
+                ));
+            }
+        }
+
+        config.validate()?;
+        if let Some(interface) = config.interface.as_deref().filter(|i| Some(*i) != current.interface.as_deref()) {
+            check_interface(interface)?;
+        }
+        // JSON 中不包含未识别的配置行，沿用文件中已有的；
+        // 提交的 static_leases 是完整的静态租约列表，文件中无法识别的静态租约行随之丢弃
+        config.remaining_lines = current
+            .remaining_lines
+            .into_iter()
+            .filter(|l| !is_static_lease_line(l))
+            .collect();
+        self.write_config(&config)?;
+        drop(_guard);
+
+        self.apply_if_enabled()?;
+        Ok(content_etag(&self.backend.render_config(&config)))
+    }
+
+    /// 批量导入静态租约：在配置锁内计算结果，全部通过校验后一次性原子写入。
+    /// dry_run 时只返回报告；否则任何一行有问题都不写入，返回 `AppError::Validation`。
+    pub fn import_static_leases(&self, rows: &[LeaseRow], mode: ImportMode, dry_run: bool) -> Result<ImportReport> {
+        let guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+
+        let current = self.read_config()?;
+        let (config, report) = lease_import::plan(&current, rows, mode, dry_run);
+        if dry_run {
+            return Ok(report);
+        }
+        if !report.issues.is_empty() {
+            return Err(AppError::Validation(report.issues));
+        }
+        self.write_config(&config)?;
+        drop(guard);
+
+        self.apply_if_enabled()?;
+        Ok(report)
+    }
+
+    /// 写入一份新的配置文件，已存在且未指定 overwrite 时报错
+    pub fn create_config(&self, config: UdhcpdConfig, overwrite: bool) -> Result<()> {
+        let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
 
         if self.config_path.exists() && !overwrite {
-            return Err(UdhcpdError::Io(io::Error::new(
+            return Err(AppError::Io(io::Error::new(
                 io::ErrorKind::AlreadyExists,
                 "Configuration file already exists. Set overwrite to true to replace it.",
             )));
@@ -280,72 +689,291 @@ impl UdhcpdManager {
             fs::create_dir_all(parent)?;
         }
 
-        let default_config = UdhcpdConfig {
-            interface: Some(interface.to_string()),
-            start: Some(Ipv4Addr::new(192, 168, 1, 100)),
-            end: Some(Ipv4Addr::new(192, 168, 1, 200)),
-            subnet_mask: Some(Ipv4Addr::new(255, 255, 255, 0)),
-            router: Some(Ipv4Addr::new(192, 168, 1, 1)),
-            dns_servers: vec![Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(1, 1, 1, 1)],
-            static_leases: vec![],
-            remaining_lines: vec!["# Auto-generated by UdhcpdManager".to_string()],
-        };
+        self.write_config(&config)
+    }
 
-        self.write_config(&default_config)
+    /// 在配置锁内读取配置、应用修改、整体校验，校验通过后才写回文件
+    fn update_config<F: FnOnce(&mut UdhcpdConfig)>(&self, update: F) -> Result<()> {
+        self.try_update_config(|config| {
+            update(config);
+            Ok(())
+        })
+    }
+
+    /// 与 update_config 相同，`update` 返回错误时不写入
+    fn try_update_config<F: FnOnce(&mut UdhcpdConfig) -> Result<()>>(&self, update: F) -> Result<()> {
+        {
+            let _guard = self.config_lock.lock().map_err(|e| AppError::Process(format!("Failed to acquire config lock: {}", e)))?;
+            let mut config = self.read_config()?;
+            // 只拒绝本次修改引入的问题，文件中原有的问题不应挡住用来修复它的修改
+            let existing = config.validation_issues();
+            update(&mut config)?;
+            let introduced: Vec<ValidationIssue> = config
+                .validation_issues()
+                .into_iter()
+                .filter(|issue| !existing.contains(issue))
+                .collect();
+            if !introduced.is_empty() {
+                return Err(AppError::Validation(introduced));
+            }
+            self.write_config(&config)?;
+        }
+        self.apply_if_enabled()
     }
 
     pub fn set_dhcp_range(&self, start: Ipv4Addr, end: Ipv4Addr) -> Result<()> {
-        let _guard = self.config_lock.lock().map_err(|e| UdhcpdError::Process(format!("Failed to acquire config lock: {}", e)))?;
-        let mut config = self.read_config()?;
-        config.start = Some(start);
-        config.end = Some(end);
-        self.write_config(&config)
+        self.update_config(|config| {
+            config.start = Some(start);
+            config.end = Some(end);
+        })
     }
 
     pub fn set_subnet_mask(&self, mask: Ipv4Addr) -> Result<()> {
-        let _guard = self.config_lock.lock().map_err(|e| UdhcpdError::Process(format!("Failed to acquire config lock: {}", e)))?;
-        let mut config = self.read_config()?;
-        config.subnet_mask = Some(mask);
-        self.write_config(&config)
+        self.update_config(|config| config.subnet_mask = Some(mask))
     }
 
     pub fn set_dns_servers(&self, servers: Vec<Ipv4Addr>) -> Result<()> {
-        let _guard = self.config_lock.lock().map_err(|e| UdhcpdError::Process(format!("Failed to acquire config lock: {}", e)))?;
-        let mut config = self.read_config()?;
-        config.dns_servers = servers;
-        self.write_config(&config)
+        self.update_config(|config| config.dns_servers = servers)
     }
 
     pub fn set_gateway(&self, gateway: Ipv4Addr) -> Result<()> {
-        let _guard = self.config_lock.lock().map_err(|e| UdhcpdError::Process(format!("Failed to acquire config lock: {}", e)))?;
-        let mut config = self.read_config()?;
-        config.router = Some(gateway);
-        self.write_config(&config)
+        self.update_config(|config| config.router = Some(gateway))
     }
 
     pub fn set_interface(&self, interface: String) -> Result<()> {
-        let _guard = self.config_lock.lock().map_err(|e| UdhcpdError::Process(format!("Failed to acquire config lock: {}", e)))?;
-        let mut config = self.read_config()?;
-        config.interface = Some(interface);
-        self.write_config(&config)
+        check_interface(&interface)?;
+        self.update_config(|config| config.interface = Some(interface))
     }
 
     pub fn add_or_update_static_lease(&self, lease: StaticLease) -> Result<()> {
-        let _guard = self.config_lock.lock().map_err(|e| UdhcpdError::Process(format!("Failed to acquire config lock: {}", e)))?;
-        let mut config = self.read_config()?;
-        if let Some(existing_lease) = config.static_leases.iter_mut().find(|l| l.mac == lease.mac)
-        {
-            *existing_lease = lease;
-        } else {
-            config.static_leases.push(lease);
+        self.update_config(|config| {
+            if let Some(existing_lease) = config.static_leases.iter_mut().find(|l| l.mac == lease.mac)
+            {
+                *existing_lease = lease;
+            } else {
+                config.static_leases.push(lease);
+            }
+        })
+    }
+
+    pub fn remove_static_lease(&self, mac_address: MacAddr) -> Result<()> {
+        self.update_config(|config| config.static_leases.retain(|l| l.mac != mac_address))
+    }
+
+    /// 用 ARP 探测静态租约的地址是否已被其他 MAC 使用，被占用时返回 `AppError::Conflict`
+    pub fn check_lease_address(&self, lease: &StaticLease) -> Result<()> {
+        let interface = self.served_interface()?;
+        let result = arp_probe::probe(&interface, lease.ip, 1)?;
+        match result.mac {
+            Some(mac) if mac == lease.mac => Ok(()),
+            _ if !result.in_use => Ok(()),
+            Some(mac) => Err(AppError::Conflict(format!(
+                "{} is already in use by {} on {}",
+                lease.ip, mac, interface
+            ))),
+            None => Err(AppError::Conflict(format!(
+                "{} is already in use on {}",
+                lease.ip, interface
+            ))),
         }
-        self.write_config(&config)
     }
 
-    pub fn remove_static_lease(&self, mac_address: &str) -> Result<()> {
-        let _guard = self.config_lock.lock().map_err(|e| UdhcpdError::Process(format!("Failed to acquire config lock: {}", e)))?;
-        let mut config = self.read_config()?;
-        config.static_leases.retain(|l| l.mac != mac_address);
-        self.write_config(&config)
+    /// ARP 探测动态地址池中的每个地址，报告有主机应答的地址
+    pub fn check_pool(&self) -> Result<PoolCheckReport> {
+        let config = self.read_config()?;
+        let interface = self.served_interface()?;
+        let (start, end) = match (config.start, config.end) {
+            (Some(start), Some(end)) if u32::from(start) <= u32::from(end) => (start, end),
+            _ => return Err(AppError::InvalidInput("The DHCP range is not configured".to_string())),
+        };
+        if u32::from(end) - u32::from(start) >= MAX_POOL_CHECK {
+            return Err(AppError::InvalidInput(format!(
+                "The DHCP range is larger than {} addresses",
+                MAX_POOL_CHECK
+            )));
+        }
+
+        let ips: Vec<Ipv4Addr> = (u32::from(start)..=u32::from(end)).map(Ipv4Addr::from).collect();
+        let active = self.leases().unwrap_or_default();
+        let expected = |ip: Ipv4Addr| {
+            config
+                .static_leases
+                .iter()
+                .find(|l| l.ip == ip)
+                .map(|l| l.mac)
+                .or_else(|| {
+                    active
+                        .iter()
+                        .find(|l| l.ip == ip)
+                        .and_then(|l| MacAddr::from_str(&l.mac).ok())
+                })
+        };
+
+        let mut in_use = Vec::new();
+        let mut errors = Vec::new();
+        for result in arp_probe::probe_many(&interface, &ips, 1) {
+            match result {
+                Ok(r) if r.in_use => {
+                    let expected_mac = expected(r.ip);
+                    in_use.push(PoolAddress {
+                        ip: r.ip,
+                        mac: r.mac,
+                        vendor: r.vendor,
+                        expected_mac,
+                        conflict: expected_mac.is_none() || expected_mac != r.mac,
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        Ok(PoolCheckReport {
+            interface,
+            start,
+            end,
+            probed: ips.len(),
+            conflicts: in_use.iter().filter(|a| a.conflict).count(),
+            in_use,
+            errors,
+        })
+    }
+
+    fn served_interface(&self) -> Result<String> {
+        self.read_config()?
+            .interface
+            .filter(|i| !i.is_empty())
+            .ok_or_else(|| AppError::InvalidInput("No interface is configured".to_string()))
+    }
+
+    /// 新增或替换同一代码的 DHCP 选项
+    pub fn set_option(&self, option: DhcpOption) -> Result<()> {
+        self.update_config(|config| {
+            match config.options.iter_mut().find(|o| o.code == option.code) {
+                Some(existing) => *existing = option,
+                None => config.options.push(option),
+            }
+        })
+    }
+
+    pub fn remove_option(&self, code: u8) -> Result<()> {
+        self.try_update_config(|config| {
+            if !config.options.iter().any(|o| o.code == code) {
+                return Err(AppError::NotFound(format!("Option {}", code)));
+            }
+            config.options.retain(|o| o.code != code);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(config: &UdhcpdConfig) -> Vec<String> {
+        config.validation_issues().into_iter().map(|i| i.field).collect()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(UdhcpdConfig::with_defaults("eth0").validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_non_contiguous_mask() {
+        let mut config = UdhcpdConfig::with_defaults("eth0");
+        config.subnet_mask = Some(Ipv4Addr::new(255, 0, 255, 0));
+        assert_eq!(fields(&config), vec!["subnet_mask"]);
+    }
+
+    #[test]
+    fn rejects_reversed_and_foreign_range() {
+        let mut config = UdhcpdConfig::with_defaults("eth0");
+        config.start = Some(Ipv4Addr::new(192, 168, 1, 200));
+        config.end = Some(Ipv4Addr::new(192, 168, 1, 100));
+        assert_eq!(fields(&config), vec!["start"]);
+
+        config.end = Some(Ipv4Addr::new(192, 168, 2, 250));
+        assert!(fields(&config).contains(&"end".to_string()));
+    }
+
+    #[test]
+    fn rejects_range_larger_than_subnet() {
+        let mut config = UdhcpdConfig::with_defaults("eth0");
+        config.router = None;
+        config.start = Some(Ipv4Addr::new(192, 168, 1, 0));
+        config.end = Some(Ipv4Addr::new(192, 168, 1, 255));
+        let issues = config.validation_issues();
+        assert!(issues.iter().any(|i| i.message.contains("does not fit")));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn range_respects_max_leases() {
+        let mut config = UdhcpdConfig::with_defaults("eth0");
+        config.max_leases = Some(101);
+        assert!(config.validate().is_ok());
+        config.max_leases = Some(50);
+        assert_eq!(fields(&config), vec!["end"]);
+        config.max_leases = Some(0);
+        assert!(fields(&config).contains(&"max_leases".to_string()));
+    }
+
+    #[test]
+    fn reports_unparsable_static_lease_lines() {
+        let mut config = UdhcpdConfig::with_defaults("eth0");
+        config.remaining_lines.push("static_lease zz:bb:cc:dd:ee:ff 192.168.1.10".to_string());
+        let content = config.render();
+        let parsed = UdhcpdConfig::parse(&content).unwrap();
+        assert!(parsed.static_leases.is_empty());
+        assert_eq!(fields(&parsed), vec!["static_leases"]);
+    }
+
+    #[test]
+    fn bad_dns_address_is_an_error() {
+        let content = "interface eth0\noption dns 192.168.1.1 not-an-ip\n";
+        assert!(matches!(UdhcpdConfig::parse(content), Err(AppError::InvalidIp(_))));
+    }
+
+    #[test]
+    fn max_leases_round_trips() {
+        let mut config = UdhcpdConfig::with_defaults("eth0");
+        config.max_leases = Some(150);
+        let parsed = UdhcpdConfig::parse(&config.render()).unwrap();
+        assert_eq!(parsed.max_leases, Some(150));
+    }
+
+    #[test]
+    fn rejects_router_in_range_and_duplicate_reservations() {
+        let mut config = UdhcpdConfig::with_defaults("eth0");
+        config.router = Some(Ipv4Addr::new(192, 168, 1, 150));
+        assert_eq!(fields(&config), vec!["router"]);
+
+        let mut config = UdhcpdConfig::with_defaults("eth0");
+        let mac = MacAddr::from_str("aa:bb:cc:dd:ee:ff").unwrap();
+        config.static_leases = vec![
+            StaticLease { mac, ip: Ipv4Addr::new(192, 168, 1, 10) },
+            StaticLease { mac, ip: Ipv4Addr::new(192, 168, 1, 10) },
+        ];
+        assert_eq!(fields(&config), vec!["static_leases[1]", "static_leases[1]"]);
+    }
+
+    #[test]
+    fn setter_can_repair_an_invalid_file() {
+        let dir = std::env::temp_dir().join(format!("meowtail-validate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("udhcpd.conf");
+        let mut config = UdhcpdConfig::with_defaults("eth0");
+        config.end = Some(Ipv4Addr::new(192, 168, 2, 50));
+        fs::write(&path, config.render()).unwrap();
+
+        let manager = UdhcpdManager::new(BackendKind::Udhcpd, &path, dir.join("udhcpd.pid"));
+        // 原有的问题不阻止无关的修改，但新引入的问题仍会被拒绝
+        assert!(manager.set_dns_servers(vec![Ipv4Addr::new(9, 9, 9, 9)]).is_ok());
+        assert!(manager.set_dns_servers(vec![Ipv4Addr::UNSPECIFIED]).is_err());
+        assert!(manager
+            .set_dhcp_range(Ipv4Addr::new(192, 168, 1, 100), Ipv4Addr::new(192, 168, 1, 150))
+            .is_ok());
+        assert!(manager.read_config().unwrap().validate().is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
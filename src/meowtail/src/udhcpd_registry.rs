@@ -0,0 +1,175 @@
+// src/udhcpd_registry.rs
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+
+use crate::dhcp_backend::BackendKind;
+use crate::error::{AppError, Result};
+use crate::interfaces::check_interface;
+use crate::udhcpd_manager::{UdhcpdConfig, UdhcpdManager, UdhcpdStatus};
+
+/// 沿用单实例时代的文件路径，升级后原有配置无需迁移
+pub const DEFAULT_INSTANCE: &str = "default";
+
+/// 与 `/api/udhcpd/instances` 路由冲突的名字
+const RESERVED_NAMES: &[&str] = &["instances"];
+
+/// 一个实例使用的文件
+struct InstancePaths {
+    config: PathBuf,
+    pid: PathBuf,
+    lease: Option<String>,
+    server_pid: Option<String>,
+}
+
+impl InstancePaths {
+    fn for_instance(kind: BackendKind, name: &str) -> Self {
+        let stem = kind.file_stem();
+        if name == DEFAULT_INSTANCE {
+            // udhcpd 和内置服务端的默认实例使用各自的默认租约文件；
+            // dnsmasq 的内置路径会与系统中作为 DNS 运行的 dnsmasq 冲突，总是显式指定
+            let (lease, server_pid) = match kind {
+                BackendKind::Udhcpd | BackendKind::Native => (None, None),
+                BackendKind::Dnsmasq => (
+                    Some(format!("/var/lib/misc/{}.leases", stem)),
+                    Some(format!("/var/run/{}.pid", stem)),
+                ),
+            };
+            InstancePaths {
+                config: PathBuf::from(format!("./{}.conf", stem)),
+                pid: PathBuf::from(format!("/tmp/meowtail_{}.pid", stem.replace('-', "_"))),
+                lease,
+                server_pid,
+            }
+        } else {
+            InstancePaths {
+                config: PathBuf::from(format!("./{}-{}.conf", stem, name)),
+                pid: PathBuf::from(format!("/tmp/meowtail_{}_{}.pid", stem.replace('-', "_"), name)),
+                lease: Some(format!("/var/lib/misc/{}-{}.leases", stem, name)),
+                server_pid: Some(format!("/var/run/{}-{}.pid", stem, name)),
+            }
+        }
+    }
+}
+
+/// 实例列表中的一项
+#[derive(Debug, Clone, Serialize)]
+pub struct InstanceSummary {
+    pub name: String,
+    pub interface: Option<String>,
+    pub status: UdhcpdStatus,
+}
+
+/// 按名字管理多个 DHCP 服务实例，每个实例有独立的配置文件、PID 文件和租约文件
+pub struct UdhcpdRegistry {
+    kind: BackendKind,
+    instances: RwLock<BTreeMap<String, Arc<UdhcpdManager>>>,
+}
+
+impl UdhcpdRegistry {
+    pub fn new(kind: BackendKind) -> Self {
+        UdhcpdRegistry {
+            kind,
+            instances: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<UdhcpdManager>> {
+        self.instances
+            .read()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("udhcpd instance '{}'", name)))
+    }
+
+    /// 所有实例，按名字排序
+    pub fn instances(&self) -> Vec<(String, Arc<UdhcpdManager>)> {
+        self.instances
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, manager)| (name.clone(), manager.clone()))
+            .collect()
+    }
+
+    pub fn list(&self) -> Vec<InstanceSummary> {
+        self.instances()
+            .into_iter()
+            .map(|(name, manager)| InstanceSummary {
+                name,
+                interface: manager.read_config().ok().and_then(|c| c.interface),
+                status: manager.status(),
+            })
+            .collect()
+    }
+
+    /// 注册一个实例；配置文件不存在时以 `interface` 生成默认配置，已存在则沿用
+    pub fn open(&self, name: &str, interface: &str) -> Result<Arc<UdhcpdManager>> {
+        validate_name(name)?;
+        if let Ok(existing) = self.get(name) {
+            return Ok(existing);
+        }
+
+        let paths = InstancePaths::for_instance(self.kind, name);
+        let manager = Arc::new(UdhcpdManager::new(self.kind, &paths.config, &paths.pid));
+        if !paths.config.exists() {
+            let mut config = UdhcpdConfig::with_defaults(interface);
+            config.lease_file = paths.lease.clone();
+            config.pidfile = paths.server_pid.clone();
+            manager.create_config(config, false)?;
+            println!("Created default configuration file at {:?} for udhcpd instance '{}'.", paths.config, name);
+        }
+
+        self.instances
+            .write()
+            .unwrap()
+            .insert(name.to_string(), manager.clone());
+        Ok(manager)
+    }
+
+    /// 新建实例，同名实例已存在时报错
+    pub fn create(&self, name: &str, interface: &str) -> Result<Arc<UdhcpdManager>> {
+        if self.get(name).is_ok() {
+            return Err(AppError::Process(format!("udhcpd instance '{}' already exists.", name)));
+        }
+        check_interface(interface)?;
+        self.open(name, interface)
+    }
+
+    /// 停止并删除实例及其配置文件，租约文件保留
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let manager = self.get(name)?;
+        manager.stop()?;
+        self.instances.write().unwrap().remove(name);
+
+        let paths = InstancePaths::for_instance(self.kind, name);
+        if let Err(e) = fs::remove_file(&paths.config) {
+            eprintln!("Failed to remove {:?}: {}", paths.config, e);
+        }
+        Ok(())
+    }
+}
+
+/// 实例名会出现在 URL 和文件名中，只允许小写字母、数字、`-` 和 `_`
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid instance name '{}': use 1-32 characters from a-z, 0-9, '-' and '_'",
+            name
+        )));
+    }
+    if RESERVED_NAMES.contains(&name) {
+        return Err(AppError::InvalidInput(format!("Instance name '{}' is reserved", name)));
+    }
+    Ok(())
+}
@@ -0,0 +1,552 @@
+// src/upnp/igd.rs
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
+use actix_web::{dev::Server, web, HttpRequest, HttpResponse, HttpServer};
+
+use super::{remaining_secs, MapError, MapRequest, UpnpManager};
+use crate::portmap_manager::{LeaseOrigin, PortMapLease};
+
+pub const DESCRIPTION_PATH: &str = "/rootDesc.xml";
+const SCPD_PATH: &str = "/WANIPCn.xml";
+const CONTROL_PATH: &str = "/ctl/IPConn";
+
+pub const IGD_DEVICE: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+pub const WAN_DEVICE: &str = "urn:schemas-upnp-org:device:WANDevice:1";
+pub const CONNECTION_DEVICE_TYPE: &str = "urn:schemas-upnp-org:device:WANConnectionDevice:1";
+pub const WANIP_SERVICE: &str = "urn:schemas-upnp-org:service:WANIPConnection:1";
+
+/// 三个嵌套设备的编号，用于从配置中的 UUID 派生各自的 UDN
+pub const ROOT_DEVICE: u8 = 0;
+pub const WAN_DEVICE_INDEX: u8 = 1;
+pub const CONNECTION_DEVICE: u8 = 2;
+
+/// 用编号替换 UUID 的最后两位十六进制数字
+pub fn device_uuid(uuid: &str, index: u8) -> String {
+    format!("{}{:02x}", &uuid[..uuid.len().saturating_sub(2)], index)
+}
+
+/// 在 LAN 接口的地址上提供设备描述和 SOAP 控制；没有可用地址时返回 None
+pub fn server(manager: Arc<UpnpManager>) -> Option<Server> {
+    let config = manager.config();
+    if !config.enabled || !config.upnp {
+        return None;
+    }
+    let addresses: Vec<Ipv4Addr> = config
+        .interfaces
+        .iter()
+        .filter_map(|name| crate::interfaces::get(name).ok())
+        .flat_map(|interface| interface.ipv4)
+        .filter_map(|a| match a.address {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    let data = web::Data::from(manager);
+    let mut server = HttpServer::new(move || {
+        actix_web::App::new()
+            .app_data(data.clone())
+            .route(DESCRIPTION_PATH, web::get().to(description))
+            .route(SCPD_PATH, web::get().to(scpd))
+            .route(CONTROL_PATH, web::post().to(control))
+    })
+    .workers(1);
+    let mut bound = false;
+    for address in addresses {
+        match server.bind((address, config.http_port)) {
+            Ok(s) => {
+                server = s;
+                bound = true;
+            }
+            Err(e) => {
+                eprintln!("[upnp] Failed to listen on {}:{}: {}", address, config.http_port, e);
+                return None;
+            }
+        }
+    }
+    bound.then(|| server.run())
+}
+
+fn xml(body: String) -> HttpResponse {
+    HttpResponse::Ok().content_type("text/xml; charset=\"utf-8\"").body(body)
+}
+
+async fn description(manager: web::Data<UpnpManager>) -> HttpResponse {
+    let uuid = manager.config().uuid;
+    xml(format!(
+        r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+<specVersion><major>1</major><minor>0</minor></specVersion>
+<device>
+<deviceType>{igd}</deviceType>
+<friendlyName>Meowtail Router</friendlyName>
+<manufacturer>Meowtail</manufacturer>
+<modelName>Meowtail</modelName>
+<UDN>uuid:{root}</UDN>
+<deviceList>
+<device>
+<deviceType>{wan}</deviceType>
+<friendlyName>WAN Device</friendlyName>
+<manufacturer>Meowtail</manufacturer>
+<modelName>Meowtail</modelName>
+<UDN>uuid:{wan_uuid}</UDN>
+<deviceList>
+<device>
+<deviceType>{connection}</deviceType>
+<friendlyName>WAN Connection Device</friendlyName>
+<manufacturer>Meowtail</manufacturer>
+<modelName>Meowtail</modelName>
+<UDN>uuid:{connection_uuid}</UDN>
+<serviceList>
+<service>
+<serviceType>{service}</serviceType>
+<serviceId>urn:upnp-org:serviceId:WANIPConn1</serviceId>
+<SCPDURL>{scpd}</SCPDURL>
+<controlURL>{control}</controlURL>
+<eventSubURL></eventSubURL>
+</service>
+</serviceList>
+</device>
+</deviceList>
+</device>
+</deviceList>
+</device>
+</root>
+"#,
+        igd = IGD_DEVICE,
+        root = device_uuid(&uuid, ROOT_DEVICE),
+        wan = WAN_DEVICE,
+        wan_uuid = device_uuid(&uuid, WAN_DEVICE_INDEX),
+        connection = CONNECTION_DEVICE_TYPE,
+        connection_uuid = device_uuid(&uuid, CONNECTION_DEVICE),
+        service = WANIP_SERVICE,
+        scpd = SCPD_PATH,
+        control = CONTROL_PATH,
+    ))
+}
+
+/// 参数名、方向与对应的状态变量
+type Argument = (&'static str, &'static str, &'static str);
+
+/// 支持的动作
+const ACTIONS: &[(&str, &[Argument])] = &[
+    ("GetExternalIPAddress", &[("NewExternalIPAddress", "out", "ExternalIPAddress")]),
+    (
+        "GetStatusInfo",
+        &[
+            ("NewConnectionStatus", "out", "ConnectionStatus"),
+            ("NewLastConnectionError", "out", "LastConnectionError"),
+            ("NewUptime", "out", "Uptime"),
+        ],
+    ),
+    (
+        "GetConnectionTypeInfo",
+        &[
+            ("NewConnectionType", "out", "ConnectionType"),
+            ("NewPossibleConnectionTypes", "out", "PossibleConnectionTypes"),
+        ],
+    ),
+    (
+        "AddPortMapping",
+        &[
+            ("NewRemoteHost", "in", "RemoteHost"),
+            ("NewExternalPort", "in", "ExternalPort"),
+            ("NewProtocol", "in", "PortMappingProtocol"),
+            ("NewInternalPort", "in", "InternalPort"),
+            ("NewInternalClient", "in", "InternalClient"),
+            ("NewEnabled", "in", "PortMappingEnabled"),
+            ("NewPortMappingDescription", "in", "PortMappingDescription"),
+            ("NewLeaseDuration", "in", "PortMappingLeaseDuration"),
+        ],
+    ),
+    (
+        "DeletePortMapping",
+        &[
+            ("NewRemoteHost", "in", "RemoteHost"),
+            ("NewExternalPort", "in", "ExternalPort"),
+            ("NewProtocol", "in", "PortMappingProtocol"),
+        ],
+    ),
+    (
+        "GetSpecificPortMappingEntry",
+        &[
+            ("NewRemoteHost", "in", "RemoteHost"),
+            ("NewExternalPort", "in", "ExternalPort"),
+            ("NewProtocol", "in", "PortMappingProtocol"),
+            ("NewInternalPort", "out", "InternalPort"),
+            ("NewInternalClient", "out", "InternalClient"),
+            ("NewEnabled", "out", "PortMappingEnabled"),
+            ("NewPortMappingDescription", "out", "PortMappingDescription"),
+            ("NewLeaseDuration", "out", "PortMappingLeaseDuration"),
+        ],
+    ),
+    (
+        "GetGenericPortMappingEntry",
+        &[
+            ("NewPortMappingIndex", "in", "PortMappingNumberOfEntries"),
+            ("NewRemoteHost", "out", "RemoteHost"),
+            ("NewExternalPort", "out", "ExternalPort"),
+            ("NewProtocol", "out", "PortMappingProtocol"),
+            ("NewInternalPort", "out", "InternalPort"),
+            ("NewInternalClient", "out", "InternalClient"),
+            ("NewEnabled", "out", "PortMappingEnabled"),
+            ("NewPortMappingDescription", "out", "PortMappingDescription"),
+            ("NewLeaseDuration", "out", "PortMappingLeaseDuration"),
+        ],
+    ),
+];
+
+const STATE_VARIABLES: &[(&str, &str)] = &[
+    ("ConnectionType", "string"),
+    ("PossibleConnectionTypes", "string"),
+    ("ConnectionStatus", "string"),
+    ("Uptime", "ui4"),
+    ("LastConnectionError", "string"),
+    ("ExternalIPAddress", "string"),
+    ("RemoteHost", "string"),
+    ("ExternalPort", "ui2"),
+    ("InternalPort", "ui2"),
+    ("PortMappingProtocol", "string"),
+    ("InternalClient", "string"),
+    ("PortMappingDescription", "string"),
+    ("PortMappingEnabled", "boolean"),
+    ("PortMappingLeaseDuration", "ui4"),
+    ("PortMappingNumberOfEntries", "ui2"),
+];
+
+async fn scpd() -> HttpResponse {
+    let mut body = String::from(
+        "<?xml version=\"1.0\"?>\n<scpd xmlns=\"urn:schemas-upnp-org:service-1-0\">\n\
+         <specVersion><major>1</major><minor>0</minor></specVersion>\n<actionList>\n",
+    );
+    for (name, arguments) in ACTIONS {
+        body.push_str(&format!("<action><name>{}</name><argumentList>\n", name));
+        for (argument, direction, variable) in arguments.iter() {
+            body.push_str(&format!(
+                "<argument><name>{}</name><direction>{}</direction><relatedStateVariable>{}</relatedStateVariable></argument>\n",
+                argument, direction, variable
+            ));
+        }
+        body.push_str("</argumentList></action>\n");
+    }
+    body.push_str("</actionList>\n<serviceStateTable>\n");
+    for (name, data_type) in STATE_VARIABLES {
+        body.push_str(&format!(
+            "<stateVariable sendEvents=\"no\"><name>{}</name><dataType>{}</dataType></stateVariable>\n",
+            name, data_type
+        ));
+    }
+    body.push_str("</serviceStateTable>\n</scpd>\n");
+    xml(body)
+}
+
+/// UPnP 错误码
+#[derive(Debug)]
+struct Fault(u16, &'static str);
+
+const INVALID_ACTION: Fault = Fault(401, "Invalid Action");
+const INVALID_ARGS: Fault = Fault(402, "Invalid Args");
+const ACTION_FAILED: Fault = Fault(501, "Action Failed");
+const NOT_AUTHORIZED: Fault = Fault(606, "Action not authorized");
+const INDEX_INVALID: Fault = Fault(713, "SpecifiedArrayIndexInvalid");
+const NO_SUCH_ENTRY: Fault = Fault(714, "NoSuchEntryInArray");
+const CONFLICT: Fault = Fault(718, "ConflictInMappingEntry");
+const WILDCARD_ONLY: Fault = Fault(726, "RemoteHostOnlySupportsWildcard");
+
+impl From<MapError> for Fault {
+    fn from(error: MapError) -> Self {
+        match error {
+            MapError::NotAuthorized => NOT_AUTHORIZED,
+            MapError::Conflict => CONFLICT,
+            MapError::NoSuchEntry => NO_SUCH_ENTRY,
+            MapError::NoResources => ACTION_FAILED,
+            MapError::Failed(e) => {
+                eprintln!("[upnp] {}", e);
+                ACTION_FAILED
+            }
+        }
+    }
+}
+
+fn fault_response(fault: Fault) -> HttpResponse {
+    HttpResponse::InternalServerError().content_type("text/xml; charset=\"utf-8\"").body(format!(
+        r#"<?xml version="1.0"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring><detail><UPnPError xmlns="urn:schemas-upnp-org:control-1-0"><errorCode>{}</errorCode><errorDescription>{}</errorDescription></UPnPError></detail></s:Fault></s:Body></s:Envelope>
+"#,
+        fault.0, fault.1
+    ))
+}
+
+fn action_response(action: &str, arguments: &[(&str, String)]) -> HttpResponse {
+    let arguments: String = arguments
+        .iter()
+        .map(|(name, value)| format!("<{0}>{1}</{0}>", name, escape(value)))
+        .collect();
+    xml(format!(
+        r#"<?xml version="1.0"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body><u:{0}Response xmlns:u="{1}">{2}</u:{0}Response></s:Body></s:Envelope>
+"#,
+        action, WANIP_SERVICE, arguments
+    ))
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// 取出 SOAP 请求中的参数值，参数元素不带命名空间前缀
+fn argument(body: &str, name: &str) -> Option<String> {
+    if body.contains(&format!("<{}/>", name)) {
+        return Some(String::new());
+    }
+    let start = body.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + body[start..].find(&format!("</{}>", name))?;
+    Some(unescape(body[start..end].trim()))
+}
+
+fn parse_argument<T: std::str::FromStr>(body: &str, name: &str) -> Result<T, Fault> {
+    argument(body, name).and_then(|v| v.parse().ok()).ok_or(INVALID_ARGS)
+}
+
+fn protocol_argument(body: &str) -> Result<&'static str, Fault> {
+    match argument(body, "NewProtocol").map(|p| p.to_ascii_uppercase()).as_deref() {
+        Some("TCP") => Ok("tcp"),
+        Some("UDP") => Ok("udp"),
+        _ => Err(INVALID_ARGS),
+    }
+}
+
+/// 只支持不限制远端地址的映射
+fn check_remote_host(body: &str) -> Result<(), Fault> {
+    match argument(body, "NewRemoteHost").as_deref() {
+        None | Some("") | Some("*") => Ok(()),
+        Some(_) => Err(WILDCARD_ONLY),
+    }
+}
+
+fn entry_arguments(lease: &PortMapLease) -> Vec<(&'static str, String)> {
+    vec![
+        ("NewInternalPort", lease.rule.internal_port.to_string()),
+        ("NewInternalClient", lease.rule.internal_ip.clone()),
+        ("NewEnabled", "1".to_string()),
+        ("NewPortMappingDescription", lease.description.clone()),
+        ("NewLeaseDuration", remaining_secs(lease.expires_at).to_string()),
+    ]
+}
+
+async fn control(req: HttpRequest, body: String, manager: web::Data<UpnpManager>) -> HttpResponse {
+    let action = req
+        .headers()
+        .get("SOAPAction")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim_matches('"').rsplit_once('#'))
+        .map(|(_, action)| action.to_string())
+        .unwrap_or_default();
+    let requester = match req.peer_addr().map(|addr| addr.ip().to_canonical()) {
+        Some(IpAddr::V4(ip)) if manager.lan_address_for(ip).is_some() => ip,
+        _ => return fault_response(NOT_AUTHORIZED),
+    };
+    let name = action.clone();
+    let result = web::block(move || handle(&manager, &name, &body, requester)).await;
+    match result {
+        Ok(Ok(arguments)) => action_response(&action, &arguments),
+        Ok(Err(fault)) => fault_response(fault),
+        Err(_) => fault_response(ACTION_FAILED),
+    }
+}
+
+/// 执行动作并返回输出参数
+fn handle(manager: &UpnpManager, action: &str, body: &str, requester: Ipv4Addr) -> Result<Vec<(&'static str, String)>, Fault> {
+    match action {
+        "GetExternalIPAddress" => {
+            let address = manager.external_ip().map(|ip| ip.to_string()).unwrap_or_default();
+            Ok(vec![("NewExternalIPAddress", address)])
+        }
+        "GetStatusInfo" => {
+            let status = if manager.external_ip().is_some() { "Connected" } else { "Disconnected" };
+            Ok(vec![
+                ("NewConnectionStatus", status.to_string()),
+                ("NewLastConnectionError", "ERROR_NONE".to_string()),
+                ("NewUptime", manager.epoch().to_string()),
+            ])
+        }
+        "GetConnectionTypeInfo" => Ok(vec![
+            ("NewConnectionType", "IP_Routed".to_string()),
+            ("NewPossibleConnectionTypes", "IP_Routed".to_string()),
+        ]),
+        "AddPortMapping" => {
+            check_remote_host(body)?;
+            let enabled = argument(body, "NewEnabled").unwrap_or_else(|| "1".to_string());
+            if enabled != "1" && !enabled.eq_ignore_ascii_case("true") {
+                return Err(INVALID_ARGS);
+            }
+            let request = MapRequest {
+                protocol: protocol_argument(body)?,
+                external_port: parse_argument(body, "NewExternalPort")?,
+                internal_ip: parse_argument(body, "NewInternalClient")?,
+                internal_port: parse_argument(body, "NewInternalPort")?,
+                lifetime: argument(body, "NewLeaseDuration").and_then(|v| v.parse().ok()).unwrap_or(0),
+                description: argument(body, "NewPortMappingDescription").unwrap_or_default(),
+                origin: LeaseOrigin::Upnp,
+                any_port: false,
+            };
+            if request.external_port == 0 || request.internal_port == 0 {
+                return Err(INVALID_ARGS);
+            }
+            let lease = manager.map(request, requester)?;
+            println!(
+                "[upnp] {} mapped {}/{} -> {}:{}",
+                requester, lease.rule.protocol, lease.rule.external_port, lease.rule.internal_ip, lease.rule.internal_port
+            );
+            Ok(vec![])
+        }
+        "DeletePortMapping" => {
+            check_remote_host(body)?;
+            manager.unmap(protocol_argument(body)?, parse_argument(body, "NewExternalPort")?, requester)?;
+            Ok(vec![])
+        }
+        "GetSpecificPortMappingEntry" => {
+            check_remote_host(body)?;
+            let protocol = protocol_argument(body)?;
+            let port: u16 = parse_argument(body, "NewExternalPort")?;
+            let lease = manager
+                .mappings()
+                .into_iter()
+                .map(|m| m.lease)
+                .find(|l| l.rule.protocol == protocol && l.rule.external_port == port)
+                .ok_or(NO_SUCH_ENTRY)?;
+            Ok(entry_arguments(&lease))
+        }
+        "GetGenericPortMappingEntry" => {
+            let index: usize = parse_argument(body, "NewPortMappingIndex")?;
+            let lease = manager.mappings().into_iter().nth(index).map(|m| m.lease).ok_or(INDEX_INVALID)?;
+            let mut arguments = vec![
+                ("NewRemoteHost", String::new()),
+                ("NewExternalPort", lease.rule.external_port.to_string()),
+                ("NewProtocol", lease.rule.protocol.to_ascii_uppercase()),
+            ];
+            arguments.extend(entry_arguments(&lease));
+            Ok(arguments)
+        }
+        _ => Err(INVALID_ACTION),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::StubUpnp;
+    use super::super::UpnpConfig;
+    use super::*;
+
+    const CLIENT: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+    const OTHER: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 11);
+
+    fn body(arguments: &[(&str, &str)]) -> String {
+        let arguments: String = arguments.iter().map(|(name, value)| format!("<{0}>{1}</{0}>", name, value)).collect();
+        format!(
+            r#"<?xml version="1.0"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body><u:Action xmlns:u="{}">{}</u:Action></s:Body></s:Envelope>"#,
+            WANIP_SERVICE, arguments
+        )
+    }
+
+    fn add(port: &str, client: &str) -> String {
+        body(&[
+            ("NewRemoteHost", ""),
+            ("NewExternalPort", port),
+            ("NewProtocol", "TCP"),
+            ("NewInternalPort", "8080"),
+            ("NewInternalClient", client),
+            ("NewEnabled", "1"),
+            ("NewPortMappingDescription", "Game &amp; chat"),
+            ("NewLeaseDuration", "0"),
+        ])
+    }
+
+    fn fault(result: Result<Vec<(&'static str, String)>, Fault>) -> u16 {
+        result.unwrap_err().0
+    }
+
+    #[test]
+    fn arguments_are_unescaped() {
+        let body = "<NewRemoteHost/><NewProtocol> udp </NewProtocol>\
+                    <NewPortMappingDescription>a &lt;b&gt; &amp;amp;</NewPortMappingDescription>";
+        assert_eq!(argument(body, "NewRemoteHost").as_deref(), Some(""));
+        assert_eq!(protocol_argument(body).unwrap(), "udp");
+        assert_eq!(argument(body, "NewPortMappingDescription").as_deref(), Some("a <b> &amp;"));
+        assert_eq!(argument(body, "NewExternalPort"), None);
+        assert_eq!(escape("a <b> & 'c'"), "a &lt;b&gt; &amp; &apos;c&apos;");
+    }
+
+    #[test]
+    fn add_query_and_delete_mappings() {
+        let stub = StubUpnp::new("igd-map", UpnpConfig::default());
+        let manager = &stub.manager;
+        assert_eq!(handle(manager, "AddPortMapping", &add("6000", "192.168.1.10"), CLIENT).unwrap(), vec![]);
+        assert_eq!(stub.calls().len(), 2);
+
+        let query = body(&[("NewRemoteHost", ""), ("NewExternalPort", "6000"), ("NewProtocol", "tcp")]);
+        let entry = handle(manager, "GetSpecificPortMappingEntry", &query, OTHER).unwrap();
+        assert_eq!(entry[1], ("NewInternalClient", "192.168.1.10".to_string()));
+        assert_eq!(entry[3], ("NewPortMappingDescription", "Game & chat".to_string()));
+        // 永久映射按 max_lease_secs 到期
+        let lease_duration: u32 = entry[4].1.parse().unwrap();
+        assert!((86390..=86400).contains(&lease_duration));
+
+        let index = |i: &str| body(&[("NewPortMappingIndex", i)]);
+        let entry = handle(manager, "GetGenericPortMappingEntry", &index("0"), CLIENT).unwrap();
+        assert_eq!(entry[1], ("NewExternalPort", "6000".to_string()));
+        assert_eq!(entry[2], ("NewProtocol", "TCP".to_string()));
+        assert_eq!(fault(handle(manager, "GetGenericPortMappingEntry", &index("1"), CLIENT)), 713);
+
+        assert_eq!(fault(handle(manager, "DeletePortMapping", &query, OTHER)), 606);
+        handle(manager, "DeletePortMapping", &query, CLIENT).unwrap();
+        assert_eq!(stub.calls().len(), 2);
+        assert_eq!(fault(handle(manager, "DeletePortMapping", &query, CLIENT)), 714);
+        assert_eq!(fault(handle(manager, "GetSpecificPortMappingEntry", &query, CLIENT)), 714);
+    }
+
+    #[test]
+    fn add_port_mapping_faults() {
+        let stub = StubUpnp::new("igd-faults", UpnpConfig::default());
+        let manager = &stub.manager;
+        handle(manager, "AddPortMapping", &add("6000", "192.168.1.10"), CLIENT).unwrap();
+
+        // UPnP 不换端口，占用时报冲突
+        assert_eq!(fault(handle(manager, "AddPortMapping", &add("6000", "192.168.1.11"), OTHER)), 718);
+        assert_eq!(fault(handle(manager, "AddPortMapping", &add("6001", "192.168.1.10"), OTHER)), 606);
+        assert_eq!(fault(handle(manager, "AddPortMapping", &add("80", "192.168.1.11"), OTHER)), 606);
+        assert_eq!(fault(handle(manager, "AddPortMapping", &add("0", "192.168.1.11"), OTHER)), 402);
+        assert_eq!(fault(handle(manager, "AddPortMapping", &add("6001", "host"), OTHER)), 402);
+        let remote = add("6001", "192.168.1.11")
+            .replace("<NewRemoteHost></NewRemoteHost>", "<NewRemoteHost>1.2.3.4</NewRemoteHost>");
+        assert_eq!(fault(handle(manager, "AddPortMapping", &remote, OTHER)), 726);
+        let disabled = add("6001", "192.168.1.11").replace("<NewEnabled>1<", "<NewEnabled>0<");
+        assert_eq!(fault(handle(manager, "AddPortMapping", &disabled, OTHER)), 402);
+        assert_eq!(fault(handle(manager, "Reboot", "", CLIENT)), 401);
+        assert_eq!(stub.manager.mappings().len(), 1);
+    }
+
+    #[test]
+    fn reports_the_external_address() {
+        let stub = StubUpnp::new("igd-address", UpnpConfig::default());
+        let address = handle(&stub.manager, "GetExternalIPAddress", "", CLIENT).unwrap();
+        assert_eq!(address, vec![("NewExternalIPAddress", "127.0.0.1".to_string())]);
+        let status = handle(&stub.manager, "GetStatusInfo", "", CLIENT).unwrap();
+        assert_eq!(status[0], ("NewConnectionStatus", "Connected".to_string()));
+    }
+}
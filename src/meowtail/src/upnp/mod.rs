@@ -0,0 +1,574 @@
+// src/upnp/mod.rs
+
+pub mod igd;
+pub mod natpmp;
+pub mod ssdp;
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use nix::sys::socket::{self, sockopt, AddressFamily, SockFlag, SockType, SockaddrIn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, Result, ValidationIssue};
+use crate::firewall_manager::PortRange;
+use crate::fs_util::write_atomic;
+use crate::interfaces;
+use crate::ip_cidr::IpCidr;
+use crate::portmap_manager::{LeaseOrigin, PortMapLease, PortMapManager, PortMapRule};
+
+/// 检查租约是否到期的间隔
+const EXPIRE_INTERVAL: Duration = Duration::from_secs(5);
+/// 接收超时，让服务线程可以定期做其他事情
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AclAction {
+    Allow,
+    Deny,
+}
+
+/// 一条访问控制规则，未设置的字段匹配任何值
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AclRule {
+    pub action: AclAction,
+    /// 申请映射的内部地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internal: Option<IpCidr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internal_ports: Option<PortRange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_ports: Option<PortRange>,
+}
+
+impl AclRule {
+    fn matches(&self, internal_ip: Ipv4Addr, internal_port: u16, external_port: u16) -> bool {
+        self.internal.is_none_or(|cidr| cidr.contains(IpAddr::V4(internal_ip)))
+            && self.internal_ports.is_none_or(|range| range.contains(internal_port))
+            && self.external_ports.is_none_or(|range| range.contains(external_port))
+    }
+}
+
+/// upnp.toml 的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpnpConfig {
+    pub enabled: bool,
+    /// 提供服务的 LAN 接口
+    pub interfaces: Vec<String>,
+    /// UPnP IGD（SSDP 与 SOAP）
+    pub upnp: bool,
+    /// NAT-PMP 与 PCP
+    pub natpmp: bool,
+    /// 设备描述与 SOAP 控制使用的 HTTP 端口
+    pub http_port: u16,
+    /// 只允许客户端为自己的地址申请映射
+    pub secure_mode: bool,
+    /// 租约的最长时间（秒），UPnP 申请的永久映射也按此到期；0 表示不限制
+    pub max_lease_secs: u32,
+    /// SSDP 与设备描述中使用，首次启动时生成
+    pub uuid: String,
+    /// 按顺序匹配，第一条匹配的规则生效；都不匹配时拒绝
+    pub acl: Vec<AclRule>,
+}
+
+impl Default for UpnpConfig {
+    fn default() -> Self {
+        let unprivileged = Some(PortRange { start: 1024, end: 65535 });
+        UpnpConfig {
+            enabled: false,
+            interfaces: vec![],
+            upnp: true,
+            natpmp: true,
+            http_port: 5000,
+            secure_mode: true,
+            max_lease_secs: 86400,
+            uuid: String::new(),
+            acl: vec![AclRule {
+                action: AclAction::Allow,
+                internal: None,
+                internal_ports: unprivileged,
+                external_ports: unprivileged,
+            }],
+        }
+    }
+}
+
+impl UpnpConfig {
+    pub fn validate(&self) -> Result<()> {
+        let mut issues = Vec::new();
+        for (i, interface) in self.interfaces.iter().enumerate() {
+            if !interfaces::is_valid_name(interface) {
+                issues.push(ValidationIssue::new(
+                    format!("interfaces[{}]", i),
+                    format!("'{}' is not a valid interface name", interface),
+                ));
+            }
+        }
+        if self.http_port == 0 {
+            issues.push(ValidationIssue::new("http_port", "Port must not be 0"));
+        }
+        for (i, rule) in self.acl.iter().enumerate() {
+            if rule.internal.is_some_and(|cidr| !cidr.is_ipv4()) {
+                issues.push(ValidationIssue::new(format!("acl[{}].internal", i), "Only IPv4 networks can be mapped"));
+            }
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(issues))
+        }
+    }
+
+    fn acl_allows(&self, internal_ip: Ipv4Addr, internal_port: u16, external_port: u16) -> bool {
+        self.acl
+            .iter()
+            .find(|rule| rule.matches(internal_ip, internal_port, external_port))
+            .is_some_and(|rule| rule.action == AclAction::Allow)
+    }
+}
+
+/// 客户端申请映射失败的原因，由各协议转换为自己的错误码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapError {
+    NotAuthorized,
+    Conflict,
+    NoSuchEntry,
+    NoResources,
+    Failed(String),
+}
+
+/// 各协议共用的映射申请
+#[derive(Debug, Clone)]
+pub struct MapRequest {
+    /// `tcp` 或 `udp`
+    pub protocol: &'static str,
+    /// 期望的外部端口，0 表示由 meowtail 选择
+    pub external_port: u16,
+    pub internal_ip: Ipv4Addr,
+    pub internal_port: u16,
+    /// 秒，0 表示永久（仍受 max_lease_secs 限制）
+    pub lifetime: u32,
+    pub description: String,
+    pub origin: LeaseOrigin,
+    /// 期望的端口不可用时改用其他端口，NAT-PMP 与 PCP 允许这样做
+    pub any_port: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MappingStatus {
+    #[serde(flatten)]
+    pub lease: PortMapLease,
+    /// 距离到期的秒数，永久映射为空
+    pub remaining_secs: Option<i64>,
+}
+
+/// 处理 UPnP IGD、NAT-PMP 与 PCP 的映射申请，映射作为租约交给 PortMapManager
+pub struct UpnpManager {
+    config: Mutex<UpnpConfig>,
+    config_path: PathBuf,
+    portmap: Arc<PortMapManager>,
+    started: Instant,
+}
+
+impl UpnpManager {
+    /// 读取 upnp.toml，文件不存在时使用默认配置（未启用）
+    pub fn new<P: Into<PathBuf>>(config_path: P, portmap: Arc<PortMapManager>) -> Result<Self> {
+        let config_path = config_path.into();
+        let mut config: UpnpConfig = match fs::read_to_string(&config_path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| AppError::ConfigParse(format!("{:?}: {}", config_path, e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => UpnpConfig::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let new_uuid = config.uuid.is_empty();
+        if new_uuid {
+            config.uuid = random_uuid();
+        }
+        let manager = UpnpManager {
+            config: Mutex::new(config),
+            config_path,
+            portmap,
+            started: Instant::now(),
+        };
+        if new_uuid {
+            manager.save()?;
+        }
+        Ok(manager)
+    }
+
+    fn save(&self) -> Result<()> {
+        let content = toml::to_string_pretty(&*self.config.lock().unwrap()).map_err(|e| AppError::Process(e.to_string()))?;
+        write_atomic(&self.config_path, &content)
+    }
+
+    pub fn config(&self) -> UpnpConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// 替换配置并保存。ACL、secure_mode 与 max_lease_secs 立即生效，
+    /// 其他选项在重启后生效
+    pub fn set_config(&self, mut config: UpnpConfig) -> Result<()> {
+        config.validate()?;
+        {
+            let mut current = self.config.lock().unwrap();
+            config.uuid = current.uuid.clone();
+            *current = config;
+        }
+        self.save()
+    }
+
+    pub fn mappings(&self) -> Vec<MappingStatus> {
+        let now = Utc::now();
+        self.portmap
+            .leases()
+            .into_iter()
+            .map(|lease| MappingStatus {
+                remaining_secs: lease.expires_at.map(|t| (t - now).num_seconds().max(0)),
+                lease,
+            })
+            .collect()
+    }
+
+    pub fn remove_mapping(&self, protocol: &str, external_port: u16) -> Result<()> {
+        match self.portmap.remove_lease(protocol, external_port)? {
+            Some(_) => Ok(()),
+            None => Err(AppError::NotFound(format!("Mapping {}/{}", protocol, external_port))),
+        }
+    }
+
+    /// 端口映射所用 WAN 接口上的 IPv4 地址
+    pub fn external_ip(&self) -> Option<Ipv4Addr> {
+        let interface = self.portmap.config().external_interface;
+        interfaces::get(&interface).ok()?.ipv4.first().and_then(|a| match a.address {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+    }
+
+    /// NAT-PMP/PCP 的 epoch：服务启动以来的秒数
+    pub fn epoch(&self) -> u32 {
+        self.started.elapsed().as_secs() as u32
+    }
+
+    /// `peer` 所在直连网络对应的已配置 LAN 接口及本机在该网络上的地址
+    pub fn lan_address_for(&self, peer: Ipv4Addr) -> Option<Ipv4Addr> {
+        let names = self.config().interfaces;
+        names.iter().filter_map(|name| interfaces::get(name).ok()).find_map(|interface| {
+            interface.ipv4.iter().find_map(|a| match a.address {
+                IpAddr::V4(ip) if IpCidr::new(a.address, a.prefix_len).is_ok_and(|c| c.contains(IpAddr::V4(peer))) => {
+                    Some(ip)
+                }
+                _ => None,
+            })
+        })
+    }
+
+    /// 按 ACL 检查后创建或续期租约
+    pub fn map(&self, request: MapRequest, requester: Ipv4Addr) -> std::result::Result<PortMapLease, MapError> {
+        let config = self.config();
+        if config.secure_mode && request.internal_ip != requester {
+            return Err(MapError::NotAuthorized);
+        }
+        let expires_in = match (request.lifetime, config.max_lease_secs) {
+            (0, 0) => None,
+            (0, max) => Some(max),
+            (lifetime, 0) => Some(lifetime),
+            (lifetime, max) => Some(lifetime.min(max)),
+        };
+        let expires_at = expires_in.map(|secs| Utc::now() + chrono::Duration::seconds(secs as i64));
+
+        let mut candidates: Vec<u16> = Vec::new();
+        if request.any_port {
+            // 同一客户端续期时沿用已分配的外部端口
+            candidates.extend(self.portmap.leases().iter().filter_map(|l| {
+                (l.rule.protocol == request.protocol
+                    && l.rule.internal_ip == request.internal_ip.to_string()
+                    && l.rule.internal_port == request.internal_port)
+                    .then_some(l.rule.external_port)
+            }));
+        }
+        let first = if request.external_port == 0 { request.internal_port } else { request.external_port };
+        candidates.push(first);
+        if request.any_port {
+            candidates.extend((first.saturating_add(1)..=u16::MAX).chain(1..first));
+        }
+
+        let mut denied = false;
+        for external_port in candidates {
+            if external_port == 0 || !config.acl_allows(request.internal_ip, request.internal_port, external_port) {
+                denied = true;
+                continue;
+            }
+            let lease = PortMapLease {
+                rule: PortMapRule {
+                    protocol: request.protocol.to_string(),
+                    external_port,
+                    internal_ip: request.internal_ip.to_string(),
+                    internal_port: request.internal_port,
+                },
+                description: request.description.clone(),
+                origin: request.origin,
+                expires_at,
+            };
+            match self.portmap.add_lease(lease.clone()) {
+                Ok(()) => return Ok(lease),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if !request.any_port {
+                        return Err(MapError::Conflict);
+                    }
+                }
+                Err(e) => return Err(MapError::Failed(e.to_string())),
+            }
+        }
+        if denied && !request.any_port {
+            Err(MapError::NotAuthorized)
+        } else {
+            Err(MapError::NoResources)
+        }
+    }
+
+    /// 删除映射。secure_mode 下只能删除自己地址的映射
+    pub fn unmap(&self, protocol: &str, external_port: u16, requester: Ipv4Addr) -> std::result::Result<(), MapError> {
+        let lease = self
+            .portmap
+            .leases()
+            .into_iter()
+            .find(|l| l.rule.protocol == protocol && l.rule.external_port == external_port)
+            .ok_or(MapError::NoSuchEntry)?;
+        if self.config().secure_mode && lease.rule.internal_ip != requester.to_string() {
+            return Err(MapError::NotAuthorized);
+        }
+        self.portmap
+            .remove_lease(protocol, external_port)
+            .map_err(|e| MapError::Failed(e.to_string()))?;
+        Ok(())
+    }
+
+    /// 删除某个客户端通过指定来源创建的映射；`internal_port` 为 0 时删除全部
+    pub fn unmap_client(&self, origin: LeaseOrigin, protocol: &str, internal_ip: Ipv4Addr, internal_port: u16) {
+        for lease in self.portmap.leases() {
+            let matches = lease.origin == origin
+                && lease.rule.protocol == protocol
+                && lease.rule.internal_ip == internal_ip.to_string()
+                && (internal_port == 0 || lease.rule.internal_port == internal_port);
+            if matches {
+                let _ = self.portmap.remove_lease(protocol, lease.rule.external_port);
+            }
+        }
+    }
+
+    /// 启动租约到期检查，并在每个 LAN 接口上启动 SSDP 与 NAT-PMP/PCP 服务。
+    /// SOAP 控制由 igd::server 提供
+    pub fn spawn(manager: Arc<UpnpManager>) {
+        let config = manager.config();
+        if !config.enabled {
+            return;
+        }
+        let expiring = manager.clone();
+        thread::spawn(move || loop {
+            for lease in expiring.portmap.expire_leases() {
+                println!(
+                    "[upnp] Mapping {}/{} -> {}:{} expired",
+                    lease.rule.protocol, lease.rule.external_port, lease.rule.internal_ip, lease.rule.internal_port
+                );
+            }
+            thread::sleep(EXPIRE_INTERVAL);
+        });
+
+        for interface in &config.interfaces {
+            if config.upnp {
+                match ssdp::bind(interface) {
+                    Ok(socket) => {
+                        let (manager, interface) = (manager.clone(), interface.clone());
+                        thread::spawn(move || ssdp::serve(socket, &interface, &manager));
+                    }
+                    Err(e) => eprintln!("[upnp] Failed to start SSDP on {}: {}", interface, e),
+                }
+            }
+            if config.natpmp {
+                match bind_udp(interface, natpmp::PORT) {
+                    Ok(socket) => {
+                        let manager = manager.clone();
+                        thread::spawn(move || natpmp::serve(socket, &manager));
+                    }
+                    Err(e) => eprintln!("[upnp] Failed to start NAT-PMP on {}: {}", interface, e),
+                }
+            }
+        }
+    }
+}
+
+/// 绑定到 `interface` 上的 UDP 端口，只收发该接口上的报文
+fn bind_udp(interface: &str, port: u16) -> io::Result<UdpSocket> {
+    let fd = socket::socket(AddressFamily::Inet, SockType::Datagram, SockFlag::SOCK_CLOEXEC, None)?;
+    socket::setsockopt(&fd, sockopt::ReuseAddr, &true)?;
+    socket::setsockopt(&fd, sockopt::BindToDevice, &OsString::from(interface))?;
+    socket::bind(
+        std::os::fd::AsRawFd::as_raw_fd(&fd),
+        &SockaddrIn::from(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)),
+    )?;
+    let socket = UdpSocket::from(fd);
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+    Ok(socket)
+}
+
+/// 随机生成的版本 4 UUID
+fn random_uuid() -> String {
+    let mut bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// 租约剩余的秒数，永久映射为 0
+pub(crate) fn remaining_secs(expires_at: Option<DateTime<Utc>>) -> u32 {
+    expires_at.map_or(0, |t| (t - Utc::now()).num_seconds().clamp(1, u32::MAX as i64) as u32)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::Command;
+
+    /// 使用记录调用的 iptables 脚本的 UpnpManager；外部接口为 lo，外部地址为 127.0.0.1
+    pub(crate) struct StubUpnp {
+        dir: PathBuf,
+        pub(crate) manager: UpnpManager,
+    }
+
+    impl StubUpnp {
+        pub(crate) fn new(name: &str, config: UpnpConfig) -> Self {
+            let dir = std::env::temp_dir().join(format!("meowtail-upnp-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let script = dir.join("iptables");
+            fs::write(&script, format!("#!/bin/sh\necho \"$*\" >> '{}/log'\n", dir.display())).unwrap();
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+            // 其他测试线程 fork 时可能短暂持有脚本的写句柄，执行会报 ETXTBSY，等它释放
+            for _ in 0..50 {
+                match Command::new(&script).arg("warmup").status() {
+                    Err(e) if e.raw_os_error() == Some(nix::libc::ETXTBSY) => {
+                        thread::sleep(Duration::from_millis(20))
+                    }
+                    _ => break,
+                }
+            }
+            let _ = fs::remove_file(dir.join("log"));
+            fs::write(dir.join("portmap.toml"), "external_interface = \"lo\"\nrules = []\n").unwrap();
+            let portmap = PortMapManager::new(script.to_str().unwrap(), dir.join("portmap.toml")).unwrap();
+            let manager = UpnpManager::new(dir.join("upnp.toml"), Arc::new(portmap)).unwrap();
+            manager.set_config(config).unwrap();
+            StubUpnp { dir, manager }
+        }
+
+        /// 取出并清空 iptables 的调用记录，不含 `-C` 检查
+        pub(crate) fn calls(&self) -> Vec<String> {
+            let path = self.dir.join("log");
+            let log = fs::read_to_string(&path).unwrap_or_default();
+            let _ = fs::remove_file(&path);
+            log.lines().filter(|l| !l.contains(" -C ")).map(str::to_string).collect()
+        }
+    }
+
+    impl Drop for StubUpnp {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn request(internal_ip: Ipv4Addr, external_port: u16, any_port: bool) -> MapRequest {
+        MapRequest {
+            protocol: "tcp",
+            external_port,
+            internal_ip,
+            internal_port: 8080,
+            lifetime: 3600,
+            description: "test".to_string(),
+            origin: LeaseOrigin::NatPmp,
+            any_port,
+        }
+    }
+
+    fn ports(stub: &StubUpnp) -> Vec<(String, u16)> {
+        stub.manager.mappings().into_iter().map(|m| (m.lease.rule.internal_ip, m.lease.rule.external_port)).collect()
+    }
+
+    #[test]
+    fn map_falls_back_to_ports_the_acl_allows() {
+        let allowed = Some(PortRange { start: 2000, end: 2001 });
+        let config = UpnpConfig {
+            acl: vec![AclRule {
+                action: AclAction::Allow,
+                internal: None,
+                internal_ports: None,
+                external_ports: allowed,
+            }],
+            ..Default::default()
+        };
+        let stub = StubUpnp::new("fallback", config);
+        let (a, b, c) = (Ipv4Addr::new(192, 168, 1, 10), Ipv4Addr::new(192, 168, 1, 11), Ipv4Addr::new(192, 168, 1, 12));
+
+        assert_eq!(stub.manager.map(request(a, 2000, false), a).unwrap().rule.external_port, 2000);
+        assert_eq!(
+            stub.calls(),
+            vec![
+                "-t nat -A PREROUTING -i lo -p tcp --dport 2000 -m comment --comment meowtail-lease \
+                 -j DNAT --to-destination 192.168.1.10:8080",
+                "-t nat -A POSTROUTING -o lo -p tcp --dport 8080 -d 192.168.1.10 -m comment --comment meowtail-lease \
+                 -j MASQUERADE",
+            ]
+        );
+        // 被占用的端口换成 ACL 允许的下一个端口，续期时沿用
+        assert_eq!(stub.manager.map(request(b, 2000, true), b).unwrap().rule.external_port, 2001);
+        assert_eq!(stub.manager.map(request(b, 2000, true), b).unwrap().rule.external_port, 2001);
+        assert_eq!(stub.manager.map(request(c, 2000, false), c).unwrap_err(), MapError::Conflict);
+        assert_eq!(stub.manager.map(request(c, 2000, true), c).unwrap_err(), MapError::NoResources);
+        assert_eq!(stub.manager.map(request(c, 3000, false), c).unwrap_err(), MapError::NotAuthorized);
+        assert_eq!(ports(&stub), vec![(a.to_string(), 2000), (b.to_string(), 2001)]);
+    }
+
+    #[test]
+    fn secure_mode_limits_clients_to_their_own_mappings() {
+        let stub = StubUpnp::new("secure", UpnpConfig::default());
+        let (a, b) = (Ipv4Addr::new(192, 168, 1, 10), Ipv4Addr::new(192, 168, 1, 11));
+
+        assert_eq!(stub.manager.map(request(a, 5000, false), b).unwrap_err(), MapError::NotAuthorized);
+        // 默认 ACL 不允许特权端口
+        assert_eq!(stub.manager.map(request(a, 80, false), a).unwrap_err(), MapError::NotAuthorized);
+        stub.manager.map(request(a, 5000, false), a).unwrap();
+        assert_eq!(stub.manager.unmap("tcp", 5000, b), Err(MapError::NotAuthorized));
+        assert_eq!(stub.manager.unmap("tcp", 5001, a), Err(MapError::NoSuchEntry));
+        stub.calls();
+        stub.manager.unmap("tcp", 5000, a).unwrap();
+        assert_eq!(stub.calls().len(), 2);
+        assert!(ports(&stub).is_empty());
+    }
+
+    #[test]
+    fn lifetimes_are_capped() {
+        let config = UpnpConfig {
+            max_lease_secs: 600,
+            ..Default::default()
+        };
+        let stub = StubUpnp::new("lifetime", config);
+        let a = Ipv4Addr::new(192, 168, 1, 10);
+        let mut permanent = request(a, 5000, false);
+        permanent.lifetime = 0;
+        let lease = stub.manager.map(permanent, a).unwrap();
+        assert!((590..=600).contains(&remaining_secs(lease.expires_at)));
+        let lease = stub.manager.map(request(a, 5001, false), a).unwrap();
+        assert!(remaining_secs(lease.expires_at) <= 600);
+    }
+}
@@ -0,0 +1,392 @@
+// src/upnp/natpmp.rs
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use super::{MapError, MapRequest, UpnpManager};
+use crate::portmap_manager::LeaseOrigin;
+
+/// NAT-PMP（RFC 6886）与 PCP（RFC 6887）共用的端口
+pub const PORT: u16 = 5351;
+
+const NATPMP_VERSION: u8 = 0;
+const PCP_VERSION: u8 = 2;
+const RESPONSE_BIT: u8 = 0x80;
+
+// NAT-PMP 结果码
+const NATPMP_SUCCESS: u16 = 0;
+const NATPMP_NOT_AUTHORIZED: u16 = 2;
+const NATPMP_NETWORK_FAILURE: u16 = 3;
+const NATPMP_NO_RESOURCES: u16 = 4;
+const NATPMP_UNSUPPORTED_OPCODE: u16 = 5;
+
+// PCP 操作码与结果码
+const PCP_ANNOUNCE: u8 = 0;
+const PCP_MAP: u8 = 1;
+const PCP_SUCCESS: u8 = 0;
+const PCP_UNSUPP_VERSION: u8 = 1;
+const PCP_NOT_AUTHORIZED: u8 = 2;
+const PCP_MALFORMED_REQUEST: u8 = 3;
+const PCP_UNSUPP_OPCODE: u8 = 4;
+const PCP_NO_RESOURCES: u8 = 8;
+const PCP_UNSUPP_PROTOCOL: u8 = 9;
+const PCP_CANNOT_PROVIDE_EXTERNAL: u8 = 11;
+const PCP_ADDRESS_MISMATCH: u8 = 12;
+const PCP_HEADER_LEN: usize = 24;
+const PCP_MAP_LEN: usize = 36;
+/// 错误应答建议客户端重试前等待的秒数
+const PCP_ERROR_LIFETIME: u32 = 30;
+
+/// 在 `socket` 上回答直连网络内客户端的请求
+pub fn serve(socket: UdpSocket, manager: &UpnpManager) {
+    let mut buf = [0u8; 1100];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+            Err(e) => {
+                eprintln!("[natpmp] Receive failed: {}", e);
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+        let SocketAddr::V4(peer) = peer else {
+            continue;
+        };
+        if manager.lan_address_for(*peer.ip()).is_none() {
+            continue;
+        }
+        let request = &buf[..len];
+        let response = match request.first() {
+            Some(&NATPMP_VERSION) => handle_natpmp(manager, request, *peer.ip()),
+            Some(_) => handle_pcp(manager, request, *peer.ip()),
+            None => None,
+        };
+        if let Some(response) = response {
+            let _ = socket.send_to(&response, peer);
+        }
+    }
+}
+
+fn natpmp_header(opcode: u8, result: u16, epoch: u32) -> Vec<u8> {
+    let mut response = vec![NATPMP_VERSION, RESPONSE_BIT | opcode];
+    response.extend_from_slice(&result.to_be_bytes());
+    response.extend_from_slice(&epoch.to_be_bytes());
+    response
+}
+
+fn handle_natpmp(manager: &UpnpManager, request: &[u8], client: Ipv4Addr) -> Option<Vec<u8>> {
+    let opcode = *request.get(1)?;
+    let epoch = manager.epoch();
+    match opcode {
+        // 查询外部地址
+        0 => {
+            let (result, address) = match manager.external_ip() {
+                Some(ip) => (NATPMP_SUCCESS, ip),
+                None => (NATPMP_NETWORK_FAILURE, Ipv4Addr::UNSPECIFIED),
+            };
+            let mut response = natpmp_header(opcode, result, epoch);
+            response.extend_from_slice(&address.octets());
+            Some(response)
+        }
+        // 1 为 UDP，2 为 TCP
+        1 | 2 => {
+            if request.len() < 12 {
+                return None;
+            }
+            let protocol = if opcode == 1 { "udp" } else { "tcp" };
+            let internal_port = u16::from_be_bytes([request[4], request[5]]);
+            let suggested_port = u16::from_be_bytes([request[6], request[7]]);
+            let lifetime = u32::from_be_bytes([request[8], request[9], request[10], request[11]]);
+
+            let (result, external_port, lifetime) = if lifetime == 0 {
+                // 生存时间为 0 表示删除，内部端口为 0 时删除该客户端的所有映射
+                manager.unmap_client(LeaseOrigin::NatPmp, protocol, client, internal_port);
+                (NATPMP_SUCCESS, 0, 0)
+            } else if internal_port == 0 {
+                (NATPMP_NOT_AUTHORIZED, 0, 0)
+            } else {
+                let request = MapRequest {
+                    protocol,
+                    external_port: suggested_port,
+                    internal_ip: client,
+                    internal_port,
+                    lifetime,
+                    description: "NAT-PMP".to_string(),
+                    origin: LeaseOrigin::NatPmp,
+                    any_port: true,
+                };
+                match manager.map(request, client) {
+                    Ok(lease) => (NATPMP_SUCCESS, lease.rule.external_port, super::remaining_secs(lease.expires_at)),
+                    Err(MapError::NotAuthorized) => (NATPMP_NOT_AUTHORIZED, 0, 0),
+                    Err(MapError::Failed(e)) => {
+                        eprintln!("[natpmp] {}", e);
+                        (NATPMP_NETWORK_FAILURE, 0, 0)
+                    }
+                    Err(_) => (NATPMP_NO_RESOURCES, 0, 0),
+                }
+            };
+            let mut response = natpmp_header(opcode, result, epoch);
+            response.extend_from_slice(&internal_port.to_be_bytes());
+            response.extend_from_slice(&external_port.to_be_bytes());
+            response.extend_from_slice(&lifetime.to_be_bytes());
+            Some(response)
+        }
+        _ => Some(natpmp_header(opcode, NATPMP_UNSUPPORTED_OPCODE, epoch)),
+    }
+}
+
+/// PCP 应答：通用头部后接操作码相关的数据
+fn pcp_response(opcode: u8, result: u8, lifetime: u32, epoch: u32, payload: &[u8]) -> Vec<u8> {
+    let mut response = vec![PCP_VERSION, RESPONSE_BIT | opcode, 0, result];
+    response.extend_from_slice(&lifetime.to_be_bytes());
+    response.extend_from_slice(&epoch.to_be_bytes());
+    response.extend_from_slice(&[0; 12]);
+    response.extend_from_slice(payload);
+    response
+}
+
+fn handle_pcp(manager: &UpnpManager, request: &[u8], client: Ipv4Addr) -> Option<Vec<u8>> {
+    let epoch = manager.epoch();
+    // 太短或本身就是应答的报文不回答
+    if request.len() < PCP_HEADER_LEN || request[1] & RESPONSE_BIT != 0 {
+        return None;
+    }
+    let opcode = request[1] & !RESPONSE_BIT;
+    if request[0] != PCP_VERSION {
+        return Some(pcp_response(opcode, PCP_UNSUPP_VERSION, PCP_ERROR_LIFETIME, epoch, &[]));
+    }
+    if !request.len().is_multiple_of(4) || request.len() > 1100 {
+        return Some(pcp_response(opcode, PCP_MALFORMED_REQUEST, PCP_ERROR_LIFETIME, epoch, &[]));
+    }
+    let lifetime = u32::from_be_bytes([request[4], request[5], request[6], request[7]]);
+    let client_field = Ipv6Addr::from(<[u8; 16]>::try_from(&request[8..24]).ok()?);
+    if client_field.to_ipv4_mapped() != Some(client) {
+        return Some(pcp_response(opcode, PCP_ADDRESS_MISMATCH, PCP_ERROR_LIFETIME, epoch, &[]));
+    }
+
+    match opcode {
+        PCP_ANNOUNCE => Some(pcp_response(opcode, PCP_SUCCESS, 0, epoch, &[])),
+        PCP_MAP => {
+            let Some(map) = request.get(PCP_HEADER_LEN..PCP_HEADER_LEN + PCP_MAP_LEN) else {
+                return Some(pcp_response(opcode, PCP_MALFORMED_REQUEST, PCP_ERROR_LIFETIME, epoch, &[]));
+            };
+            let nonce = &map[..12];
+            let protocol_number = map[12];
+            let internal_port = u16::from_be_bytes([map[16], map[17]]);
+            let suggested_port = u16::from_be_bytes([map[18], map[19]]);
+            // 应答中的 MAP 数据：nonce、协议、分配的外部端口与地址
+            let payload = |external_port: u16, external_ip: Ipv4Addr| {
+                let mut payload = nonce.to_vec();
+                payload.extend_from_slice(&[protocol_number, 0, 0, 0]);
+                payload.extend_from_slice(&internal_port.to_be_bytes());
+                payload.extend_from_slice(&external_port.to_be_bytes());
+                payload.extend_from_slice(&external_ip.to_ipv6_mapped().octets());
+                payload
+            };
+            let error = |result: u8| pcp_response(opcode, result, PCP_ERROR_LIFETIME, epoch, &payload(0, Ipv4Addr::UNSPECIFIED));
+
+            let protocol = match protocol_number {
+                6 => "tcp",
+                17 => "udp",
+                _ => return Some(error(PCP_UNSUPP_PROTOCOL)),
+            };
+            if lifetime == 0 {
+                manager.unmap_client(LeaseOrigin::Pcp, protocol, client, internal_port);
+                return Some(pcp_response(opcode, PCP_SUCCESS, 0, epoch, &payload(0, Ipv4Addr::UNSPECIFIED)));
+            }
+            // 内部端口为 0 表示所有端口，只允许用于删除
+            if internal_port == 0 {
+                return Some(error(PCP_MALFORMED_REQUEST));
+            }
+            let Some(external_ip) = manager.external_ip() else {
+                return Some(error(PCP_CANNOT_PROVIDE_EXTERNAL));
+            };
+            let request = MapRequest {
+                protocol,
+                external_port: suggested_port,
+                internal_ip: client,
+                internal_port,
+                lifetime,
+                description: "PCP".to_string(),
+                origin: LeaseOrigin::Pcp,
+                any_port: true,
+            };
+            match manager.map(request, client) {
+                Ok(lease) => Some(pcp_response(
+                    opcode,
+                    PCP_SUCCESS,
+                    super::remaining_secs(lease.expires_at),
+                    epoch,
+                    &payload(lease.rule.external_port, external_ip),
+                )),
+                Err(MapError::NotAuthorized) => Some(error(PCP_NOT_AUTHORIZED)),
+                Err(MapError::Failed(e)) => {
+                    eprintln!("[pcp] {}", e);
+                    Some(error(PCP_NO_RESOURCES))
+                }
+                Err(_) => Some(error(PCP_NO_RESOURCES)),
+            }
+        }
+        _ => Some(pcp_response(opcode, PCP_UNSUPP_OPCODE, PCP_ERROR_LIFETIME, epoch, &[])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::StubUpnp;
+    use super::super::UpnpConfig;
+    use super::*;
+
+    const CLIENT: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+
+    fn natpmp_map(opcode: u8, internal_port: u16, suggested_port: u16, lifetime: u32) -> Vec<u8> {
+        let mut request = vec![NATPMP_VERSION, opcode, 0, 0];
+        request.extend_from_slice(&internal_port.to_be_bytes());
+        request.extend_from_slice(&suggested_port.to_be_bytes());
+        request.extend_from_slice(&lifetime.to_be_bytes());
+        request
+    }
+
+    fn pcp_map(client: Ipv4Addr, protocol: u8, internal_port: u16, suggested_port: u16, lifetime: u32) -> Vec<u8> {
+        let mut request = vec![PCP_VERSION, PCP_MAP, 0, 0];
+        request.extend_from_slice(&lifetime.to_be_bytes());
+        request.extend_from_slice(&client.to_ipv6_mapped().octets());
+        request.extend_from_slice(&[7; 12]);
+        request.extend_from_slice(&[protocol, 0, 0, 0]);
+        request.extend_from_slice(&internal_port.to_be_bytes());
+        request.extend_from_slice(&suggested_port.to_be_bytes());
+        request.extend_from_slice(&Ipv6Addr::UNSPECIFIED.octets());
+        request
+    }
+
+    fn u16_at(response: &[u8], at: usize) -> u16 {
+        u16::from_be_bytes([response[at], response[at + 1]])
+    }
+
+    fn u32_at(response: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes([response[at], response[at + 1], response[at + 2], response[at + 3]])
+    }
+
+    #[test]
+    fn natpmp_ignores_short_requests() {
+        let stub = StubUpnp::new("natpmp-short", UpnpConfig::default());
+        assert_eq!(handle_natpmp(&stub.manager, &[NATPMP_VERSION], CLIENT), None);
+        assert_eq!(handle_natpmp(&stub.manager, &natpmp_map(2, 8080, 8080, 3600)[..11], CLIENT), None);
+        assert!(stub.calls().is_empty());
+    }
+
+    #[test]
+    fn natpmp_external_address_and_unknown_opcode() {
+        let stub = StubUpnp::new("natpmp-address", UpnpConfig::default());
+        let response = handle_natpmp(&stub.manager, &[NATPMP_VERSION, 0], CLIENT).unwrap();
+        assert_eq!(response.len(), 12);
+        assert_eq!(response[..4], [NATPMP_VERSION, RESPONSE_BIT, 0, 0]);
+        assert_eq!(response[8..], [127, 0, 0, 1]);
+
+        let response = handle_natpmp(&stub.manager, &[NATPMP_VERSION, 9], CLIENT).unwrap();
+        assert_eq!(response.len(), 8);
+        assert_eq!(response[1], RESPONSE_BIT | 9);
+        assert_eq!(u16_at(&response, 2), NATPMP_UNSUPPORTED_OPCODE);
+    }
+
+    #[test]
+    fn natpmp_maps_and_deletes() {
+        let stub = StubUpnp::new("natpmp-map", UpnpConfig::default());
+        let response = handle_natpmp(&stub.manager, &natpmp_map(2, 8080, 9000, 3600), CLIENT).unwrap();
+        assert_eq!(response.len(), 16);
+        assert_eq!(response[1], RESPONSE_BIT | 2);
+        assert_eq!(u16_at(&response, 2), NATPMP_SUCCESS);
+        assert_eq!(u16_at(&response, 8), 8080);
+        assert_eq!(u16_at(&response, 10), 9000);
+        assert!((3590..=3600).contains(&u32_at(&response, 12)));
+        assert_eq!(stub.calls().len(), 2);
+
+        // 内部端口为 0 只能用于删除
+        let response = handle_natpmp(&stub.manager, &natpmp_map(2, 0, 9000, 3600), CLIENT).unwrap();
+        assert_eq!(u16_at(&response, 2), NATPMP_NOT_AUTHORIZED);
+        // 生存时间为 0 删除该客户端的映射，其他协议的映射不受影响
+        let response = handle_natpmp(&stub.manager, &natpmp_map(1, 8080, 0, 0), CLIENT).unwrap();
+        assert_eq!(u16_at(&response, 2), NATPMP_SUCCESS);
+        assert_eq!(stub.manager.mappings().len(), 1);
+        let response = handle_natpmp(&stub.manager, &natpmp_map(2, 0, 0, 0), CLIENT).unwrap();
+        assert_eq!((u16_at(&response, 2), u16_at(&response, 10), u32_at(&response, 12)), (NATPMP_SUCCESS, 0, 0));
+        assert!(stub.manager.mappings().is_empty());
+        assert_eq!(stub.calls().len(), 2);
+    }
+
+    #[test]
+    fn natpmp_falls_back_when_the_port_is_denied() {
+        let stub = StubUpnp::new("natpmp-acl", UpnpConfig::default());
+        // 默认 ACL 只允许非特权端口
+        let response = handle_natpmp(&stub.manager, &natpmp_map(2, 8080, 80, 60), CLIENT).unwrap();
+        assert_eq!(u16_at(&response, 2), NATPMP_SUCCESS);
+        assert_eq!(u16_at(&response, 10), 1024);
+    }
+
+    #[test]
+    fn pcp_ignores_short_requests_and_responses() {
+        let stub = StubUpnp::new("pcp-short", UpnpConfig::default());
+        let request = pcp_map(CLIENT, 6, 8080, 8080, 3600);
+        assert_eq!(handle_pcp(&stub.manager, &request[..PCP_HEADER_LEN - 1], CLIENT), None);
+        let mut response = request.clone();
+        response[1] |= RESPONSE_BIT;
+        assert_eq!(handle_pcp(&stub.manager, &response, CLIENT), None);
+        assert!(stub.manager.mappings().is_empty());
+    }
+
+    #[test]
+    fn pcp_rejects_bad_headers() {
+        let stub = StubUpnp::new("pcp-header", UpnpConfig::default());
+        let request = pcp_map(CLIENT, 6, 8080, 8080, 3600);
+
+        let mut old = request.clone();
+        old[0] = 1;
+        let response = handle_pcp(&stub.manager, &old, CLIENT).unwrap();
+        assert_eq!(response.len(), PCP_HEADER_LEN);
+        assert_eq!(response[..4], [PCP_VERSION, RESPONSE_BIT | PCP_MAP, 0, PCP_UNSUPP_VERSION]);
+        assert_eq!(u32_at(&response, 4), PCP_ERROR_LIFETIME);
+
+        let response = handle_pcp(&stub.manager, &request[..request.len() - 2], CLIENT).unwrap();
+        assert_eq!(response[3], PCP_MALFORMED_REQUEST);
+        let response = handle_pcp(&stub.manager, &request[..PCP_HEADER_LEN + 4], CLIENT).unwrap();
+        assert_eq!(response[3], PCP_MALFORMED_REQUEST);
+        let response = handle_pcp(&stub.manager, &request, Ipv4Addr::new(192, 168, 1, 11)).unwrap();
+        assert_eq!(response[3], PCP_ADDRESS_MISMATCH);
+        let response = handle_pcp(&stub.manager, &pcp_map(CLIENT, 1, 8080, 8080, 3600), CLIENT).unwrap();
+        assert_eq!(response[3], PCP_UNSUPP_PROTOCOL);
+
+        let mut announce = request[..PCP_HEADER_LEN].to_vec();
+        announce[1] = PCP_ANNOUNCE;
+        let response = handle_pcp(&stub.manager, &announce, CLIENT).unwrap();
+        assert_eq!(response[..4], [PCP_VERSION, RESPONSE_BIT, 0, PCP_SUCCESS]);
+        announce[1] = 5;
+        assert_eq!(handle_pcp(&stub.manager, &announce, CLIENT).unwrap()[3], PCP_UNSUPP_OPCODE);
+        assert!(stub.calls().is_empty());
+    }
+
+    #[test]
+    fn pcp_maps_and_deletes() {
+        let stub = StubUpnp::new("pcp-map", UpnpConfig::default());
+        let response = handle_pcp(&stub.manager, &pcp_map(CLIENT, 17, 5353, 6000, 3600), CLIENT).unwrap();
+        assert_eq!(response.len(), PCP_HEADER_LEN + PCP_MAP_LEN);
+        assert_eq!(response[..4], [PCP_VERSION, RESPONSE_BIT | PCP_MAP, 0, PCP_SUCCESS]);
+        assert!((3590..=3600).contains(&u32_at(&response, 4)));
+        let map = &response[PCP_HEADER_LEN..];
+        assert_eq!(map[..12], [7; 12]);
+        assert_eq!(map[12], 17);
+        assert_eq!((u16_at(map, 16), u16_at(map, 18)), (5353, 6000));
+        assert_eq!(map[20..], Ipv4Addr::LOCALHOST.to_ipv6_mapped().octets());
+        assert_eq!(stub.manager.mappings()[0].lease.origin, LeaseOrigin::Pcp);
+        stub.calls();
+
+        let response = handle_pcp(&stub.manager, &pcp_map(CLIENT, 17, 0, 6000, 3600), CLIENT).unwrap();
+        assert_eq!(response[3], PCP_MALFORMED_REQUEST);
+        let response = handle_pcp(&stub.manager, &pcp_map(CLIENT, 17, 5353, 0, 0), CLIENT).unwrap();
+        assert_eq!((response[3], u32_at(&response, 4)), (PCP_SUCCESS, 0));
+        assert!(stub.manager.mappings().is_empty());
+        assert_eq!(stub.calls().len(), 2);
+    }
+}
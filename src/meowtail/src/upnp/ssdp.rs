@@ -0,0 +1,146 @@
+// src/upnp/ssdp.rs
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::{Duration, Instant};
+
+use super::{bind_udp, igd, UpnpManager};
+use crate::interfaces;
+
+pub const PORT: u16 = 1900;
+const GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+/// 客户端缓存通告的时间，NOTIFY 每隔一半时间重发一次
+const MAX_AGE_SECS: u64 = 1800;
+const SERVER: &str = concat!("Linux UPnP/1.1 meowtail/", env!("CARGO_PKG_VERSION"));
+
+/// 绑定 1900 端口并在 `interface` 上加入 SSDP 组播组
+pub fn bind(interface: &str) -> io::Result<UdpSocket> {
+    let socket = bind_udp(interface, PORT)?;
+    let address = interface_ipv4(interface)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::AddrNotAvailable, format!("{} has no IPv4 address", interface)))?;
+    socket.join_multicast_v4(&GROUP, &address)?;
+    socket.set_multicast_ttl_v4(2)?;
+    Ok(socket)
+}
+
+fn interface_ipv4(interface: &str) -> Option<Ipv4Addr> {
+    interfaces::get(interface).ok()?.ipv4.iter().find_map(|a| match a.address {
+        IpAddr::V4(ip) => Some(ip),
+        IpAddr::V6(_) => None,
+    })
+}
+
+/// 通告的设备与服务类型，以及各自 USN 使用的 UUID
+fn targets(manager: &UpnpManager) -> Vec<(String, String)> {
+    let uuid = manager.config().uuid;
+    let root = igd::device_uuid(&uuid, igd::ROOT_DEVICE);
+    let connection = igd::device_uuid(&uuid, igd::CONNECTION_DEVICE);
+    vec![
+        ("upnp:rootdevice".to_string(), root.clone()),
+        (format!("uuid:{}", root), root.clone()),
+        (igd::IGD_DEVICE.to_string(), root),
+        (igd::WAN_DEVICE.to_string(), igd::device_uuid(&uuid, igd::WAN_DEVICE_INDEX)),
+        (igd::CONNECTION_DEVICE_TYPE.to_string(), connection.clone()),
+        (igd::WANIP_SERVICE.to_string(), connection),
+    ]
+}
+
+fn usn(target: &str, uuid: &str) -> String {
+    if target.starts_with("uuid:") {
+        target.to_string()
+    } else {
+        format!("uuid:{}::{}", uuid, target)
+    }
+}
+
+fn location(address: Ipv4Addr, manager: &UpnpManager) -> String {
+    format!("http://{}:{}{}", address, manager.config().http_port, igd::DESCRIPTION_PATH)
+}
+
+/// 回答 M-SEARCH，并定期发送 ssdp:alive 通告
+pub fn serve(socket: UdpSocket, interface: &str, manager: &UpnpManager) {
+    let mut buf = [0u8; 1500];
+    let mut next_notify = Instant::now();
+    loop {
+        if Instant::now() >= next_notify {
+            notify(&socket, interface, manager);
+            next_notify = Instant::now() + Duration::from_secs(MAX_AGE_SECS / 2);
+        }
+        let (len, peer) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+            Err(e) => {
+                eprintln!("[ssdp] Receive failed on {}: {}", interface, e);
+                std::thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+        let SocketAddr::V4(peer) = peer else {
+            continue;
+        };
+        let Some(search_target) = parse_search(&String::from_utf8_lossy(&buf[..len])) else {
+            continue;
+        };
+        // 只回答直连网络上的客户端
+        let Some(address) = manager.lan_address_for(*peer.ip()) else {
+            continue;
+        };
+        for (target, uuid) in targets(manager) {
+            if search_target != "ssdp:all" && search_target != target {
+                continue;
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age={}\r\nEXT:\r\nLOCATION: {}\r\nSERVER: {}\r\nST: {}\r\nUSN: {}\r\n\r\n",
+                MAX_AGE_SECS,
+                location(address, manager),
+                SERVER,
+                target,
+                usn(&target, &uuid)
+            );
+            let _ = socket.send_to(response.as_bytes(), peer);
+        }
+    }
+}
+
+/// M-SEARCH 请求的 ST，其他报文返回 None
+fn parse_search(message: &str) -> Option<String> {
+    let mut lines = message.lines();
+    if !lines.next()?.trim().eq_ignore_ascii_case("M-SEARCH * HTTP/1.1") {
+        return None;
+    }
+    let mut discover = false;
+    let mut target = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_uppercase().as_str() {
+            "MAN" => discover = value.trim().trim_matches('"') == "ssdp:discover",
+            "ST" => target = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    target.filter(|_| discover)
+}
+
+fn notify(socket: &UdpSocket, interface: &str, manager: &UpnpManager) {
+    let Some(address) = interface_ipv4(interface) else {
+        return;
+    };
+    let group = SocketAddrV4::new(GROUP, PORT);
+    for (target, uuid) in targets(manager) {
+        let message = format!(
+            "NOTIFY * HTTP/1.1\r\nHOST: {}\r\nCACHE-CONTROL: max-age={}\r\nLOCATION: {}\r\nNT: {}\r\nNTS: ssdp:alive\r\nSERVER: {}\r\nUSN: {}\r\n\r\n",
+            group,
+            MAX_AGE_SECS,
+            location(address, manager),
+            target,
+            SERVER,
+            usn(&target, &uuid)
+        );
+        if let Err(e) = socket.send_to(message.as_bytes(), group) {
+            eprintln!("[ssdp] Failed to send NOTIFY on {}: {}", interface, e);
+            return;
+        }
+    }
+}